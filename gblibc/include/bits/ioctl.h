@@ -3,10 +3,23 @@
 
 #include <sys/uio.h>
 
+#define TCGETS (0x5401)
+#define TCSETS (0x5402)
+#define TCSETSW (0x5403)
+#define TCSETSF (0x5404)
 #define TIOCGPGRP (0x540f)
 #define TIOCSPGRP (0x5410)
 #define TIOCGWINSZ (0x5413)
 
+#define FIFREEZE (0xc0045877)
+#define FITHAW (0xc0045878)
+
+#define FS_IOC_GETFLAGS (0x80046601)
+#define FS_IOC_SETFLAGS (0x40046602)
+
+#define FS_IMMUTABLE_FL (0x00000010)
+#define FS_APPEND_FL (0x00000020)
+
 #ifdef __cplusplus
 extern "C" {
 #endif
@@ -2,6 +2,7 @@
 #define __GBLIBC_FCNTL_H_
 
 #include <stdint.h>
+#include <sys/types.h>
 
 #define O_RDONLY          00
 #define O_WRONLY          01
@@ -10,8 +11,13 @@
 #define O_EXCL          0200
 #define O_TRUNC        01000
 #define O_APPEND       02000
+#define O_NONBLOCK     04000
+#define O_DSYNC       010000
+#define O_DIRECT      040000
 #define O_DIRECTORY  0200000
+#define O_NOFOLLOW   0400000
 #define O_CLOEXEC   02000000
+#define O_SYNC      04010000
 
 #define F_DUPFD 0
 #define F_GETFD 1
@@ -20,6 +26,11 @@
 
 #define FD_CLOEXEC 1
 
+#define SPLICE_F_MOVE     1
+#define SPLICE_F_NONBLOCK 2
+#define SPLICE_F_MORE     4
+#define SPLICE_F_GIFT     8
+
 #define AT_FDCWD (-100)
 #define AT_SYMLINK_NOFOLLOW   0x100
 #define AT_REMOVEDIR          0x200
@@ -35,6 +46,14 @@ extern "C" {
 
 int open(const char* filename, int flags, ...);
 
+struct iovec;
+
+ssize_t splice(int fd_in, off64_t* off_in, int fd_out,
+    off64_t* off_out, size_t len, unsigned int flags);
+ssize_t tee(int fd_in, int fd_out, size_t len, unsigned int flags);
+ssize_t vmsplice(int fd, const struct iovec* iov,
+    size_t nr_segs, unsigned int flags);
+
 #ifdef __cplusplus
 }
 #endif
@@ -0,0 +1,14 @@
+#ifndef __GBLIBC_SCHED_H_
+#define __GBLIBC_SCHED_H_
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+int getcpu(unsigned int* cpu, unsigned int* node);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
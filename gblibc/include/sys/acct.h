@@ -0,0 +1,57 @@
+#ifndef __GBLIBC_SYS_ACCT_H
+#define __GBLIBC_SYS_ACCT_H
+
+#include <stdint.h>
+
+#define ACCT_COMM 16
+
+// ticks per second of the time fields
+#define AHZ 100
+
+// ac_flag
+#define AFORK 0x01
+#define ASU 0x02
+#define ACORE 0x08
+#define AXSIG 0x10
+
+#define ACCT_VERSION 3
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+// 3 bits of base 8 exponent and 13 bits of fraction
+typedef uint16_t comp_t;
+
+struct acct_v3 {
+    char ac_flag;
+    char ac_version;
+    uint16_t ac_tty;
+    uint32_t ac_exitcode;
+    uint32_t ac_uid;
+    uint32_t ac_gid;
+    uint32_t ac_pid;
+    uint32_t ac_ppid;
+    // process creation time
+    uint32_t ac_btime;
+    // elapsed time in AHZ, stored as the bits of a float
+    uint32_t ac_etime;
+    comp_t ac_utime;
+    comp_t ac_stime;
+    // memory usage in kB
+    comp_t ac_mem;
+    comp_t ac_io;
+    comp_t ac_rw;
+    comp_t ac_minflt;
+    comp_t ac_majflt;
+    comp_t ac_swaps;
+    char ac_comm[ACCT_COMM];
+};
+
+int acct(const char* filename);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
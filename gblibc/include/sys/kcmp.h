@@ -0,0 +1,25 @@
+#ifndef __GBLIBC_SYS_KCMP_H
+#define __GBLIBC_SYS_KCMP_H
+
+#include <sys/types.h>
+
+#define KCMP_FILE 0
+#define KCMP_VM 1
+#define KCMP_FILES 2
+#define KCMP_FS 3
+#define KCMP_SIGHAND 4
+#define KCMP_IO 5
+#define KCMP_SYSVSEM 6
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+int kcmp(pid_t pid1, pid_t pid2, int type,
+    unsigned long idx1, unsigned long idx2);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
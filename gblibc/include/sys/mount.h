@@ -0,0 +1,9 @@
+#ifndef __GBLIBC_SYS_MOUNT_H
+#define __GBLIBC_SYS_MOUNT_H
+
+// mount flags
+#define MS_NOATIME 1024
+#define MS_RELATIME (1 << 21)
+#define MS_STRICTATIME (1 << 24)
+
+#endif
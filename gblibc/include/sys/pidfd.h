@@ -0,0 +1,22 @@
+#ifndef __GBLIBC_SYS_PIDFD_H
+#define __GBLIBC_SYS_PIDFD_H
+
+#include <fcntl.h>
+#include <sys/types.h>
+
+#define PIDFD_NONBLOCK O_NONBLOCK
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+int pidfd_open(pid_t pid, unsigned int flags);
+
+// info MUST be null since siginfo is not supported
+int pidfd_send_signal(int pidfd, int sig, void* info, unsigned int flags);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,38 @@
+#ifndef __GBLIBC_SYS_RSEQ_H
+#define __GBLIBC_SYS_RSEQ_H
+
+#include <stdint.h>
+
+#define RSEQ_CPU_ID_UNINITIALIZED (-1)
+#define RSEQ_CPU_ID_REGISTRATION_FAILED (-2)
+
+#define RSEQ_FLAG_UNREGISTER (1 << 0)
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+// describes a restartable sequence
+struct rseq_cs {
+    uint32_t version;
+    uint32_t flags;
+    uint64_t start_ip;
+    // the sequence is [start_ip, start_ip + post_commit_offset)
+    uint64_t post_commit_offset;
+    // preceded by the signature given at registration
+    uint64_t abort_ip;
+} __attribute__((aligned(32)));
+
+struct rseq {
+    uint32_t cpu_id_start;
+    uint32_t cpu_id;
+    // pointer to struct rseq_cs of the sequence in progress
+    uint64_t rseq_cs;
+    uint32_t flags;
+} __attribute__((aligned(32)));
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -2,6 +2,45 @@
 #define __GBLIBC_SYS_STAT_H
 
 #include <stdint.h>
+#include <sys/types.h>
+#include <time.h>
+
+#ifndef S_IFMT
+#define S_IFMT 0170000
+
+#define S_IFSOCK 0140000
+#define S_IFLNK 0120000
+#define S_IFREG 0100000
+#define S_IFBLK 0060000
+#define S_IFDIR 0040000
+#define S_IFCHR 0020000
+#define S_IFIFO 0010000
+
+#define S_ISSOCK(m) (((m)&S_IFMT) == S_IFSOCK)
+#define S_ISLNK(m) (((m)&S_IFMT) == S_IFLNK)
+#define S_ISREG(m) (((m)&S_IFMT) == S_IFREG)
+#define S_ISBLK(m) (((m)&S_IFMT) == S_IFBLK)
+#define S_ISDIR(m) (((m)&S_IFMT) == S_IFDIR)
+#define S_ISCHR(m) (((m)&S_IFMT) == S_IFCHR)
+#define S_ISFIFO(m) (((m)&S_IFMT) == S_IFIFO)
+#endif
+
+#define S_ISUID 04000
+#define S_ISGID 02000
+#define S_ISVTX 01000
+
+#define S_IRWXU 0700
+#define S_IRUSR 0400
+#define S_IWUSR 0200
+#define S_IXUSR 0100
+#define S_IRWXG 0070
+#define S_IRGRP 0040
+#define S_IWGRP 0020
+#define S_IXGRP 0010
+#define S_IRWXO 0007
+#define S_IROTH 0004
+#define S_IWOTH 0002
+#define S_IXOTH 0001
 
 #define STATX_TYPE (1 << 0)
 #define STATX_MODE (1 << 1)
@@ -17,6 +56,10 @@
 #define STATX_BASIC_STATS (0x7ff)
 #define STATX_BTIME (1 << 11)
 
+// tv_nsec of the times given to utimensat
+#define UTIME_NOW ((1l << 30) - 1l)
+#define UTIME_OMIT ((1l << 30) - 2l)
+
 #ifdef __cplusplus
 extern "C" {
 #endif
@@ -52,6 +95,13 @@ struct statx {
     uint64_t stx_dio_alignment[13];
 };
 
+int mknod(const char* pathname, mode_t mode, dev_t dev);
+int mkfifo(const char* pathname, mode_t mode);
+
+int utimensat(int dirfd, const char* pathname,
+    const struct timespec times[2], int flags);
+int futimens(int fd, const struct timespec times[2]);
+
 #ifdef __cplusplus
 }
 #endif
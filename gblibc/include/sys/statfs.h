@@ -0,0 +1,46 @@
+#ifndef __GBLIBC_SYS_STATFS_H
+#define __GBLIBC_SYS_STATFS_H
+
+#include <stdint.h>
+
+// f_type
+#define TMPFS_MAGIC 0x01021994
+#define MSDOS_SUPER_MAGIC 0x4d44
+#define PROC_SUPER_MAGIC 0x9fa0
+#define SYSFS_MAGIC 0x62656572
+
+// f_flags
+#define ST_NOATIME 1024
+#define ST_RELATIME 4096
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct {
+    int32_t __val[2];
+} fsid_t;
+
+struct statfs {
+    uint32_t f_type;
+    uint32_t f_bsize;
+    uint32_t f_blocks;
+    uint32_t f_bfree;
+    uint32_t f_bavail;
+    uint32_t f_files;
+    uint32_t f_ffree;
+    fsid_t f_fsid;
+    uint32_t f_namelen;
+    uint32_t f_frsize;
+    uint32_t f_flags;
+    uint32_t f_spare[4];
+};
+
+int statfs(const char* path, struct statfs* buf);
+int fstatfs(int fd, struct statfs* buf);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
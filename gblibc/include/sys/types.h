@@ -10,6 +10,10 @@ extern "C" {
 typedef int pid_t;
 typedef uint32_t ino_t;
 typedef int32_t off_t;
+typedef unsigned mode_t;
+typedef unsigned dev_t;
+typedef unsigned uid_t;
+typedef unsigned gid_t;
 
 typedef uint64_t ino64_t;
 typedef int64_t off64_t;
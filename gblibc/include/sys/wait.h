@@ -3,12 +3,37 @@
 
 #include <sys/types.h>
 
+#define WNOHANG 1
+#define WEXITED 4
+#define WNOWAIT 0x01000000
+
+#define CLD_EXITED 1
+
+typedef enum {
+    P_ALL = 0,
+    P_PID = 1,
+    P_PGID = 2,
+    P_PIDFD = 3,
+} idtype_t;
+
+// the fields filled by waitid, padded to the size of linux siginfo_t
+typedef struct {
+    int si_signo;
+    int si_errno;
+    int si_code;
+    pid_t si_pid;
+    unsigned si_uid;
+    int si_status;
+    int __pad[26];
+} siginfo_t;
+
 #ifdef __cplusplus
 extern "C" {
 #endif
 
 pid_t wait(int* code);
 pid_t waitpid(pid_t pid, int* code, int options);
+int waitid(idtype_t idtype, int id, siginfo_t* info, int options);
 
 #ifdef __cplusplus
 }
@@ -0,0 +1,71 @@
+#ifndef __GBLIBC_TERMIOS_H_
+#define __GBLIBC_TERMIOS_H_
+
+#include <stdint.h>
+
+// the layout used by the TCGETS and TCSETS ioctls
+#define NCCS 19
+
+// c_cc
+#define VINTR 0
+#define VQUIT 1
+#define VERASE 2
+#define VKILL 3
+#define VEOF 4
+#define VTIME 5
+#define VMIN 6
+#define VSTART 8
+#define VSTOP 9
+#define VSUSP 10
+#define VEOL 11
+
+// c_iflag
+#define IGNCR 0000200
+#define ICRNL 0000400
+
+// c_oflag
+#define OPOST 0000001
+#define ONLCR 0000004
+
+// c_cflag
+#define CS8 0000060
+#define CREAD 0000200
+#define B38400 0000017
+
+// c_lflag
+#define ISIG 0000001
+#define ICANON 0000002
+#define ECHO 0000010
+#define ECHOE 0000020
+#define ECHOK 0000040
+#define ECHONL 0000100
+
+// tcsetattr
+#define TCSANOW 0
+#define TCSADRAIN 1
+#define TCSAFLUSH 2
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef uint8_t cc_t;
+typedef uint32_t tcflag_t;
+
+struct termios {
+    tcflag_t c_iflag;
+    tcflag_t c_oflag;
+    tcflag_t c_cflag;
+    tcflag_t c_lflag;
+    cc_t c_line;
+    cc_t c_cc[NCCS];
+};
+
+int tcgetattr(int fd, struct termios* termios_p);
+int tcsetattr(int fd, int optional_actions, const struct termios* termios_p);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
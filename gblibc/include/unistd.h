@@ -16,6 +16,8 @@ extern "C" {
 
 ssize_t read(int fd, void* buf, size_t count);
 ssize_t write(int fd, const void* buf, size_t count);
+ssize_t pread(int fd, void* buf, size_t count, off_t offset);
+ssize_t pwrite(int fd, const void* buf, size_t count, off_t offset);
 
 int dup(int oldfd);
 int dup2(int oldfd, int newfd);
@@ -31,10 +33,34 @@ int execve(const char* pathname, char* const argv[], char* const envp[]);
 unsigned int sleep(unsigned int seconds);
 
 int chdir(const char* path);
+int chroot(const char* path);
 char* getcwd(char* buf, size_t bufsize);
 
+int link(const char* oldpath, const char* newpath);
+int linkat(int olddirfd, const char* oldpath,
+    int newdirfd, const char* newpath, int flags);
+int unlink(const char* pathname);
+
+int symlink(const char* target, const char* linkpath);
+ssize_t readlink(const char* pathname, char* buf, size_t bufsize);
+
+int fsync(int fd);
+int fdatasync(int fd);
+int syncfs(int fd);
+
+ssize_t copy_file_range(int fd_in, off64_t* off_in, int fd_out,
+    off64_t* off_out, size_t len, unsigned int flags);
+
 pid_t getpid(void);
 pid_t getppid(void);
+pid_t gettid(void);
+
+uid_t getuid(void);
+uid_t geteuid(void);
+gid_t getgid(void);
+gid_t getegid(void);
+int setuid(uid_t uid);
+int setgid(gid_t gid);
 
 int setpgid(pid_t pid, pid_t pgid);
 
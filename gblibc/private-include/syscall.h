@@ -10,23 +10,64 @@
 #define SYS_open (0x05)
 #define SYS_close (0x06)
 #define SYS_waitpid (0x07)
+#define SYS_link (0x09)
+#define SYS_unlink (0x0a)
 #define SYS_execve (0x0b)
 #define SYS_chdir (0x0c)
+#define SYS_mknod (0x0e)
 #define SYS_getpid (0x14)
 #define SYS_dup (0x29)
 #define SYS_pipe (0x2a)
+#define SYS_acct (0x33)
 #define SYS_ioctl (0x36)
 #define SYS_setpgid (0x39)
+#define SYS_chroot (0x3d)
 #define SYS_dup2 (0x3f)
 #define SYS_getppid (0x40)
 #define SYS_setsid (0x42)
+#define SYS_symlink (0x53)
+#define SYS_readlink (0x55)
+#define SYS_statfs (0x63)
+#define SYS_fstatfs (0x64)
+#define SYS_fsync (0x76)
 #define SYS_getdents (0x84)
+#define SYS_readv (0x91)
 #define SYS_writev (0x92)
 #define SYS_getsid (0x93)
+#define SYS_fdatasync (0x94)
+#define SYS_pread64 (0xb4)
+#define SYS_pwrite64 (0xb5)
 #define SYS_getcwd (0xb7)
+#define SYS_getuid32 (0xc7)
+#define SYS_getgid32 (0xc8)
+#define SYS_geteuid32 (0xc9)
+#define SYS_getegid32 (0xca)
+#define SYS_setuid32 (0xd5)
+#define SYS_setgid32 (0xd6)
+#define SYS_gettid (0xe0)
 #define SYS_set_thread_area (0xf3)
 #define SYS_exit_group (0xfc)
 #define SYS_set_tid_address (0x102)
+#define SYS_waitid (0x11c)
+#define SYS_inotify_init (0x123)
+#define SYS_inotify_add_watch (0x124)
+#define SYS_inotify_rm_watch (0x125)
+#define SYS_linkat (0x12f)
+#define SYS_splice (0x139)
+#define SYS_tee (0x13b)
+#define SYS_vmsplice (0x13c)
+#define SYS_getcpu (0x13e)
+#define SYS_inotify_init1 (0x14c)
+#define SYS_preadv (0x14d)
+#define SYS_pwritev (0x14e)
+#define SYS_syncfs (0x158)
+#define SYS_process_vm_readv (0x15b)
+#define SYS_process_vm_writev (0x15c)
+#define SYS_kcmp (0x15d)
+#define SYS_copy_file_range (0x179)
+#define SYS_utimensat_time64 (0x19c)
+#define SYS_pidfd_send_signal (0x1a8)
+#define SYS_pidfd_open (0x1b2)
 
 #ifdef __cplusplus
 extern "C" {
@@ -82,6 +123,45 @@ static inline uint32_t syscall3(uint32_t no, uint32_t arg1, uint32_t arg2, uint3
         : "eax", "ebx", "ecx", "edx");
     return no;
 }
+static inline uint32_t syscall5(uint32_t no, uint32_t arg1, uint32_t arg2,
+    uint32_t arg3, uint32_t arg4, uint32_t arg5)
+{
+    asm volatile(
+        "movl %1, %%ebx\n"
+        "movl %2, %%ecx\n"
+        "movl %3, %%edx\n"
+        "movl %4, %%esi\n"
+        "movl %5, %%edi\n"
+        "movl %6, %%eax\n"
+        "int $0x80\n"
+        "movl %%eax, %0"
+        : "=g"(no)
+        : "g"(arg1), "g"(arg2), "g"(arg3), "g"(arg4), "g"(arg5), "g"(no)
+        : "eax", "ebx", "ecx", "edx", "esi", "edi");
+    return no;
+}
+static inline uint32_t syscall6(uint32_t no, uint32_t arg1, uint32_t arg2,
+    uint32_t arg3, uint32_t arg4, uint32_t arg5, uint32_t arg6)
+{
+    // ebp can't be clobbered and the operands might be addressed
+    // relative to esp, so everything is loaded through eax
+    uint32_t args[7] = { arg1, arg2, arg3, arg4, arg5, arg6, no };
+    asm volatile(
+        "pushl %%ebp\n"
+        "movl 0(%%eax), %%ebx\n"
+        "movl 4(%%eax), %%ecx\n"
+        "movl 8(%%eax), %%edx\n"
+        "movl 12(%%eax), %%esi\n"
+        "movl 16(%%eax), %%edi\n"
+        "movl 20(%%eax), %%ebp\n"
+        "movl 24(%%eax), %%eax\n"
+        "int $0x80\n"
+        "popl %%ebp"
+        : "=a"(no)
+        : "a"(args), "m"(args)
+        : "ebx", "ecx", "edx", "esi", "edi", "memory");
+    return no;
+}
 
 #ifdef __cplusplus
 }
@@ -0,0 +1,7 @@
+#include <sys/acct.h>
+#include <syscall.h>
+
+int acct(const char* filename)
+{
+    return syscall1(SYS_acct, (uint32_t)filename);
+}
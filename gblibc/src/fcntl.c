@@ -5,3 +5,21 @@ int open(const char* filename, int flags, ...)
 {
     return syscall2(SYS_open, (uint32_t)filename, flags);
 }
+
+ssize_t splice(int fd_in, off64_t* off_in, int fd_out,
+    off64_t* off_out, size_t len, unsigned int flags)
+{
+    return syscall6(SYS_splice, fd_in, (uint32_t)off_in,
+        fd_out, (uint32_t)off_out, len, flags);
+}
+
+ssize_t tee(int fd_in, int fd_out, size_t len, unsigned int flags)
+{
+    return syscall5(SYS_tee, fd_in, fd_out, len, flags, 0);
+}
+
+ssize_t vmsplice(int fd, const struct iovec* iov,
+    size_t nr_segs, unsigned int flags)
+{
+    return syscall5(SYS_vmsplice, fd, (uint32_t)iov, nr_segs, flags, 0);
+}
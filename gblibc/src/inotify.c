@@ -0,0 +1,22 @@
+#include <sys/inotify.h>
+#include <syscall.h>
+
+int inotify_init(void)
+{
+    return syscall0(SYS_inotify_init);
+}
+
+int inotify_init1(int flags)
+{
+    return syscall1(SYS_inotify_init1, flags);
+}
+
+int inotify_add_watch(int fd, const char* pathname, uint32_t mask)
+{
+    return syscall3(SYS_inotify_add_watch, fd, (uint32_t)pathname, mask);
+}
+
+int inotify_rm_watch(int fd, int wd)
+{
+    return syscall2(SYS_inotify_rm_watch, fd, wd);
+}
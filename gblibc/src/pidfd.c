@@ -0,0 +1,13 @@
+#include <sys/pidfd.h>
+#include <syscall.h>
+
+int pidfd_open(pid_t pid, unsigned int flags)
+{
+    return syscall2(SYS_pidfd_open, (uint32_t)pid, flags);
+}
+
+int pidfd_send_signal(int pidfd, int sig, void* info, unsigned int flags)
+{
+    return syscall5(SYS_pidfd_send_signal, (uint32_t)pidfd, (uint32_t)sig,
+        (uint32_t)info, flags, 0);
+}
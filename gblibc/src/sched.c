@@ -0,0 +1,7 @@
+#include <sched.h>
+#include <syscall.h>
+
+int getcpu(unsigned int* cpu, unsigned int* node)
+{
+    return syscall3(SYS_getcpu, (uint32_t)cpu, (uint32_t)node, 0);
+}
@@ -0,0 +1,24 @@
+#include <sys/stat.h>
+#include <syscall.h>
+
+int mknod(const char* pathname, mode_t mode, dev_t dev)
+{
+    return syscall3(SYS_mknod, (uint32_t)pathname, mode, dev);
+}
+
+int mkfifo(const char* pathname, mode_t mode)
+{
+    return mknod(pathname, (mode & 07777) | S_IFIFO, 0);
+}
+
+int utimensat(int dirfd, const char* pathname,
+    const struct timespec times[2], int flags)
+{
+    return syscall5(SYS_utimensat_time64, dirfd, (uint32_t)pathname,
+        (uint32_t)times, flags, 0);
+}
+
+int futimens(int fd, const struct timespec times[2])
+{
+    return utimensat(fd, NULL, times, 0);
+}
@@ -0,0 +1,12 @@
+#include <sys/statfs.h>
+#include <syscall.h>
+
+int statfs(const char* path, struct statfs* buf)
+{
+    return syscall2(SYS_statfs, (uint32_t)path, (uint32_t)buf);
+}
+
+int fstatfs(int fd, struct statfs* buf)
+{
+    return syscall2(SYS_fstatfs, fd, (uint32_t)buf);
+}
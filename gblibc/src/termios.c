@@ -0,0 +1,16 @@
+#include <bits/ioctl.h>
+#include <termios.h>
+#include <syscall.h>
+
+int tcgetattr(int fd, struct termios* termios_p)
+{
+    return syscall3(SYS_ioctl, fd, TCGETS, (uint32_t)termios_p);
+}
+
+int tcsetattr(int fd, int optional_actions, const struct termios* termios_p)
+{
+    if (optional_actions < TCSANOW || optional_actions > TCSAFLUSH)
+        return -1;
+
+    return syscall3(SYS_ioctl, fd, TCSETS + optional_actions, (uint32_t)termios_p);
+}
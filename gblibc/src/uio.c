@@ -0,0 +1,47 @@
+#include <sys/kcmp.h>
+#include <sys/uio.h>
+#include <syscall.h>
+
+ssize_t readv(int fd, const struct iovec* iov, int iovcnt)
+{
+    return syscall3(SYS_readv, fd, (uint32_t)iov, iovcnt);
+}
+
+ssize_t writev(int fd, const struct iovec* iov, int iovcnt)
+{
+    return syscall3(SYS_writev, fd, (uint32_t)iov, iovcnt);
+}
+
+ssize_t preadv(int fd, const struct iovec* iov, int iovcnt, off_t offset)
+{
+    return syscall5(SYS_preadv, fd, (uint32_t)iov, iovcnt, offset, offset < 0 ? -1 : 0);
+}
+
+ssize_t pwritev(int fd, const struct iovec* iov, int iovcnt, off_t offset)
+{
+    return syscall5(SYS_pwritev, fd, (uint32_t)iov, iovcnt, offset, offset < 0 ? -1 : 0);
+}
+
+ssize_t process_vm_readv(pid_t pid,
+    const struct iovec* local_iov, unsigned long liovcnt,
+    const struct iovec* remote_iov, unsigned long riovcnt,
+    unsigned long flags)
+{
+    return syscall6(SYS_process_vm_readv, pid,
+        (uint32_t)local_iov, liovcnt, (uint32_t)remote_iov, riovcnt, flags);
+}
+
+ssize_t process_vm_writev(pid_t pid,
+    const struct iovec* local_iov, unsigned long liovcnt,
+    const struct iovec* remote_iov, unsigned long riovcnt,
+    unsigned long flags)
+{
+    return syscall6(SYS_process_vm_writev, pid,
+        (uint32_t)local_iov, liovcnt, (uint32_t)remote_iov, riovcnt, flags);
+}
+
+int kcmp(pid_t pid1, pid_t pid2, int type,
+    unsigned long idx1, unsigned long idx2)
+{
+    return syscall5(SYS_kcmp, pid1, pid2, type, idx1, idx2);
+}
@@ -13,6 +13,16 @@ ssize_t write(int fd, const void* buf, size_t count)
     return syscall3(SYS_write, fd, (uint32_t)buf, count);
 }
 
+ssize_t pread(int fd, void* buf, size_t count, off_t offset)
+{
+    return syscall5(SYS_pread64, fd, (uint32_t)buf, count, offset, offset < 0 ? -1 : 0);
+}
+
+ssize_t pwrite(int fd, const void* buf, size_t count, off_t offset)
+{
+    return syscall5(SYS_pwrite64, fd, (uint32_t)buf, count, offset, offset < 0 ? -1 : 0);
+}
+
 int dup(int oldfd)
 {
     return syscall1(SYS_dup, oldfd);
@@ -60,11 +70,65 @@ int chdir(const char* path)
     return syscall1(SYS_chdir, (uint32_t)path);
 }
 
+int chroot(const char* path)
+{
+    return syscall1(SYS_chroot, (uint32_t)path);
+}
+
 char* getcwd(char* buf, size_t bufsize)
 {
     return (char*)syscall2(SYS_getcwd, (uint32_t)buf, bufsize);
 }
 
+int link(const char* oldpath, const char* newpath)
+{
+    return syscall2(SYS_link, (uint32_t)oldpath, (uint32_t)newpath);
+}
+
+int linkat(int olddirfd, const char* oldpath,
+    int newdirfd, const char* newpath, int flags)
+{
+    return syscall5(SYS_linkat, olddirfd, (uint32_t)oldpath,
+        newdirfd, (uint32_t)newpath, flags);
+}
+
+int unlink(const char* pathname)
+{
+    return syscall1(SYS_unlink, (uint32_t)pathname);
+}
+
+int symlink(const char* target, const char* linkpath)
+{
+    return syscall2(SYS_symlink, (uint32_t)target, (uint32_t)linkpath);
+}
+
+ssize_t readlink(const char* pathname, char* buf, size_t bufsize)
+{
+    return syscall3(SYS_readlink, (uint32_t)pathname, (uint32_t)buf, bufsize);
+}
+
+int fsync(int fd)
+{
+    return syscall1(SYS_fsync, fd);
+}
+
+int fdatasync(int fd)
+{
+    return syscall1(SYS_fdatasync, fd);
+}
+
+int syncfs(int fd)
+{
+    return syscall1(SYS_syncfs, fd);
+}
+
+ssize_t copy_file_range(int fd_in, off64_t* off_in, int fd_out,
+    off64_t* off_out, size_t len, unsigned int flags)
+{
+    return syscall6(SYS_copy_file_range, fd_in, (uint32_t)off_in,
+        fd_out, (uint32_t)off_out, len, flags);
+}
+
 pid_t getpid(void)
 {
     return syscall0(SYS_getpid);
@@ -75,6 +139,41 @@ pid_t getppid(void)
     return syscall0(SYS_getppid);
 }
 
+pid_t gettid(void)
+{
+    return syscall0(SYS_gettid);
+}
+
+uid_t getuid(void)
+{
+    return syscall0(SYS_getuid32);
+}
+
+uid_t geteuid(void)
+{
+    return syscall0(SYS_geteuid32);
+}
+
+gid_t getgid(void)
+{
+    return syscall0(SYS_getgid32);
+}
+
+gid_t getegid(void)
+{
+    return syscall0(SYS_getegid32);
+}
+
+int setuid(uid_t uid)
+{
+    return syscall1(SYS_setuid32, uid);
+}
+
+int setgid(gid_t gid)
+{
+    return syscall1(SYS_setgid32, gid);
+}
+
 int setpgid(pid_t pid, pid_t pgid)
 {
     return syscall2(SYS_setpgid, pid, pgid);
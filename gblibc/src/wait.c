@@ -10,3 +10,9 @@ pid_t wait(int* code)
 {
     return waitpid(-1, code, 0);
 }
+
+int waitid(idtype_t idtype, int id, siginfo_t* info, int options)
+{
+    return syscall5(SYS_waitid, (uint32_t)idtype, (uint32_t)id,
+        (uint32_t)info, (uint32_t)options, 0);
+}
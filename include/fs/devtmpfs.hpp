@@ -0,0 +1,17 @@
+#pragma once
+
+#include <kernel/vfs.hpp>
+#include <sys/types.h>
+
+namespace fs::devtmpfs {
+
+// record a device node named 'name' under /dev
+// the node is created at once if devtmpfs is already mounted
+// and the nodes with duplicate names are ignored
+void add(const char* name, mode_t mode, node_t node);
+
+// mount a new devtmpfs instance at mnt and
+// create all the device nodes recorded so far
+int mount(vfs::dentry* mnt);
+
+} // namespace fs::devtmpfs
@@ -101,11 +101,37 @@ struct PACKED directory_entry {
     uint32_t size;
 };
 
+// VFAT long file name entry, stored before the short name entry
+struct PACKED long_name_entry {
+    uint8_t ord;
+    uint16_t name1[5];
+    // always 0x0f
+    uint8_t attributes;
+    uint8_t type;
+    // checksum of the short name
+    uint8_t checksum;
+    uint16_t name2[6];
+    uint16_t cluster_lo;
+    uint16_t name3[2];
+};
+
 // TODO: deallocate inodes when dentry is destroyed
 class fat32 : public virtual fs::vfs {
 private:
     constexpr static uint32_t SECTOR_SIZE = 512;
     constexpr static cluster_t EOC = 0xffffff8;
+    // value written to the fat to mark the end of a chain
+    constexpr static cluster_t EOC_MARK = 0x0fffffff;
+    constexpr static cluster_t CLUSTER_MASK = 0x0fffffff;
+
+    constexpr static uint8_t DIRENT_FREE = 0xe5;
+
+    constexpr static uint8_t LFN_ATTR = 0x0f;
+    constexpr static uint8_t LFN_LAST = 0x40;
+    constexpr static uint8_t LFN_ORD_MASK = 0x1f;
+    constexpr static int LFN_MAX_CHARS = 255;
+    // a name of LFN_MAX_CHARS takes up to this many long name entries
+    constexpr static int LFN_MAX_ENTRIES = 20;
 
 private:
     uint32_t sector_cnt;
@@ -118,6 +144,7 @@ private:
     // TODO: use block device special node id
     inode* device;
     uint16_t reserved_sectors;
+    uint16_t fs_info_sector_no;
     uint8_t fat_copies;
     uint8_t sectors_per_cluster;
     char label[12];
@@ -130,19 +157,125 @@ private:
     };
     types::hash_map<cluster_t, buf_object> buf;
 
+    // where the directory entry of a file is stored
+    struct dirent_location {
+        cluster_t cluster;
+        // index of the entry in the cluster
+        uint32_t idx;
+    };
+    types::hash_map<ino_t, dirent_location> dirent_locs;
+
+    // the inode number of a directory is its first cluster, files
+    // may have no cluster at all, so they are numbered by where their
+    // entries are from next_file_ino on, which is above any cluster
+    types::hash_map<uint64_t, ino_t> file_inos;
+    ino_t next_file_ino;
+    // the first cluster of the files, 0 if a file is empty
+    types::hash_map<ino_t, cluster_t> file_clusters;
+
     // buf MUST be larger than 512 bytes
     inline void _raw_read_sector(void* buf, uint32_t sector_no);
+    // @return GB_OK or negative error code
+    inline int _raw_write_sector(const void* buf, uint32_t sector_no);
 
     // buf MUST be larger than 4096 bytes
     inline void _raw_read_cluster(void* buf, cluster_t no);
+    inline int _raw_write_cluster(const void* buf, cluster_t no);
 
     // buffered version, release_cluster(cluster_no) after used
     char* read_cluster(cluster_t no);
     void release_cluster(cluster_t no);
+    // write the buffered content of cluster back to the disk
+    // the cluster MUST have been read by read_cluster()
+    // @return GB_OK or negative error code
+    int write_back_cluster(cluster_t no);
+
+    constexpr size_t cluster_size(void) const
+    { return sectors_per_cluster * SECTOR_SIZE; }
 
-    static constexpr cluster_t cl(const inode* ind)
+    constexpr cluster_t cluster_cnt(void) const
     {
-        return ind->ino;
+        cluster_t cnt = (sector_cnt - data_region_offset) / sectors_per_cluster + 2;
+        cluster_t max = sectors_per_fat * (SECTOR_SIZE / sizeof(cluster_t));
+        return cnt < max ? cnt : max;
+    }
+
+    // update the fat entry of cluster no in every copy of the fat
+    // the entry in memory is updated even if writing it fails
+    // @return GB_OK or negative error code
+    int set_fat(cluster_t no, cluster_t val);
+
+    // allocate a zeroed cluster and append it to the chain ending at prev
+    // if prev is 0, a new chain is created
+    // @param out_no the cluster allocated
+    // @return GB_OK, -ENOSPC if no space is left on the device
+    //         or other negative error code
+    int alloc_cluster(cluster_t prev, cluster_t& out_no);
+
+    // free the chain starting at cluster no
+    // @return GB_OK or negative error code
+    int free_chain(cluster_t no);
+
+    int sync_fs_info(void);
+
+    // find cnt consecutive free entries in directory dir,
+    // extend it if there is not enough space
+    // the entries are always in the same cluster
+    // @param out_idx index of the first entry in cluster out_cl
+    // @return nullptr if cnt entries don't fit in a cluster or
+    //         no space is left on the device
+    //         otherwise, release_cluster(out_cl) after used
+    directory_entry* alloc_dirents(inode* dir, int cnt, cluster_t& out_cl, uint32_t& out_idx);
+
+    void set_dirent_location(ino_t ino, cluster_t cluster, uint32_t idx);
+
+    // the inode number of the file whose entry is at index idx of
+    // cluster no, a new one is given if the file has none yet
+    // @param first the first cluster of the file stored in the entry
+    ino_t file_ino(cluster_t no, uint32_t idx, cluster_t first);
+
+    // update the directory entry of file on the disk
+    // @return GB_OK or negative error code
+    int sync_dirent(inode* file);
+
+    // create a new entry named filename in directory dir
+    // new files are empty and have no cluster allocated
+    // @param out_ino the inode number of the new file or directory
+    // @return GB_OK or a negative error code
+    int create_entry(dentry* dir, const char* filename, bool is_dir, ino_t& out_ino);
+
+    // generate a short name in the 8.3 format for name
+    // @param out the 11 byte name and extension, padded with spaces
+    // @param out_case the VFAT lowercase flags
+    // @return true if the name can be represented by the short name
+    static bool make_short_name(const char* name, char* out, uint8_t& out_case);
+
+    bool short_name_exists(inode* dir, const char* name);
+    // names are looked up ignoring the case on fat
+    bool name_exists(inode* dir, const char* name);
+
+    static inline bool is_long_name(const directory_entry* d)
+    { return reinterpret_cast<const uint8_t*>(d)[11] == LFN_ATTR; }
+
+    static uint8_t short_name_checksum(const char* name);
+
+    static uint16_t lfn_char(const long_name_entry* ent, int i);
+    static void set_lfn_char(long_name_entry* ent, int i, uint16_t ch);
+
+    // @return number of utf-16 code units, -EINVAL if str is not
+    //         valid utf-8 or -ENAMETOOLONG if it's longer than max
+    //         code units
+    static int utf8_to_utf16(const char* str, uint16_t* out, int max);
+    static void utf16_to_utf8(const uint16_t* str, types::string<>& out);
+
+    // @return the first cluster of ind, 0 if it's an empty file
+    cluster_t cl(const inode* ind)
+    {
+        if (!S_ISREG(ind->mode))
+            return ind->ino;
+
+        auto iter = file_clusters.find(ind->ino);
+        return iter ? iter->second : 0;
     }
 
     static inline cluster_t _rearrange(directory_entry* d)
@@ -167,8 +300,19 @@ public:
     ~fat32();
 
     virtual size_t inode_read(inode* file, char* buf, size_t buf_size, size_t offset, size_t n) override;
+    virtual size_t inode_write(inode* file, const char* buf, size_t offset, size_t n) override;
+    virtual int inode_mkfile(dentry* dir, const char* filename, mode_t mode) override;
+    virtual int inode_mkdir(dentry* dir, const char* dirname) override;
+    virtual int inode_truncate(inode* file, size_t size) override;
     virtual int inode_stat(dentry* ent, statx* st, unsigned int mask) override;
     virtual int inode_readdir(fs::inode* dir, size_t offset, const fs::vfs::filldir_func& callback) override;
+
+    virtual int sync_fs(void) override;
+    virtual int inode_sync(inode* file, bool datasync) override;
+    virtual int statfs(struct statfs* buf) override;
+
+    virtual bool use_page_cache(void) const override
+    { return true; }
 };
 
 }; // namespace fs::fat
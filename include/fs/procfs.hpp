@@ -0,0 +1,43 @@
+#pragma once
+
+#include <fs/pseudofs.hpp>
+#include <kernel/vfs.hpp>
+#include <sys/types.h>
+
+namespace fs::procfs {
+
+// the global procfs instance, created on first use
+//
+// layout:
+// /meminfo        physical memory usage
+// /stat           scheduler and interrupt statistics
+// /uptime         seconds since boot
+// /boottime       time spent in each boot phase
+// /kstackinfo     kernel stack pool statistics
+// /self           link to the directory of the calling process
+// /thread-self    link to the directory of the calling thread
+// /kallsyms       the symbols of the kernel text
+// /<pid>/status   human readable process status
+// /<pid>/stat     process status in the format of linux
+// /<pid>/cmdline  arguments separated by '\0'
+// /<pid>/maps     user memory areas
+// /<pid>/mem      user memory, addressed by file offset
+// /<pid>/exe      link to the executable
+// /<pid>/fd/<n>   links to the opened files, or their types
+// /<pid>/fdinfo/<n>  offset, flags and inode of the opened files
+//                 followed by the lines given by file::describe()
+// /<pid>/task/<tid>/  status, stat, cmdline, maps and exe of the thread
+// /sys/kernel/tainted   the taint mask of the kernel
+// /sys/kernel/kptr_restrict  who sees the addresses in /kallsyms
+// /sys/kernel/syscall_trace  whether the syscalls are logged
+// /sys/fs/binfmt_misc/  register, status and the registered interpreters
+pseudofs* instance(void);
+
+int mount(vfs::dentry* mnt);
+
+// create or remove /proc/<pid>
+// called by proclist when processes come and go
+void add_process(pid_t pid);
+void remove_process(pid_t pid);
+
+} // namespace fs::procfs
@@ -0,0 +1,91 @@
+#pragma once
+
+#include <functional>
+#include <list>
+#include <map>
+
+#include <kernel/vfs.hpp>
+#include <stdint.h>
+#include <types/string.hpp>
+
+namespace fs {
+
+// in-memory filesystem whose regular files are generated on read
+class pseudofs : public virtual vfs {
+public:
+    // fill buf with the content of the file
+    // @return bytes written, no more than buf_size
+    using show_func = std::function<size_t(char* buf, size_t buf_size)>;
+
+    // called before the entries of a directory are read
+    // so that it can update them with add_file() and remove()
+    using refresh_func = std::function<void(void)>;
+
+    // for files accessed at arbitrary offsets instead of generated
+    // @return bytes read or written, or negative error code
+    using read_func = std::function<ssize_t(char* buf, size_t offset, size_t n)>;
+    using write_func = std::function<ssize_t(const char* buf, size_t offset, size_t n)>;
+
+    // files larger than this are truncated
+    static constexpr size_t MAX_FILE_SIZE = 4096;
+
+private:
+    struct node {
+        ino_t ino;
+        types::string<> name;
+        node* parent;
+        // generates the file content, or the target of symbolic links
+        show_func show;
+        refresh_func refresh;
+        std::list<node*> children;
+        read_func read;
+        write_func write;
+        // removed while files are opened in it, freed on inode_release()
+        bool dead;
+    };
+
+    std::map<ino_t, node*> m_nodes;
+    ino_t m_next_ino { 1 };
+    // reported as f_type by statfs
+    uint32_t m_magic;
+
+    node* new_node(node* parent, const char* name, mode_t mode, show_func show);
+    node* lookup(const types::path& path);
+    void free_node(node* n);
+    void mark_dead(node* n);
+
+    // @return dentry of n if its parents have been loaded
+    dentry* loaded_dentry(node* n);
+
+protected:
+    // create a node at path relative to the root
+    // parent directories should have been created
+    // @return 0 or negative error code
+    int create(const char* path, mode_t mode, show_func show, refresh_func refresh);
+
+public:
+    explicit pseudofs(uint32_t magic);
+
+    int mkdir(const char* path, mode_t perm = 0555, refresh_func refresh = nullptr);
+    int add_file(const char* path, show_func show, mode_t perm = 0444);
+    int add_rw_file(const char* path, read_func read, write_func write, mode_t perm = 0600);
+    // the link target is generated by show every time the link is followed
+    int add_symlink(const char* path, show_func show);
+
+    // remove the node at path, directories are removed recursively
+    // the files opened in them fail with ESRCH from then on
+    int remove(const char* path);
+    // remove all the entries in directory path
+    int clear(const char* path);
+
+    virtual size_t inode_read(inode* file, char* buf, size_t buf_size, size_t offset, size_t n) override;
+    virtual size_t inode_write(inode* file, const char* buf, size_t offset, size_t n) override;
+    virtual int inode_readlink(inode* link, char* buf, size_t buf_size) override;
+    virtual int inode_stat(dentry* ent, statx* st, unsigned int mask) override;
+    virtual int inode_readdir(inode* dir, size_t offset, const filldir_func& callback) override;
+    virtual void inode_release(inode* ind) override;
+
+    virtual int statfs(struct statfs* buf) override;
+};
+
+} // namespace fs
@@ -0,0 +1,18 @@
+#pragma once
+
+#include <fs/pseudofs.hpp>
+#include <kernel/vfs.hpp>
+
+namespace fs::sysfs {
+
+// the global sysfs instance, created on first use so that
+// the drivers can add their entries before it is mounted
+//
+// layout:
+// /bus/pci/devices/<domain:bus:dev.func>/ pci devices
+// /block/<name>/                         block devices
+pseudofs* instance(void);
+
+int mount(vfs::dentry* mnt);
+
+} // namespace fs::sysfs
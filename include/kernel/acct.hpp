@@ -0,0 +1,16 @@
+#pragma once
+
+#include <kernel/vfs.hpp>
+
+class process;
+
+namespace kernel::acct {
+
+// start writing accounting records to file, or stop if file is null
+int set_file(fs::inode* file);
+
+// append the accounting record of proc to the accounting file
+// MUST be called before the user memory of proc is released
+void record_exit(const process& proc, int exit_code);
+
+} // namespace kernel::acct
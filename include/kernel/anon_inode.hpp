@@ -0,0 +1,53 @@
+#pragma once
+
+#include <list>
+
+#include <kernel/vfs.hpp>
+
+namespace fs {
+
+// base of the files backed by kernel objects rather than by the
+// inodes of some filesystem, e.g. inotify instances
+//
+// derived classes SHOULD define ANON_NAME to be used with anon_file_cast
+struct anon_file : public virtual file {
+private:
+    const char* m_name;
+    bool m_nonblock;
+    // the inode number shown in fdinfo, 0 if they are all used
+    ino_t m_ino;
+
+public:
+    anon_file(const char* name, file_flags flags, bool nonblock = false);
+    virtual ~anon_file();
+
+    constexpr const char* name(void) const
+    { return m_name; }
+    constexpr bool nonblock(void) const
+    { return m_nonblock; }
+    constexpr ino_t ino(void) const
+    { return m_ino; }
+
+    virtual ssize_t read(char* __user buf, size_t n) override;
+    virtual ssize_t write(const char* __user buf, size_t n) override;
+    virtual void close() override;
+};
+
+// all the living anon files
+const std::list<anon_file*>& anon_files(void);
+
+// @param name if not null, the file should also have the name
+// @return nullptr if fp is not an anon file with the given name
+anon_file* anon_file_from(file* fp, const char* name = nullptr);
+
+template <typename T>
+inline T* anon_file_cast(file* fp)
+{
+    return static_cast<T*>(anon_file_from(fp, T::ANON_NAME));
+}
+
+// install the file into the file table of the current process
+// @return the new fd
+int anon_inode_getfd(anon_file* file);
+
+} // namespace fs
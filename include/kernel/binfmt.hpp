@@ -0,0 +1,48 @@
+#pragma once
+
+#include <stdint.h>
+#include <types/elf.hpp>
+
+namespace kernel::binfmt {
+
+// bytes at the start of the executable given to the handlers
+constexpr size_t BINPRM_BUF_SIZE = 128;
+
+// how many interpreters may be chained, e.g. a script run by a script
+constexpr int MAX_INTERP_DEPTH = 4;
+
+struct binprm {
+    types::elf::elf32_load_data* data;
+    char buf[BINPRM_BUF_SIZE];
+    size_t len;
+    int depth;
+};
+
+// @return GB_OK, -ENOEXEC if the format is not recognized so that
+// the next handler is tried, or other negative error codes
+//
+// the handlers running an interpreter replace data->exec_dent and
+// data->argv and call search_handler() again
+using load_func = int (*)(binprm* prm);
+
+struct binfmt {
+    const char* name;
+    load_func load;
+};
+
+// handlers registered in front are tried before the others
+void register_binfmt(const binfmt* fmt, bool front = false);
+void unregister_binfmt(const binfmt* fmt);
+
+// load data->exec_dent into the current process, errcode is set on failure
+// @return GB_OK or GB_FAILED
+int exec(types::elf::elf32_load_data* data);
+
+// read the head of data->exec_dent and pass it to the handlers
+// @return GB_OK or negative error code
+int search_handler(types::elf::elf32_load_data* data, int depth);
+
+// create /proc/sys/fs/binfmt_misc
+void init_misc(void);
+
+} // namespace kernel::binfmt
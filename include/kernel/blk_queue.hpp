@@ -0,0 +1,130 @@
+#pragma once
+
+#include <cstddef>
+#include <functional>
+#include <list>
+
+#include <kernel/event/evtqueue.hpp>
+#include <kernel/sg_list.hpp>
+#include <stdint.h>
+#include <types/cplusplus.hpp>
+#include <types/types.h>
+
+namespace kernel::block {
+
+constexpr std::size_t SECTOR_SIZE = 512;
+
+// transfer the whole sectors in the memory of sg starting at lba
+// @return 0 or negative error code
+using submit_func = std::function<int(uint64_t lba, const memory::sg_list& sg, bool write)>;
+
+// write the volatile cache of the device to the medium
+// @return 0 or negative error code
+using flush_func = std::function<int(void)>;
+
+// the requests to a block device on their way to the driver
+//
+// the pending requests are kept sorted by lba. those of the highest
+// priority pending are served in one direction, starting over from the
+// lowest lba at the end (c-look). a request that continues the one
+// served, in the same direction, is merged into the same transfer of
+// at most max_sectors
+//
+// a flush is a barrier: it's done after all the requests queued before
+// it, and none of those queued after it is served before it's done
+//
+// whoever finds fewer than depth transfers in flight drains the queue,
+// the others wait for their requests to be done. with a depth of more
+// than one, the transfers of different tasks overlap on the device
+class request_queue : public types::non_copyable {
+public:
+    // the lower ones are served first
+    enum class priority {
+        // someone is waiting for the data
+        sync_read,
+        write,
+        // write back in the background
+        background,
+    };
+
+private:
+    struct request {
+        uint64_t lba;
+        std::size_t nsect;
+        bool write;
+        priority prio;
+        const memory::sg_list* sg;
+        // the number of dispatches done when it was queued
+        std::size_t queued_at;
+        // the number of flushes queued before it
+        std::size_t epoch;
+        int status;
+        bool done;
+    };
+
+    submit_func m_submit;
+    std::size_t m_max_sectors;
+    std::size_t m_capacity;
+    std::size_t m_depth;
+    flush_func m_flush;
+
+    // protects the fields below, notified when a batch is done
+    kernel::cond_var m_cv;
+    std::list<request*> m_pending;
+    // the flushes in the order they're queued
+    std::list<request*> m_flushes;
+    std::size_t m_flushes_queued {};
+    std::size_t m_flushes_done {};
+    // the lba after the last transfer
+    uint64_t m_head {};
+    std::size_t m_dispatches {};
+    // the number of tasks dispatching at the moment
+    std::size_t m_inflight {};
+
+    // whether there's something to serve with self of the
+    // tasks in flight being the one asking
+    // m_cv.mtx() MUST be held
+    bool ready(std::size_t self) const;
+    // @return the request to serve next, or the end of m_pending
+    //         if none may be served before the next flush
+    // m_cv.mtx() MUST be held
+    std::list<request*>::iterator pick(void);
+    // serve the pending requests and flushes until there is none
+    // we may serve, m_cv.mtx() MUST be held, it's released during
+    // the transfers
+    void dispatch(void);
+    // wait for req to be done, dispatching if no one else does
+    // m_cv.mtx() MUST be held
+    // @return whether we have dispatched
+    bool wait_done(request& req);
+
+public:
+    // requests passed over for this many transfers are served next,
+    // whatever their priority
+    static constexpr std::size_t DEADLINE = 16;
+
+    // @param capacity the number of sectors of the device
+    // @param depth the number of transfers submit can take at once
+    // @param flush nullptr if the device has no volatile cache
+    request_queue(submit_func submit, std::size_t max_sectors,
+        std::size_t capacity = -1U, std::size_t depth = 1,
+        flush_func flush = nullptr);
+
+    // transfer the sectors in the memory of sg starting at lba,
+    // sg.len() MUST be a multiple of SECTOR_SIZE
+    // @return 0 or negative error code
+    int transfer(uint64_t lba, const memory::sg_list& sg, bool write, priority prio);
+    // reads are sync_read and writes are write
+    int transfer(uint64_t lba, const memory::sg_list& sg, bool write);
+
+    // wait for the requests queued so far, then the cache of the device
+    // @return 0 or negative error code
+    int flush(void);
+
+    // byte granular access as blkdev_ops needs, the partial
+    // sectors at both ends are read before they are written
+    ssize_t read(char* buf, std::size_t buf_size, std::size_t offset, std::size_t n);
+    ssize_t write(const char* buf, std::size_t offset, std::size_t n);
+};
+
+} // namespace kernel::block
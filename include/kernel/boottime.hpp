@@ -0,0 +1,66 @@
+#pragma once
+
+#include <cstddef>
+
+#include <stdint.h>
+#include <types/types.h>
+
+namespace kernel::kinit {
+
+constexpr int MAX_BOOT_PHASES = 32;
+
+struct boot_phase_record {
+    const char* name;
+    uint64_t tsc;
+};
+
+inline uint64_t rdtsc(void)
+{
+    uint64_t val;
+    asm volatile("rdtsc" : "=A"(val));
+    return val;
+}
+
+// mark the start of a boot phase, the previous phase ends here
+// this MUST NOT allocate memory since it's called before init_mem()
+void boot_phase(const char* name);
+
+// mark the point where the PIT starts ticking, used to convert
+// time stamp counter values into milliseconds
+void boot_phase_calibrate(void);
+
+// init functions run by run_initcalls() level by level
+// @return GB_OK or negative error code
+using initcall_func = int (*)(void);
+
+struct initcall {
+    const char* name;
+    initcall_func func;
+};
+
+// register func to be run by run_initcalls() as boot phase name
+//
+// level is a single digit, the lower levels are run first. the link
+// order of the initcalls of a level, and so the order they're run in,
+// is up to the compiler, which may well reverse those of a file
+//
+// 1: the pseudo filesystems that don't depend on each other
+// 2: filling in the procfs tree before it's mounted
+// 3: mounting procfs
+#define INITCALL(level, name, func) \
+    SECTION(".initcalls." #level) __attribute__((used)) \
+    kernel::kinit::initcall const name##_initcall = { #name, (func) }
+
+// run the registered init functions, each timed as a boot phase,
+// the ones that fail are reported and the rest are run anyway
+void run_initcalls(void);
+
+// print the time spent in each boot phase, the last phase ends here
+void report_boot_phases(void);
+
+// the lines printed by report_boot_phases() without the prefix,
+// for /proc/boottime, nothing is written before boot is done
+// @return the number of bytes written
+std::size_t show_boot_phases(char* buf, std::size_t buf_size);
+
+} // namespace kernel::kinit
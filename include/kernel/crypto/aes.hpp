@@ -0,0 +1,43 @@
+#pragma once
+
+#include <cstddef>
+
+#include <stdint.h>
+
+namespace kernel::crypto {
+
+constexpr std::size_t AES_BLOCK_SIZE = 16;
+
+// software implementation of AES-128 and AES-256
+class aes {
+private:
+    // 15 round keys at most (AES-256)
+    uint8_t m_rkeys[15 * AES_BLOCK_SIZE];
+    int m_rounds {};
+
+public:
+    // @param key_len 16 or 32
+    // @return 0 on success, -1 if key_len is not supported
+    int set_key(const uint8_t* key, std::size_t key_len);
+
+    void encrypt(uint8_t* block) const;
+    void decrypt(uint8_t* block) const;
+};
+
+// XTS mode as described in IEEE 1619
+class aes_xts {
+private:
+    aes m_data;
+    aes m_tweak;
+
+public:
+    // @param key_len 32 for AES-128-XTS or 64 for AES-256-XTS
+    // @return 0 on success, -1 if key_len is not supported
+    int set_key(const uint8_t* key, std::size_t key_len);
+
+    // len MUST be a multiple of AES_BLOCK_SIZE
+    void encrypt(uint8_t* buf, std::size_t len, uint64_t sector) const;
+    void decrypt(uint8_t* buf, std::size_t len, uint64_t sector) const;
+};
+
+} // namespace kernel::crypto
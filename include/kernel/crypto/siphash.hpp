@@ -0,0 +1,20 @@
+#pragma once
+
+#include <cstddef>
+
+#include <stdint.h>
+
+namespace kernel::crypto {
+
+struct siphash_key {
+    uint64_t k0;
+    uint64_t k1;
+};
+
+// SipHash-1-3, a keyed hash for short inputs
+//
+// with a secret key, the hashes of inputs chosen by the user
+// can't be predicted to make them all fall into a single bucket
+uint64_t siphash_1_3(const void* data, std::size_t len, const siphash_key& key);
+
+} // namespace kernel::crypto
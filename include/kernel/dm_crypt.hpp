@@ -0,0 +1,18 @@
+#pragma once
+
+#include <stdint.h>
+
+// ioctl requests on /dev/dm-crypt
+#define DM_CRYPT_CREATE (0xfd01)
+
+// create an encrypted block device on top of the block device
+// with node number source. the key is 32 bytes long for AES-128-XTS
+// or 64 bytes long for AES-256-XTS
+//
+// on success, the ioctl returns the minor number n of the new device
+// and /dev/dm-n is created for it
+struct dm_crypt_create {
+    uint32_t source;
+    uint32_t key_len;
+    uint8_t key[64];
+};
@@ -1,6 +1,6 @@
 #pragma once
 
-#include <list>
+#include <cstddef>
 
 #include <types/cplusplus.hpp>
 #include <types/lock.hpp>
@@ -16,10 +16,26 @@ struct thread;
 
 class cond_var : public types::non_copyable {
 private:
-    using list_type = std::list<tasks::thread*>;
+    // waiter nodes live on the stack of the waiting thread,
+    // so waiting on a cond_var never allocates memory
+    struct waiter {
+        tasks::thread* thd;
+        waiter* next;
+    };
 
     types::mutex m_mtx;
-    list_type m_subscribers;
+
+    // FIFO queue of waiters, woken up in the order they came
+    waiter* m_head {};
+    waiter* m_tail {};
+
+    bool m_closed {};
+
+    void enqueue(waiter* w);
+    void unlink(waiter* w);
+
+    // m_mtx MUST be held
+    bool wake_one(void);
 
 public:
     cond_var(void) = default;
@@ -29,10 +45,37 @@ public:
         return m_mtx;
     }
 
+    // whether close() has been called and reopen() has not since
+    constexpr bool closed(void) const
+    {
+        return m_closed;
+    }
+
     /// @param lock should have already been locked
+    /// @return true if woken up by notify, false if interrupted
+    ///         or the cond_var is closed, see closed()
     bool wait(types::mutex& lock);
+    /// for state shared with interrupt handlers, which may call
+    /// notify while lock is not held
+    /// @param lock should have already been locked with flags
+    /// @return true if woken up by notify, false if interrupted
+    ///         or the cond_var is closed, see closed()
+    bool wait(types::spinlock& lock, uint32_t& flags);
+
+    // wake up the earliest waiter
     void notify(void);
+    // wake up at most n waiters in FIFO order
+    void notify_n(std::size_t n);
+    // wake up all the waiters
     void notify_all(void);
+
+    // wake up all the waiters, and fail the waits from now on
+    // until reopen(), for when what they wait for is gone
+    //
+    // mtx() MUST be held, so that the state they wait for
+    // changes together with the cond_var
+    void close(void);
+    void reopen(void);
 };
 
 } // namespace kernel
@@ -0,0 +1,36 @@
+#pragma once
+
+#include <stdint.h>
+
+namespace kernel::hw {
+
+struct cpuid_regs {
+    uint32_t eax;
+    uint32_t ebx;
+    uint32_t ecx;
+    uint32_t edx;
+};
+
+inline cpuid_regs cpuid(uint32_t leaf, uint32_t subleaf = 0)
+{
+    cpuid_regs regs;
+    asm volatile("cpuid"
+        : "=a"(regs.eax), "=b"(regs.ebx), "=c"(regs.ecx), "=d"(regs.edx)
+        : "a"(leaf), "c"(subleaf));
+    return regs;
+}
+
+// the feature flags in edx of cpuid leaf 1, reported
+// to the user programs as AT_HWCAP on x86
+inline uint32_t cpu_hwcap(void)
+{
+    return cpuid(1).edx;
+}
+
+// AT_HWCAP2 on x86, ring 3 mwait and fsgsbase are never enabled
+inline uint32_t cpu_hwcap2(void)
+{
+    return 0;
+}
+
+} // namespace kernel::hw
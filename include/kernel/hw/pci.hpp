@@ -83,9 +83,12 @@ public:
     uint16_t device;
 
     uint8_t revision_id;
+    uint8_t prog_if;
     uint8_t subclass;
     uint8_t class_code;
     uint8_t header_type;
+    // the pic irq routed to the device by the firmware, 0xff if none
+    uint8_t interrupt_line;
 
     explicit pci_device(config_reg reg);
 };
@@ -7,6 +7,8 @@ extern "C" {
 
 #define PORT_SERIAL0 (0x3f8)
 #define PORT_SERIAL1 (0x2f8)
+#define PORT_SERIAL2 (0x3e8)
+#define PORT_SERIAL3 (0x2e8)
 
 int32_t init_serial_port(port_id_t port);
 
@@ -19,3 +21,23 @@ void serial_send_data(port_id_t port, uint8_t data);
 #ifdef __cplusplus
 }
 #endif
+
+#ifdef __cplusplus
+class serial_tty;
+
+namespace kernel::hw::serial {
+
+constexpr int MAX_PORTS = 4;
+
+// probe the standard isa uarts and create a tty for each of them
+// @return number of the ports found
+int init_serial(void);
+
+// @return the tty of ttySN, or nullptr if there's no such port
+serial_tty* get_tty(int index);
+
+// create /dev/ttySN for the ports found
+void init_serial_devices(void);
+
+} // namespace kernel::hw::serial
+#endif
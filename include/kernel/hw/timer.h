@@ -1,6 +1,7 @@
 #pragma once
 
 #include <stdint.h>
+#include <time.h>
 
 #ifdef __cplusplus
 extern "C" {
@@ -12,6 +13,9 @@ void inc_tick(void);
 
 size_t current_ticks(void);
 
+// the time of CLOCK_REALTIME
+struct timespec current_time(void);
+
 #ifdef __cplusplus
 }
 #endif
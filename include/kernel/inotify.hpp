@@ -0,0 +1,71 @@
+#pragma once
+
+#include <list>
+#include <map>
+
+#include <kernel/anon_inode.hpp>
+#include <kernel/event/evtqueue.hpp>
+#include <kernel/vfs.hpp>
+#include <stdint.h>
+#include <sys/inotify.h>
+#include <types/string.hpp>
+
+namespace fs {
+
+struct inotify_file : public anon_file {
+public:
+    static constexpr const char* ANON_NAME = "[inotify]";
+
+    // events queued after this are dropped and
+    // a single IN_Q_OVERFLOW event is reported instead
+    static constexpr size_t MAX_QUEUED_EVENTS = 256;
+
+    struct watch {
+        inode* ind;
+        uint32_t mask;
+    };
+
+    struct pending_event {
+        int wd;
+        uint32_t mask;
+        uint32_t cookie;
+        types::string<> name;
+    };
+
+private:
+    kernel::cond_var m_cv;
+    std::map<int, watch> m_watches;
+    std::list<pending_event> m_events;
+    int m_next_wd { 1 };
+
+    // m_cv.mtx() MUST be held
+    void queue_event(int wd, uint32_t mask, const char* name);
+
+public:
+    inotify_file(file_flags flags, bool nonblock);
+
+    virtual ssize_t read(char* __user buf, size_t n) override;
+
+    // @return watch descriptor or negative error code
+    int add_watch(inode* ind, uint32_t mask);
+    int rm_watch(int wd);
+
+    // called by the vfs when something happens to ind
+    void handle_event(inode* ind, uint32_t mask, const char* name);
+    // called by the vfs when ind is gone
+    void handle_inode_removed(inode* ind);
+
+    // one line for each watch
+    virtual size_t describe(char* buf, size_t buf_size) override;
+};
+
+// report event on inode ind, name is the name of the child
+// that the event happened to if ind is a directory
+void inotify_notify(inode* ind, uint32_t mask, const char* name = nullptr);
+// report event on the directory dent is in with the name of dent
+void inotify_notify_parent(vfs::dentry* dent, uint32_t mask);
+
+// send IN_IGNORED to all the watches of ind and drop them
+void inotify_inode_removed(inode* ind);
+
+} // namespace fs
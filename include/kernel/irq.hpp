@@ -2,10 +2,17 @@
 
 #include <functional>
 
+#include <stdint.h>
+
 namespace kernel::irq {
 
 using irq_handler_t = std::function<void()>;
 
 void register_handler(int irqno, irq_handler_t handler);
 
+constexpr int IRQ_COUNT = 16;
+
+// number of times irqno has fired since boot
+size_t irq_count(int irqno);
+
 };
@@ -0,0 +1,39 @@
+#pragma once
+
+#include <cstddef>
+
+#include <stdint.h>
+
+namespace kernel::kallsyms {
+
+// a symbol of the kernel text, as linked in by make_kallsyms.sh
+struct symbol {
+    const char* name;
+    uintptr_t addr;
+    // up to the next symbol
+    std::size_t size;
+    // as nm shows it, 't' or 'T' for local or global functions
+    char type;
+};
+
+// find the symbol that addr is in
+// the table is empty without KERNEL_KALLSYMS, i.e. outside Release builds
+// @return false if addr is not in the kernel text or there's no table
+bool symbol_for_addr(uintptr_t addr, symbol& sym);
+
+// print addr as "name+0xoff/0xsize", or in hex if not in the kernel text
+// @return the same as snprintf
+int snprint_symbol(char* buf, std::size_t buf_size, uintptr_t addr);
+
+// the restriction on the addresses shown in /proc/kallsyms,
+// /proc/sys/kernel/kptr_restrict
+// 0: shown to all, 1: shown to root only, 2: hidden from all
+int kptr_restrict(void);
+// @return 0 or -EINVAL if val is none of the above
+int set_kptr_restrict(int val);
+
+// /proc/kallsyms, "address type name" lines in the address order,
+// the addresses are zeros if hidden from the current process
+ssize_t read(char* buf, std::size_t offset, std::size_t n);
+
+} // namespace kernel::kallsyms
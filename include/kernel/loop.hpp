@@ -0,0 +1,15 @@
+#pragma once
+
+// ioctl requests on /dev/loop-control
+
+// the ioctl returns the minor number n of a loop device bound to
+// nothing, /dev/loopn is created for it if there was none
+#define LOOP_CTL_GET_FREE (0x4C82)
+
+// ioctl requests on /dev/loopn
+
+// back the device by the regular file or the block device opened as
+// the fd given, it's read-only unless the fd is opened for writing
+#define LOOP_SET_FD (0x4C00)
+// unbind the device from its file
+#define LOOP_CLR_FD (0x4C01)
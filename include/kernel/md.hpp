@@ -0,0 +1,26 @@
+#pragma once
+
+#include <stdint.h>
+
+// ioctl requests on /dev/md
+#define MD_CREATE (0x0901)
+
+#define MD_RAID0 (0)
+#define MD_RAID1 (1)
+
+#define MD_MAX_DEVICES (8)
+
+// combine block devices with node numbers in devs into a virtual disk
+// chunk_sectors is the stripe size in 512 byte sectors, used by RAID0 only,
+// it's a power of 2 no larger than 65536
+// a device can be a member only once
+// a RAID1 member a write fails on is marked degraded and no longer used
+//
+// on success, the ioctl returns the minor number n of the new device
+// and /dev/mdn is created for it
+struct md_create {
+    uint32_t level;
+    uint32_t chunk_sectors;
+    uint32_t ndevs;
+    uint32_t devs[MD_MAX_DEVICES];
+};
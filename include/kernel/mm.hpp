@@ -106,8 +106,14 @@ void free_page(page* pg);
 // TODO: this is for alloc_kstack()
 // CHANGE THIS
 page_t __alloc_raw_page(void);
+// n physically contiguous pages, all below page limit
+page_t __alloc_raw_pages(size_t n, page_t limit);
 void __free_raw_page(page_t pg);
 
+// number of physical pages in total and not allocated
+size_t total_raw_pages(void);
+size_t free_raw_pages(void);
+
 namespace kernel {
 
 void* pmap(page_t pg, bool cached = true);
@@ -171,6 +177,10 @@ public:
 
     void append_page(pd_t pd, const page& pg, uint32_t attr, bool priv);
 
+    // make the page containing addr present and writable if the area is,
+    // breaking copy on write and reading in mmapped file contents
+    void resolve_fault(void* addr);
+
     /**
      * @brief Splits the memory block at the specified address.
      * 
@@ -236,6 +246,16 @@ public:
 
     int unmap(void* start, size_t len, bool priv);
 
+    // change the write permission of the user areas in [start, start + len)
+    // @return GB_OK, -EINVAL if start is not page aligned or the range is
+    //         not in user space, -ENOMEM if some part of it is not mapped
+    int protect(void* start, size_t len, bool write);
+
+    // copy n bytes between buf and the user space of this mm_list
+    // which is not necessarily the one currently in use
+    // @return bytes copied or -EFAULT if nothing could be copied
+    ssize_t access(void* addr, void* buf, size_t n, bool write);
+
     constexpr mm& addarea(void* start, bool w, bool system)
     {
         auto [ iter, inserted ] = m_areas.emplace(mm {
@@ -289,8 +309,16 @@ public:
             invalidate_tlb((uint32_t)area.start + (i++) * PAGE_SIZE);
         }
         types::pdelete<types::kernel_ident_allocator>(area.pgs);
+
+        if (area.attr.mapped)
+            fs::inode_put(area.mapped_file);
     }
 
+    constexpr const_iterator begin() const
+    { return m_areas.cbegin(); }
+    constexpr const_iterator end() const
+    { return m_areas.cend(); }
+
     constexpr mm* find(void* lp)
     {
         auto iter = m_areas.find(lp);
@@ -324,6 +352,20 @@ public:
     }
 };
 
+// remap the __ro_after_init section read-only, called once boot completes
+void protect_ro_after_init(void);
+
+// make the __ro_after_init section writable during its lifetime,
+// used for registering things like module loading after boot
+class ro_after_init_unprotect : public types::non_copyable {
+private:
+    bool m_protected;
+
+public:
+    ro_after_init_unprotect(void);
+    ~ro_after_init_unprotect();
+};
+
 } // namespace memory
 
 } // namespace kernel
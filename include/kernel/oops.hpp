@@ -0,0 +1,30 @@
+#pragma once
+
+#include <kernel/interrupt.h>
+#include <types/size.h>
+#include <types/types.h>
+
+namespace kernel {
+
+// bits of the taint mask, numbered as in linux
+// a kernel fault has been survived, the kernel state might be broken
+constexpr unsigned TAINT_DIE = 1 << 7;
+
+// the taint mask, shown in /proc/sys/kernel/tainted
+unsigned tainted(void);
+void add_taint(unsigned flags);
+
+// print the return addresses found by following the frame pointers
+// starting from ebp, within the kernel stack of the current thread
+void print_backtrace(uint32_t ebp);
+
+// report a fault of the kernel at eip with the registers, if any,
+// and the backtrace, then kill the current task and taint the kernel
+//
+// the machine is frozen instead if the task can't be killed safely:
+// before the scheduler starts, in kernel threads or init, while
+// spinlocks are held, which includes interrupt handlers, or if the
+// report itself faults
+void NORETURN oops(const char* reason, const regs_32* regs, ptr_t eip);
+
+} // namespace kernel
@@ -0,0 +1,81 @@
+#pragma once
+
+#include <list>
+#include <map>
+
+#include <kernel/mem.h>
+#include <kernel/vfs.hpp>
+#include <stdint.h>
+#include <sys/types.h>
+
+namespace fs {
+
+// caches regular file contents in page sized chunks keyed by
+// (inode, offset) so that repeated reads do not hit the block device
+//
+// writes go straight to the filesystem and drop the pages they touch,
+// the page the file used to end in is filled again once it grows
+//
+// the pages of an inode are dropped when the inode is freed, and the
+// least recently used page is evicted when the cache is full
+class page_cache {
+public:
+    static constexpr size_t MAX_CACHED_PAGES = 1024;
+
+private:
+    struct lru_entry {
+        inode* ind;
+        size_t pgidx;
+    };
+    // most recently used first
+    using lru_list = std::list<lru_entry>;
+
+    struct cached_page {
+        char* data;
+        // bytes in the page that belong to the file
+        size_t len;
+        lru_list::iterator lru;
+    };
+
+    // page index => page
+    using page_map = std::map<size_t, cached_page>;
+
+private:
+    // inodes without cached pages are not kept
+    std::map<inode*, page_map> m_inodes;
+    lru_list m_lru;
+    size_t m_cnt {};
+
+    // @return nullptr if the page is not cached
+    cached_page* lookup(inode* ind, size_t pgidx);
+
+    // read the page from the filesystem and cache it
+    // @return nullptr if the filesystem returns an error
+    cached_page* fill(inode* ind, size_t pgidx);
+
+    void evict_one(void);
+
+    // the map is left in m_inodes even if it's empty
+    // @return the page after pg
+    page_map::iterator drop(page_map& pages, page_map::iterator pg);
+
+public:
+    // read n bytes at offset of the file through the cache
+    // @return bytes read, or -1U with errno set on error
+    size_t read(inode* ind, char* buf, size_t buf_size, size_t offset, size_t n);
+
+    // drop the cached pages overlapping with [offset, offset + n)
+    void invalidate(inode* ind, size_t offset, size_t n);
+
+    // drop all cached pages of the inode
+    void invalidate(inode* ind);
+
+    constexpr size_t cached_pages(void) const
+    { return m_cnt; }
+};
+
+inline page_cache* pcache;
+
+void init_page_cache(void);
+
+} // namespace fs
@@ -0,0 +1,48 @@
+#pragma once
+
+#include <kernel/anon_inode.hpp>
+#include <kernel/event/evtqueue.hpp>
+#include <kernel/vfs.hpp>
+#include <sys/types.h>
+
+namespace fs {
+
+// a reference to a process that stays valid after the pid is reused
+//
+// the file is told by the process list when the process exits and when
+// it is reaped, after which the pid no longer refers to the process
+struct pidfd_file : public anon_file {
+public:
+    static constexpr const char* ANON_NAME = "[pidfd]";
+
+private:
+    kernel::cond_var m_cv;
+    pid_t m_pid;
+    int m_exit_code {};
+    bool m_exited {};
+    bool m_reaped {};
+
+public:
+    pidfd_file(pid_t pid, file_flags flags, bool nonblock);
+
+    constexpr pid_t pid(void) const
+    { return m_pid; }
+
+    // whether the process has exited, code is set if so
+    bool exited(int* code = nullptr);
+    // whether the pid might have been given to another process
+    bool reaped(void);
+
+    void handle_exit(pid_t pid, int exit_code);
+    void handle_reap(pid_t pid);
+
+    virtual size_t describe(char* buf, size_t buf_size) override;
+};
+
+// called by the process list when process pid becomes a zombie
+void pidfd_process_exited(pid_t pid, int exit_code);
+
+// called by the process list when process pid is removed
+void pidfd_process_reaped(pid_t pid);
+
+} // namespace fs
@@ -7,21 +7,26 @@
 #include <set>
 #include <tuple>
 #include <utility>
+#include <vector>
 
 #include <fcntl.h>
+#include <fs/procfs.hpp>
 #include <kernel/errno.h>
 #include <kernel/event/evtqueue.hpp>
 #include <kernel/interrupt.h>
 #include <kernel/mm.hpp>
+#include <kernel/pidfd.hpp>
 #include <kernel/signal.hpp>
 #include <kernel/task.h>
 #include <kernel/tty.hpp>
 #include <kernel/vfs.hpp>
 #include <stdint.h>
+#include <sys/rseq.h>
 #include <sys/types.h>
 #include <types/allocator.hpp>
 #include <types/cplusplus.hpp>
 #include <types/hash_map.hpp>
+#include <types/ida.hpp>
 #include <types/path.hpp>
 #include <types/status.h>
 #include <types/string.hpp>
@@ -45,6 +50,33 @@ inline readyqueue* readythds;
 
 inline tss32_t tss;
 
+namespace kernel::tasks {
+
+// scheduler statistics, shown in /proc/stat
+struct sched_stat {
+    size_t ctx_switches;
+    size_t forks;
+    // timer ticks spent in user and kernel threads
+    size_t user_ticks;
+    size_t system_ticks;
+};
+
+inline sched_stat stats;
+
+// kernel stack pool statistics, shown in /proc/kstackinfo
+struct kstack_stat {
+    // stacks ever mapped, freed ones are kept for reuse
+    size_t allocated;
+    size_t in_use;
+    size_t peak_in_use;
+    // deepest usage seen on a freed stack in bytes, 0 if NDEBUG
+    size_t max_depth;
+};
+
+inline kstack_stat kstack_stats;
+
+} // namespace kernel::tasks
+
 struct process_attr {
     uint16_t system : 1;
     uint16_t zombie : 1 = 0;
@@ -74,8 +106,15 @@ public:
     int* __user set_child_tid {};
     int* __user clear_child_tid {};
 
+    // registered by rseq(), inherited on fork and dropped on exec
+    ::rseq* __user rseq_area {};
+    uint32_t rseq_sig {};
+
     types::string<> name {};
 
+    // the locks held by the thread, see types::current_held_locks
+    types::held_locks held_locks {};
+
     explicit inline thread(types::string<> name, pid_t owner)
         : owner { owner }
         , attr { .system = 1, .ready = 1, .wait = 0, }
@@ -85,7 +124,9 @@ public:
     }
 
     inline thread(const thread& val, pid_t owner)
-        : owner { owner }, attr { val.attr }, name { val.name }
+        : owner { owner }, attr { val.attr }
+        , rseq_area { val.rseq_area }, rseq_sig { val.rseq_sig }
+        , name { val.name }
     {
         alloc_kstack();
     }
@@ -157,6 +198,13 @@ public:
             this->arr.emplace(fd, fp);
     }
 
+    template <typename Func>
+    constexpr void for_each(Func&& func) const
+    {
+        for (const auto& [ fd, fp ] : arr)
+            func(fd, fp.get());
+    }
+
     constexpr fs::file* operator[](int i) const
     {
         auto iter = arr.find(i);
@@ -165,6 +213,15 @@ public:
         return iter->second.get();
     }
 
+    // for those keeping the file after the fd is closed
+    std::shared_ptr<fs::file> get(int i) const
+    {
+        auto iter = arr.find(i);
+        if (!iter)
+            return nullptr;
+        return iter->second;
+    }
+
     int pipe(int pipefd[2])
     {
         std::shared_ptr<fs::pipe> ppipe { new fs::pipe };
@@ -177,6 +234,9 @@ public:
                     .read = 1,
                     .write = 0,
                     .close_on_exec = 0,
+                    .direct = 0,
+                    .sync = 0,
+                    .append = 0,
                 }, ppipe),
         });
         assert(inserted);
@@ -191,6 +251,9 @@ public:
                     .read = 0,
                     .write = 1,
                     .close_on_exec = 0,
+                    .direct = 0,
+                    .sync = 0,
+                    .append = 0,
                 }, ppipe),
         });
         assert(inserted);
@@ -203,6 +266,15 @@ public:
 
     int open(const process& current, const types::path& filepath, int flags, mode_t mode);
 
+    // take the ownership of file and assign a new fd to it
+    int install(fs::file* file)
+    {
+        int fd = next_fd();
+        auto [ _, inserted ] = arr.emplace(fd, std::shared_ptr<fs::file> { file });
+        assert(inserted);
+        return fd;
+    }
+
     constexpr void close(int fd)
     {
         auto iter = arr.find(fd);
@@ -259,10 +331,37 @@ public:
     pid_t pgid {};
     pid_t sid {};
 
+    // real, effective and saved ids, everyone is root by default
+    uid_t uid {};
+    uid_t euid {};
+    uid_t suid {};
+    gid_t gid {};
+    gid_t egid {};
+    gid_t sgid {};
+
     tty* control_tty {};
     fs::vfs::dentry* root { fs::fs_root };
     std::set<pid_t> children;
 
+    // arguments separated by '\0' and the path of the executable
+    // of the latest execve, shown in /proc/<pid>
+    std::vector<char> cmdline;
+    types::path exe;
+    // NT_GNU_BUILD_ID of the executable, empty if it has none
+    std::vector<uint8_t> build_id;
+
+    // for process accounting, times are in timer ticks
+    size_t start_ticks {};
+    size_t utime {};
+    size_t stime {};
+    // set if the process has forked but not exec'ed
+    bool forked_only {};
+
+    // join argv in the format of cmdline, argv is a kernel copy
+    static std::vector<char> pack_args(const char* const* argv);
+    // called after the process has loaded a new program
+    void set_exec_info(const fs::vfs::dentry* exec, std::vector<char> args);
+
 public:
     process(const process&) = delete;
     explicit process(const process& parent, pid_t pid);
@@ -277,6 +376,9 @@ public:
     { return attr.zombie; }
 };
 
+// pids are in range [1, PID_MAX)
+inline constexpr pid_t PID_MAX = 32768;
+
 class proclist final {
 public:
     using list_type = std::map<pid_t, process>;
@@ -285,9 +387,27 @@ public:
 
 private:
     list_type m_procs;
-    pid_t m_nextpid = 1;
+    types::ida<PID_MAX> m_pids { 1 };
 
-    constexpr pid_t next_pid() { return m_nextpid++; }
+    // a pid still used as the pgid or the sid of some process is
+    // skipped, or the new process would be taken as in that group
+    constexpr bool is_pgid_or_sid(pid_t pid) const
+    {
+        for (const auto& [ _, proc ] : m_procs) {
+            if (proc.pgid == pid || proc.sid == pid)
+                return true;
+        }
+        return false;
+    }
+
+    constexpr pid_t next_pid()
+    {
+        pid_t pid = m_pids.alloc([this](std::size_t n) {
+            return is_pgid_or_sid(n);
+        });
+        assert(pid > 0);
+        return pid;
+    }
 
 public:
     process& emplace(pid_t ppid)
@@ -295,6 +415,7 @@ public:
         pid_t pid = next_pid();
         auto [ iter, inserted ] = m_procs.try_emplace(pid, pid, ppid);
         assert(inserted);
+        fs::procfs::add_process(pid);
 
         if (try_find(ppid)) {
             bool success = false;
@@ -311,12 +432,14 @@ public:
         pid_t pid = next_pid();
         auto [ iter, inserted ] = m_procs.try_emplace(pid, proc, pid);
         assert(inserted);
+        fs::procfs::add_process(pid);
+        ++kernel::tasks::stats.forks;
 
         proc.children.insert(pid);
         return iter->second;
     }
 
-    constexpr void remove(pid_t pid)
+    void remove(pid_t pid)
     {
         make_children_orphans(pid);
 
@@ -326,8 +449,14 @@ public:
         find(ppid).children.erase(pid);
 
         m_procs.erase(proc_iter);
+        m_pids.free(pid);
+        fs::procfs::remove_process(pid);
+        fs::pidfd_process_reaped(pid);
     }
 
+    constexpr bool pid_available(void) const
+    { return !m_pids.full(); }
+
     constexpr bool try_find(pid_t pid) const
     { return m_procs.find(pid); }
 
@@ -437,3 +566,11 @@ void NORETURN freeze(void);
 void NORETURN kill_current(int exit_code);
 
 void check_signal(void);
+
+// whether the current process may signal proc,
+// root may signal all the processes, others only those of the same user
+bool may_signal(const process& proc);
+
+// whether the current process may access the memory of proc,
+// the uid of the caller has to be all of the ids of proc unless it is root
+bool may_access(const process& proc);
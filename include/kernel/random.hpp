@@ -0,0 +1,22 @@
+#pragma once
+
+#include <cstddef>
+
+#include <stdint.h>
+
+namespace kernel::random {
+
+// mix val into the entropy pool, callable from interrupt handlers
+//
+// the timestamps of interrupts are the only source at the moment
+void add_entropy(uint32_t val);
+
+// fill buf with n bytes generated from the entropy pool
+//
+// the output is AES-128 in counter mode keyed with the pool, which
+// is rekeyed with its own output after each call
+void get_random_bytes(void* buf, std::size_t n);
+
+uint32_t get_random_u32(void);
+
+} // namespace kernel::random
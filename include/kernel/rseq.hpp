@@ -0,0 +1,19 @@
+#pragma once
+
+#include <stdint.h>
+#include <sys/rseq.h>
+#include <types/types.h>
+
+namespace kernel::rseq {
+
+// size of struct rseq in the original abi
+constexpr uint32_t RSEQ_SIZE = 32;
+
+// register or unregister the rseq area of the current thread
+int set_area(::rseq* __user area, uint32_t len, int flags, uint32_t sig);
+
+// called when the current thread is scheduled again, if it was interrupted
+// in the middle of a restartable sequence, jump to its abort handler
+void handle_preempt(void);
+
+} // namespace kernel::rseq
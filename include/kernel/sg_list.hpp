@@ -0,0 +1,133 @@
+#pragma once
+
+#include <cstddef>
+#include <vector>
+
+#include <kernel/mem.h>
+#include <stdint.h>
+#include <types/cplusplus.hpp>
+
+namespace kernel::memory {
+
+// a physically contiguous part of a dma transfer
+struct sg_segment {
+    uint32_t phys;
+    uint32_t len;
+};
+
+// what a dma capable device can address
+struct dma_limits {
+    // the last byte the device can reach
+    uint32_t max_addr;
+    // addresses and lengths of the segments MUST be multiples of it
+    uint32_t align;
+    uint32_t max_seg_len;
+    std::size_t max_segs;
+};
+
+// scatter-gather list, the physical memory of a dma transfer
+//
+// adjacent segments are merged, and the ones longer than max_seg_len
+// of the device are split by fit()
+class sg_list {
+private:
+    std::vector<sg_segment> m_segs;
+    std::size_t m_len {};
+
+public:
+    // add the memory of buf[0, len), which MUST be in kernel space
+    //
+    // @param dev_writes the device writes to buf, so pages shared
+    //        copy on write are made private first
+    void add_kernel_buf(void* buf, std::size_t len, bool dev_writes);
+
+    void add_page(page_t pg, uint32_t offset, uint32_t len);
+
+    // add the segments of other after ours
+    void append(const sg_list& other);
+
+    // whether the device can use the segments as they are
+    bool fits(const dma_limits& limits) const;
+
+    // split the segments longer than limits.max_seg_len
+    void split(const dma_limits& limits);
+
+    constexpr const std::vector<sg_segment>& segments(void) const
+    { return m_segs; }
+    constexpr std::size_t len(void) const
+    { return m_len; }
+    constexpr bool empty(void) const
+    { return m_segs.empty(); }
+};
+
+// copy min(dst.len(), src.len()) bytes between the memory of two lists
+void sg_copy(const sg_list& dst, const sg_list& src);
+
+// pages the device can address used in place of an sg_list that it can't
+//
+// the pages come from the swiotlb pool in low memory if the device can't
+// address all of the ram, waiting for other transfers to free them
+//
+// the caller copies the data in with fill() before the device reads it,
+// and out with drain() after the device has written to it
+class bounce_buffer : public types::non_copyable {
+private:
+    const sg_list& m_orig;
+    std::vector<page_t> m_pages;
+    // the first slot taken from the swiotlb pool, or -1
+    std::size_t m_slot { -1U };
+    std::size_t m_nslots {};
+    sg_list m_sg;
+
+public:
+    bounce_buffer(const sg_list& orig, const dma_limits& limits);
+    ~bounce_buffer();
+
+    constexpr const sg_list& sg(void) const
+    { return m_sg; }
+
+    // false if orig is larger than the memory the device can be given
+    constexpr bool valid(void) const
+    { return !m_sg.empty() || m_orig.empty(); }
+
+    void fill(void);
+    void drain(void);
+};
+
+// a dma transfer as the device sees it
+//
+// the memory is bounced if the device can't use it in place, copying
+// it in on map and out by sync_for_cpu(), so drivers don't need to care
+class dma_mapping : public types::non_copyable {
+private:
+    const sg_list& m_sg;
+    bounce_buffer* m_bounce {};
+
+public:
+    // map sg for a transfer
+    //
+    // @param dev_writes the device writes to the memory of sg
+    dma_mapping(const sg_list& sg, const dma_limits& limits, bool dev_writes);
+    // unmap the transfer, dropping the data the device has written
+    // if sync_for_cpu() was not called
+    ~dma_mapping();
+
+    // false if the transfer can't be mapped at all
+    constexpr bool valid(void) const
+    { return !m_bounce || m_bounce->valid(); }
+
+    // the segments to give to the device
+    constexpr const sg_list& sg(void) const
+    { return m_bounce ? m_bounce->sg() : m_sg; }
+
+    // make what the device has written visible to the cpu
+    void sync_for_cpu(void);
+};
+
+// reserve the swiotlb pool in low memory
+void init_swiotlb(void);
+
+// the physical address of a mapped kernel space address
+uint32_t virt_to_phys(void* addr);
+
+} // namespace kernel::memory
@@ -7,3 +7,9 @@
 typedef int (*syscall_handler)(interrupt_stack* data);
 
 void init_syscall(void);
+
+// /proc/sys/kernel/syscall_trace, whether the syscalls made are
+// printed to the kernel log with their arguments and return values
+int syscall_trace(void);
+// @return 0 or -EINVAL if val is neither 0 nor 1
+int set_syscall_trace(int val);
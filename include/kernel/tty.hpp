@@ -2,6 +2,7 @@
 #include <kernel/event/evtqueue.hpp>
 #include <stdint.h>
 #include <sys/types.h>
+#include <termios.h>
 #include <types/allocator.hpp>
 #include <types/buffer.hpp>
 #include <types/cplusplus.hpp>
@@ -15,8 +16,14 @@ public:
     tty();
     virtual void putchar(char c) = 0;
     virtual void recvchar(char c) = 0;
+    // wait until the output queued is sent to the device
+    virtual void flush(void) {}
     void print(const char* str);
-    size_t read(char* buf, size_t buf_size, size_t n);
+    // @return bytes read, 0 on EOF or -EINTR
+    ssize_t read(char* buf, size_t buf_size, size_t n);
+
+    // TCGETS and TCSETS*
+    int ioctl(unsigned long request, uintptr_t arg);
 
     constexpr void set_pgrp(pid_t pgid)
     {
@@ -28,14 +35,42 @@ public:
         return fg_pgroup;
     }
 
+    // make the tty the controlling terminal of session sid
+    void set_session(pid_t sid);
+
+    constexpr pid_t get_session(void) const
+    {
+        return m_sid;
+    }
+
+    // the session has gone, drop the input and make the reads
+    // return 0 until another session takes the tty
+    void hangup(void);
+
     char name[NAME_SIZE];
-    bool echo = true;
 
 protected:
+    // protects buf, m_lines and m_termios, which recvchar
+    // changes from the interrupt handlers
+    types::spinlock m_lock;
     types::buffer<types::kernel_ident_allocator> buf;
     kernel::cond_var m_cv;
+    struct termios m_termios;
+    // complete lines in buf in canonical mode, ended by '\n' or VEOF
+    size_t m_lines {};
 
     pid_t fg_pgroup;
+    // the session the tty is the controlling terminal of
+    pid_t m_sid {};
+
+    bool is_line_end(char c) const;
+    // recount m_lines after the mode changed, m_lock MUST be held
+    void count_lines(void);
+
+    constexpr bool lflag(tcflag_t flag) const
+    { return m_termios.c_lflag & flag; }
+    constexpr bool iflag(tcflag_t flag) const
+    { return m_termios.c_iflag & flag; }
 };
 
 class vga_tty : public virtual tty {
@@ -46,13 +81,30 @@ public:
 };
 
 class serial_tty : public virtual tty {
+private:
+    static constexpr size_t TX_BUFFER_SIZE = 4096;
+
+    // characters waiting for the transmitter, drained from its interrupt
+    types::buffer<types::kernel_ident_allocator> m_tx;
+    // bytes the transmitter takes at once when it's empty
+    size_t m_fifo_size;
+    bool m_tx_irq {};
+
+    void echo(char c);
+    // fill the transmitter from the tx ring, interrupts MUST be disabled
+    void tx_ready(void);
+
 public:
-    serial_tty(int id);
+    serial_tty(int index, uint16_t port);
     virtual void putchar(char c) override;
     virtual void recvchar(char c) override;
+    virtual void flush(void) override;
+
+    // called from the interrupt of the uart
+    void handle_irq(void);
 
 public:
     uint16_t id;
 };
 
-inline tty* console;
+extern tty* console;
@@ -0,0 +1,51 @@
+#pragma once
+
+#include <cstddef>
+
+#include <stdint.h>
+#include <types/types.h>
+
+namespace kernel::user {
+
+// the user space ends where the kernel is mapped
+constexpr uintptr_t USER_SPACE_END = 0xc0000000;
+
+// instructions that may fault on user addresses are listed in the
+// __ex_table section with the address to continue at after a fault
+struct exception_table_entry {
+    uintptr_t insn;
+    uintptr_t fixup;
+};
+
+// find the fixup of a faulting kernel instruction at eip
+// @return the address of the fixup or 0 if there is none
+uintptr_t search_exception_table(uintptr_t eip);
+
+// whether [addr, addr + n) is in the user space
+inline bool access_ok(const void __user* addr, std::size_t n)
+{
+    auto start = (uintptr_t)addr;
+    return start <= USER_SPACE_END && n <= USER_SPACE_END - start;
+}
+
+// @return the number of bytes NOT copied, 0 on success
+std::size_t copy_from_user(void* dst, const void __user* src, std::size_t n);
+std::size_t copy_to_user(void __user* dst, const void* src, std::size_t n);
+
+// copy the string at src including the terminating null byte,
+// but no more than n bytes
+// @return the length of the string, n if it's not terminated
+//         in n bytes, or -EFAULT
+long strncpy_from_user(char* dst, const char __user* src, std::size_t n);
+
+// @return 0 or -EFAULT
+int get_user(uint32_t& val, const uint32_t __user* addr);
+int put_user(uint32_t val, uint32_t __user* addr);
+
+// atomically replace *addr with desired if it equals expected,
+// the value found is stored in cur, as futex needs
+// @return 0 or -EFAULT
+int cmpxchg_user(uint32_t __user* addr,
+    uint32_t expected, uint32_t desired, uint32_t& cur);
+
+} // namespace kernel::user
@@ -5,8 +5,11 @@
 #include <vector>
 #include <functional>
 
+#include <sys/mount.h>
 #include <sys/stat.h>
+#include <sys/statfs.h>
 #include <sys/types.h>
+#include <time.h>
 #include <kernel/errno.h>
 #include <bits/alltypes.h>
 
@@ -39,6 +42,15 @@
 
 #define DT_MAX (S_DT_MASK + 1) /* 16 */
 
+// convert between the file type in mode and DT_*
+#define IFTODT(mode) (((mode) & S_IFMT) >> 12)
+#define DTTOIF(type) ((type) << 12)
+
+// access modes checked by vfs_permission()
+#define MAY_EXEC 1
+#define MAY_WRITE 2
+#define MAY_READ 4
+
 namespace fs {
 using blksize_t = size_t;
 using blkcnt_t = size_t;
@@ -53,6 +65,24 @@ struct inode {
     mode_t mode;
     uid_t uid;
     gid_t gid;
+
+    // FS_*_FL, kept in memory only
+    uint32_t attr_flags {};
+
+    // number of directory entries referring to the inode
+    size_t nlink { 1 };
+    // the opened files, mappings and other kernel users of the inode,
+    // its data stays till they are all gone even if nlink is 0
+    size_t refs {};
+
+    // last access, modification of the data and change of the inode,
+    // kept in memory only
+    timespec atime {};
+    timespec mtime {};
+    timespec ctime {};
+
+    // set while a write at the end of the file is in progress
+    bool appending {};
 };
 
 using node_t = uint32_t;
@@ -72,9 +102,17 @@ using blkdev_read = std::function<ssize_t(char*, std::size_t, std::size_t, std::
 // buf, offset, cnt
 using blkdev_write = std::function<ssize_t(const char*, std::size_t, std::size_t)>;
 
+// wait until the data written is on the medium, optional
+using blkdev_flush = std::function<int()>;
+
+// request, arg, optional
+using blkdev_ioctl = std::function<int(unsigned long, uintptr_t)>;
+
 struct blkdev_ops {
     blkdev_read read;
     blkdev_write write;
+    blkdev_flush flush;
+    blkdev_ioctl ioctl;
 };
 
 // buf, buf_size, cnt
@@ -83,9 +121,13 @@ using chrdev_read = std::function<ssize_t(char*, std::size_t, std::size_t)>;
 // buf, cnt
 using chrdev_write = std::function<ssize_t(const char*, std::size_t)>;
 
+// request, arg
+using chrdev_ioctl = std::function<int(unsigned long, uintptr_t)>;
+
 struct chrdev_ops {
     chrdev_read read;
     chrdev_write write;
+    chrdev_ioctl ioctl;
 };
 
 struct PACKED user_dirent {
@@ -112,13 +154,28 @@ public:
         template <typename T>
         using allocator_type = types::kernel_allocator<T>;
 
+        // the index of a directory starts small and doubles
+        // when it holds twice as many entries as its buckets
+        static constexpr std::size_t INITIAL_INDEX_BUCKETS = 8;
+
     private:
+        // allocated when the first child is added
         std::list<dentry, types::allocator_adapter<dentry, allocator_type>>* children = nullptr;
         types::hash_map<name_type, dentry*, types::linux_hasher, allocator_type>* idx_children = nullptr;
 
+        // the files opened on the entry
+        std::size_t refs {};
+        // removed from the parent while in use, freed by the last put()
+        bool removed {};
+
+        void alloc_children(void);
+        void erase_child(dentry* ent);
+
     public:
         dentry* parent;
         inode* ind;
+        // DT_* type of ind, cached for the path lookups
+        uint8_t type;
         // if the entry is a file, this flag is ignored
         union {
             uint32_t v;
@@ -134,8 +191,11 @@ public:
         constexpr dentry(dentry&& val)
             : children(std::exchange(val.children, nullptr))
             , idx_children(std::exchange(val.idx_children, nullptr))
+            , refs { val.refs }
+            , removed { val.removed }
             , parent(std::exchange(val.parent, nullptr))
             , ind(std::exchange(val.ind, nullptr))
+            , type { val.type }
             , flags { val.flags }
             , name(std::move(val.name))
         {
@@ -164,6 +224,20 @@ public:
         dentry* append(inode* ind, name_type&& name, bool set_dirty);
 
         dentry* find(const name_type& name);
+        // find the child named name[0, len) without making a string of it
+        dentry* find(const char* name, std::size_t len);
+
+        // remove the child named name together with its children
+        // pointers to the removed dentries become invalid, unless
+        // files are opened in them, then they are kept till closed
+        // @return false if the child is kept
+        bool remove(const name_type& name);
+
+        // held by the files opened on the entry
+        void get(void);
+        void put(void);
+        // whether there are files opened on the entry or its children
+        bool in_use(void) const;
 
         dentry* replace(dentry* val);
 
@@ -184,12 +258,21 @@ private:
     inode_list _inodes;
     types::hash_map<dentry*, dentry*> _mount_recover_list;
 
+    // MS_* flags the filesystem is mounted with
+    unsigned long m_mount_flags { MS_RELATIME };
+
+    // protected by m_freeze_cv.mtx()
+    kernel::cond_var m_freeze_cv;
+    bool m_frozen {};
+    size_t m_writers {};
+
 protected:
     dentry _root;
 
 protected:
     inode* cache_inode(size_t size, ino_t ino, mode_t mode, uid_t uid, gid_t gid);
     inode* get_inode(ino_t ino);
+    void free_inode(ino_t ino);
     void register_root_node(inode* root);
 
     int load_dentry(dentry* ent);
@@ -206,16 +289,62 @@ public:
         return &_root;
     }
 
-    int mount(dentry* mnt, vfs* new_fs);
+    // atime is updated as MS_NOATIME, MS_RELATIME (the default)
+    // or MS_STRICTATIME in flags says
+    int mount(dentry* mnt, vfs* new_fs, unsigned long flags = MS_RELATIME);
+
+    constexpr unsigned long mount_flags(void) const
+    {
+        return m_mount_flags;
+    }
+
+    // wait for the modifications in progress to finish, block the
+    // new ones until thaw() is called and flush the filesystem
+    int freeze(void);
+    int thaw(void);
+
+    // called around every modification of the filesystem
+    // @return false if interrupted while waiting for thaw()
+    bool begin_write(void);
+    void end_write(void);
+
+    // write back everything that is not on the disk yet
+    virtual int sync_fs(void);
+
+    // write back the data of file, and its metadata unless datasync
+    // the default implementation syncs the whole filesystem
+    virtual int inode_sync(inode* file, bool datasync);
+
+    // fill in the usage of the filesystem, buf is zeroed by the caller
+    virtual int statfs(struct statfs* buf);
 
     virtual size_t inode_read(inode* file, char* buf, size_t buf_size, size_t offset, size_t n);
     virtual size_t inode_write(inode* file, const char* buf, size_t offset, size_t n);
+    // copy n bytes between two regular files of the filesystem without
+    // a round trip through memory, n is within the size of src
+    // @return bytes copied, or -EOPNOTSUPP to fall back to read and write
+    virtual ssize_t inode_copy_range(inode* src, size_t src_off,
+        inode* dst, size_t dst_off, size_t n);
     virtual int inode_mkfile(dentry* dir, const char* filename, mode_t mode);
     virtual int inode_mknode(dentry* dir, const char* filename, mode_t mode, node_t sn);
     virtual int inode_rmfile(dentry* dir, const char* filename);
+    // create the entry filename in dir referring to the existing inode
+    virtual int inode_link(dentry* dir, const char* filename, inode* target);
     virtual int inode_mkdir(dentry* dir, const char* dirname);
+    virtual int inode_symlink(dentry* dir, const char* linkname, const char* target);
+    // @return bytes of the link target copied to buf, not null terminated
+    virtual int inode_readlink(inode* link, char* buf, size_t buf_size);
     virtual int inode_stat(dentry* dent, statx* buf, unsigned int mask);
+    virtual int inode_truncate(inode* file, size_t size);
     virtual uint32_t inode_getnode(inode* file);
+    // the entry of the inode removed while in use is freed, the last
+    // file opened in it is closed, or the last reference taken by
+    // inode_get() is dropped
+    virtual void inode_release(inode* ind);
+
+    // whether regular file contents should go through the page cache
+    // filesystems keeping their data in memory SHOULD NOT enable this
+    virtual bool use_page_cache(void) const;
 
     // parameter 'length' in callback:
     // if 0, 'name' should be null terminated
@@ -231,16 +360,24 @@ public:
 class pipe : public types::non_copyable {
 private:
     static constexpr size_t PIPE_SIZE = 4096;
-    static constexpr uint32_t READABLE = 1;
-    static constexpr uint32_t WRITABLE = 2;
 
 private:
     types::buffer<types::kernel_allocator> buf;
     kernel::cond_var m_cv;
-    uint32_t flags;
+    // number of opened read and write ends
+    size_t m_readers;
+    size_t m_writers;
 
 public:
-    pipe(void);
+    // anonymous pipes are created with both ends opened
+    // named pipes start with none, see open_read() and open_write()
+    explicit pipe(size_t readers = 1, size_t writers = 1);
+
+    // open another end of the pipe and wait for the other side to be
+    // opened unless nonblock is set
+    // @return 0 or negative error code
+    int open_read(bool nonblock);
+    int open_write(bool nonblock);
 
     void close_read(void);
     void close_write(void);
@@ -248,19 +385,30 @@ public:
     int write(const char* buf, size_t n);
     int read(char* buf, size_t n);
 
+    // used by splice(), tee() and vmsplice(), which move what they can
+    // instead of waiting for all of n
+    //
+    // wait until the pipe is not empty and read at most n bytes,
+    // leaving them in the pipe if peek is set
+    // @return bytes read, 0 if there are no writers, or negative error code
+    int read_some(char* buf, size_t n, bool nonblock, bool peek = false);
+    // wait until the pipe is not full
+    // @return bytes that can be written, or negative error code
+    int wait_space(bool nonblock);
+
     constexpr bool is_readable(void) const
     {
-        return flags & READABLE;
+        return m_readers;
     }
 
     constexpr bool is_writeable(void) const
     {
-        return flags & WRITABLE;
+        return m_writers;
     }
 
     constexpr bool is_free(void) const
     {
-        return !(flags & (READABLE | WRITABLE));
+        return !m_readers && !m_writers;
     }
 };
 
@@ -271,6 +419,9 @@ struct file {
         uint32_t read : 1;
         uint32_t write : 1;
         uint32_t close_on_exec : 1;
+        uint32_t direct : 1;
+        uint32_t sync : 1;
+        uint32_t append : 1;
     } flags {};
 
     file(mode_t mode, vfs::dentry* parent, file_flags flags)
@@ -282,70 +433,190 @@ struct file {
     virtual ssize_t write(const char* __user buf, size_t n) = 0;
     virtual void close() = 0;
 
+    // read or write at offset, the file offset is left untouched
+    virtual ssize_t pread(char* __user buf, size_t n, size_t offset)
+    { return (void)buf, (void)n, (void)offset, -ESPIPE; }
+    virtual ssize_t pwrite(const char* __user buf, size_t n, size_t offset)
+    { return (void)buf, (void)n, (void)offset, -ESPIPE; }
+
+    virtual int ioctl(unsigned long request, uintptr_t arg)
+    { return (void)request, (void)arg, -ENOTTY; }
+
+    // @return the dentry the file is opened from, or nullptr if there's none
+    virtual vfs::dentry* get_dentry(void) const
+    { return nullptr; }
+
     // regular files should override this method
     virtual int getdents(char* __user buf, size_t cnt)
     { return (void)buf, (void)cnt, -ENOTDIR; }
     virtual int getdents64(char* __user buf, size_t cnt)
     { return (void)buf, (void)cnt, -ENOTDIR; }
+
+    // files not backed by storage can't be synced
+    virtual int fsync(bool datasync)
+    { return (void)datasync, -EINVAL; }
+
+    // @return the pipe of pipes and fifos, or nullptr
+    virtual pipe* get_pipe(void) const
+    { return nullptr; }
+
+    // @return the offset used by read() and write(), or nullptr
+    //         if the file has none
+    virtual size_t* pos(void)
+    { return nullptr; }
+
+    // serialize the users of pos() as the transfers might sleep
+    // @return false if interrupted by signals
+    virtual bool lock_pos(void)
+    { return true; }
+    virtual void unlock_pos(void) { }
+
+    // write the lines specific to the type of the file shown in
+    // /proc/<pid>/fdinfo/<fd>, each of them like "key:\tvalue\n"
+    // @return the length written
+    virtual size_t describe(char* buf, size_t buf_size)
+    { return (void)buf, (void)buf_size, 0; }
 };
 
 struct regular_file : public virtual file {
-    virtual ~regular_file() = default;
+private:
+    // held by read() and write() across the whole transfer
+    kernel::cond_var m_pos_cv;
+    bool m_pos_locked {};
+
+    ssize_t do_read(char* __user buf, size_t n, size_t offset);
+    // with append, the data goes to the end of the file
+    // and offset is set to where it's written
+    ssize_t do_write(const char* __user buf, size_t n, size_t& offset, bool append);
+
+public:
+    virtual ~regular_file();
     std::size_t cursor { };
     inode* ind { };
+    vfs::dentry* dent { };
 
-    regular_file(vfs::dentry* parent, file_flags flags, size_t cursor, inode* ind);
+    regular_file(vfs::dentry* dent, file_flags flags, size_t cursor);
 
     virtual ssize_t read(char* __user buf, size_t n) override;
     virtual ssize_t write(const char* __user buf, size_t n) override;
+    // character devices and directories can't be accessed at an offset
+    virtual ssize_t pread(char* __user buf, size_t n, size_t offset) override;
+    virtual ssize_t pwrite(const char* __user buf, size_t n, size_t offset) override;
     virtual void close() override;
+    virtual int ioctl(unsigned long request, uintptr_t arg) override;
+    virtual vfs::dentry* get_dentry(void) const override;
     virtual int getdents(char* __user buf, size_t cnt) override;
     virtual int getdents64(char* __user buf, size_t cnt) override;
+    virtual int fsync(bool datasync) override;
+    virtual size_t* pos(void) override;
+    virtual bool lock_pos(void) override;
+    virtual void unlock_pos(void) override;
 };
 
 struct fifo_file : public virtual file {
     virtual ~fifo_file() = default;
     std::shared_ptr<pipe> ppipe;
+    // the named pipe opened, nullptr for anonymous pipes
+    vfs::dentry* dent { };
 
     fifo_file(vfs::dentry* parent, file_flags flags, std::shared_ptr<fs::pipe> ppipe);
 
     virtual ssize_t read(char* __user buf, size_t n) override;
     virtual ssize_t write(const char* __user buf, size_t n) override;
     virtual void close() override;
+    virtual vfs::dentry* get_dentry(void) const override;
+    virtual pipe* get_pipe(void) const override;
 };
 
+// open the named pipe dent, all the opens of it share the same pipe
+// blocks until the other side is opened unless nonblock is set
+// @return nullptr and set errno if failed
+fifo_file* fifo_open(vfs::dentry* dent, file::file_flags flags, bool nonblock);
+
 inline fs::vfs::dentry* fs_root;
 
-int register_block_device(node_t node, blkdev_ops ops);
-int register_char_device(node_t node, chrdev_ops ops);
+// if name is not null, a device node is created for the device in devtmpfs
+int register_block_device(node_t node, blkdev_ops ops,
+    const char* name = nullptr, mode_t perm = 0660);
+int register_char_device(node_t node, chrdev_ops ops,
+    const char* name = nullptr, mode_t perm = 0660);
 
 void partprobe();
 
 ssize_t block_device_read(node_t node, char* buf, size_t buf_size, size_t offset, size_t n);
 ssize_t block_device_write(node_t node, const char* buf, size_t offset, size_t n);
+int block_device_flush(node_t node);
+int block_device_ioctl(node_t node, unsigned long request, uintptr_t arg);
 
 ssize_t char_device_read(node_t node, char* buf, size_t buf_size, size_t n);
 ssize_t char_device_write(node_t node, const char* buf, size_t n);
+int char_device_ioctl(node_t node, unsigned long request, uintptr_t arg);
 
 vfs* register_fs(vfs* fs);
 
+// hold ind for a kernel user other than an opened file, e.g. a mapping
+void inode_get(inode* ind);
+void inode_put(inode* ind);
+
+// create an empty in-memory filesystem
+vfs* make_tmpfs(void);
+
 size_t vfs_read(inode* file, char* buf, size_t buf_size, size_t offset, size_t n);
-size_t vfs_write(inode* file, const char* buf, size_t offset, size_t n);
+// @return bytes written or negative error code
+ssize_t vfs_write(inode* file, const char* buf, size_t offset, size_t n);
+// write at the end of the file, appends to the same file are serialized
+// so that none of them overwrites another
+// @param offset where the data is written is stored here
+ssize_t vfs_append(inode* file, const char* buf, size_t n, size_t& offset);
+// copy within the filesystem using inode_copy_range()
+// @return bytes copied, -EXDEV if the files are on different
+//         filesystems or other negative error code
+ssize_t vfs_copy_file_range(inode* src, size_t src_off,
+    inode* dst, size_t dst_off, size_t n);
 int vfs_mkfile(fs::vfs::dentry* dir, const char* filename, mode_t mode);
 int vfs_mknode(fs::vfs::dentry* dir, const char* filename, mode_t mode, node_t sn);
 int vfs_rmfile(fs::vfs::dentry* dir, const char* filename);
+int vfs_link(fs::vfs::dentry* dir, const char* filename, inode* target);
 int vfs_mkdir(fs::vfs::dentry* dir, const char* dirname);
+int vfs_symlink(fs::vfs::dentry* dir, const char* linkname, const char* target);
+int vfs_readlink(inode* link, char* buf, size_t buf_size);
 int vfs_stat(fs::vfs::dentry* dent, statx* stat, unsigned int mask);
+int vfs_truncate(inode* file, size_t size);
+// wait until data written to file is on the device
+// metadata is skipped if datasync is set
+int vfs_sync(inode* file, bool datasync = false);
+// usage of the filesystem containing file
+int vfs_statfs(inode* file, struct statfs* buf);
+// set FS_*_FL of file, only the immutable and the append-only flags are supported
+// only root may set them
+int vfs_setflags(inode* file, uint32_t flags);
+// set the access and modification time of file to times[0] and times[1],
+// or to the current time if times is null
+int vfs_utimens(inode* file, const timespec* times);
+// check the owner, group and other bits of file against MAY_* in mask
+// with the effective ids of the current process. root may read and
+// write anything but executes only files with an execute bit set
+// @return GB_OK or -EACCES
+int vfs_permission(inode* file, int mask);
 
 /**
  * @brief Opens a file or directory specified by the given path.
  * 
  * @param root The root directory of the file system.
  * @param path The absolute path to the file or directory to be opened.
+ * @param follow_symlinks Whether to follow the last component of the path
+ *        if it is a symbolic link. Links in the middle are always followed.
  * @return A pointer to the opened file or directory entry if found.
- *         Otherwise, nullptr is returned.
+ *         Otherwise, nullptr is returned and errno is set. errno is ELOOP
+ *         if too many symbolic links are encountered, EACCES if a directory
+ *         on the way can't be searched and ENOENT otherwise.
  */
-fs::vfs::dentry* vfs_open(fs::vfs::dentry& root, const types::path& path);
+fs::vfs::dentry* vfs_open(fs::vfs::dentry& root,
+    const types::path& path, bool follow_symlinks = true);
+// the same as above, but walks the path in place without
+// splitting it into strings first
+fs::vfs::dentry* vfs_open(fs::vfs::dentry& root,
+    const char* path, bool follow_symlinks = true);
 
 } // namespace fs
 
@@ -31,10 +31,14 @@ namespace __allocator {
         };
 
     private:
+        byte* p_base;
         byte* p_start;
         byte* p_break;
         byte* p_limit;
 
+        // bytes handed out to the users, headers excluded
+        size_type m_used {};
+
         brk_memory_allocator() = delete;
         brk_memory_allocator(const brk_memory_allocator&) = delete;
         brk_memory_allocator(brk_memory_allocator&&) = delete;
@@ -129,7 +133,8 @@ namespace __allocator {
 
     public:
         constexpr brk_memory_allocator(byte* start, size_type limit)
-            : p_start(start)
+            : p_base(start)
+            , p_start(start)
             , p_limit(start + limit)
         {
             brk(p_start);
@@ -157,6 +162,7 @@ namespace __allocator {
             }
 
             block_allocated->flags.is_free = 0;
+            m_used += block_allocated->size;
 
             auto* blkpos = std::bit_cast<byte*>(block_allocated);
             if (blkpos > p_start)
@@ -170,6 +176,7 @@ namespace __allocator {
                 std::bit_cast<byte*>(ptr) - sizeof(mem_blk));
 
             blk->flags.is_free = 1;
+            m_used -= blk->size;
 
             if (std::bit_cast<byte*>(blk) < p_start)
                 p_start = std::bit_cast<byte*>(blk);
@@ -177,6 +184,14 @@ namespace __allocator {
             // unite free blocks nearby
             unite_afterwards(blk);
         }
+
+        constexpr size_type used(void) const
+        { return m_used; }
+        // size of the area covered by the blocks
+        constexpr size_type heap_size(void) const
+        { return p_break - p_base; }
+        constexpr size_type limit(void) const
+        { return p_limit - p_base; }
     };
 }; // namespace __allocator
 
@@ -0,0 +1,38 @@
+#pragma once
+
+#include <stdint.h>
+#include <types/elf.hpp>
+
+namespace types::aout {
+
+// a.out executable header, the QMAGIC variant is supported only
+//
+// the header is a part of the text segment and the image is a flat
+// copy of the memory starting at AOUT_TEXT_ADDR, followed by bss
+struct PACKED aout_header {
+    // magic in the lower 16 bits, machine type in bits 16 to 23
+    uint32_t midmag;
+    uint32_t text;
+    uint32_t data;
+    uint32_t bss;
+    uint32_t syms;
+    uint32_t entry;
+    uint32_t trsize;
+    uint32_t drsize;
+};
+
+constexpr uint16_t QMAGIC = 0314;
+constexpr uint8_t M_386 = 100;
+
+constexpr uint32_t AOUT_TEXT_ADDR = 0x1000;
+
+constexpr bool aout_check_magic(const aout_header* hdr)
+{
+    uint8_t machine = (hdr->midmag >> 16) & 0xff;
+    return (hdr->midmag & 0xffff) == QMAGIC && (machine == M_386 || machine == 0);
+}
+
+// @return GB_OK or GB_FAILED with data->errcode set
+int aout_load(types::elf::elf32_load_data* data);
+
+} // namespace types::aout
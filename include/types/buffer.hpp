@@ -105,6 +105,16 @@ public:
         return *_backward(head);
     }
 
+    // the n-th char from the front, left in the buffer
+    constexpr int peek(size_t n) const
+    {
+        if (n >= count)
+            return EOF;
+
+        size_t off = (base - start + n) % static_cast<size_t>(end - start + 1);
+        return start[off];
+    }
+
     constexpr int get(void)
     {
         if (empty())
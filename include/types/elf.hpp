@@ -1,4 +1,7 @@
 #pragma once
+
+#include <vector>
+
 #include <kernel/errno.h>
 #include <kernel/interrupt.h>
 #include <kernel/process.hpp>
@@ -6,6 +9,7 @@
 #include <stdint.h>
 #include <types/size.h>
 #include <types/status.h>
+#include <types/string.hpp>
 
 namespace types::elf {
 using elf32_addr_t = uint32_t;
@@ -18,6 +22,11 @@ constexpr elf32_addr_t ELF_STACK_BOTTOM = 0xbffff000;
 constexpr elf32_off_t ELF_STACK_SIZE = 8 * 1024 * 1024;
 constexpr elf32_addr_t ELF_STACK_TOP = ELF_STACK_BOTTOM - ELF_STACK_SIZE;
 
+// ET_DYN executables are loaded at ELF_DYN_BASE plus
+// a random page aligned offset less than ELF_DYN_RANDOM
+constexpr elf32_addr_t ELF_DYN_BASE = 0x40000000;
+constexpr elf32_off_t ELF_DYN_RANDOM = 16 * 1024 * 1024;
+
 struct PACKED elf32_header {
     // 0x7f, "ELF"
     char magic[4];
@@ -94,6 +103,9 @@ struct PACKED elf32_program_header_entry {
         PT_PHDR = 0x06,
         PT_TLS = 0x07,
         PT_LOOS = 0x60000000,
+        PT_GNU_EH_FRAME = 0x6474e550,
+        PT_GNU_STACK = 0x6474e551,
+        PT_GNU_RELRO = 0x6474e552,
         PT_HIOS = 0x6fffffff,
         PT_LIPROC = 0x70000000,
         PT_HIPROC = 0x7fffffff,
@@ -104,6 +116,11 @@ struct PACKED elf32_program_header_entry {
     elf32_off_t filesz;
     elf32_off_t memsz;
     // segment dependent
+    enum : uint32_t {
+        PF_X = 0x1,
+        PF_W = 0x2,
+        PF_R = 0x4,
+    };
     uint32_t flags;
     // 0 and 1 for no alignment, otherwise power of 2
     uint32_t align;
@@ -135,7 +152,22 @@ struct PACKED elf32_section_header_entry {
     char _[16];
 };
 
+// followed by the name and the descriptor, each padded to 4 bytes
+struct PACKED elf32_note_header {
+    enum : uint32_t {
+        NT_GNU_BUILD_ID = 3,
+    };
+    uint32_t namesz;
+    uint32_t descsz;
+    uint32_t type;
+};
+
+// build ids longer than this are ignored
+constexpr size_t ELF_BUILD_ID_MAX = 64;
+
 struct elf32_load_data {
+    // the path given to execve, kept when an interpreter is run
+    const char* filename;
     const fs::vfs::dentry* exec_dent;
     const char* const* argv;
     const char* const* envp;
@@ -145,7 +177,47 @@ struct elf32_load_data {
     bool system;
 };
 
+struct elf32_auxv_entry {
+    enum : uint32_t {
+        AT_NULL = 0,
+        AT_PHDR = 3,
+        AT_PHENT = 4,
+        AT_PHNUM = 5,
+        AT_PAGESZ = 6,
+        AT_BASE = 7,
+        AT_ENTRY = 9,
+        AT_UID = 11,
+        AT_EUID = 12,
+        AT_GID = 13,
+        AT_EGID = 14,
+        AT_HWCAP = 16,
+        AT_CLKTCK = 17,
+        AT_SECURE = 23,
+        AT_RANDOM = 25,
+        AT_HWCAP2 = 26,
+        AT_EXECFN = 31,
+    } type;
+    uint32_t val;
+};
+
+// check the first 4 bytes of the file
+constexpr bool elf32_check_magic(const char* magic)
+{
+    return magic[0] == 0x7f && magic[1] == 'E' && magic[2] == 'L' && magic[3] == 'F';
+}
+
 // TODO: environment variables
 int elf32_load(elf32_load_data* data);
 
+// map the user stack of the current process and push argc, argv, envp
+// and auxv onto it, d->sp is set to the top of it
+//
+// auxv should contain the entries specific to the format only, the
+// common ones and AT_NULL are appended here
+void elf32_init_stack(elf32_load_data* d,
+    const types::string<>& filename,
+    const std::vector<types::string<>>& argv,
+    const std::vector<types::string<>>& envp,
+    std::vector<elf32_auxv_entry> auxv);
+
 } // namespace types::elf
@@ -53,17 +53,23 @@ struct linux_hasher<T,
     }
 };
 
+// strings, like file names, might be chosen by the user space to make
+// them collide, so they are hashed with SipHash-1-3 keyed at boot
+uint32_t hash_string(const char* str, std::size_t len);
+
 template <typename T>
 struct linux_hasher<T, std::enable_if_t<is_c_string_v<T>>> {
-    static inline constexpr hash_t hash(const char* str, uint32_t bits)
+    static inline hash_t hash(const char* str, uint32_t bits)
     {
-        constexpr uint32_t seed = 131;
-        uint32_t hash = 0;
-
-        while (*str)
-            hash = hash * seed + (*str++);
-
-        return hash32(hash, bits);
+        return hash32(hash_string(str, strlen(str)), bits);
+    }
+};
+template <>
+struct linux_hasher<string_view> {
+    // hashes the same as the whole string does
+    static inline hash_t hash(const string_view& str, uint32_t bits)
+    {
+        return hash32(hash_string(str.str, str.len), bits);
     }
 };
 template <template <typename> typename String, typename Allocator>
@@ -74,13 +80,13 @@ struct linux_hasher<String<Allocator>,
         >
     >
 > {
-    static inline constexpr hash_t hash(types::string<Allocator>&& str, uint32_t bits)
+    static inline hash_t hash(types::string<Allocator>&& str, uint32_t bits)
     {
-        return linux_hasher<const char*>::hash(str.c_str(), bits);
+        return hash32(hash_string(str.c_str(), str.size()), bits);
     }
-    static inline constexpr hash_t hash(const types::string<Allocator>& str, uint32_t bits)
+    static inline hash_t hash(const types::string<Allocator>& str, uint32_t bits)
     {
-        return linux_hasher<const char*>::hash(str.c_str(), bits);
+        return hash32(hash_string(str.c_str(), str.size()), bits);
     }
 };
 
@@ -175,44 +181,64 @@ public:
 
 private:
     bucket_array_type buckets;
+    size_type m_size {};
 
 protected:
     constexpr uint32_t hash_length(void) const
     {
-        switch (buckets.capacity()) {
-        case 32:
-            return 5;
-        case 64:
-            return 6;
-        case 128:
-            return 7;
-        case 256:
-            return 8;
-        // TODO
-        default:
-            return 9;
-        }
+        // the number of buckets is always a power of 2
+        uint32_t bits = 0;
+        while ((size_type)1 << bits < buckets.size())
+            ++bits;
+        return bits;
     }
 
 public:
-    explicit constexpr hash_map(void)
-        : buckets(INITIAL_BUCKETS_ALLOCATED) {}
+    // @param nbuckets the number of buckets, MUST be a power of 2
+    explicit constexpr hash_map(size_type nbuckets = INITIAL_BUCKETS_ALLOCATED)
+        : buckets(nbuckets) {}
 
     constexpr hash_map(const hash_map& v)
-        : buckets(v.buckets) {}
+        : buckets(v.buckets), m_size(v.m_size) {}
 
     constexpr hash_map(hash_map&& v)
-        : buckets(std::move(v.buckets)) {}
+        : buckets(std::move(v.buckets)), m_size(std::exchange(v.m_size, 0)) {}
 
     constexpr ~hash_map()
     {
         buckets.clear();
     }
 
+    constexpr size_type size(void) const
+    {
+        return m_size;
+    }
+
+    constexpr size_type bucket_count(void) const
+    {
+        return buckets.size();
+    }
+
+    // redistribute the items into nbuckets buckets, a power of 2
+    // iterators and references to the items are invalidated
+    constexpr void rehash(size_type nbuckets)
+    {
+        bucket_array_type old(nbuckets);
+        old.swap(buckets);
+
+        for (auto& bucket : old) {
+            for (auto& item : bucket) {
+                auto hash_value = hasher_type::hash(item.first, hash_length());
+                buckets.at(hash_value).push_back(std::move(item));
+            }
+        }
+    }
+
     constexpr void emplace(pair_type p)
     {
         auto hash_value = hasher_type::hash(p.first, hash_length());
         buckets.at(hash_value).push_back(std::move(p));
+        ++m_size;
     }
 
     template <typename _key_type, typename _value_type>
@@ -228,6 +254,7 @@ public:
         for (auto iter = bucket.begin(); iter != bucket.end(); ++iter) {
             if (iter->first == key) {
                 bucket.erase(iter);
+                --m_size;
                 return;
             }
         }
@@ -255,6 +282,20 @@ public:
         return iterator_type(nullptr);
     }
 
+    // look up with a key of another type, e.g. a string_view,
+    // that Hasher hashes the same and compares equal to the key
+    template <typename _key_type>
+    constexpr iterator_type find_as(const _key_type& key)
+    {
+        auto hash_value = Hasher<_key_type>::hash(key, hash_length());
+        auto& bucket = buckets.at(hash_value);
+        for (auto& item : bucket) {
+            if (item.first == key)
+                return iterator_type(&item);
+        }
+        return iterator_type(nullptr);
+    }
+
     constexpr const_iterator_type find(const key_type& key) const
     {
         auto hash_value = hasher_type::hash(key, hash_length());
@@ -270,6 +311,7 @@ public:
     {
         for (auto& bucket : buckets)
             bucket.clear();
+        m_size = 0;
     }
 };
 
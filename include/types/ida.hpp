@@ -0,0 +1,97 @@
+#pragma once
+
+#include <cstddef>
+
+#include <stdint.h>
+
+namespace types {
+
+// bitmap based id allocator for ids in range [min, N)
+//
+// ids are handed out in increasing order starting after the last
+// allocated one and wrap around to the lowest available id when the
+// end of the range is reached, so a freed id is not reused immediately
+template <std::size_t N>
+class ida {
+private:
+    static constexpr std::size_t BITS = sizeof(uint32_t) * 8;
+
+    uint32_t m_bm[(N + BITS - 1) / BITS] {};
+    std::size_t m_min;
+    std::size_t m_next;
+    std::size_t m_used {};
+
+    constexpr bool test(std::size_t n) const
+    { return m_bm[n / BITS] & (1U << (n % BITS)); }
+
+    // @return first free id in [from, N), or N if there is none
+    constexpr std::size_t find_free(std::size_t from) const
+    {
+        while (from < N) {
+            uint32_t word = m_bm[from / BITS] | ((1U << (from % BITS)) - 1);
+            if (word != ~0U) {
+                std::size_t n = (from & ~(BITS - 1)) + __builtin_ctz(~word);
+                return n < N ? n : N;
+            }
+            from = (from & ~(BITS - 1)) + BITS;
+        }
+        return N;
+    }
+
+public:
+    explicit constexpr ida(std::size_t min = 0)
+        : m_min(min), m_next(min) {}
+
+    ida(const ida&) = delete;
+    ida& operator=(const ida&) = delete;
+
+    // @param skip the free ids it returns true for are not handed out
+    // @return allocated id, or -1 if all ids are in use or skipped
+    template <typename Skip>
+    constexpr long alloc(Skip skip)
+    {
+        // search till the end, then from the start to where we began
+        bool wrapped = false;
+        std::size_t n = find_free(m_next);
+        for (;;) {
+            if (n == N) {
+                if (wrapped)
+                    return -1;
+                wrapped = true;
+                n = find_free(m_min);
+                continue;
+            }
+            if (wrapped && n >= m_next)
+                return -1;
+            if (!skip(n))
+                break;
+            n = find_free(n + 1);
+        }
+
+        m_bm[n / BITS] |= 1U << (n % BITS);
+        m_next = n + 1;
+        ++m_used;
+        return n;
+    }
+
+    // @return allocated id, or -1 if all ids are in use
+    constexpr long alloc(void)
+    { return alloc([](std::size_t) { return false; }); }
+
+    constexpr void free(std::size_t n)
+    {
+        if (n < m_min || n >= N || !test(n))
+            return;
+
+        m_bm[n / BITS] &= ~(1U << (n % BITS));
+        --m_used;
+    }
+
+    constexpr bool full(void) const
+    { return m_used == N - m_min; }
+
+    constexpr bool allocated(std::size_t n) const
+    { return n < N && test(n); }
+};
+
+} // namespace types
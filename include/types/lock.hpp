@@ -28,6 +28,20 @@ inline void spin_unlock(uint32_t volatile* lock_addr)
 
 namespace types {
 
+// the locks held by a task, kept in its thread and
+// switched along with it by the scheduler
+struct held_locks {
+    // the task can't be killed safely while holding any of them
+    int mutexes;
+    // sleeping while holding any of them is a bug
+    int rw_spinlocks;
+};
+
+// where the locks are counted before the first task runs
+inline held_locks boot_held_locks;
+// the locks held by the running task
+inline held_locks* volatile current_held_locks = &boot_held_locks;
+
 struct mutex {
     using mtx_t = volatile uint32_t;
 
@@ -36,10 +50,12 @@ struct mutex {
     inline void lock(void)
     {
         spin_lock(&m_lock);
+        ++current_held_locks->mutexes;
     }
 
     inline void unlock(void)
     {
+        --current_held_locks->mutexes;
         spin_unlock(&m_lock);
     }
 };
@@ -64,4 +80,167 @@ public:
     }
 };
 
+inline uint32_t irq_save(void)
+{
+    uint32_t flags;
+    asm volatile(
+        "pushfl\n\t"
+        "popl %0\n\t"
+        "cli"
+        : "=r"(flags)
+        :
+        : "memory");
+    return flags;
+}
+
+inline void irq_restore(uint32_t flags)
+{
+    // restore IF only if it was set before
+    if (flags & 0x200)
+        asm volatile("sti" : : : "memory");
+}
+
+// spin lock shared with interrupt handlers
+//
+// interrupts are disabled while the lock is held, so that a handler
+// can't spin on a lock taken by the code it has interrupted
+struct spinlock {
+    mutex::mtx_t m_lock = 0;
+
+    inline uint32_t lock(void)
+    {
+        uint32_t flags = irq_save();
+        spin_lock(&m_lock);
+        return flags;
+    }
+
+    inline void unlock(uint32_t flags)
+    {
+        spin_unlock(&m_lock);
+        irq_restore(flags);
+    }
+};
+
+class spin_guard {
+private:
+    spinlock& m_lck;
+    uint32_t m_flags;
+
+public:
+    explicit spin_guard(spinlock& lck)
+        : m_lck(lck), m_flags(lck.lock()) { }
+
+    spin_guard(const spin_guard&) = delete;
+    spin_guard(spin_guard&&) = delete;
+
+    ~spin_guard()
+    {
+        m_lck.unlock(m_flags);
+    }
+};
+
+// reader-writer spin lock usable from interrupt handlers
+//
+// interrupts are disabled while the lock is held, and pending
+// writers block new readers so that writers won't starve
+struct rw_spinlock {
+    // protects the fields below
+    mutex::mtx_t m_lock = 0;
+    uint32_t m_readers = 0;
+    uint32_t m_writers_waiting = 0;
+    uint32_t m_writer = 0;
+
+    inline uint32_t read_lock(void)
+    {
+        uint32_t flags = irq_save();
+        for (;;) {
+            spin_lock(&m_lock);
+            if (!m_writer && !m_writers_waiting) {
+                ++m_readers;
+                spin_unlock(&m_lock);
+                break;
+            }
+            spin_unlock(&m_lock);
+        }
+        ++current_held_locks->rw_spinlocks;
+        return flags;
+    }
+
+    inline void read_unlock(uint32_t flags)
+    {
+        spin_lock(&m_lock);
+        --m_readers;
+        spin_unlock(&m_lock);
+        --current_held_locks->rw_spinlocks;
+        irq_restore(flags);
+    }
+
+    inline uint32_t write_lock(void)
+    {
+        uint32_t flags = irq_save();
+
+        spin_lock(&m_lock);
+        ++m_writers_waiting;
+        spin_unlock(&m_lock);
+
+        for (;;) {
+            spin_lock(&m_lock);
+            if (!m_writer && !m_readers) {
+                --m_writers_waiting;
+                m_writer = 1;
+                spin_unlock(&m_lock);
+                break;
+            }
+            spin_unlock(&m_lock);
+        }
+        ++current_held_locks->rw_spinlocks;
+        return flags;
+    }
+
+    inline void write_unlock(uint32_t flags)
+    {
+        spin_lock(&m_lock);
+        m_writer = 0;
+        spin_unlock(&m_lock);
+        --current_held_locks->rw_spinlocks;
+        irq_restore(flags);
+    }
+};
+
+class read_guard {
+private:
+    rw_spinlock& m_lck;
+    uint32_t m_flags;
+
+public:
+    explicit read_guard(rw_spinlock& lck)
+        : m_lck(lck), m_flags(lck.read_lock()) { }
+
+    read_guard(const read_guard&) = delete;
+    read_guard(read_guard&&) = delete;
+
+    ~read_guard()
+    {
+        m_lck.read_unlock(m_flags);
+    }
+};
+
+class write_guard {
+private:
+    rw_spinlock& m_lck;
+    uint32_t m_flags;
+
+public:
+    explicit write_guard(rw_spinlock& lck)
+        : m_lck(lck), m_flags(lck.write_lock()) { }
+
+    write_guard(const write_guard&) = delete;
+    write_guard(write_guard&&) = delete;
+
+    ~write_guard()
+    {
+        m_lck.write_unlock(m_flags);
+    }
+};
+
 } // namespace types
@@ -0,0 +1,95 @@
+#pragma once
+
+#include <cstddef>
+#include <utility>
+
+#include <stdint.h>
+
+namespace types {
+
+// bounded lock-free queue with multiple producers and a single consumer
+//
+// producers may run in interrupt context, so neither push() nor pop()
+// takes locks or allocates memory. N MUST be a power of 2
+template <typename T, std::size_t N>
+class mpsc_ring {
+    static_assert(N && !(N & (N - 1)), "N must be a power of 2");
+
+private:
+    struct slot {
+        // sequence number telling whose turn it is on this slot
+        // seq == pos: free for the producer claiming pos
+        // seq == pos + 1: filled, ready for the consumer
+        uint32_t seq;
+        T data;
+    };
+
+    slot m_slots[N];
+    uint32_t m_head {}; // producers
+    uint32_t m_tail {}; // consumer
+
+    static constexpr uint32_t load(const uint32_t& val)
+    { return __atomic_load_n(&val, __ATOMIC_ACQUIRE); }
+
+    static constexpr void store(uint32_t& val, uint32_t n)
+    { __atomic_store_n(&val, n, __ATOMIC_RELEASE); }
+
+public:
+    mpsc_ring()
+    {
+        for (std::size_t i = 0; i < N; ++i)
+            m_slots[i].seq = i;
+    }
+
+    mpsc_ring(const mpsc_ring&) = delete;
+    mpsc_ring& operator=(const mpsc_ring&) = delete;
+
+    // @return false if the ring is full
+    bool push(const T& val)
+    {
+        uint32_t pos = __atomic_load_n(&m_head, __ATOMIC_RELAXED);
+        for (;;) {
+            auto& s = m_slots[pos & (N - 1)];
+            int32_t diff = (int32_t)(load(s.seq) - pos);
+
+            if (diff < 0)
+                return false;
+
+            if (diff > 0) {
+                // another producer has claimed this slot
+                pos = __atomic_load_n(&m_head, __ATOMIC_RELAXED);
+                continue;
+            }
+
+            if (__atomic_compare_exchange_n(&m_head, &pos, pos + 1,
+                    true, __ATOMIC_RELAXED, __ATOMIC_RELAXED)) {
+                s.data = val;
+                store(s.seq, pos + 1);
+                return true;
+            }
+        }
+    }
+
+    // MUST be called by the consumer only
+    // @return false if the ring is empty
+    bool pop(T& out)
+    {
+        auto& s = m_slots[m_tail & (N - 1)];
+        if ((int32_t)(load(s.seq) - (m_tail + 1)) < 0)
+            return false;
+
+        out = std::move(s.data);
+        store(s.seq, m_tail + N);
+        ++m_tail;
+        return true;
+    }
+
+    // MUST be called by the consumer only
+    bool empty() const
+    {
+        const auto& s = m_slots[m_tail & (N - 1)];
+        return (int32_t)(load(s.seq) - (m_tail + 1)) < 0;
+    }
+};
+
+} // namespace types
@@ -1,5 +1,6 @@
 #pragma once
 
+#include <cstddef>
 #include <vector>
 
 #include <string.h>
@@ -8,6 +9,13 @@
 
 namespace types {
 
+// a part of a string that is not null terminated,
+// for lookups that don't need a string of their own
+struct string_view {
+    const char* str;
+    std::size_t len;
+};
+
 template <typename Allocator = std::allocator<char>>
 class string : public std::vector<char, Allocator> {
 public:
@@ -58,6 +66,16 @@ public:
     {
         return strcmp(c_str(), rhs.c_str()) == 0;
     }
+    constexpr bool operator==(const string_view& rhs) const
+    {
+        if (size() != rhs.len)
+            return false;
+        for (size_type i = 0; i < rhs.len; ++i) {
+            if (c_str()[i] != rhs.str[i])
+                return false;
+        }
+        return true;
+    }
     constexpr string& assign(const char* str, size_type n = npos)
     {
         this->clear();
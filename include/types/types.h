@@ -14,6 +14,8 @@
 
 #ifdef __GNUC__
 #define SECTION(x) __attribute__((section(x)))
+// written during boot only, remapped read-only after init
+#define __ro_after_init SECTION(".data.ro_after_init")
 #else
 #error "no definition for ((SECTION))"
 #endif
@@ -0,0 +1,51 @@
+#!/bin/sh
+
+# make the kernel symbol table as an assembly file
+#
+# $1: the output file
+# $2: the kernel linked, an empty table is made if not given
+#
+# the text symbols between __text_start and __text_end are put in
+# the address order, as kallsyms_addrs, kallsyms_types and the
+# pointers to their names kallsyms_names, the number in kallsyms_num
+
+NM=${NM:-nm}
+
+if [ -n "$2" ]; then
+    $NM -n "$2" | awk '
+        $3 == "__text_start" { intext = 1; next }
+        $3 == "__text_end" { intext = 0 }
+        intext && NF == 3 && $2 ~ /^[tTwW]$/ && $3 !~ /^(\.L|kallsyms_)/ { print }
+    '
+fi | awk '
+    { addr[NR] = $1; type[NR] = $2; name[NR] = $3 }
+    END {
+        print "    .section .rodata.kallsyms, \"a\""
+        print "    .balign 4"
+        print ""
+        print "    .globl kallsyms_num"
+        print "kallsyms_num:"
+        printf "    .long %d\n\n", NR
+
+        print "    .globl kallsyms_addrs"
+        print "kallsyms_addrs:"
+        for (i = 1; i <= NR; ++i)
+            printf "    .long 0x%s\n", addr[i]
+        print ""
+
+        print "    .globl kallsyms_names"
+        print "kallsyms_names:"
+        for (i = 1; i <= NR; ++i)
+            printf "    .long .Lkallsyms_name%d\n", i
+        print ""
+
+        print "    .globl kallsyms_types"
+        print "kallsyms_types:"
+        for (i = 1; i <= NR; ++i)
+            printf "    .ascii \"%s\"\n", type[i]
+        print ""
+
+        for (i = 1; i <= NR; ++i)
+            printf ".Lkallsyms_name%d:\n    .asciz \"%s\"\n", i, name[i]
+    }
+' > "$1"
@@ -0,0 +1,57 @@
+#include <list>
+
+#include <fs/devtmpfs.hpp>
+#include <kernel/errno.h>
+#include <string.h>
+#include <types/allocator.hpp>
+#include <types/status.h>
+#include <types/string.hpp>
+
+namespace fs::devtmpfs {
+
+struct dev_entry {
+    types::string<> name;
+    mode_t mode;
+    node_t node;
+};
+
+static std::list<dev_entry>* s_devs;
+static vfs::dentry* s_root;
+
+void add(const char* name, mode_t mode, node_t node)
+{
+    if (!s_devs)
+        s_devs = new std::list<dev_entry>;
+
+    for (const auto& dev : *s_devs) {
+        if (strcmp(dev.name.c_str(), name) == 0)
+            return;
+    }
+
+    s_devs->emplace_back(dev_entry { name, mode, node });
+
+    if (s_root)
+        vfs_mknode(s_root, name, mode, node);
+}
+
+int mount(vfs::dentry* mnt)
+{
+    if (s_root)
+        return -EBUSY;
+
+    auto* devfs = register_fs(make_tmpfs());
+    int ret = mnt->ind->fs->mount(mnt, devfs);
+    if (ret != GB_OK)
+        return ret;
+
+    s_root = devfs->root();
+
+    if (s_devs) {
+        for (const auto& dev : *s_devs)
+            vfs_mknode(s_root, dev.name.c_str(), dev.mode, dev.node);
+    }
+
+    return GB_OK;
+}
+
+} // namespace fs::devtmpfs
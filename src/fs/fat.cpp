@@ -26,6 +26,20 @@ inline void fat32::_raw_read_sector(void* buf, uint32_t sector_no)
     assert(n == SECTOR_SIZE);
 }
 
+inline int fat32::_raw_write_sector(const void* buf, uint32_t sector_no)
+{
+    ssize_t n = vfs_write(
+        device,
+        (const char*)buf,
+        sector_no * SECTOR_SIZE,
+        SECTOR_SIZE);
+    if (n < 0)
+        return n;
+    if (n != SECTOR_SIZE)
+        return -EIO;
+    return GB_OK;
+}
+
 // buf MUST be larger than 4096 bytes
 inline void fat32::_raw_read_cluster(void* buf, cluster_t no)
 {
@@ -37,6 +51,17 @@ inline void fat32::_raw_read_cluster(void* buf, cluster_t no)
     }
 }
 
+inline int fat32::_raw_write_cluster(const void* buf, cluster_t no)
+{
+    no -= 2;
+    for (int i = 0; i < sectors_per_cluster; ++i) {
+        int ret = _raw_write_sector((const char*)buf + SECTOR_SIZE * i, data_region_offset + no * sectors_per_cluster + i);
+        if (ret != GB_OK)
+            return ret;
+    }
+    return GB_OK;
+}
+
 char* fat32::read_cluster(cluster_t no)
 {
     auto iter = buf.find(no);
@@ -63,6 +88,585 @@ void fat32::release_cluster(cluster_t no)
         --iter->second.ref;
 }
 
+int fat32::write_back_cluster(cluster_t no)
+{
+    auto iter = buf.find(no);
+    assert(iter);
+    return _raw_write_cluster(iter->second.data, no);
+}
+
+int fat32::set_fat(cluster_t no, cluster_t val)
+{
+    fat[no] = (fat[no] & ~CLUSTER_MASK) | (val & CLUSTER_MASK);
+
+    uint32_t fat_sector = no * sizeof(cluster_t) / SECTOR_SIZE;
+    auto* data = (char*)fat + fat_sector * SECTOR_SIZE;
+
+    // the other copies are still written if one of them fails
+    int ret = GB_OK;
+    for (uint32_t i = 0; i < fat_copies; ++i) {
+        int err = _raw_write_sector(data, reserved_sectors + i * sectors_per_fat + fat_sector);
+        if (err != GB_OK)
+            ret = err;
+    }
+    return ret;
+}
+
+int fat32::alloc_cluster(cluster_t prev, cluster_t& out_no)
+{
+    cluster_t cnt = cluster_cnt();
+    cluster_t start = next_free_cluster_hint;
+    if (start < 2 || start >= cnt)
+        start = 2;
+
+    cluster_t no = start;
+    while (fat[no] & CLUSTER_MASK) {
+        if (++no == cnt)
+            no = 2;
+        if (no == start)
+            return -ENOSPC;
+    }
+
+    auto iter = buf.find(no);
+    if (iter) {
+        memset(iter->second.data, 0x00, cluster_size());
+    } else {
+        auto* data = new char[cluster_size()] {};
+        buf.emplace(no, buf_object { data, 0 });
+    }
+    // the cluster is still free if zeroing it fails
+    int ret = write_back_cluster(no);
+    if (ret != GB_OK)
+        return ret;
+
+    // put back what was changed in memory if the fat can't be written
+    ret = set_fat(no, EOC_MARK);
+    if (ret != GB_OK) {
+        set_fat(no, 0);
+        return ret;
+    }
+    if (prev) {
+        ret = set_fat(prev, no);
+        if (ret != GB_OK) {
+            set_fat(prev, EOC_MARK);
+            set_fat(no, 0);
+            return ret;
+        }
+    }
+
+    next_free_cluster_hint = no + 1;
+    // 0xffffffff means the free cluster count is unknown
+    if (free_clusters != 0xffffffff)
+        --free_clusters;
+
+    out_no = no;
+    return GB_OK;
+}
+
+int fat32::free_chain(cluster_t no)
+{
+    int ret = GB_OK;
+    while (no >= 2 && no < EOC) {
+        cluster_t next = fat[no] & CLUSTER_MASK;
+        int err = set_fat(no, 0);
+        if (err != GB_OK)
+            ret = err;
+        if (free_clusters != 0xffffffff)
+            ++free_clusters;
+        no = next;
+    }
+    return ret;
+}
+
+int fat32::sync_fs_info(void)
+{
+    auto* sect = new char[SECTOR_SIZE];
+    _raw_read_sector(sect, fs_info_sector_no);
+
+    auto* info = reinterpret_cast<fs_info_sector*>(sect);
+    info->free_clusters = free_clusters;
+    info->next_free_cluster = next_free_cluster_hint;
+
+    int ret = _raw_write_sector(sect, fs_info_sector_no);
+    delete[] sect;
+    return ret;
+}
+
+// data and the fat are written through, only the fs info sector is left
+int fat32::sync_fs(void)
+{
+    int ret = sync_fs_info();
+    if (ret != GB_OK)
+        return ret;
+
+    if (!S_ISBLK(device->mode))
+        return GB_OK;
+    return block_device_flush(device->fs->inode_getnode(device));
+}
+
+// file data, the fat and directory entries are written through, so
+// only the drive cache is left. the fs info sector is just a hint
+int fat32::inode_sync(inode*, bool)
+{
+    if (!S_ISBLK(device->mode))
+        return GB_OK;
+    return block_device_flush(device->fs->inode_getnode(device));
+}
+
+int fat32::statfs(struct statfs* buf)
+{
+    buf->f_type = MSDOS_SUPER_MAGIC;
+    buf->f_bsize = cluster_size();
+    // the first two entries of the fat are reserved
+    buf->f_blocks = cluster_cnt() - 2;
+    buf->f_bfree = free_clusters;
+    buf->f_bavail = free_clusters;
+    buf->f_fsid.__val[0] = serial_number;
+    buf->f_namelen = LFN_MAX_CHARS;
+
+    return GB_OK;
+}
+
+void fat32::set_dirent_location(ino_t ino, cluster_t cluster, uint32_t idx)
+{
+    auto iter = dirent_locs.find(ino);
+    if (iter)
+        iter->second = { cluster, idx };
+    else
+        dirent_locs.emplace(ino, dirent_location { cluster, idx });
+}
+
+directory_entry* fat32::alloc_dirents(inode* dir, int cnt, cluster_t& out_cl, uint32_t& out_idx)
+{
+    uint32_t per_cluster = cluster_size() / sizeof(directory_entry);
+    if ((uint32_t)cnt > per_cluster)
+        return nullptr;
+
+    cluster_t no = cl(dir);
+    cluster_t last = 0;
+    for (; no >= 2 && no < EOC; last = no, no = fat[no] & CLUSTER_MASK) {
+        auto* ents = reinterpret_cast<directory_entry*>(read_cluster(no));
+
+        int found = 0;
+        for (uint32_t i = 0; i < per_cluster; ++i) {
+            uint8_t first = ents[i].filename[0];
+            if (first != 0x00 && first != DIRENT_FREE) {
+                found = 0;
+                continue;
+            }
+
+            if (++found == cnt) {
+                out_cl = no;
+                out_idx = i + 1 - cnt;
+                return ents + out_idx;
+            }
+        }
+
+        release_cluster(no);
+    }
+
+    if (alloc_cluster(last, no) != GB_OK)
+        return nullptr;
+    dir->size += cluster_size();
+
+    out_cl = no;
+    out_idx = 0;
+    return reinterpret_cast<directory_entry*>(read_cluster(no));
+}
+
+ino_t fat32::file_ino(cluster_t no, uint32_t idx, cluster_t first)
+{
+    // cluster numbers vary more than indices, keep them in the low bits
+    uint64_t key = ((uint64_t)idx << 32) | no;
+    auto iter = file_inos.find(key);
+    if (iter)
+        return iter->second;
+
+    ino_t ino = next_file_ino++;
+    file_inos.emplace(key, ino);
+    file_clusters.emplace(ino, first);
+    set_dirent_location(ino, no, idx);
+
+    return ino;
+}
+
+int fat32::sync_dirent(inode* file)
+{
+    auto iter = dirent_locs.find(file->ino);
+    if (!iter)
+        return GB_OK;
+
+    auto [ no, idx ] = iter->second;
+    auto* d = reinterpret_cast<directory_entry*>(read_cluster(no)) + idx;
+    d->size = S_ISDIR(file->mode) ? 0 : file->size;
+    if (S_ISREG(file->mode)) {
+        cluster_t first = cl(file);
+        d->cluster_hi = first >> 16;
+        d->cluster_lo = first & 0xffff;
+    }
+
+    int ret = write_back_cluster(no);
+    release_cluster(no);
+    return ret;
+}
+
+static inline bool is_valid_short_char(char ch)
+{
+    if ((uint8_t)ch < 0x20)
+        return false;
+
+    for (const char* p = "\"*+,./:;<=>?[\\]| "; *p; ++p) {
+        if (ch == *p)
+            return false;
+    }
+    return true;
+}
+
+uint8_t fat32::short_name_checksum(const char* name)
+{
+    uint8_t sum = 0;
+    for (int i = 0; i < 11; ++i)
+        sum = ((sum & 1) << 7) + (sum >> 1) + (uint8_t)name[i];
+    return sum;
+}
+
+uint16_t fat32::lfn_char(const long_name_entry* ent, int i)
+{
+    if (i < 5)
+        return ent->name1[i];
+    if (i < 11)
+        return ent->name2[i - 5];
+    return ent->name3[i - 11];
+}
+
+void fat32::set_lfn_char(long_name_entry* ent, int i, uint16_t ch)
+{
+    if (i < 5)
+        ent->name1[i] = ch;
+    else if (i < 11)
+        ent->name2[i - 5] = ch;
+    else
+        ent->name3[i - 11] = ch;
+}
+
+int fat32::utf8_to_utf16(const char* str, uint16_t* out, int max)
+{
+    int n = 0;
+    auto* p = (const uint8_t*)str;
+    while (*p) {
+        uint32_t cp;
+        int len;
+        if (*p < 0x80)
+            cp = *p, len = 1;
+        else if ((*p & 0xe0) == 0xc0)
+            cp = *p & 0x1f, len = 2;
+        else if ((*p & 0xf0) == 0xe0)
+            cp = *p & 0x0f, len = 3;
+        else if ((*p & 0xf8) == 0xf0)
+            cp = *p & 0x07, len = 4;
+        else
+            return -EINVAL;
+
+        for (int i = 1; i < len; ++i) {
+            if ((p[i] & 0xc0) != 0x80)
+                return -EINVAL;
+            cp = (cp << 6) | (p[i] & 0x3f);
+        }
+        p += len;
+
+        if (cp >= 0x10000) {
+            if (n + 2 > max)
+                return -ENAMETOOLONG;
+            cp -= 0x10000;
+            out[n++] = 0xd800 | (cp >> 10);
+            out[n++] = 0xdc00 | (cp & 0x3ff);
+        } else {
+            if (n + 1 > max)
+                return -ENAMETOOLONG;
+            out[n++] = cp;
+        }
+    }
+    return n;
+}
+
+void fat32::utf16_to_utf8(const uint16_t* str, types::string<>& out)
+{
+    for (; *str && *str != 0xffff; ++str) {
+        uint32_t cp = *str;
+        if ((cp & 0xfc00) == 0xd800 && (str[1] & 0xfc00) == 0xdc00) {
+            cp = 0x10000 + (((cp & 0x3ff) << 10) | (str[1] & 0x3ff));
+            ++str;
+        }
+
+        if (cp < 0x80) {
+            out += (char)cp;
+        } else if (cp < 0x800) {
+            out += (char)(0xc0 | (cp >> 6));
+            out += (char)(0x80 | (cp & 0x3f));
+        } else if (cp < 0x10000) {
+            out += (char)(0xe0 | (cp >> 12));
+            out += (char)(0x80 | ((cp >> 6) & 0x3f));
+            out += (char)(0x80 | (cp & 0x3f));
+        } else {
+            out += (char)(0xf0 | (cp >> 18));
+            out += (char)(0x80 | ((cp >> 12) & 0x3f));
+            out += (char)(0x80 | ((cp >> 6) & 0x3f));
+            out += (char)(0x80 | (cp & 0x3f));
+        }
+    }
+}
+
+bool fat32::make_short_name(const char* name, char* out, uint8_t& out_case)
+{
+    memset(out, ' ', 11);
+    out_case = 0;
+    bool lossless = true;
+
+    // leading dots and spaces are not allowed
+    while (*name == '.' || *name == ' ') {
+        ++name;
+        lossless = false;
+    }
+
+    const char* ext = nullptr;
+    const char* end = name;
+    for (; *end; ++end) {
+        if (*end == '.')
+            ext = end;
+    }
+
+    auto convert = [&lossless](const char* s, const char* end,
+        char* dst, int max, uint8_t lower_flag) -> uint8_t {
+        bool has_lower = false, has_upper = false;
+        int n = 0;
+        for (; s != end; ++s) {
+            char ch = *s;
+            if (ch == ' ' || ch == '.') {
+                lossless = false;
+                continue;
+            }
+
+            if (islower(ch)) {
+                has_lower = true;
+                ch = toupper(ch);
+            } else if (isupper(ch)) {
+                has_upper = true;
+            }
+
+            if (!is_valid_short_char(ch)) {
+                ch = '_';
+                lossless = false;
+            }
+
+            if (n == max) {
+                lossless = false;
+                break;
+            }
+            dst[n++] = ch;
+        }
+
+        if (has_lower && has_upper)
+            lossless = false;
+        return has_lower ? lower_flag : 0;
+    };
+
+    out_case |= convert(name, ext ? ext : end, out, 8, VFAT_FILENAME_LOWERCASE);
+    if (ext)
+        out_case |= convert(ext + 1, end, out + 8, 3, VFAT_EXTENSION_LOWERCASE);
+
+    if (out[0] == ' ') {
+        out[0] = '_';
+        lossless = false;
+    }
+
+    return lossless;
+}
+
+bool fat32::short_name_exists(inode* dir, const char* name)
+{
+    uint32_t per_cluster = cluster_size() / sizeof(directory_entry);
+
+    for (cluster_t no = cl(dir); no >= 2 && no < EOC; no = fat[no] & CLUSTER_MASK) {
+        auto* ents = reinterpret_cast<directory_entry*>(read_cluster(no));
+
+        for (uint32_t i = 0; i < per_cluster; ++i) {
+            auto* d = ents + i;
+            if (!d->filename[0]) {
+                release_cluster(no);
+                return false;
+            }
+
+            if ((uint8_t)d->filename[0] == DIRENT_FREE || d->attributes.volume_label)
+                continue;
+
+            int k = 0;
+            while (k < 11 && d->filename[k] == name[k])
+                ++k;
+
+            if (k == 11) {
+                release_cluster(no);
+                return true;
+            }
+        }
+
+        release_cluster(no);
+    }
+
+    return false;
+}
+
+bool fat32::name_exists(inode* dir, const char* name)
+{
+    bool found = false;
+
+    inode_readdir(dir, 0, [name, &found](const char* fn, size_t len, ino_t, uint8_t) -> int {
+        if (!len)
+            len = strlen(fn);
+
+        for (size_t i = 0; i < len; ++i) {
+            if (!name[i] || tolower(fn[i]) != tolower(name[i]))
+                return GB_OK;
+        }
+        if (name[len])
+            return GB_OK;
+
+        found = true;
+        return GB_FAILED;
+    });
+
+    return found;
+}
+
+int fat32::create_entry(dentry* dir, const char* filename, bool is_dir, ino_t& out_ino)
+{
+    char sname[11];
+    uint8_t name_case;
+    bool exact = make_short_name(filename, sname, name_case);
+
+    if (name_exists(dir->ind, filename))
+        return -EEXIST;
+    if (exact && short_name_exists(dir->ind, sname))
+        return -EEXIST;
+
+    // the name is stored in long name entries if the short one is lossy
+    uint16_t lfn[LFN_MAX_CHARS];
+    int lfn_len = 0;
+    int lfn_cnt = 0;
+
+    if (!exact) {
+        lfn_len = utf8_to_utf16(filename, lfn, LFN_MAX_CHARS);
+        if (lfn_len < 0)
+            return lfn_len;
+        lfn_cnt = (lfn_len + 12) / 13;
+
+        // the entries of a name are kept in a single cluster
+        if ((uint32_t)lfn_cnt + 1 > cluster_size() / sizeof(directory_entry))
+            return -ENAMETOOLONG;
+
+        // generate a numeric tail like NAME~1.EXT
+        char base[8];
+        memcpy(base, sname, 8);
+
+        int base_len = 0;
+        while (base_len < 8 && base[base_len] != ' ')
+            ++base_len;
+
+        name_case = 0;
+        for (int i = 1; ; ++i) {
+            if (i == 1000000)
+                return -EEXIST;
+
+            char tail[9];
+            int tail_len = snprintf(tail, sizeof(tail), "~%d", i);
+
+            int keep = base_len < 8 - tail_len ? base_len : 8 - tail_len;
+            memset(sname, ' ', 8);
+            memcpy(sname, base, keep);
+            memcpy(sname + keep, tail, tail_len);
+
+            if (!short_name_exists(dir->ind, sname))
+                break;
+        }
+    }
+
+    cluster_t first = 0;
+    if (is_dir) {
+        int ret = alloc_cluster(0, first);
+        if (ret != GB_OK)
+            return ret;
+    }
+
+    cluster_t ent_cl;
+    uint32_t ent_idx;
+    auto* d = alloc_dirents(dir->ind, lfn_cnt + 1, ent_cl, ent_idx);
+    if (!d) {
+        if (first) {
+            free_chain(first);
+            sync_fs_info();
+        }
+        return -ENOSPC;
+    }
+
+    // long name entries are stored in reverse order before the short one
+    uint8_t checksum = short_name_checksum(sname);
+    for (int i = 0; i < lfn_cnt; ++i) {
+        int ord = lfn_cnt - i;
+        auto* ln = reinterpret_cast<long_name_entry*>(d);
+
+        memset(ln, 0x00, sizeof(long_name_entry));
+        ln->ord = ord | (i == 0 ? LFN_LAST : 0);
+        ln->attributes = LFN_ATTR;
+        ln->checksum = checksum;
+
+        for (int k = 0; k < 13; ++k) {
+            int idx = (ord - 1) * 13 + k;
+            uint16_t ch = 0xffff;
+            if (idx < lfn_len)
+                ch = lfn[idx];
+            else if (idx == lfn_len)
+                ch = 0x0000;
+            set_lfn_char(ln, k, ch);
+        }
+
+        ++d, ++ent_idx;
+    }
+
+    memset(d, 0x00, sizeof(directory_entry));
+    memcpy(d->filename, sname, 11);
+    d->_reserved = name_case;
+    if (is_dir)
+        d->attributes.subdir = 1;
+    else
+        d->attributes.archive = 1;
+    d->cluster_hi = first >> 16;
+    d->cluster_lo = first & 0xffff;
+
+    int ret = write_back_cluster(ent_cl);
+    if (ret != GB_OK) {
+        // the entries stay in the buffer, free them there
+        for (auto* ent = d - lfn_cnt; ent <= d; ++ent)
+            ent->filename[0] = DIRENT_FREE;
+        release_cluster(ent_cl);
+
+        if (first) {
+            free_chain(first);
+            sync_fs_info();
+        }
+        return ret;
+    }
+    release_cluster(ent_cl);
+
+    if (is_dir) {
+        set_dirent_location(first, ent_cl, ent_idx);
+        out_ino = first;
+    } else {
+        out_ino = file_ino(ent_cl, ent_idx, 0);
+    }
+    sync_fs_info();
+
+    return GB_OK;
+}
+
 int fat32::inode_readdir(fs::inode* dir, size_t offset, const fs::vfs::filldir_func& filldir)
 {
     cluster_t next = cl(dir);
@@ -71,19 +675,80 @@ int fat32::inode_readdir(fs::inode* dir, size_t offset, const fs::vfs::filldir_f
             return 0;
         next = fat[next];
     }
+
+    // long name entries collected so far
+    // other systems may pad the last entry past LFN_MAX_CHARS,
+    // the name ends at the first 0x0000 or 0xffff in it
+    uint16_t lfn[LFN_MAX_ENTRIES * 13 + 1];
+    uint8_t lfn_checksum = 0;
+    int lfn_next_ord = 0;
+    // nread before the long name entries, so that we can
+    // start over from the first of them if filldir fails
+    size_t lfn_start = 0;
+
     size_t nread = 0;
     do {
         char* buf = read_cluster(next);
         auto* d = reinterpret_cast<directory_entry*>(buf) + (offset % (sectors_per_cluster * SECTOR_SIZE)) / sizeof(directory_entry);
         offset = 0;
-        auto* end = d + (sectors_per_cluster * SECTOR_SIZE / sizeof(directory_entry));
+        auto* end = reinterpret_cast<directory_entry*>(buf) + (sectors_per_cluster * SECTOR_SIZE / sizeof(directory_entry));
         for (; d < end && d->filename[0]; ++d) {
+            if ((uint8_t)d->filename[0] == DIRENT_FREE) {
+                lfn_next_ord = 0;
+                nread += sizeof(directory_entry);
+                continue;
+            }
+
+            if (is_long_name(d)) {
+                auto* ln = reinterpret_cast<long_name_entry*>(d);
+                int ord = ln->ord & LFN_ORD_MASK;
+
+                if (ln->ord & LFN_LAST) {
+                    if (ord < 1 || ord > LFN_MAX_ENTRIES) {
+                        lfn_next_ord = 0;
+                    } else {
+                        lfn_checksum = ln->checksum;
+                        lfn[ord * 13] = 0;
+                        lfn_next_ord = ord;
+                        lfn_start = nread;
+                    }
+                }
+
+                // broken sequence, fall back to the short name
+                if (lfn_next_ord != ord || ln->checksum != lfn_checksum) {
+                    lfn_next_ord = 0;
+                } else {
+                    for (int i = 0; i < 13; ++i)
+                        lfn[(ord - 1) * 13 + i] = lfn_char(ln, i);
+                    --lfn_next_ord;
+                    // wait for the short entry
+                    if (!lfn_next_ord)
+                        lfn_next_ord = -1;
+                }
+
+                nread += sizeof(directory_entry);
+                continue;
+            }
+
+            bool has_lfn = lfn_next_ord == -1
+                && lfn_checksum == short_name_checksum(d->filename);
+            if (lfn_next_ord != -1)
+                lfn_start = nread;
+            lfn_next_ord = 0;
+
             if (d->attributes.volume_label) {
                 nread += sizeof(directory_entry);
                 continue;
             }
 
-            ino_t ino = _rearrange(d);
+            uint32_t idx = d - reinterpret_cast<directory_entry*>(buf);
+            ino_t ino;
+            if (d->attributes.subdir) {
+                ino = _rearrange(d);
+                set_dirent_location(ino, next, idx);
+            } else {
+                ino = file_ino(next, idx, _rearrange(d));
+            }
             auto* ind = get_inode(ino);
             if (!ind) {
                 mode_t mode = 0777;
@@ -95,29 +760,33 @@ int fat32::inode_readdir(fs::inode* dir, size_t offset, const fs::vfs::filldir_f
             }
 
             types::string<> fname;
-            for (int i = 0; i < 8; ++i) {
-                if (d->filename[i] == ' ')
-                    break;
-                if (d->_reserved & VFAT_FILENAME_LOWERCASE)
-                    fname += tolower(d->filename[i]);
-                else
-                    fname += toupper(d->filename[i]);
-            }
-            if (d->extension[0] != ' ')
-                fname += '.';
-            for (int i = 1; i < 3; ++i) {
-                if (d->extension[i] == ' ')
-                    break;
-                if (d->_reserved & VFAT_EXTENSION_LOWERCASE)
-                    fname += tolower(d->extension[i]);
-                else
-                    fname += toupper(d->extension[i]);
+            if (has_lfn) {
+                utf16_to_utf8(lfn, fname);
+            } else {
+                for (int i = 0; i < 8; ++i) {
+                    if (d->filename[i] == ' ')
+                        break;
+                    if (d->_reserved & VFAT_FILENAME_LOWERCASE)
+                        fname += tolower(d->filename[i]);
+                    else
+                        fname += toupper(d->filename[i]);
+                }
+                if (d->extension[0] != ' ')
+                    fname += '.';
+                for (int i = 0; i < 3; ++i) {
+                    if (d->extension[i] == ' ')
+                        break;
+                    if (d->_reserved & VFAT_EXTENSION_LOWERCASE)
+                        fname += tolower(d->extension[i]);
+                    else
+                        fname += toupper(d->extension[i]);
+                }
             }
-            auto ret = filldir(fname.c_str(), 0, ind->ino, ind->mode & S_IFMT);
+            auto ret = filldir(fname.c_str(), 0, ind->ino, IFTODT(ind->mode));
 
             if (ret != GB_OK) {
                 release_cluster(next);
-                return nread;
+                return lfn_start;
             }
 
             nread += sizeof(directory_entry);
@@ -148,10 +817,8 @@ fat32::fat32(inode* _device)
     data_region_offset = reserved_sectors + fat_copies * sectors_per_fat;
     fat = (cluster_t*)new char[SECTOR_SIZE * sectors_per_fat];
     // TODO: optimize
-    for (uint32_t i = 0; i < 4; ++i)
+    for (uint32_t i = 0; i < sectors_per_fat; ++i)
         _raw_read_sector((char*)fat + i * SECTOR_SIZE, reserved_sectors + i);
-    for (uint32_t i = 4; i < sectors_per_fat; ++i)
-        memset((char*)fat + i * SECTOR_SIZE, 0x00, SECTOR_SIZE);
 
     int i = 0;
     while (i < 11 && info->label[i] != 0x20) {
@@ -160,7 +827,8 @@ fat32::fat32(inode* _device)
     }
     label[i] = 0x00;
 
-    _raw_read_sector(buf, info->fs_info_sector);
+    fs_info_sector_no = info->fs_info_sector;
+    _raw_read_sector(buf, fs_info_sector_no);
 
     auto* fsinfo = reinterpret_cast<fs_info_sector*>(buf);
     free_clusters = fsinfo->free_clusters;
@@ -168,6 +836,8 @@ fat32::fat32(inode* _device)
 
     delete[] buf;
 
+    next_file_ino = cluster_cnt();
+
     size_t _root_dir_clusters = 1;
     cluster_t next = root_dir;
     while ((next = fat[next]) < EOC)
@@ -186,6 +856,9 @@ fat32::~fat32()
 size_t fat32::inode_read(inode* file, char* buf, size_t buf_size, size_t offset, size_t n)
 {
     cluster_t next = cl(file);
+    if (next < 2)
+        return 0;
+
     uint32_t cluster_size = SECTOR_SIZE * sectors_per_cluster;
     size_t orig_n = n;
 
@@ -234,6 +907,235 @@ size_t fat32::inode_read(inode* file, char* buf, size_t buf_size, size_t offset,
     return orig_n - n;
 }
 
+size_t fat32::inode_write(inode* file, const char* buf, size_t offset, size_t n)
+{
+    if (!S_ISREG(file->mode))
+        return -EINVAL;
+
+    // the data after the end of file is not necessarily zeros,
+    // fill the gap before writing after it
+    if (offset > file->size) {
+        int ret = inode_truncate(file, offset);
+        if (ret != GB_OK)
+            return ret;
+    }
+
+    size_t csize = cluster_size();
+    bool allocated = false;
+
+    if (!n)
+        return 0;
+
+    // empty files have no cluster
+    if (cl(file) < 2) {
+        cluster_t first;
+        int ret = alloc_cluster(0, first);
+        if (ret == GB_OK) {
+            file_clusters.find(file->ino)->second = first;
+            ret = sync_dirent(file);
+        }
+
+        sync_fs_info();
+        if (ret != GB_OK)
+            return ret;
+    }
+
+    // find the cluster where offset lies in, extend the file if needed
+    cluster_t no = cl(file);
+    for (size_t i = offset / csize; i; --i) {
+        cluster_t next = fat[no] & CLUSTER_MASK;
+        if (next >= EOC) {
+            int ret = alloc_cluster(no, next);
+            if (ret != GB_OK) {
+                sync_fs_info();
+                return ret;
+            }
+            allocated = true;
+        }
+        no = next;
+    }
+
+    // the error that stopped the write
+    int err = GB_OK;
+
+    size_t orig_n = n;
+    size_t pos = offset % csize;
+    while (n) {
+        auto* data = read_cluster(no);
+
+        size_t cnt = n < csize - pos ? n : csize - pos;
+        memcpy(data + pos, buf, cnt);
+
+        err = write_back_cluster(no);
+        release_cluster(no);
+        if (err != GB_OK)
+            break;
+
+        buf += cnt;
+        n -= cnt;
+        pos = 0;
+
+        if (!n)
+            break;
+
+        cluster_t next = fat[no] & CLUSTER_MASK;
+        if (next >= EOC) {
+            err = alloc_cluster(no, next);
+            if (err != GB_OK)
+                break;
+            allocated = true;
+        }
+        no = next;
+    }
+
+    if (allocated)
+        sync_fs_info();
+
+    size_t written = orig_n - n;
+    if (offset + written > file->size) {
+        file->size = offset + written;
+        int ret = sync_dirent(file);
+        if (ret != GB_OK)
+            return ret;
+    }
+
+    if (!written && orig_n)
+        return err;
+
+    return written;
+}
+
+int fat32::inode_mkfile(dentry* dir, const char* filename, mode_t)
+{
+    ino_t no;
+    int ret = create_entry(dir, filename, false, no);
+    if (ret != GB_OK)
+        return ret;
+
+    // fat doesn't store permissions
+    auto* ind = cache_inode(0, no, S_IFREG | 0777, 0, 0);
+    if (dir->flags.in.present)
+        dir->append(ind, filename, true);
+
+    return GB_OK;
+}
+
+int fat32::inode_mkdir(dentry* dir, const char* dirname)
+{
+    ino_t no;
+    int ret = create_entry(dir, dirname, true, no);
+    if (ret != GB_OK)
+        return ret;
+
+    // '..' of the directories under root points to cluster 0
+    cluster_t parent = cl(dir->ind);
+    if (parent == root_dir)
+        parent = 0;
+
+    auto* ents = reinterpret_cast<directory_entry*>(read_cluster(no));
+    memcpy(ents[0].filename, ".          ", 11);
+    ents[0].attributes.subdir = 1;
+    ents[0].cluster_hi = no >> 16;
+    ents[0].cluster_lo = no & 0xffff;
+
+    memcpy(ents[1].filename, "..         ", 11);
+    ents[1].attributes.subdir = 1;
+    ents[1].cluster_hi = parent >> 16;
+    ents[1].cluster_lo = parent & 0xffff;
+
+    ret = write_back_cluster(no);
+    release_cluster(no);
+    if (ret != GB_OK)
+        return ret;
+
+    auto* ind = cache_inode(cluster_size(), no, S_IFDIR | 0777, 0, 0);
+    if (dir->flags.in.present)
+        dir->append(ind, dirname, true);
+
+    return GB_OK;
+}
+
+int fat32::inode_truncate(inode* file, size_t size)
+{
+    if (!S_ISREG(file->mode))
+        return -EINVAL;
+
+    // extend the file with zeros
+    if (size > file->size) {
+        char zeros[SECTOR_SIZE] {};
+        while (file->size < size) {
+            size_t cnt = size - file->size;
+            if (cnt > SECTOR_SIZE)
+                cnt = SECTOR_SIZE;
+
+            ssize_t ret = inode_write(file, zeros, file->size, cnt);
+            if (ret < 0)
+                return ret;
+        }
+        return GB_OK;
+    }
+
+    size_t csize = cluster_size();
+
+    // empty files have no cluster, the entry is updated before
+    // the chain is freed so that it never points to free clusters
+    if (!size) {
+        cluster_t first = cl(file);
+        size_t old_size = file->size;
+
+        file_clusters.find(file->ino)->second = 0;
+        file->size = 0;
+        int ret = sync_dirent(file);
+        if (ret != GB_OK) {
+            file_clusters.find(file->ino)->second = first;
+            file->size = old_size;
+            return ret;
+        }
+
+        if (first >= 2) {
+            ret = free_chain(first);
+            sync_fs_info();
+        }
+        return ret;
+    }
+
+    size_t keep = (size + csize - 1) / csize;
+
+    cluster_t no = cl(file);
+    for (size_t i = 1; i < keep; ++i)
+        no = fat[no] & CLUSTER_MASK;
+
+    // the first error is reported, the rest is done anyway
+    int ret = GB_OK;
+
+    // the clusters cut off are leaked if the cut fails
+    cluster_t rest = fat[no] & CLUSTER_MASK;
+    if (rest < EOC) {
+        ret = set_fat(no, EOC_MARK);
+        if (ret == GB_OK)
+            ret = free_chain(rest);
+        sync_fs_info();
+    }
+
+    // clear the data after the new end of file in the last cluster
+    size_t tail = size % csize;
+    if (tail) {
+        auto* data = read_cluster(no);
+        memset(data + tail, 0x00, csize - tail);
+        int err = write_back_cluster(no);
+        release_cluster(no);
+        if (ret == GB_OK)
+            ret = err;
+    }
+
+    file->size = size;
+    int err = sync_dirent(file);
+    if (ret == GB_OK)
+        ret = err;
+
+    return ret;
+}
+
 int fat32::inode_stat(dentry* ent, statx* st, unsigned int mask)
 {
     st->stx_mask = 0;
@@ -0,0 +1,625 @@
+#include <algorithm>
+
+#include <fs/procfs.hpp>
+#include <kernel/anon_inode.hpp>
+#include <kernel/errno.h>
+#include <kernel/hw/timer.h>
+#include <kernel/boottime.hpp>
+#include <kernel/irq.hpp>
+#include <kernel/kallsyms.hpp>
+#include <kernel/mm.hpp>
+#include <kernel/oops.hpp>
+#include <kernel/pagecache.hpp>
+#include <kernel/process.hpp>
+#include <kernel/syscall.hpp>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <types/status.h>
+#include <types/string.hpp>
+
+namespace fs::procfs {
+
+static pseudofs* s_procfs;
+static bool s_mounted;
+
+// helper for generating file contents piece by piece
+class printer {
+private:
+    char* m_buf;
+    size_t m_size;
+    size_t m_len {};
+
+public:
+    printer(char* buf, size_t size) : m_buf(buf), m_size(size) { }
+
+    constexpr size_t len(void) const { return m_len; }
+
+    template <typename... Args>
+    void print(const char* fmt, Args... args)
+    {
+        if (m_len + 1 >= m_size)
+            return;
+
+        int n = snprintf(m_buf + m_len, m_size - m_len, fmt, args...);
+        if (n > 0)
+            m_len += n;
+        if (m_len >= m_size)
+            m_len = m_size - 1;
+    }
+
+    void putc(char ch)
+    {
+        if (m_len < m_size)
+            m_buf[m_len++] = ch;
+    }
+
+    void hex(uint32_t val, int digits)
+    {
+        for (int i = digits - 1; i >= 0; --i)
+            putc("0123456789abcdef"[(val >> (i * 4)) & 0xf]);
+    }
+
+    // with a leading 0, our snprintf has no %o
+    void octal(uint32_t val)
+    {
+        char digits[11];
+        int n = 0;
+        do {
+            digits[n++] = '0' + (val & 7);
+            val >>= 3;
+        } while (val);
+
+        putc('0');
+        while (n)
+            putc(digits[--n]);
+    }
+
+    void skip(size_t n)
+    {
+        m_len = std::min(m_len + n, m_size - 1);
+    }
+
+    constexpr char* end(void) const { return m_buf + m_len; }
+    constexpr size_t left(void) const { return m_size - m_len; }
+};
+
+static process* get_process(pid_t pid)
+{
+    if (!procs->try_find(pid))
+        return nullptr;
+    return &procs->find(pid);
+}
+
+static const char* proc_name(const process& proc)
+{
+    if (proc.thds.empty())
+        return "";
+    return proc.thds.begin()->name.c_str();
+}
+
+static char proc_state(const process& proc)
+{
+    if (proc.is_zombie())
+        return 'Z';
+
+    for (const auto& thd : proc.thds) {
+        if (thd.attr.ready)
+            return 'R';
+    }
+    return 'S';
+}
+
+static size_t show_status(pid_t pid, char* buf, size_t buf_size)
+{
+    auto* proc = get_process(pid);
+    if (!proc)
+        return 0;
+
+    const char* state;
+    switch (proc_state(*proc)) {
+    case 'R': state = "R (running)"; break;
+    case 'Z': state = "Z (zombie)"; break;
+    default: state = "S (sleeping)"; break;
+    }
+
+    printer out(buf, buf_size);
+    out.print("Name:\t%s\n", proc_name(*proc));
+    out.print("State:\t%s\n", state);
+    out.print("Tgid:\t%d\n", proc->pid);
+    out.print("Pid:\t%d\n", proc->pid);
+    out.print("PPid:\t%d\n", proc->ppid);
+    out.print("Pgid:\t%d\n", proc->pgid);
+    out.print("Sid:\t%d\n", proc->sid);
+    out.print("Threads:\t%d\n", (int)proc->thds.size());
+
+    if (!proc->build_id.empty()) {
+        out.print("BuildID:\t");
+        for (auto byte : proc->build_id)
+            out.hex(byte, 2);
+        out.putc('\n');
+    }
+
+    return out.len();
+}
+
+static size_t show_stat(pid_t pid, char* buf, size_t buf_size)
+{
+    auto* proc = get_process(pid);
+    if (!proc)
+        return 0;
+
+    printer out(buf, buf_size);
+    out.print("%d (%s) %c %d %d %d",
+        proc->pid, proc_name(*proc), proc_state(*proc),
+        proc->ppid, proc->pgid, proc->sid);
+
+    // the fields we don't keep track of are reported as zeros
+    for (int field = 7; field <= 52; ++field) {
+        if (field == 20)
+            out.print(" %d", (int)proc->thds.size());
+        else
+            out.print(" 0");
+    }
+    out.putc('\n');
+
+    return out.len();
+}
+
+static size_t show_cmdline(pid_t pid, char* buf, size_t buf_size)
+{
+    auto* proc = get_process(pid);
+    if (!proc)
+        return 0;
+
+    size_t n = proc->cmdline.size();
+    if (n > buf_size)
+        n = buf_size;
+    memcpy(buf, proc->cmdline.data(), n);
+
+    return n;
+}
+
+static size_t show_maps(pid_t pid, char* buf, size_t buf_size)
+{
+    auto* proc = get_process(pid);
+    if (!proc)
+        return 0;
+
+    printer out(buf, buf_size);
+    for (const auto& area : proc->mms) {
+        if (area.is_kernel_space())
+            continue;
+
+        out.hex((uint32_t)area.start, 8);
+        out.putc('-');
+        out.hex((uint32_t)area.end(), 8);
+        out.print(" r%cxp ", area.attr.write ? 'w' : '-');
+        out.hex(area.file_offset, 8);
+        out.print(" 00:00 %d\n", area.attr.mapped ? (int)area.mapped_file->ino : 0);
+    }
+
+    return out.len();
+}
+
+static size_t show_exe(pid_t pid, char* buf, size_t buf_size)
+{
+    auto* proc = get_process(pid);
+    if (!proc || proc->exe.empty())
+        return 0;
+
+    auto path = proc->exe.full_path();
+    size_t n = path.size();
+    if (n > buf_size)
+        n = buf_size;
+    memcpy(buf, path.c_str(), n);
+
+    return n;
+}
+
+// offset is the user space address of the process
+static ssize_t access_mem(pid_t pid, char* buf, size_t offset, size_t n, bool write)
+{
+    auto* proc = get_process(pid);
+    if (!proc || proc->is_zombie())
+        return -ESRCH;
+
+    if (!may_access(*proc))
+        return -EPERM;
+
+    return proc->mms.access((void*)offset, buf, n, write);
+}
+
+static size_t show_fd(pid_t pid, int fd, char* buf, size_t buf_size)
+{
+    auto* proc = get_process(pid);
+    if (!proc)
+        return 0;
+
+    auto* file = proc->files[fd];
+    if (!file)
+        return 0;
+
+    printer out(buf, buf_size);
+    if (auto* dent = file->get_dentry(); dent) {
+        types::path path;
+        dent->path(*fs::fs_root, path);
+        out.print("%s", path.full_path().c_str());
+    } else if (auto* anon = fs::anon_file_from(file); anon) {
+        out.print("anon_inode:%s", anon->name());
+    } else if (S_ISFIFO(file->mode)) {
+        out.print("pipe:");
+    }
+
+    return out.len();
+}
+
+// the O_* flags the file is opened with
+static uint32_t file_open_flags(fs::file* file)
+{
+    uint32_t flags = O_RDONLY;
+    if (file->flags.read && file->flags.write)
+        flags = O_RDWR;
+    else if (file->flags.write)
+        flags = O_WRONLY;
+
+    if (file->flags.close_on_exec)
+        flags |= O_CLOEXEC;
+    if (file->flags.direct)
+        flags |= O_DIRECT;
+    if (file->flags.sync)
+        flags |= O_SYNC;
+    if (file->flags.append)
+        flags |= O_APPEND;
+    if (auto* anon = fs::anon_file_from(file); anon && anon->nonblock())
+        flags |= O_NONBLOCK;
+
+    return flags;
+}
+
+static size_t show_fdinfo(pid_t pid, int fd, char* buf, size_t buf_size)
+{
+    auto* proc = get_process(pid);
+    if (!proc)
+        return 0;
+
+    auto* file = proc->files[fd];
+    if (!file)
+        return 0;
+
+    auto* pos = file->pos();
+    auto* dent = file->get_dentry();
+
+    printer out(buf, buf_size);
+    out.print("pos:\t%d\n", pos ? (int)*pos : 0);
+    out.print("flags:\t");
+    out.octal(file_open_flags(file));
+    out.print("\nmnt_id:\t0\n");
+    ino_t ino = 0;
+    if (dent)
+        ino = dent->ind->ino;
+    else if (auto* anon = fs::anon_file_from(file); anon)
+        ino = anon->ino();
+    out.print("ino:\t%d\n", (int)ino);
+
+    if (out.left() > 1)
+        out.skip(file->describe(out.end(), out.left()));
+
+    return out.len();
+}
+
+static void refresh_fds(pid_t pid)
+{
+    char path[32];
+    snprintf(path, sizeof(path), "%d/fd", pid);
+
+    s_procfs->clear(path);
+
+    auto* proc = get_process(pid);
+    if (!proc)
+        return;
+
+    proc->files.for_each([pid](int fd, fs::file*) {
+        char name[48];
+        snprintf(name, sizeof(name), "%d/fd/%d", pid, fd);
+        s_procfs->add_symlink(name, [pid, fd](char* buf, size_t buf_size) -> size_t {
+            return show_fd(pid, fd, buf, buf_size);
+        });
+    });
+}
+
+static void refresh_fdinfo(pid_t pid)
+{
+    char path[32];
+    snprintf(path, sizeof(path), "%d/fdinfo", pid);
+
+    s_procfs->clear(path);
+
+    auto* proc = get_process(pid);
+    if (!proc)
+        return;
+
+    proc->files.for_each([pid](int fd, fs::file*) {
+        char name[48];
+        snprintf(name, sizeof(name), "%d/fdinfo/%d", pid, fd);
+        s_procfs->add_file(name, [pid, fd](char* buf, size_t buf_size) -> size_t {
+            return show_fdinfo(pid, fd, buf, buf_size);
+        });
+    });
+}
+
+static size_t show_meminfo(char* buf, size_t buf_size)
+{
+    size_t total = total_raw_pages() * (PAGE_SIZE / 1024);
+    size_t free = free_raw_pages() * (PAGE_SIZE / 1024);
+    size_t cached = fs::pcache ? fs::pcache->cached_pages() * (PAGE_SIZE / 1024) : 0;
+    size_t heap = types::__allocator::m_palloc->used() / 1024;
+
+    printer out(buf, buf_size);
+    out.print("MemTotal: %d kB\n", (int)total);
+    out.print("MemFree: %d kB\n", (int)free);
+    out.print("MemAvailable: %d kB\n", (int)free);
+    out.print("Buffers: 0 kB\n");
+    out.print("Cached: %d kB\n", (int)cached);
+    out.print("SwapTotal: 0 kB\n");
+    out.print("SwapFree: 0 kB\n");
+    out.print("Shmem: 0 kB\n");
+    // the kernel heap is the closest thing we have to the slab
+    out.print("Slab: %d kB\n", (int)heap);
+    out.print("KernelStack: %d kB\n",
+        (int)(kernel::tasks::kstack_stats.in_use * THREAD_KERNEL_STACK_SIZE / 1024));
+
+    return out.len();
+}
+
+// in USER_HZ (100 per second) while the timer runs at 1000Hz
+static size_t show_stat_all(char* buf, size_t buf_size)
+{
+    const auto& stats = kernel::tasks::stats;
+    int user = stats.user_ticks / 10;
+    int system = stats.system_ticks / 10;
+
+    printer out(buf, buf_size);
+    out.print("cpu  %d 0 %d 0 0 0 0 0 0 0\n", user, system);
+    out.print("cpu0 %d 0 %d 0 0 0 0 0 0 0\n", user, system);
+
+    size_t intr = 0;
+    for (int i = 0; i < kernel::irq::IRQ_COUNT; ++i)
+        intr += kernel::irq::irq_count(i);
+
+    out.print("intr %d", (int)intr);
+    for (int i = 0; i < kernel::irq::IRQ_COUNT; ++i)
+        out.print(" %d", (int)kernel::irq::irq_count(i));
+    out.putc('\n');
+
+    out.print("ctxt %d\n", (int)stats.ctx_switches);
+    // there's no rtc driver to tell the boot time
+    out.print("btime 0\n");
+    out.print("processes %d\n", (int)stats.forks);
+
+    return out.len();
+}
+
+static size_t show_kstackinfo(char* buf, size_t buf_size)
+{
+    const auto& st = kernel::tasks::kstack_stats;
+
+    printer out(buf, buf_size);
+    out.print("allocated: %d\n", (int)st.allocated);
+    out.print("in_use: %d\n", (int)st.in_use);
+    out.print("peak_in_use: %d\n", (int)st.peak_in_use);
+    out.print("max_depth: %d\n", (int)st.max_depth);
+
+    return out.len();
+}
+
+static size_t show_uptime(char* buf, size_t buf_size)
+{
+    // ticks are in milliseconds
+    size_t ticks = current_ticks();
+    int sec = ticks / 1000;
+    int centisec = (ticks / 10) % 100;
+
+    printer out(buf, buf_size);
+    out.print("%d.%d%d 0.00\n", sec, centisec / 10, centisec % 10);
+
+    return out.len();
+}
+
+static size_t show_tainted(char* buf, size_t buf_size)
+{
+    printer out(buf, buf_size);
+    out.print("%d\n", (int)kernel::tainted());
+
+    return out.len();
+}
+
+static ssize_t read_kptr_restrict(char* buf, size_t offset, size_t n)
+{
+    char text[4];
+    size_t len = snprintf(text, sizeof(text), "%d\n", kernel::kallsyms::kptr_restrict());
+    if (offset >= len)
+        return 0;
+
+    n = std::min(n, len - offset);
+    memcpy(buf, text + offset, n);
+    return n;
+}
+
+// a single digit, optionally followed by a newline
+static ssize_t write_kptr_restrict(const char* buf, size_t, size_t n)
+{
+    if (n == 0 || n > 2 || (n == 2 && buf[1] != '\n'))
+        return -EINVAL;
+    if (buf[0] < '0' || buf[0] > '9')
+        return -EINVAL;
+
+    int ret = kernel::kallsyms::set_kptr_restrict(buf[0] - '0');
+    if (ret != 0)
+        return ret;
+    return n;
+}
+
+// 0 or 1, optionally followed by a newline
+static ssize_t write_syscall_trace(const char* buf, size_t, size_t n)
+{
+    if (n == 0 || n > 2 || (n == 2 && buf[1] != '\n'))
+        return -EINVAL;
+    if (buf[0] < '0' || buf[0] > '9')
+        return -EINVAL;
+
+    int ret = set_syscall_trace(buf[0] - '0');
+    if (ret != 0)
+        return ret;
+    return n;
+}
+
+static ssize_t read_syscall_trace(char* buf, size_t offset, size_t n)
+{
+    char text[4];
+    size_t len = snprintf(text, sizeof(text), "%d\n", syscall_trace());
+    if (offset >= len)
+        return 0;
+
+    n = std::min(n, len - offset);
+    memcpy(buf, text + offset, n);
+    return n;
+}
+
+// the link targets depend on who follows them
+static size_t show_self(char* buf, size_t buf_size)
+{
+    printer out(buf, buf_size);
+    out.print("%d", current_process->pid);
+
+    return out.len();
+}
+
+static size_t show_thread_self(char* buf, size_t buf_size)
+{
+    pid_t pid = current_process->pid;
+
+    printer out(buf, buf_size);
+    out.print("%d/task/%d", pid, pid);
+
+    return out.len();
+}
+
+static void populate_root(void)
+{
+    s_procfs->add_file("meminfo", show_meminfo);
+    s_procfs->add_file("stat", show_stat_all);
+    s_procfs->add_file("uptime", show_uptime);
+    s_procfs->add_file("boottime", kernel::kinit::show_boot_phases);
+    s_procfs->add_file("kstackinfo", show_kstackinfo);
+    s_procfs->add_symlink("self", show_self);
+    s_procfs->add_symlink("thread-self", show_thread_self);
+    s_procfs->add_rw_file("kallsyms", kernel::kallsyms::read, nullptr, 0444);
+
+    s_procfs->mkdir("sys");
+    s_procfs->mkdir("sys/kernel");
+    s_procfs->add_file("sys/kernel/tainted", show_tainted);
+    s_procfs->add_rw_file("sys/kernel/kptr_restrict",
+        read_kptr_restrict, write_kptr_restrict, 0644);
+    s_procfs->add_rw_file("sys/kernel/syscall_trace",
+        read_syscall_trace, write_syscall_trace, 0644);
+}
+
+pseudofs* instance(void)
+{
+    if (s_procfs)
+        return s_procfs;
+
+    s_procfs = new pseudofs(PROC_SUPER_MAGIC);
+    populate_root();
+
+    return s_procfs;
+}
+
+int mount(vfs::dentry* mnt)
+{
+    if (s_mounted)
+        return -EBUSY;
+
+    auto* procfs = register_fs(instance());
+    int ret = mnt->ind->fs->mount(mnt, procfs);
+    if (ret != GB_OK)
+        return ret;
+
+    s_mounted = true;
+    return GB_OK;
+}
+
+// files shared by /proc/<pid> and /proc/<pid>/task/<tid>
+static void add_pid_files(const char* dir, pid_t pid)
+{
+    auto* procfs = instance();
+
+    const struct {
+        const char* name;
+        size_t (*show)(pid_t, char*, size_t);
+    } files[] = {
+        { "status", show_status },
+        { "stat", show_stat },
+        { "cmdline", show_cmdline },
+        { "maps", show_maps },
+    };
+
+    char path[64];
+    for (const auto& file : files) {
+        snprintf(path, sizeof(path), "%s/%s", dir, file.name);
+
+        auto* show = file.show;
+        procfs->add_file(path, [pid, show](char* buf, size_t buf_size) -> size_t {
+            return show(pid, buf, buf_size);
+        });
+    }
+
+    snprintf(path, sizeof(path), "%s/exe", dir);
+    procfs->add_symlink(path, [pid](char* buf, size_t buf_size) -> size_t {
+        return show_exe(pid, buf, buf_size);
+    });
+}
+
+void add_process(pid_t pid)
+{
+    auto* procfs = instance();
+
+    char path[48];
+    snprintf(path, sizeof(path), "%d", pid);
+    if (procfs->mkdir(path) != GB_OK)
+        return;
+
+    add_pid_files(path, pid);
+
+    // everyone may open it, access_mem() checks the caller per access
+    snprintf(path, sizeof(path), "%d/mem", pid);
+    procfs->add_rw_file(path,
+        [pid](char* buf, size_t offset, size_t n) -> ssize_t {
+            return access_mem(pid, buf, offset, n, false);
+        },
+        [pid](const char* buf, size_t offset, size_t n) -> ssize_t {
+            return access_mem(pid, (char*)buf, offset, n, true);
+        }, 0666);
+
+    snprintf(path, sizeof(path), "%d/fd", pid);
+    procfs->mkdir(path, 0500, [pid]() { refresh_fds(pid); });
+    snprintf(path, sizeof(path), "%d/fdinfo", pid);
+    procfs->mkdir(path, 0500, [pid]() { refresh_fdinfo(pid); });
+
+    // processes have a single thread whose tid is the pid
+    snprintf(path, sizeof(path), "%d/task", pid);
+    procfs->mkdir(path);
+    snprintf(path, sizeof(path), "%d/task/%d", pid, pid);
+    procfs->mkdir(path);
+    add_pid_files(path, pid);
+}
+
+void remove_process(pid_t pid)
+{
+    char path[32];
+    snprintf(path, sizeof(path), "%d", pid);
+    instance()->remove(path);
+}
+
+} // namespace fs::procfs
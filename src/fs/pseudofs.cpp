@@ -0,0 +1,346 @@
+#include <fs/pseudofs.hpp>
+#include <kernel/errno.h>
+#include <kernel/mem.h>
+#include <string.h>
+#include <types/path.hpp>
+#include <types/status.h>
+
+fs::pseudofs::pseudofs(uint32_t magic)
+    : m_magic(magic)
+{
+    auto* root = new_node(nullptr, "", S_IFDIR | 0555, nullptr);
+    register_root_node(get_inode(root->ino));
+}
+
+fs::pseudofs::node* fs::pseudofs::new_node(
+    node* parent, const char* name, mode_t mode, show_func show)
+{
+    auto* n = new node { m_next_ino++, name, parent, std::move(show), nullptr, {}, nullptr, nullptr, false };
+    m_nodes.emplace(n->ino, n);
+    cache_inode(0, n->ino, mode, 0, 0);
+
+    if (parent)
+        parent->children.push_back(n);
+
+    return n;
+}
+
+fs::pseudofs::node* fs::pseudofs::lookup(const types::path& path)
+{
+    auto* cur = m_nodes.find(1)->second;
+    for (const auto& item : path) {
+        if (item.empty())
+            continue;
+
+        node* next = nullptr;
+        for (auto* child : cur->children) {
+            if (child->name == item) {
+                next = child;
+                break;
+            }
+        }
+
+        if (!next)
+            return nullptr;
+        cur = next;
+    }
+
+    return cur;
+}
+
+fs::vfs::dentry* fs::pseudofs::loaded_dentry(node* n)
+{
+    if (!n->parent)
+        return root();
+
+    auto* parent = loaded_dentry(n->parent);
+    if (!parent || !parent->flags.in.present)
+        return nullptr;
+
+    return parent->find(n->name);
+}
+
+void fs::pseudofs::free_node(node* n)
+{
+    for (auto* child : n->children)
+        free_node(child);
+
+    m_nodes.erase(n->ino);
+    free_inode(n->ino);
+    delete n;
+}
+
+void fs::pseudofs::mark_dead(node* n)
+{
+    for (auto* child : n->children)
+        mark_dead(child);
+
+    n->dead = true;
+}
+
+int fs::pseudofs::create(const char* path, mode_t mode, show_func show, refresh_func refresh)
+{
+    types::path p(path);
+    auto name = p.last_name();
+    if (name.empty())
+        return -EINVAL;
+
+    p.remove_last();
+    auto* dir = lookup(p);
+    if (!dir)
+        return -ENOENT;
+    if (!S_ISDIR(get_inode(dir->ino)->mode))
+        return -ENOTDIR;
+
+    for (auto* child : dir->children) {
+        if (child->name == name)
+            return -EEXIST;
+    }
+
+    auto* n = new_node(dir, name.c_str(), mode, std::move(show));
+    n->refresh = std::move(refresh);
+
+    // the directory has been read already, so the new
+    // node won't show up unless we add it ourselves
+    auto* dent = loaded_dentry(dir);
+    if (dent && dent->flags.in.present)
+        dent->append(get_inode(n->ino), name, false);
+
+    return GB_OK;
+}
+
+int fs::pseudofs::mkdir(const char* path, mode_t perm, refresh_func refresh)
+{
+    return create(path, S_IFDIR | (perm & 07777), nullptr, std::move(refresh));
+}
+
+int fs::pseudofs::add_file(const char* path, show_func show, mode_t perm)
+{
+    return create(path, S_IFREG | (perm & 07777), std::move(show), nullptr);
+}
+
+int fs::pseudofs::add_rw_file(const char* path, read_func read, write_func write, mode_t perm)
+{
+    int ret = create(path, S_IFREG | (perm & 07777), nullptr, nullptr);
+    if (ret != GB_OK)
+        return ret;
+
+    auto* n = lookup(path);
+    n->read = std::move(read);
+    n->write = std::move(write);
+
+    return GB_OK;
+}
+
+int fs::pseudofs::add_symlink(const char* path, show_func show)
+{
+    return create(path, S_IFLNK | 0777, std::move(show), nullptr);
+}
+
+int fs::pseudofs::remove(const char* path)
+{
+    auto* n = lookup(path);
+    if (!n)
+        return -ENOENT;
+    if (!n->parent)
+        return -EINVAL;
+
+    auto* dent = loaded_dentry(n->parent);
+
+    auto& siblings = n->parent->children;
+    for (auto iter = siblings.begin(); iter != siblings.end(); ++iter) {
+        if (*iter == n) {
+            siblings.erase(iter);
+            break;
+        }
+    }
+
+    if (dent && !dent->remove(n->name))
+        mark_dead(n);
+    else
+        free_node(n);
+    return GB_OK;
+}
+
+int fs::pseudofs::clear(const char* path)
+{
+    auto* dir = lookup(path);
+    if (!dir)
+        return -ENOENT;
+
+    auto* dent = loaded_dentry(dir);
+    for (auto* child : dir->children) {
+        if (dent && !dent->remove(child->name))
+            mark_dead(child);
+        else
+            free_node(child);
+    }
+    dir->children.clear();
+
+    return GB_OK;
+}
+
+size_t fs::pseudofs::inode_read(inode* file, char* buf, size_t buf_size, size_t offset, size_t n)
+{
+    auto iter = m_nodes.find(file->ino);
+    if (!iter || !S_ISREG(file->mode))
+        return 0;
+    if (iter->second->dead)
+        return -ESRCH;
+
+    if (iter->second->read) {
+        if (n > buf_size)
+            n = buf_size;
+        return iter->second->read(buf, offset, n);
+    }
+
+    if (!iter->second->show)
+        return 0;
+
+    // the content is generated again on every read, so the readers
+    // reading in small chunks might see a mix of different versions
+    auto* data = new char[MAX_FILE_SIZE];
+    size_t len = iter->second->show(data, MAX_FILE_SIZE);
+    if (len > MAX_FILE_SIZE)
+        len = MAX_FILE_SIZE;
+
+    if (offset >= len) {
+        delete[] data;
+        return 0;
+    }
+
+    if (n > len - offset)
+        n = len - offset;
+    if (n > buf_size)
+        n = buf_size;
+
+    memcpy(buf, data + offset, n);
+    delete[] data;
+
+    return n;
+}
+
+size_t fs::pseudofs::inode_write(inode* file, const char* buf, size_t offset, size_t n)
+{
+    auto iter = m_nodes.find(file->ino);
+    if (!iter || !S_ISREG(file->mode) || !iter->second->write)
+        return -EINVAL;
+    if (iter->second->dead)
+        return -ESRCH;
+
+    return iter->second->write(buf, offset, n);
+}
+
+int fs::pseudofs::inode_readlink(inode* link, char* buf, size_t buf_size)
+{
+    auto iter = m_nodes.find(link->ino);
+    if (!iter || !iter->second->show)
+        return -EINVAL;
+    if (iter->second->dead)
+        return -ESRCH;
+
+    size_t len = iter->second->show(buf, buf_size);
+    if (len > buf_size)
+        len = buf_size;
+    return len;
+}
+
+int fs::pseudofs::inode_stat(dentry* ent, statx* st, unsigned int mask)
+{
+    auto* ind = ent->ind;
+
+    st->stx_mask = 0;
+    if (mask & STATX_SIZE) {
+        // the size is unknown until the file is generated
+        st->stx_size = 0;
+        st->stx_mask |= STATX_SIZE;
+    }
+
+    if (mask & STATX_BLOCKS) {
+        st->stx_blocks = 0;
+        st->stx_blksize = 4096;
+        st->stx_mask |= STATX_BLOCKS;
+    }
+
+    st->stx_mode = 0;
+    if (mask & STATX_MODE) {
+        st->stx_mode |= ind->mode & ~S_IFMT;
+        st->stx_mask |= STATX_MODE;
+    }
+
+    if (mask & STATX_TYPE) {
+        st->stx_mode |= ind->mode & S_IFMT;
+        st->stx_mask |= STATX_TYPE;
+    }
+
+    if (mask & STATX_INO) {
+        st->stx_ino = ind->ino;
+        st->stx_mask |= STATX_INO;
+    }
+
+    if (mask & STATX_UID) {
+        st->stx_uid = ind->uid;
+        st->stx_mask |= STATX_UID;
+    }
+
+    if (mask & STATX_GID) {
+        st->stx_gid = ind->gid;
+        st->stx_mask |= STATX_GID;
+    }
+
+    return GB_OK;
+}
+
+// offset is the index of the entry, with 0 and 1 being "." and ".."
+int fs::pseudofs::inode_readdir(inode* dir, size_t offset, const filldir_func& filldir)
+{
+    auto iter = m_nodes.find(dir->ino);
+    if (!iter || !S_ISDIR(dir->mode) || iter->second->dead)
+        return -1;
+
+    auto* n = iter->second;
+    size_t nread = 0;
+
+    if (offset == 0 && n->refresh)
+        n->refresh();
+
+    for (; offset < 2; ++offset, ++nread) {
+        auto* ent = (offset == 0 || !n->parent) ? n : n->parent;
+        if (filldir(offset == 0 ? "." : "..", 0, ent->ino, DT_DIR) != GB_OK)
+            return nread;
+    }
+
+    size_t idx = 2;
+    for (auto* child : n->children) {
+        if (idx++ < offset)
+            continue;
+
+        auto* ind = get_inode(child->ino);
+        if (filldir(child->name.c_str(), 0, ind->ino, IFTODT(ind->mode)) != GB_OK)
+            break;
+        ++nread;
+    }
+
+    return nread;
+}
+
+void fs::pseudofs::inode_release(inode* ind)
+{
+    // mapped or opened through another entry
+    if (ind->refs)
+        return;
+
+    auto iter = m_nodes.find(ind->ino);
+    if (iter && iter->second->dead)
+        free_node(iter->second);
+}
+
+int fs::pseudofs::statfs(struct statfs* buf)
+{
+    buf->f_type = m_magic;
+    buf->f_bsize = PAGE_SIZE;
+    buf->f_files = m_nodes.size();
+    buf->f_namelen = 255;
+
+    return GB_OK;
+}
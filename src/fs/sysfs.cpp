@@ -0,0 +1,41 @@
+#include <assert.h>
+#include <fs/sysfs.hpp>
+#include <kernel/errno.h>
+#include <types/status.h>
+
+namespace fs::sysfs {
+
+static pseudofs* s_sysfs;
+static bool s_mounted;
+
+pseudofs* instance(void)
+{
+    if (s_sysfs)
+        return s_sysfs;
+
+    s_sysfs = new pseudofs(SYSFS_MAGIC);
+
+    int ret = s_sysfs->mkdir("bus");
+    ret |= s_sysfs->mkdir("bus/pci");
+    ret |= s_sysfs->mkdir("bus/pci/devices");
+    ret |= s_sysfs->mkdir("block");
+    assert(ret == GB_OK);
+
+    return s_sysfs;
+}
+
+int mount(vfs::dentry* mnt)
+{
+    if (s_mounted)
+        return -EBUSY;
+
+    auto* sysfs = register_fs(instance());
+    int ret = mnt->ind->fs->mount(mnt, sysfs);
+    if (ret != GB_OK)
+        return ret;
+
+    s_mounted = true;
+    return GB_OK;
+}
+
+} // namespace fs::sysfs
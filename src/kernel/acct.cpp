@@ -0,0 +1,103 @@
+#include <kernel/acct.hpp>
+#include <kernel/errno.h>
+#include <kernel/hw/timer.h>
+#include <kernel/process.hpp>
+#include <string.h>
+#include <sys/acct.h>
+#include <types/status.h>
+
+static fs::inode* s_acct_file;
+
+// timer ticks are in milliseconds
+static constexpr size_t TICKS_PER_AHZ = 1000 / AHZ;
+
+static comp_t encode_comp_t(uint32_t value)
+{
+    constexpr int MANTSIZE = 13;
+    constexpr int EXPSIZE = 3;
+    constexpr uint32_t MAXFRACT = (1 << MANTSIZE) - 1;
+
+    int exp = 0;
+    uint32_t rnd = 0;
+    while (value > MAXFRACT) {
+        rnd = value & (1 << (EXPSIZE - 1));
+        value >>= EXPSIZE;
+        ++exp;
+    }
+
+    // round up
+    if (rnd && ++value > MAXFRACT) {
+        value >>= EXPSIZE;
+        ++exp;
+    }
+
+    return (exp << MANTSIZE) + value;
+}
+
+// build the bits of an ieee 754 float without using the fpu
+static uint32_t encode_float(uint32_t value)
+{
+    if (value == 0)
+        return 0;
+
+    uint32_t exp = 127 + 31;
+    while (!(value & 0x80000000)) {
+        value <<= 1;
+        --exp;
+    }
+
+    return ((value >> 8) & 0x7fffff) | (exp << 23);
+}
+
+int kernel::acct::set_file(fs::inode* file)
+{
+    if (file && !S_ISREG(file->mode))
+        return -EACCES;
+
+    // the file is written to till it's replaced even if it's removed
+    if (file)
+        fs::inode_get(file);
+    if (s_acct_file)
+        fs::inode_put(s_acct_file);
+
+    s_acct_file = file;
+    return GB_OK;
+}
+
+void kernel::acct::record_exit(const process& proc, int exit_code)
+{
+    if (!s_acct_file)
+        return;
+
+    acct_v3 rec {};
+    rec.ac_version = ACCT_VERSION;
+
+    if (proc.forked_only)
+        rec.ac_flag |= AFORK;
+    // killed by the kernel
+    if (exit_code < 0)
+        rec.ac_flag |= AXSIG;
+
+    rec.ac_exitcode = exit_code;
+    rec.ac_pid = proc.pid;
+    rec.ac_ppid = proc.ppid;
+
+    // there's no rtc, so we can only tell the time since boot
+    rec.ac_btime = proc.start_ticks / 1000;
+    rec.ac_etime = encode_float((current_ticks() - proc.start_ticks) / TICKS_PER_AHZ);
+    rec.ac_utime = encode_comp_t(proc.utime / TICKS_PER_AHZ);
+    rec.ac_stime = encode_comp_t(proc.stime / TICKS_PER_AHZ);
+
+    // memory in use when the process exits
+    size_t pages = 0;
+    for (const auto& area : proc.mms) {
+        if (!area.is_kernel_space())
+            pages += area.pgs->size();
+    }
+    rec.ac_mem = encode_comp_t(pages * (PAGE_SIZE / 1024));
+
+    if (!proc.thds.empty())
+        strncpy(rec.ac_comm, proc.thds.begin()->name.c_str(), ACCT_COMM - 1);
+
+    fs::vfs_write(s_acct_file, (const char*)&rec, s_acct_file->size, sizeof(rec));
+}
@@ -0,0 +1,73 @@
+#include <kernel/anon_inode.hpp>
+#include <kernel/errno.h>
+#include <kernel/process.hpp>
+#include <string.h>
+#include <types/ida.hpp>
+
+static std::list<fs::anon_file*>* s_anon_files;
+
+// inode numbers of the anon files, 0 is left for none
+static types::ida<65536> s_anon_inos { 1 };
+
+fs::anon_file::anon_file(const char* name, file_flags flags, bool nonblock)
+    : file(0, nullptr, flags), m_name(name), m_nonblock(nonblock)
+{
+    long ino = s_anon_inos.alloc();
+    m_ino = ino < 0 ? 0 : ino;
+
+    if (!s_anon_files)
+        s_anon_files = new std::list<fs::anon_file*>;
+    s_anon_files->push_back(this);
+}
+
+fs::anon_file::~anon_file()
+{
+    if (m_ino)
+        s_anon_inos.free(m_ino);
+
+    for (auto iter = s_anon_files->begin(); iter != s_anon_files->end(); ++iter) {
+        if (*iter == this) {
+            s_anon_files->erase(iter);
+            break;
+        }
+    }
+}
+
+ssize_t fs::anon_file::read(char* __user, size_t)
+{
+    return -EINVAL;
+}
+
+ssize_t fs::anon_file::write(const char* __user, size_t)
+{
+    return -EINVAL;
+}
+
+// the kernel object is released along with the last reference
+void fs::anon_file::close(void) { }
+
+const std::list<fs::anon_file*>& fs::anon_files(void)
+{
+    if (!s_anon_files)
+        s_anon_files = new std::list<fs::anon_file*>;
+    return *s_anon_files;
+}
+
+fs::anon_file* fs::anon_file_from(file* fp, const char* name)
+{
+    for (auto* inst : anon_files()) {
+        // no rtti for dynamic_cast, so we compare the pointers instead
+        if (static_cast<file*>(inst) != fp)
+            continue;
+
+        if (name && strcmp(inst->name(), name) != 0)
+            return nullptr;
+        return inst;
+    }
+    return nullptr;
+}
+
+int fs::anon_inode_getfd(anon_file* file)
+{
+    return current_process->files.install(file);
+}
@@ -0,0 +1,571 @@
+#include <list>
+#include <vector>
+
+#include <fs/procfs.hpp>
+#include <kernel/binfmt.hpp>
+#include <kernel/errno.h>
+#include <kernel/process.hpp>
+#include <kernel/vfs.hpp>
+#include <string.h>
+#include <types/aout.hpp>
+#include <types/path.hpp>
+#include <types/status.h>
+#include <types/string.hpp>
+
+namespace kernel::binfmt {
+
+static std::list<const binfmt*>* s_formats;
+
+void register_binfmt(const binfmt* fmt, bool front)
+{
+    if (!s_formats)
+        s_formats = new std::list<const binfmt*>;
+
+    if (front)
+        s_formats->push_front(fmt);
+    else
+        s_formats->push_back(fmt);
+}
+
+void unregister_binfmt(const binfmt* fmt)
+{
+    if (s_formats)
+        s_formats->remove(fmt);
+}
+
+// the path of the executable, passed to its interpreter
+static types::string<> exec_path(const binprm* prm)
+{
+    types::path path;
+    prm->data->exec_dent->path(*current_process->root, path);
+    return path.full_path();
+}
+
+// run interp with args instead of the executable of prm
+static int run_interp(binprm* prm, const char* interp, std::vector<types::string<>>& args)
+{
+    auto* dent = fs::vfs_open(*current_process->root,
+        types::make_path(interp, current_process->pwd));
+    if (!dent)
+        return -errno;
+
+    std::vector<const char*> argv;
+    for (const auto& arg : args)
+        argv.push_back(arg.c_str());
+    argv.push_back(nullptr);
+
+    // the strings are copied by the loaders before the
+    // user memory is released, so they can live here
+    auto* d = prm->data;
+    d->exec_dent = dent;
+    d->argv = argv.data();
+
+    return search_handler(d, prm->depth + 1);
+}
+
+// #!interpreter [optional-arg]
+static int load_script(binprm* prm)
+{
+    if (prm->len < 2 || prm->buf[0] != '#' || prm->buf[1] != '!')
+        return -ENOEXEC;
+
+    const char* p = prm->buf + 2;
+    const char* end = p;
+    while (end < prm->buf + prm->len && *end != '\n')
+        ++end;
+    if (end == prm->buf + prm->len)
+        return -ENOEXEC;
+
+    while (p < end && (*p == ' ' || *p == '\t'))
+        ++p;
+    const char* interp = p;
+    while (p < end && *p != ' ' && *p != '\t')
+        ++p;
+    if (p == interp)
+        return -ENOEXEC;
+    types::string<> interp_str(interp, p - interp);
+
+    // the rest of the line is passed as a single argument
+    while (p < end && (*p == ' ' || *p == '\t'))
+        ++p;
+    while (end > p && (end[-1] == ' ' || end[-1] == '\t' || end[-1] == '\r'))
+        --end;
+
+    std::vector<types::string<>> args;
+    args.emplace_back(interp_str);
+    if (p != end)
+        args.emplace_back(p, end - p);
+    args.emplace_back(exec_path(prm));
+
+    auto* argv = prm->data->argv;
+    if (argv[0]) {
+        for (++argv; *argv; ++argv)
+            args.emplace_back(*argv);
+    }
+
+    return run_interp(prm, interp_str.c_str(), args);
+}
+
+static int load_elf(binprm* prm)
+{
+    if (prm->len < 4 || !types::elf::elf32_check_magic(prm->buf))
+        return -ENOEXEC;
+
+    int ret = types::elf::elf32_load(prm->data);
+    if (ret != GB_OK)
+        return -prm->data->errcode;
+    return GB_OK;
+}
+
+static int load_aout(binprm* prm)
+{
+    if (prm->len < sizeof(types::aout::aout_header)
+        || !types::aout::aout_check_magic((const types::aout::aout_header*)prm->buf))
+        return -ENOEXEC;
+
+    int ret = types::aout::aout_load(prm->data);
+    if (ret != GB_OK)
+        return -prm->data->errcode;
+    return GB_OK;
+}
+
+static const binfmt script_format { "script", load_script };
+static const binfmt elf_format { "elf", load_elf };
+static const binfmt aout_format { "aout", load_aout };
+
+static void register_builtin_formats(void)
+{
+    register_binfmt(&script_format);
+    register_binfmt(&elf_format);
+    register_binfmt(&aout_format);
+}
+
+int search_handler(types::elf::elf32_load_data* data, int depth)
+{
+    if (depth > MAX_INTERP_DEPTH)
+        return -ELOOP;
+
+    if (!data->exec_dent)
+        return -ENOENT;
+
+    auto* ind = data->exec_dent->ind;
+    if (!S_ISREG(ind->mode))
+        return -EACCES;
+
+    // the buffer is rather large for the kernel stack
+    // since the handlers might be called recursively
+    auto* prm = new binprm { data, {}, 0, depth };
+
+    size_t n = fs::vfs_read(ind, prm->buf, BINPRM_BUF_SIZE, 0, BINPRM_BUF_SIZE);
+    if (n == -1U) {
+        delete prm;
+        return -EIO;
+    }
+    prm->len = n;
+
+    int ret = -ENOEXEC;
+    for (const auto* fmt : *s_formats) {
+        ret = fmt->load(prm);
+        if (ret != -ENOEXEC)
+            break;
+    }
+
+    delete prm;
+    return ret;
+}
+
+int exec(types::elf::elf32_load_data* data)
+{
+    if (!s_formats)
+        register_builtin_formats();
+
+    int ret = search_handler(data, 0);
+    if (ret != GB_OK) {
+        data->errcode = -ret;
+        return GB_FAILED;
+    }
+
+    return GB_OK;
+}
+
+// binfmt_misc: interpreters registered at runtime by writing
+// ":name:type:offset:magic:mask:interpreter:flags" to register
+// where type is 'M' to match magic bytes at offset or 'E' to match
+// the file name extension given in magic
+
+static constexpr const char MISC_DIR[] = "sys/fs/binfmt_misc";
+
+struct misc_entry {
+    types::string<> name;
+    char type;
+    size_t offset;
+    size_t magic_len;
+    char magic[BINPRM_BUF_SIZE];
+    char mask[BINPRM_BUF_SIZE];
+    types::string<> interpreter;
+    types::string<> flags;
+    bool enabled;
+};
+
+static std::list<misc_entry*> s_misc_entries;
+// removed by writing -1 to them, their files are released on the next
+// refresh since we can't free the inode being written
+static std::list<misc_entry*> s_misc_removed;
+static bool s_misc_enabled = true;
+
+static bool has_char(const char* str, size_t len, char c)
+{
+    for (size_t i = 0; i < len; ++i) {
+        if (str[i] == c)
+            return true;
+    }
+    return false;
+}
+
+static bool misc_match(const misc_entry* ent, const binprm* prm)
+{
+    if (ent->type == 'E') {
+        const auto& name = prm->data->exec_dent->name;
+        size_t len = strlen(name.c_str());
+        if (len <= ent->magic_len || name[len - ent->magic_len - 1] != '.')
+            return false;
+
+        const char* ext = name.c_str() + len - ent->magic_len;
+        for (size_t i = 0; i < ent->magic_len; ++i) {
+            if (ext[i] != ent->magic[i])
+                return false;
+        }
+        return true;
+    }
+
+    if (ent->offset + ent->magic_len > prm->len)
+        return false;
+
+    const char* p = prm->buf + ent->offset;
+    for (size_t i = 0; i < ent->magic_len; ++i) {
+        if ((p[i] & ent->mask[i]) != ent->magic[i])
+            return false;
+    }
+    return true;
+}
+
+// interpreter path-of-executable argv[1]...
+static int load_misc(binprm* prm)
+{
+    if (!s_misc_enabled)
+        return -ENOEXEC;
+
+    for (const auto* ent : s_misc_entries) {
+        if (!ent->enabled || !misc_match(ent, prm))
+            continue;
+
+        std::vector<types::string<>> args;
+        args.emplace_back(ent->interpreter);
+        args.emplace_back(exec_path(prm));
+
+        auto* argv = prm->data->argv;
+        if (argv[0]) {
+            for (++argv; *argv; ++argv)
+                args.emplace_back(*argv);
+        }
+
+        return run_interp(prm, ent->interpreter.c_str(), args);
+    }
+
+    return -ENOEXEC;
+}
+
+static const binfmt misc_format { "misc", load_misc };
+
+static void misc_release_removed(void)
+{
+    char path[64];
+    for (auto* ent : s_misc_removed) {
+        snprintf(path, sizeof(path), "%s/%s", MISC_DIR, ent->name.c_str());
+        fs::procfs::instance()->remove(path);
+        delete ent;
+    }
+    s_misc_removed.clear();
+}
+
+static int hex_digit(char c)
+{
+    if (c >= '0' && c <= '9')
+        return c - '0';
+    if (c >= 'a' && c <= 'f')
+        return c - 'a' + 10;
+    if (c >= 'A' && c <= 'F')
+        return c - 'A' + 10;
+    return -1;
+}
+
+// decode the \xHH escapes in str[0, len) into out
+// @return decoded length, or -1 if invalid or too long
+static int unescape(const char* str, size_t len, char* out)
+{
+    size_t n = 0;
+    for (size_t i = 0; i < len; ++i) {
+        if (n >= BINPRM_BUF_SIZE)
+            return -1;
+
+        if (str[i] != '\\') {
+            out[n++] = str[i];
+            continue;
+        }
+
+        if (i + 1 < len && str[i + 1] == '\\') {
+            out[n++] = '\\';
+            i += 1;
+            continue;
+        }
+
+        if (i + 3 >= len || str[i + 1] != 'x')
+            return -1;
+        int hi = hex_digit(str[i + 2]), lo = hex_digit(str[i + 3]);
+        if (hi < 0 || lo < 0)
+            return -1;
+        out[n++] = (char)(hi << 4 | lo);
+        i += 3;
+    }
+    return n;
+}
+
+static size_t show_text(const char* text, size_t len, char* buf, size_t offset, size_t n)
+{
+    if (offset >= len)
+        return 0;
+    if (n > len - offset)
+        n = len - offset;
+    memcpy(buf, text + offset, n);
+    return n;
+}
+
+static ssize_t misc_entry_read(const misc_entry* ent, char* buf, size_t offset, size_t n)
+{
+    char* text = new char[512];
+    int len = snprintf(text, 512, "%s\ninterpreter %s\nflags: %s\n",
+        ent->enabled ? "enabled" : "disabled",
+        ent->interpreter.c_str(), ent->flags.c_str());
+
+    if (ent->type == 'E') {
+        len += snprintf(text + len, 512 - len, "extension .");
+        memcpy(text + len, ent->magic, ent->magic_len);
+        len += ent->magic_len;
+        text[len++] = '\n';
+    }
+    else {
+        len += snprintf(text + len, 512 - len, "offset %d\nmagic ", (int)ent->offset);
+        for (size_t i = 0; i < ent->magic_len; ++i)
+            len += snprintf(text + len, 512 - len, "%c%c",
+                "0123456789abcdef"[(ent->magic[i] >> 4) & 0xf],
+                "0123456789abcdef"[ent->magic[i] & 0xf]);
+        text[len++] = '\n';
+    }
+
+    n = show_text(text, len, buf, offset, n);
+    delete[] text;
+    return n;
+}
+
+// "1" enables, "0" disables and "-1" removes
+static int parse_control(const char* buf, size_t n)
+{
+    if (n && buf[n - 1] == '\n')
+        --n;
+    if (n == 1 && buf[0] == '1')
+        return 1;
+    if (n == 1 && buf[0] == '0')
+        return 0;
+    if (n == 2 && buf[0] == '-' && buf[1] == '1')
+        return -1;
+    return -2;
+}
+
+static ssize_t misc_entry_write(misc_entry* ent, const char* buf, size_t n)
+{
+    switch (parse_control(buf, n)) {
+    case 1:
+        ent->enabled = true;
+        break;
+    case 0:
+        ent->enabled = false;
+        break;
+    case -1:
+        s_misc_entries.remove(ent);
+        ent->enabled = false;
+        s_misc_removed.push_back(ent);
+        break;
+    default:
+        return -EINVAL;
+    }
+    return n;
+}
+
+static ssize_t misc_register(const char* buf, size_t count)
+{
+    size_t n = count;
+    if (n && buf[n - 1] == '\n')
+        --n;
+    if (n < 2)
+        return -EINVAL;
+
+    // the first character is the delimiter of the fields
+    char delim = buf[0];
+    const char* fields[7] {};
+    size_t lens[7] {};
+
+    const char* p = buf + 1;
+    const char* end = buf + n;
+    int cnt = 0;
+    while (cnt < 7) {
+        const char* q = p;
+        while (q < end && *q != delim)
+            ++q;
+        fields[cnt] = p, lens[cnt] = q - p;
+        ++cnt;
+        if (q == end)
+            break;
+        p = q + 1;
+    }
+    if (cnt < 6)
+        return -EINVAL;
+
+    auto* ent = new misc_entry {};
+    ent->name = types::string<>(fields[0], lens[0]);
+    ent->interpreter = types::string<>(fields[5], lens[5]);
+    if (cnt == 7)
+        ent->flags = types::string<>(fields[6], lens[6]);
+    ent->enabled = true;
+
+    const char* name = ent->name.c_str();
+    bool valid = lens[0] && lens[1] == 1 && lens[5]
+        && !has_char(name, lens[0], '/') && strcmp(name, ".") != 0 && strcmp(name, "..") != 0
+        && strcmp(name, "register") != 0 && strcmp(name, "status") != 0;
+
+    if (valid)
+        ent->type = fields[1][0];
+
+    if (valid && ent->type == 'E') {
+        // the extension is in the magic field, offset and mask are unused
+        valid = !lens[2] && !lens[4] && lens[3] && lens[3] < BINPRM_BUF_SIZE
+            && !has_char(fields[3], lens[3], '/');
+        if (valid) {
+            memcpy(ent->magic, fields[3], lens[3]);
+            ent->magic_len = lens[3];
+        }
+    }
+    else if (valid && ent->type == 'M') {
+        size_t offset = 0;
+        for (size_t i = 0; i < lens[2]; ++i) {
+            if (fields[2][i] < '0' || fields[2][i] > '9')
+                valid = false;
+            offset = offset * 10 + (fields[2][i] - '0');
+        }
+
+        int len = valid ? unescape(fields[3], lens[3], ent->magic) : -1;
+        valid = len > 0 && offset + len <= BINPRM_BUF_SIZE;
+        if (valid) {
+            ent->offset = offset;
+            ent->magic_len = len;
+            memset(ent->mask, 0xff, sizeof(ent->mask));
+        }
+
+        if (valid && lens[4]) {
+            int mask_len = unescape(fields[4], lens[4], ent->mask);
+            valid = mask_len == len;
+            for (int i = 0; valid && i < len; ++i)
+                ent->magic[i] &= ent->mask[i];
+        }
+    }
+    else {
+        valid = false;
+    }
+
+    if (!valid) {
+        delete ent;
+        return -EINVAL;
+    }
+
+    for (const auto* item : s_misc_entries) {
+        if (item->name == ent->name) {
+            delete ent;
+            return -EEXIST;
+        }
+    }
+
+    misc_release_removed();
+
+    char path[64];
+    snprintf(path, sizeof(path), "%s/%s", MISC_DIR, name);
+    int ret = fs::procfs::instance()->add_rw_file(path,
+        [ent](char* buf, size_t offset, size_t n) -> ssize_t {
+            return misc_entry_read(ent, buf, offset, n);
+        },
+        [ent](const char* buf, size_t, size_t n) -> ssize_t {
+            return misc_entry_write(ent, buf, n);
+        }, 0644);
+
+    if (ret != GB_OK) {
+        delete ent;
+        return ret;
+    }
+
+    s_misc_entries.push_back(ent);
+    return count;
+}
+
+static ssize_t misc_status_write(const char* buf, size_t n)
+{
+    switch (parse_control(buf, n)) {
+    case 1:
+        s_misc_enabled = true;
+        break;
+    case 0:
+        s_misc_enabled = false;
+        break;
+    case -1:
+        for (auto* ent : s_misc_entries) {
+            ent->enabled = false;
+            s_misc_removed.push_back(ent);
+        }
+        s_misc_entries.clear();
+        misc_release_removed();
+        break;
+    default:
+        return -EINVAL;
+    }
+    return n;
+}
+
+void init_misc(void)
+{
+    if (!s_formats)
+        register_builtin_formats();
+    // tried first so that it can take over even elf files
+    register_binfmt(&misc_format, true);
+
+    auto* procfs = fs::procfs::instance();
+    int ret = procfs->mkdir("sys/fs");
+    ret |= procfs->mkdir(MISC_DIR, 0555, misc_release_removed);
+    assert(ret == GB_OK);
+
+    char path[64];
+    snprintf(path, sizeof(path), "%s/register", MISC_DIR);
+    procfs->add_rw_file(path,
+        [](char*, size_t, size_t) -> ssize_t { return -EINVAL; },
+        [](const char* buf, size_t, size_t n) -> ssize_t {
+            return misc_register(buf, n);
+        }, 0200);
+
+    snprintf(path, sizeof(path), "%s/status", MISC_DIR);
+    procfs->add_rw_file(path,
+        [](char* buf, size_t offset, size_t n) -> ssize_t {
+            const char* text = s_misc_enabled ? "enabled\n" : "disabled\n";
+            return show_text(text, strlen(text), buf, offset, n);
+        },
+        [](const char* buf, size_t, size_t n) -> ssize_t {
+            return misc_status_write(buf, n);
+        }, 0644);
+}
+
+} // namespace kernel::binfmt
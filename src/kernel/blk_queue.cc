@@ -0,0 +1,302 @@
+#include <algorithm>
+#include <vector>
+
+#include <kernel/blk_queue.hpp>
+#include <kernel/errno.h>
+#include <string.h>
+#include <types/lock.hpp>
+
+using namespace kernel::block;
+
+request_queue::request_queue(submit_func submit, std::size_t max_sectors,
+    std::size_t capacity, std::size_t depth, flush_func flush)
+    : m_submit(std::move(submit)), m_max_sectors(max_sectors)
+    , m_capacity(capacity), m_depth(depth), m_flush(std::move(flush)) { }
+
+bool request_queue::ready(std::size_t self) const
+{
+    for (auto* req : m_pending) {
+        if (req->epoch == m_flushes_done)
+            return true;
+    }
+
+    // the flush is due once the transfers before it are done
+    return !m_flushes.empty() && m_inflight == self;
+}
+
+std::list<request_queue::request*>::iterator request_queue::pick(void)
+{
+    // only those queued before the next flush may be served
+    auto oldest = m_pending.end();
+    priority best = priority::background;
+    for (auto iter = m_pending.begin(); iter != m_pending.end(); ++iter) {
+        auto* req = *iter;
+        if (req->epoch != m_flushes_done)
+            continue;
+
+        if (oldest == m_pending.end() || req->queued_at < (*oldest)->queued_at)
+            oldest = iter;
+        if (req->prio < best)
+            best = req->prio;
+    }
+    if (oldest == m_pending.end())
+        return oldest;
+    if (m_dispatches - (*oldest)->queued_at >= DEADLINE)
+        return oldest;
+
+    auto first = m_pending.end();
+    for (auto iter = m_pending.begin(); iter != m_pending.end(); ++iter) {
+        auto* req = *iter;
+        if (req->epoch != m_flushes_done || req->prio != best)
+            continue;
+
+        if (first == m_pending.end())
+            first = iter;
+        if (req->lba >= m_head)
+            return iter;
+    }
+
+    // nothing ahead of us, start over from the lowest lba
+    return first;
+}
+
+void request_queue::dispatch(void)
+{
+    ++m_inflight;
+
+    for (;;) {
+        auto iter = pick();
+        if (iter == m_pending.end()) {
+            // the others in flight do the flush when they're done
+            if (m_flushes.empty() || m_inflight != 1)
+                break;
+
+            auto* req = m_flushes.front();
+            m_flushes.pop_front();
+
+            m_cv.mtx().unlock();
+            int ret = m_flush ? m_flush() : 0;
+            m_cv.mtx().lock();
+
+            req->status = ret;
+            req->done = true;
+            ++m_flushes_done;
+            continue;
+        }
+
+        std::vector<request*> batch { *iter };
+        iter = m_pending.erase(iter);
+
+        bool write = batch[0]->write;
+        uint64_t end = batch[0]->lba + batch[0]->nsect;
+        std::size_t nsect = batch[0]->nsect;
+
+        // the ones that continue it are right after it as the list is sorted
+        while (iter != m_pending.end()) {
+            auto* next = *iter;
+            if (next->lba != end || next->write != write)
+                break;
+            if (next->epoch != batch[0]->epoch)
+                break;
+            if (nsect + next->nsect > m_max_sectors)
+                break;
+
+            batch.push_back(next);
+            end += next->nsect;
+            nsect += next->nsect;
+            iter = m_pending.erase(iter);
+        }
+
+        memory::sg_list sg;
+        for (auto* req : batch)
+            sg.append(*req->sg);
+
+        m_head = end;
+        ++m_dispatches;
+
+        m_cv.mtx().unlock();
+        int ret = m_submit(batch[0]->lba, sg, write);
+        m_cv.mtx().lock();
+
+        for (auto* req : batch) {
+            req->status = ret;
+            req->done = true;
+        }
+    }
+
+    --m_inflight;
+}
+
+bool request_queue::wait_done(request& req)
+{
+    bool dispatched = false;
+
+    // req lives on our stack, so we can't leave on signals
+    while (!req.done) {
+        // ours is being served by someone else if we may serve none
+        if (m_inflight >= m_depth || !ready(0)) {
+            m_cv.wait(m_cv.mtx());
+            continue;
+        }
+
+        dispatch();
+        dispatched = true;
+    }
+
+    return dispatched;
+}
+
+int request_queue::transfer(uint64_t lba, const memory::sg_list& sg, bool write)
+{
+    return transfer(lba, sg, write, write ? priority::write : priority::sync_read);
+}
+
+int request_queue::transfer(uint64_t lba, const memory::sg_list& sg,
+    bool write, priority prio)
+{
+    if (sg.len() % SECTOR_SIZE)
+        return -EINVAL;
+
+    request req {
+        .lba = lba,
+        .nsect = sg.len() / SECTOR_SIZE,
+        .write = write,
+        .prio = prio,
+        .sg = &sg,
+        .queued_at = 0,
+        .epoch = 0,
+        .status = 0,
+        .done = false,
+    };
+
+    bool dispatched = false;
+    {
+        types::lock_guard lck(m_cv.mtx());
+
+        req.queued_at = m_dispatches;
+        req.epoch = m_flushes_queued;
+        auto iter = m_pending.begin();
+        while (iter != m_pending.end() && (*iter)->lba <= lba)
+            ++iter;
+        m_pending.insert(iter, &req);
+
+        dispatched = wait_done(req);
+    }
+
+    // wake up those whose requests were served in our batch
+    if (dispatched)
+        m_cv.notify_all();
+
+    return req.status;
+}
+
+int request_queue::flush(void)
+{
+    request req {
+        .lba = 0,
+        .nsect = 0,
+        .write = true,
+        .prio = priority::write,
+        .sg = nullptr,
+        .queued_at = 0,
+        .epoch = 0,
+        .status = 0,
+        .done = false,
+    };
+
+    bool dispatched = false;
+    {
+        types::lock_guard lck(m_cv.mtx());
+
+        req.epoch = m_flushes_queued++;
+        m_flushes.push_back(&req);
+
+        dispatched = wait_done(req);
+    }
+
+    // the requests queued after the flush may go on now
+    if (dispatched)
+        m_cv.notify_all();
+
+    return req.status;
+}
+
+ssize_t request_queue::read(char* buf, std::size_t buf_size,
+    std::size_t offset, std::size_t n)
+{
+    n = std::min(buf_size, n);
+
+    std::vector<char> chunk;
+    std::size_t done = 0;
+    while (done < n) {
+        std::size_t pos = offset + done;
+        std::size_t lba = pos / SECTOR_SIZE;
+        std::size_t skip = pos % SECTOR_SIZE;
+        if (lba >= m_capacity)
+            break;
+
+        std::size_t nsect = (skip + (n - done) + SECTOR_SIZE - 1) / SECTOR_SIZE;
+        nsect = std::min(std::min(nsect, m_max_sectors), m_capacity - lba);
+
+        chunk.resize(nsect * SECTOR_SIZE);
+        memory::sg_list sg;
+        sg.add_kernel_buf(chunk.data(), chunk.size(), true);
+
+        int ret = transfer(lba, sg, false);
+        if (ret != 0)
+            return ret;
+
+        std::size_t len = std::min(chunk.size() - skip, n - done);
+        memcpy(buf + done, chunk.data() + skip, len);
+        done += len;
+    }
+
+    return done;
+}
+
+ssize_t request_queue::write(const char* buf, std::size_t offset, std::size_t n)
+{
+    std::vector<char> chunk;
+    std::size_t done = 0;
+    while (done < n) {
+        std::size_t pos = offset + done;
+        std::size_t lba = pos / SECTOR_SIZE;
+        std::size_t skip = pos % SECTOR_SIZE;
+        if (lba >= m_capacity)
+            break;
+
+        std::size_t nsect = (skip + (n - done) + SECTOR_SIZE - 1) / SECTOR_SIZE;
+        nsect = std::min(std::min(nsect, m_max_sectors), m_capacity - lba);
+
+        chunk.resize(nsect * SECTOR_SIZE);
+        std::size_t len = std::min(chunk.size() - skip, n - done);
+
+        // the first and the last sectors might be written in part,
+        // read them first to keep the rest of them
+        auto read_sector = [&](std::size_t i) {
+            memory::sg_list sg;
+            sg.add_kernel_buf(chunk.data() + i * SECTOR_SIZE, SECTOR_SIZE, true);
+            return transfer(lba + i, sg, false);
+        };
+
+        int ret = 0;
+        if (skip || skip + len < SECTOR_SIZE)
+            ret = read_sector(0);
+        if (ret == 0 && nsect > 1 && (skip + len) % SECTOR_SIZE)
+            ret = read_sector(nsect - 1);
+        if (ret != 0)
+            return ret;
+
+        memcpy(chunk.data() + skip, buf + done, len);
+
+        memory::sg_list sg;
+        sg.add_kernel_buf(chunk.data(), chunk.size(), false);
+        ret = transfer(lba, sg, true);
+        if (ret != 0)
+            return ret;
+
+        done += len;
+    }
+
+    return done;
+}
@@ -0,0 +1,104 @@
+#include <algorithm>
+
+#include <kernel/hw/timer.h>
+#include <kernel/boottime.hpp>
+#include <kernel/log.hpp>
+#include <stdio.h>
+#include <types/status.h>
+
+// provided by the linker script
+extern "C" const kernel::kinit::initcall __initcalls_start[];
+extern "C" const kernel::kinit::initcall __initcalls_end[];
+
+namespace kernel::kinit {
+
+static boot_phase_record s_phases[MAX_BOOT_PHASES];
+static int s_phase_cnt;
+
+static uint64_t s_calib_tsc;
+static size_t s_calib_ticks;
+
+void boot_phase(const char* name)
+{
+    if (s_phase_cnt >= MAX_BOOT_PHASES)
+        return;
+
+    s_phases[s_phase_cnt++] = { name, rdtsc() };
+}
+
+void boot_phase_calibrate(void)
+{
+    s_calib_tsc = rdtsc();
+    s_calib_ticks = current_ticks();
+}
+
+void run_initcalls(void)
+{
+    for (auto* call = __initcalls_start; call < __initcalls_end; ++call) {
+        boot_phase(call->name);
+
+        int ret = call->func();
+        if (ret == GB_OK)
+            continue;
+
+        char buf[128];
+        snprintf(buf, sizeof(buf),
+            "[kernel] initcall %s failed with %d\n", call->name, ret);
+        kmsg(buf);
+    }
+}
+
+// set when the boot phases are reported
+static uint64_t s_end_tsc;
+static int64_t s_cycles_per_ms;
+
+// format the time spent in boot phase i as a line
+static int format_phase(int i, char* buf, size_t buf_size)
+{
+    uint64_t end = (i + 1 < s_phase_cnt) ? s_phases[i + 1].tsc : s_end_tsc;
+    int64_t delta = end - s_phases[i].tsc;
+
+    if (s_cycles_per_ms) {
+        int64_t us = delta * 1000 / s_cycles_per_ms;
+        return snprintf(buf, buf_size, "%s: %d.%d%d%d ms\n",
+            s_phases[i].name, (int)(us / 1000), (int)(us / 100 % 10),
+            (int)(us / 10 % 10), (int)(us % 10));
+    }
+
+    // timer not calibrated, print raw kilocycles instead
+    return snprintf(buf, buf_size, "%s: %d kcycles\n",
+        s_phases[i].name, (int)(delta / 1000));
+}
+
+void report_boot_phases(void)
+{
+    s_end_tsc = rdtsc();
+
+    // the PIT runs at 1000Hz, so a tick is exactly 1ms
+    int64_t ticks = current_ticks() - s_calib_ticks;
+    if (s_calib_tsc && ticks > 0)
+        s_cycles_per_ms = (int64_t)(s_end_tsc - s_calib_tsc) / ticks;
+
+    char buf[128];
+    kmsg("[kernel] boot phases:\n");
+    for (int i = 0; i < s_phase_cnt; ++i) {
+        kmsg("[kernel]     ");
+        format_phase(i, buf, sizeof(buf));
+        kmsg(buf);
+    }
+}
+
+size_t show_boot_phases(char* buf, size_t buf_size)
+{
+    if (!s_end_tsc)
+        return 0;
+
+    size_t len = 0;
+    for (int i = 0; i < s_phase_cnt && len < buf_size; ++i) {
+        int n = format_phase(i, buf + len, buf_size - len);
+        len += std::min((size_t)n, buf_size - len);
+    }
+    return len;
+}
+
+} // namespace kernel::kinit
@@ -0,0 +1,213 @@
+#include <kernel/crypto/aes.hpp>
+#include <string.h>
+
+namespace kernel::crypto {
+
+static constexpr uint8_t SBOX[256] = {
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+};
+
+static consteval auto make_inv_sbox(void)
+{
+    struct { uint8_t val[256]; } inv {};
+    for (int i = 0; i < 256; ++i)
+        inv.val[SBOX[i]] = i;
+    return inv;
+}
+
+static constexpr auto INV_SBOX = make_inv_sbox();
+
+static constexpr uint8_t xtime(uint8_t x)
+{
+    return (x << 1) ^ ((x & 0x80) ? 0x1b : 0x00);
+}
+
+static constexpr uint8_t gmul(uint8_t a, uint8_t b)
+{
+    uint8_t ret = 0;
+    for (; b; b >>= 1, a = xtime(a)) {
+        if (b & 1)
+            ret ^= a;
+    }
+    return ret;
+}
+
+static inline void add_round_key(uint8_t* s, const uint8_t* rk)
+{
+    for (std::size_t i = 0; i < AES_BLOCK_SIZE; ++i)
+        s[i] ^= rk[i];
+}
+
+// the state is stored column by column, s[col * 4 + row]
+static inline void shift_rows(uint8_t* s, bool inverse)
+{
+    uint8_t t[AES_BLOCK_SIZE];
+    for (int col = 0; col < 4; ++col) {
+        for (int row = 0; row < 4; ++row) {
+            int src = inverse ? (col - row + 4) % 4 : (col + row) % 4;
+            t[col * 4 + row] = s[src * 4 + row];
+        }
+    }
+    memcpy(s, t, AES_BLOCK_SIZE);
+}
+
+static inline void mix_columns(uint8_t* s)
+{
+    for (int col = 0; col < 4; ++col) {
+        uint8_t* c = s + col * 4;
+        uint8_t a0 = c[0], a1 = c[1], a2 = c[2], a3 = c[3];
+        c[0] = xtime(a0) ^ (xtime(a1) ^ a1) ^ a2 ^ a3;
+        c[1] = a0 ^ xtime(a1) ^ (xtime(a2) ^ a2) ^ a3;
+        c[2] = a0 ^ a1 ^ xtime(a2) ^ (xtime(a3) ^ a3);
+        c[3] = (xtime(a0) ^ a0) ^ a1 ^ a2 ^ xtime(a3);
+    }
+}
+
+static inline void inv_mix_columns(uint8_t* s)
+{
+    for (int col = 0; col < 4; ++col) {
+        uint8_t* c = s + col * 4;
+        uint8_t a0 = c[0], a1 = c[1], a2 = c[2], a3 = c[3];
+        c[0] = gmul(a0, 14) ^ gmul(a1, 11) ^ gmul(a2, 13) ^ gmul(a3, 9);
+        c[1] = gmul(a0, 9) ^ gmul(a1, 14) ^ gmul(a2, 11) ^ gmul(a3, 13);
+        c[2] = gmul(a0, 13) ^ gmul(a1, 9) ^ gmul(a2, 14) ^ gmul(a3, 11);
+        c[3] = gmul(a0, 11) ^ gmul(a1, 13) ^ gmul(a2, 9) ^ gmul(a3, 14);
+    }
+}
+
+int aes::set_key(const uint8_t* key, std::size_t key_len)
+{
+    if (key_len != 16 && key_len != 32)
+        return -1;
+
+    int nk = key_len / 4;
+    m_rounds = nk + 6;
+
+    memcpy(m_rkeys, key, key_len);
+
+    uint8_t rcon = 1;
+    int words = (m_rounds + 1) * 4;
+    for (int i = nk; i < words; ++i) {
+        uint8_t t[4];
+        memcpy(t, m_rkeys + (i - 1) * 4, 4);
+
+        if (i % nk == 0) {
+            uint8_t tmp = t[0];
+            t[0] = SBOX[t[1]] ^ rcon;
+            t[1] = SBOX[t[2]];
+            t[2] = SBOX[t[3]];
+            t[3] = SBOX[tmp];
+            rcon = xtime(rcon);
+        } else if (nk > 6 && i % nk == 4) {
+            for (int k = 0; k < 4; ++k)
+                t[k] = SBOX[t[k]];
+        }
+
+        for (int k = 0; k < 4; ++k)
+            m_rkeys[i * 4 + k] = m_rkeys[(i - nk) * 4 + k] ^ t[k];
+    }
+
+    return 0;
+}
+
+void aes::encrypt(uint8_t* s) const
+{
+    add_round_key(s, m_rkeys);
+
+    for (int round = 1; round <= m_rounds; ++round) {
+        for (std::size_t i = 0; i < AES_BLOCK_SIZE; ++i)
+            s[i] = SBOX[s[i]];
+        shift_rows(s, false);
+        if (round != m_rounds)
+            mix_columns(s);
+        add_round_key(s, m_rkeys + round * AES_BLOCK_SIZE);
+    }
+}
+
+void aes::decrypt(uint8_t* s) const
+{
+    add_round_key(s, m_rkeys + m_rounds * AES_BLOCK_SIZE);
+
+    for (int round = m_rounds - 1; round >= 0; --round) {
+        shift_rows(s, true);
+        for (std::size_t i = 0; i < AES_BLOCK_SIZE; ++i)
+            s[i] = INV_SBOX.val[s[i]];
+        add_round_key(s, m_rkeys + round * AES_BLOCK_SIZE);
+        if (round != 0)
+            inv_mix_columns(s);
+    }
+}
+
+int aes_xts::set_key(const uint8_t* key, std::size_t key_len)
+{
+    if (key_len != 32 && key_len != 64)
+        return -1;
+
+    m_data.set_key(key, key_len / 2);
+    m_tweak.set_key(key + key_len / 2, key_len / 2);
+    return 0;
+}
+
+// multiply the tweak by the primitive element alpha in GF(2^128)
+static inline void xts_next_tweak(uint8_t* t)
+{
+    uint8_t carry = 0;
+    for (std::size_t i = 0; i < AES_BLOCK_SIZE; ++i) {
+        uint8_t next_carry = t[i] >> 7;
+        t[i] = (t[i] << 1) | carry;
+        carry = next_carry;
+    }
+    if (carry)
+        t[0] ^= 0x87;
+}
+
+static inline void xts_init_tweak(const aes& cipher, uint8_t* t, uint64_t sector)
+{
+    memset(t, 0x00, AES_BLOCK_SIZE);
+    for (int i = 0; i < 8; ++i)
+        t[i] = (sector >> (i * 8)) & 0xff;
+    cipher.encrypt(t);
+}
+
+void aes_xts::encrypt(uint8_t* buf, std::size_t len, uint64_t sector) const
+{
+    uint8_t t[AES_BLOCK_SIZE];
+    xts_init_tweak(m_tweak, t, sector);
+
+    for (; len >= AES_BLOCK_SIZE; len -= AES_BLOCK_SIZE, buf += AES_BLOCK_SIZE) {
+        add_round_key(buf, t);
+        m_data.encrypt(buf);
+        add_round_key(buf, t);
+        xts_next_tweak(t);
+    }
+}
+
+void aes_xts::decrypt(uint8_t* buf, std::size_t len, uint64_t sector) const
+{
+    uint8_t t[AES_BLOCK_SIZE];
+    xts_init_tweak(m_tweak, t, sector);
+
+    for (; len >= AES_BLOCK_SIZE; len -= AES_BLOCK_SIZE, buf += AES_BLOCK_SIZE) {
+        add_round_key(buf, t);
+        m_data.decrypt(buf);
+        add_round_key(buf, t);
+        xts_next_tweak(t);
+    }
+}
+
+} // namespace kernel::crypto
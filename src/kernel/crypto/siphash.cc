@@ -0,0 +1,64 @@
+#include <kernel/crypto/siphash.hpp>
+#include <string.h>
+
+namespace kernel::crypto {
+
+static constexpr uint64_t rotl(uint64_t val, int n)
+{
+    return (val << n) | (val >> (64 - n));
+}
+
+struct sip_state {
+    uint64_t v0, v1, v2, v3;
+
+    constexpr void round(void)
+    {
+        v0 += v1, v1 = rotl(v1, 13), v1 ^= v0, v0 = rotl(v0, 32);
+        v2 += v3, v3 = rotl(v3, 16), v3 ^= v2;
+        v0 += v3, v3 = rotl(v3, 21), v3 ^= v0;
+        v2 += v1, v1 = rotl(v1, 17), v1 ^= v2, v2 = rotl(v2, 32);
+    }
+
+    // one compression round per message word
+    constexpr void compress(uint64_t m)
+    {
+        v3 ^= m;
+        round();
+        v0 ^= m;
+    }
+};
+
+uint64_t siphash_1_3(const void* data, std::size_t len, const siphash_key& key)
+{
+    sip_state s {
+        0x736f6d6570736575ULL ^ key.k0,
+        0x646f72616e646f6dULL ^ key.k1,
+        0x6c7967656e657261ULL ^ key.k0,
+        0x7465646279746573ULL ^ key.k1,
+    };
+
+    auto* p = (const uint8_t*)data;
+    std::size_t left = len;
+    for (; left >= 8; p += 8, left -= 8) {
+        // x86 is little endian as SipHash wants
+        uint64_t m;
+        memcpy(&m, p, 8);
+        s.compress(m);
+    }
+
+    // the last word holds the rest of the bytes and the length
+    uint64_t last = (uint64_t)len << 56;
+    for (std::size_t i = 0; i < left; ++i)
+        last |= (uint64_t)p[i] << (i * 8);
+    s.compress(last);
+
+    // three finalization rounds
+    s.v2 ^= 0xff;
+    s.round();
+    s.round();
+    s.round();
+
+    return s.v0 ^ s.v1 ^ s.v2 ^ s.v3;
+}
+
+} // namespace kernel::crypto
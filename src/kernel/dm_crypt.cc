@@ -0,0 +1,207 @@
+#include <algorithm>
+#include <vector>
+
+#include <kernel/crypto/aes.hpp>
+#include <kernel/dm_crypt.hpp>
+#include <kernel/errno.h>
+#include <kernel/log.hpp>
+#include <kernel/module.hpp>
+#include <kernel/user/uaccess.hpp>
+#include <kernel/vfs.hpp>
+#include <stdio.h>
+#include <string.h>
+
+using namespace kernel::module;
+
+namespace dm_crypt {
+
+constexpr uint32_t DM_MAJOR = 253;
+constexpr std::size_t SECTOR_SIZE = 512;
+
+// sectors of the source device are encrypted with AES-XTS,
+// using the sector number as the tweak
+struct crypt_device {
+    fs::node_t source;
+    kernel::crypto::aes_xts cipher;
+
+    ssize_t read(char* buf, std::size_t buf_size, std::size_t offset, std::size_t cnt)
+    {
+        cnt = std::min(buf_size, cnt);
+
+        uint8_t b[SECTOR_SIZE];
+        std::size_t orig_cnt = cnt;
+        while (cnt) {
+            std::size_t sector = offset / SECTOR_SIZE;
+            std::size_t pos = offset % SECTOR_SIZE;
+
+            ssize_t ret = fs::block_device_read(source, (char*)b,
+                SECTOR_SIZE, sector * SECTOR_SIZE, SECTOR_SIZE);
+            if (ret < 0)
+                return ret;
+            if (ret != SECTOR_SIZE)
+                break;
+
+            cipher.decrypt(b, SECTOR_SIZE, sector);
+
+            std::size_t n = std::min(cnt, SECTOR_SIZE - pos);
+            memcpy(buf, b + pos, n);
+            buf += n, offset += n, cnt -= n;
+        }
+
+        memset(b, 0x00, sizeof(b));
+        return orig_cnt - cnt;
+    }
+
+    ssize_t write(const char* buf, std::size_t offset, std::size_t cnt)
+    {
+        uint8_t b[SECTOR_SIZE];
+        std::size_t orig_cnt = cnt;
+        while (cnt) {
+            std::size_t sector = offset / SECTOR_SIZE;
+            std::size_t pos = offset % SECTOR_SIZE;
+            std::size_t n = std::min(cnt, SECTOR_SIZE - pos);
+
+            // partial sector, decrypt the original content first
+            if (n != SECTOR_SIZE) {
+                ssize_t ret = fs::block_device_read(source, (char*)b,
+                    SECTOR_SIZE, sector * SECTOR_SIZE, SECTOR_SIZE);
+                if (ret < 0)
+                    return ret;
+                if (ret != SECTOR_SIZE)
+                    break;
+                cipher.decrypt(b, SECTOR_SIZE, sector);
+            }
+
+            memcpy(b + pos, buf, n);
+            cipher.encrypt(b, SECTOR_SIZE, sector);
+
+            ssize_t ret = fs::block_device_write(source, (const char*)b,
+                sector * SECTOR_SIZE, SECTOR_SIZE);
+            if (ret < 0)
+                return ret;
+            if (ret != SECTOR_SIZE)
+                break;
+
+            buf += n, offset += n, cnt -= n;
+        }
+
+        memset(b, 0x00, sizeof(b));
+        return orig_cnt - cnt;
+    }
+
+    int flush()
+    {
+        return fs::block_device_flush(source);
+    }
+};
+
+class dm_crypt_module : public virtual kernel::module::module {
+private:
+    std::vector<crypt_device*> devices;
+
+    crypt_device* find(fs::node_t node)
+    {
+        if (NODE_MAJOR(node) != DM_MAJOR)
+            return nullptr;
+
+        auto minor = NODE_MINOR(node);
+        if (minor >= devices.size())
+            return nullptr;
+        return devices[minor];
+    }
+
+    // whether the device node is, or is on top of, the device target
+    bool backed_by(fs::node_t node, fs::node_t target)
+    {
+        while (node != target) {
+            auto* cur = find(node);
+            if (!cur)
+                return false;
+            node = cur->source;
+        }
+        return true;
+    }
+
+    int create(const dm_crypt_create* __user ureq)
+    {
+        dm_crypt_create req;
+        if (kernel::user::copy_from_user(&req, ureq, sizeof(req)) != 0) {
+            memset(&req, 0x00, sizeof(req));
+            return -EFAULT;
+        }
+
+        int minor = devices.size();
+        auto node = fs::make_node(DM_MAJOR, minor);
+
+        // the source might name the device before it exists
+        if (backed_by(req.source, node)) {
+            memset(&req, 0x00, sizeof(req));
+            return -EINVAL;
+        }
+
+        auto* dev = new crypt_device { req.source, {} };
+        int ret = dev->cipher.set_key(req.key, req.key_len);
+        memset(&req, 0x00, sizeof(req));
+        if (ret != 0) {
+            delete dev;
+            return -EINVAL;
+        }
+
+        char name[16];
+        snprintf(name, sizeof(name), "dm-%d", minor);
+
+        ret = fs::register_block_device(node, {
+            [dev](char* buf, std::size_t buf_size, std::size_t offset, std::size_t cnt) {
+                return dev->read(buf, buf_size, offset, cnt);
+            },
+            [dev](const char* buf, std::size_t offset, std::size_t cnt) {
+                return dev->write(buf, offset, cnt);
+            },
+            [dev]() -> int {
+                return dev->flush();
+            },
+            nullptr,
+        }, name);
+        if (ret != 0) {
+            delete dev;
+            return ret;
+        }
+        devices.push_back(dev);
+
+        return minor;
+    }
+
+public:
+    dm_crypt_module() : module("dm-crypt") { }
+    ~dm_crypt_module()
+    {
+        for (auto* dev : devices)
+            delete dev;
+    }
+
+    virtual int init() override
+    {
+        auto node = fs::make_node(10, 236);
+        int ret = fs::register_char_device(node, {
+            nullptr, nullptr,
+            [this](unsigned long request, uintptr_t arg) -> int {
+                switch (request) {
+                case DM_CRYPT_CREATE:
+                    return create((const dm_crypt_create*)arg);
+                default:
+                    return -EINVAL;
+                }
+            }
+        }, "dm-crypt", 0600);
+        if (ret != 0)
+            return MODULE_FAILED;
+
+        return MODULE_SUCCESS;
+    }
+};
+
+} // namespace dm_crypt
+
+kernel::module::module* dm_crypt_module_init()
+{ return new dm_crypt::dm_crypt_module(); }
+INTERNAL_MODULE(dm_crypt_module_loader, dm_crypt_module_init);
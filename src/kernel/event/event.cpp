@@ -9,82 +9,160 @@
 #include <types/allocator.hpp>
 #include <types/cplusplus.hpp>
 #include <types/lock.hpp>
+#include <types/mpsc_ring.hpp>
 
-static std::list<::input_event>* _input_event_queue;
+// filled by the keyboard interrupt handler, MUST NOT allocate there
+static types::mpsc_ring<::input_event, 64> s_input_event_queue;
 
-namespace event {
-std::list<::input_event>& input_event_queue(void)
+void commit_input_event(struct input_event* evt)
 {
-    if (!_input_event_queue) {
-        _input_event_queue = new std::list<input_event>;
+    // drop the event if nobody is consuming them
+    s_input_event_queue.push(*evt);
+}
+
+void dispatch_event(void)
+{
+    char buf[1024];
+    ::input_event item;
+
+    while (s_input_event_queue.pop(item)) {
+        snprintf(buf, 1024, "\rinput event: type%x, data%x, code%x\r", item.type, item.data, item.code);
+        kmsg(buf);
     }
-    return *_input_event_queue;
 }
-} // namespace event
 
-void commit_input_event(struct input_event* evt)
+void kernel::cond_var::enqueue(waiter* w)
 {
-    event::input_event_queue().push_back(*evt);
+    w->next = nullptr;
+    if (m_tail)
+        m_tail->next = w;
+    else
+        m_head = w;
+    m_tail = w;
 }
 
-void dispatch_event(void)
+void kernel::cond_var::unlink(waiter* w)
 {
-    char buf[1024];
-    auto& input_event_queue = event::input_event_queue();
-
-    // char* ptr = (char*)0x8000000;
-    // *ptr = 0xff;
-
-    while (!input_event_queue.empty()) {
-        for (auto iter = input_event_queue.begin(); iter != input_event_queue.end(); ++iter) {
-            const auto& item = *iter;
-            snprintf(buf, 1024, "\rinput event: type%x, data%x, code%x\r", item.type, item.data, item.code);
-            kmsg(buf);
-            input_event_queue.erase(iter);
-        }
+    waiter* prev = nullptr;
+    for (auto* cur = m_head; cur; prev = cur, cur = cur->next) {
+        if (cur != w)
+            continue;
+
+        if (prev)
+            prev->next = cur->next;
+        else
+            m_head = cur->next;
+
+        if (m_tail == cur)
+            m_tail = prev;
+
+        return;
     }
 }
 
+bool kernel::cond_var::wake_one(void)
+{
+    auto* w = m_head;
+    if (!w)
+        return false;
+
+    m_head = w->next;
+    if (!m_head)
+        m_tail = nullptr;
+
+    auto* thd = w->thd;
+    // tell the waiter that it has been dequeued
+    w->thd = nullptr;
+
+    thd->attr.ready = 1;
+    thd->attr.wait = 0;
+    readythds->push(thd);
+
+    return true;
+}
+
 bool kernel::cond_var::wait(types::mutex& lock)
 {
-    kernel::tasks::thread* thd = current_thread;
+    // we would never be woken up with interrupts disabled
+    assert(current_thread->held_locks.rw_spinlocks == 0);
+
+    if (m_closed)
+        return false;
+
+    waiter w { current_thread, nullptr };
 
     current_thread->attr.ready = 0;
     current_thread->attr.wait = 1;
-    m_subscribers.push_back(thd);
+    enqueue(&w);
 
     lock.unlock();
     bool ret = schedule();
     lock.lock();
 
-    return ret;
+    // we are woken up by something other than notify,
+    // our node is still in the queue and about to go out of scope
+    if (w.thd)
+        unlink(&w);
+
+    return ret && !m_closed;
 }
 
-void kernel::cond_var::notify(void)
+bool kernel::cond_var::wait(types::spinlock& lock, uint32_t& flags)
 {
+    waiter w { current_thread, nullptr };
+
+    {
+        types::lock_guard lck(m_mtx);
+        if (m_closed)
+            return false;
+
+        current_thread->attr.ready = 0;
+        current_thread->attr.wait = 1;
+        enqueue(&w);
+    }
+
+    // a notify from now on finds us in the queue
+    lock.unlock(flags);
+    assert(current_thread->held_locks.rw_spinlocks == 0);
+    bool ret = schedule();
+    flags = lock.lock();
+
+    // woken up by something other than notify
     types::lock_guard lck(m_mtx);
+    if (w.thd)
+        unlink(&w);
 
-    auto iter = m_subscribers.begin();
-    if (iter == m_subscribers.end())
-        return;
+    return ret && !m_closed;
+}
 
-    auto* thd = *iter;
-    thd->attr.ready = 1;
-    thd->attr.wait = 0;
-    readythds->push(thd);
+void kernel::cond_var::notify(void)
+{
+    types::lock_guard lck(m_mtx);
+    wake_one();
+}
 
-    m_subscribers.erase(iter);
+void kernel::cond_var::notify_n(std::size_t n)
+{
+    types::lock_guard lck(m_mtx);
+    while (n-- && wake_one())
+        ;
 }
 
 void kernel::cond_var::notify_all(void)
 {
     types::lock_guard lck(m_mtx);
+    while (wake_one())
+        ;
+}
 
-    for (auto& thd : m_subscribers) {
-        thd->attr.ready = 1;
-        thd->attr.wait = 0;
-        readythds->push(thd);
-    }
+void kernel::cond_var::close(void)
+{
+    m_closed = true;
+    while (wake_one())
+        ;
+}
 
-    m_subscribers.clear();
+void kernel::cond_var::reopen(void)
+{
+    m_closed = false;
 }
@@ -4,7 +4,9 @@
 #include <algorithm>
 
 #include <kernel/log.hpp>
+#include <kernel/blk_queue.hpp>
 #include <kernel/mm.hpp>
+#include <kernel/sg_list.hpp>
 #include <kernel/module.hpp>
 #include <kernel/hw/pci.hpp>
 #include <kernel/irq.hpp>
@@ -13,6 +15,7 @@
 #include <types/size.h>
 
 #include <stdint.h>
+#include <stdio.h>
 
 #define SPIN(cond, spin) \
     (spin) = 0; \
@@ -29,13 +32,21 @@ constexpr uint16_t DEVICE_AHCI = 0x2922;
 
 constexpr uint32_t PCI_REG_ABAR = 0x09;
 
-constexpr uint32_t ATA_DEV_BSY = 0x08;
-constexpr uint32_t ATA_DEV_DRQ = 0x04;
+constexpr uint32_t HBA_CAP_SNCQ = 0x40000000;
+
+constexpr uint32_t SATA_SIG_ATAPI = 0xEB140101;
+
+constexpr uint8_t ATA_CMD_PACKET = 0xA0;
+
+constexpr uint32_t PORT_IRQ_D2H = 0x00000001;
+constexpr uint32_t PORT_IRQ_SDB = 0x00000008;
+constexpr uint32_t PORT_IRQ_TFE = 0x40000000;
 
 constexpr uint32_t PORT_CMD_ST = 0x00000001;
 constexpr uint32_t PORT_CMD_FRE = 0x00000010;
 constexpr uint32_t PORT_CMD_FR = 0x00004000;
 constexpr uint32_t PORT_CMD_CR = 0x00008000;
+constexpr uint32_t PORT_CMD_ATAPI = 0x01000000;
 
 namespace ahci {
 
@@ -242,6 +253,21 @@ struct command_table {
     prdt_entry prdt[];
 };
 
+// the block size of cd-roms
+static constexpr std::size_t CD_BLOCK_SIZE = 2048;
+
+// 32-bit addresses only, word aligned and at most 4MiB per prdt entry
+// the transfers are bounced through the swiotlb pool if needed,
+// so they should stay far below its size
+static constexpr std::size_t MAX_SECTORS = 128;
+
+static constexpr kernel::memory::dma_limits DMA_LIMITS {
+    .max_addr = 0xffffffff,
+    .align = 2,
+    .max_seg_len = 4 * 1024 * 1024,
+    .max_segs = (PAGE_SIZE - sizeof(command_table)) / sizeof(prdt_entry),
+};
+
 static int stop_command(hba_port* port)
 {
     port->command_status =
@@ -271,112 +297,207 @@ static inline hba_port* port_ptr(hba_ghc* ghc, int i)
     return (hba_port*)((char*)ghc + 0x100 + i * 0x80);
 }
 
-template <std::size_t N>
-struct quick_queue {
-    std::size_t start { };
-    std::size_t end { };
-    uint8_t arr[N] { };
-
-    quick_queue()
-    {
-        for (std::size_t i = 0; i < N; ++i)
-            arr[i] = i;
-    }
-
-    bool empty() { return start == end; }
-    void push(uint8_t val) { arr[end++ % N] = val; }
-    uint8_t pop() { return arr[start++ % N]; }
-};
-
 struct ahci_port {
+public:
+    // a command in flight, it lives on the stack of the issuer
+    // from issue() till wait() returns
+    struct command {
+        kernel::memory::dma_mapping dma;
+        uint64_t lba;
+        uint32_t count;
+        uint8_t cmd;
+        bool write;
+        // READ or WRITE FPDMA QUEUED, the slot is the ncq tag
+        bool queued;
+        // the scsi command of ATA_CMD_PACKET
+        uint8_t packet[12] { };
+
+        int slot { -1 };
+        page_t cmdtable_page { };
+
+        // @param sg the memory to transfer, empty for non-data commands
+        //        (e.g. FLUSH CACHE), its length MUST be a multiple of 512
+        //        except for packet commands
+        command(const kernel::memory::sg_list& sg,
+            uint64_t lba, uint8_t cmd, bool write, bool queued = false)
+            : dma(sg, DMA_LIMITS, !write), lba(lba), count(sg.len())
+            , cmd(cmd), write(write), queued(queued) { }
+    };
+
 private:
-    // quick_queue<32> qu;
     page_t page;
     hba_port* port;
     command_header* cmd_header { };
     received_fis* fis { };
-    std::size_t sectors { -1U };
 
-    int send_command(char* buf, uint64_t lba, uint32_t count, uint8_t cmd, bool write)
+    // the command slots of the port
+    uint32_t nslots;
+    // whether the hba can do ncq, the drive is asked by identify()
+    bool ncq;
+    // a packet device, cd-roms for the most part
+    bool atapi;
+    // the medium of packet devices, unknown if block_size is 0
+    uint32_t blocks { };
+    uint32_t block_size { };
+    // completions are reaped by handle_interrupt() and slept for,
+    // or polled for if the hba has no irq
+    bool use_irq;
+
+    // notified when commands complete and when slots are freed
+    // the fields below are changed by handle_interrupt() too, so the
+    // interrupts are disabled while they are used out of the irq
+    kernel::cond_var cv;
+    // the slots taken, till the issuers are done with them
+    uint32_t slots_used { };
+    // the slots issued to the hba and not complete yet
+    uint32_t slots_issued { };
+    // the slots whose commands failed
+    uint32_t slots_failed { };
+    // the commands in the slots taken are ncq ones, the drive
+    // doesn't take the two kinds at the same time
+    bool queued_used { };
+
+    // interrupts MUST be disabled
+    // @return a slot to issue a command in or -1 if none is free
+    int free_slot(bool queued)
     {
-        // count must be a multiple of 512
-        if (count & (512 - 1))
+        if (slots_used && queued != queued_used)
             return -1;
 
-        // TODO: get an availablee command slot
-        int n = 0;
-        // auto n = qu.pop();
+        for (uint32_t n = 0; n < nslots; ++n) {
+            if (!(slots_used & (1U << n)))
+                return n;
+        }
+        return -1;
+    }
 
-        // for now, we read 3.5KB at most at a time
-        // command fis and prdt will take up the lower 128+Bytes
-        auto cmdtable_page = __alloc_raw_page();
+    // abort the commands issued after an error, they fail all
+    // together as the drive drops its ncq queue on errors anyway
+    // interrupts MUST be disabled
+    void reset()
+    {
+        slots_failed |= slots_issued;
+        slots_issued = 0;
 
-        // construct command header
-        memset(cmd_header + n, 0x00, sizeof(command_header));
-        cmd_header[n].command_fis_length = 5;
-        cmd_header[n].clear_busy_upon_ok = 1;
+        // clearing PORT_CMD_ST clears command_issue and sata_active
+        stop_command(port);
+        port->sata_error = ~0;
+        port->interrupt_status = ~0;
+        start_command(port);
+    }
 
-        cmd_header[n].write = write;
-        cmd_header[n].prdt_length = 1;
-        cmd_header[n].command_table_base = cmdtable_page << 12;
+    // mark the slots the hba is done with as complete
+    // interrupts MUST be disabled
+    void reap()
+    {
+        uint32_t status = port->interrupt_status;
+        port->interrupt_status = status;
 
-        auto* cmdtable = (command_table*)kernel::pmap(cmdtable_page);
-        memset(cmdtable, 0x00, sizeof(command_table) + sizeof(prdt_entry));
+        if (status & PORT_IRQ_TFE) {
+            reset();
+            return;
+        }
 
-        // first, set up command fis
-        cmdtable->command_fis.fis_type = FIS_REG_H2D;
-        cmdtable->command_fis.is_command = 1;
-        cmdtable->command_fis.command = cmd;
+        // ncq commands are done when the drive clears their
+        // sata_active bits, the others when command_issue is
+        uint32_t active = port->command_issue;
+        if (queued_used)
+            active |= port->sata_active;
+        slots_issued &= active;
+    }
 
-        cmdtable->command_fis.lba0 = lba & 0xff;
-        cmdtable->command_fis.lba1 = (lba >> 8) & 0xff;
-        cmdtable->command_fis.lba2 = (lba >> 16) & 0xff;
-        cmdtable->command_fis.device = 1 << 6; // lba mode
-        cmdtable->command_fis.lba3 = (lba >> 24) & 0xff;
-        cmdtable->command_fis.lba4 = (lba >> 32) & 0xff;
-        cmdtable->command_fis.lba5 = (lba >> 40) & 0xff;
+    // a single sector transfer into a kernel buffer
+    int send_command(char* buf, uint64_t lba, uint8_t cmd, bool write)
+    {
+        kernel::memory::sg_list sg;
+        sg.add_kernel_buf(buf, 512, !write);
+        command c(sg, lba, cmd, write);
+        if (issue(c) != 0)
+            return -1;
+        return wait(c);
+    }
 
-        cmdtable->command_fis.count = count >> 9;
+    // READ and WRITE FPDMA QUEUED or DMA EXT
+    int submit(uint64_t lba, const kernel::memory::sg_list& sg, bool write)
+    {
+        uint8_t cmd = write ? 0x35 : 0xC8;
+        if (ncq)
+            cmd = write ? 0x61 : 0x60;
+
+        command c(sg, lba, cmd, write, ncq);
+        if (issue(c) != 0 || wait(c) != 0)
+            return -EIO;
+        return 0;
+    }
 
-        // fill in prdt
-        auto* pprdt = cmdtable->prdt;
-        pprdt->data_base = (cmdtable_page << 12) + 512;
-        pprdt->byte_count = count;
-        pprdt->interrupt = 1;
+    // send the scsi command in packet, the device transfers
+    // the data, if any, to the memory of sg
+    int send_packet(const uint8_t (&packet)[12], const kernel::memory::sg_list& sg)
+    {
+        command c(sg, 0, ATA_CMD_PACKET, false);
+        memcpy(c.packet, packet, sizeof(c.packet));
+        if (issue(c) != 0)
+            return -1;
+        return wait(c);
+    }
 
-        // clear the received fis
-        memset(fis, 0x00, sizeof(received_fis));
+    // READ CAPACITY (10), retried as drives report a unit attention
+    // on the first command after reset or medium change
+    int read_capacity()
+    {
+        alignas(4) uint8_t data[8];
+        kernel::memory::sg_list sg;
+        sg.add_kernel_buf(data, sizeof(data), true);
 
-        // issue the command
-        port->command_issue = 1 << n;
+        const uint8_t packet[12] { 0x25 };
+        for (int i = 0; i < 3; ++i) {
+            if (send_packet(packet, sg) != 0)
+                continue;
 
-        // TODO: use interrupt
-        uint32_t spins = 0;
-        SPIN(port->task_file_data & (ATA_DEV_BSY | ATA_DEV_DRQ), spins)
-            return -1;
-        
-        SPIN(port->command_issue & (1 << n), spins)
-            return -1;
-        
-        memcpy(buf, (char*)cmdtable + 512, count);
+            // the last lba and the block size, in big endian
+            uint32_t last = (data[0] << 24) | (data[1] << 16) | (data[2] << 8) | data[3];
+            block_size = (data[4] << 24) | (data[5] << 16) | (data[6] << 8) | data[7];
+            if (!block_size)
+                block_size = CD_BLOCK_SIZE;
+            blocks = last + 1;
+            return 0;
+        }
 
-        kernel::pfree(cmdtable_page);
-        __free_raw_page(cmdtable_page);
-        return 0;
+        return -1;
     }
 
+    // IDENTIFY DEVICE or IDENTIFY PACKET DEVICE
     int identify()
     {
-        char buf[512];
-        int ret = send_command(buf, 0, 512, 0xEC, false);
+        uint16_t buf[256];
+        int ret = send_command((char*)buf, 0, atapi ? 0xA1 : 0xEC, false);
         if (ret != 0)
             return -1;
+
+        // word 76 bit 8: ncq supported, word 75: queue depth - 1
+        ncq = ncq && !atapi && (buf[76] & (1 << 8));
+        if (ncq)
+            nslots = std::min(nslots, (uint32_t)(buf[75] & 0x1f) + 1);
+
         return 0;
     }
 
 public:
-    explicit ahci_port(hba_port* port)
-        : page(__alloc_raw_page()), port(port) { }
+    // sectors are read and written through the queue
+    kernel::block::request_queue queue;
+
+    // @param nslots the number of command slots of the hba
+    // @param ncq whether the hba supports native command queuing
+    // @param use_irq whether the hba irq calls handle_interrupt()
+    // @param atapi whether a packet device is attached
+    ahci_port(hba_port* port, uint32_t nslots, bool ncq, bool use_irq, bool atapi)
+        : page(__alloc_raw_page()), port(port)
+        , nslots(nslots), ncq(ncq), atapi(atapi), use_irq(use_irq)
+        , queue([this](uint64_t lba, const kernel::memory::sg_list& sg, bool write) {
+            return submit(lba, sg, write);
+        }, MAX_SECTORS, -1U, nslots, [this]() {
+            return flush();
+        }) { }
 
     ~ahci_port()
     {
@@ -386,32 +507,210 @@ public:
         __free_raw_page(page);
     }
 
-    ssize_t read(char* buf, std::size_t buf_size, std::size_t offset, std::size_t cnt)
+    // put the command in a free slot and start it, waiting
+    // for a slot if they are all taken
+    // @return 0 or -1 if the command can't be issued,
+    //         it MUST be waited for if issued
+    int issue(command& c)
+    {
+        if (c.count & (c.cmd == ATA_CMD_PACKET ? 1 : 512 - 1))
+            return -1;
+        if (!c.dma.valid())
+            return -1;
+        const auto* dma_sg = &c.dma.sg();
+
+        auto nprdt = dma_sg->segments().size();
+        if (nprdt > DMA_LIMITS.max_segs)
+            return -1;
+
+        types::lock_guard lck(cv.mtx());
+        uint32_t flags = types::irq_save();
+
+        // the slot lives till wait(), so we can't leave on signals
+        int n;
+        while ((n = free_slot(c.queued)) < 0)
+            cv.wait(cv.mtx());
+
+        slots_used |= 1U << n;
+        queued_used = c.queued;
+        c.slot = n;
+
+        types::irq_restore(flags);
+
+        // command fis takes up the lower 128 bytes, followed by the prdt
+        c.cmdtable_page = __alloc_raw_page();
+
+        // construct command header
+        memset(cmd_header + n, 0x00, sizeof(command_header));
+        cmd_header[n].command_fis_length = 5;
+        cmd_header[n].clear_busy_upon_ok = 1;
+
+        cmd_header[n].write = c.write;
+        cmd_header[n].prdt_length = nprdt;
+        cmd_header[n].command_table_base = c.cmdtable_page << 12;
+
+        auto* cmdtable = (command_table*)kernel::pmap(c.cmdtable_page);
+        memset(cmdtable, 0x00, sizeof(command_table) + nprdt * sizeof(prdt_entry));
+
+        // first, set up command fis
+        cmdtable->command_fis.fis_type = FIS_REG_H2D;
+        cmdtable->command_fis.is_command = 1;
+        cmdtable->command_fis.command = c.cmd;
+
+        cmdtable->command_fis.lba0 = c.lba & 0xff;
+        cmdtable->command_fis.lba1 = (c.lba >> 8) & 0xff;
+        cmdtable->command_fis.lba2 = (c.lba >> 16) & 0xff;
+        cmdtable->command_fis.device = 1 << 6; // lba mode
+        cmdtable->command_fis.lba3 = (c.lba >> 24) & 0xff;
+        cmdtable->command_fis.lba4 = (c.lba >> 32) & 0xff;
+        cmdtable->command_fis.lba5 = (c.lba >> 40) & 0xff;
+
+        if (c.cmd == ATA_CMD_PACKET) {
+            cmd_header[n].atapi = 1;
+            memcpy(cmdtable->atapi_command, c.packet, sizeof(c.packet));
+            // the data goes by dma
+            cmdtable->command_fis.feature = c.count ? 1 : 0;
+        } else if (c.queued) {
+            // the sector count goes in the feature, the tag in the count
+            cmdtable->command_fis.feature = (c.count >> 9) & 0xff;
+            cmdtable->command_fis.feature_high = (c.count >> 17) & 0xff;
+            cmdtable->command_fis.count = n << 3;
+        } else {
+            cmdtable->command_fis.count = c.count >> 9;
+        }
+
+        // fill in prdt, byte_count is 0 based
+        for (std::size_t i = 0; i < nprdt; ++i) {
+            const auto& seg = dma_sg->segments()[i];
+            cmdtable->prdt[i].data_base = seg.phys;
+            cmdtable->prdt[i].byte_count = seg.len - 1;
+        }
+        if (nprdt)
+            cmdtable->prdt[nprdt - 1].interrupt = 1;
+
+        kernel::pfree(c.cmdtable_page);
+
+        // issue the command, the slot mustn't be reaped before
+        // the hba has seen it
+        flags = types::irq_save();
+        slots_failed &= ~(1U << n);
+        slots_issued |= 1U << n;
+        if (c.queued)
+            port->sata_active = 1U << n;
+        port->command_issue = 1U << n;
+        types::irq_restore(flags);
+
+        return 0;
+    }
+
+    // wait for the command issued to complete and free its slot
+    // @return 0 or -1 if the command failed
+    int wait(command& c)
     {
-        cnt = std::min(buf_size, cnt);
+        uint32_t bit = 1U << c.slot;
+        int ret = 0;
+        {
+            types::lock_guard lck(cv.mtx());
+            // or the completion might come between the check and the
+            // sleep, and we would never be woken up
+            uint32_t flags = types::irq_save();
+
+            uint32_t spins = 0;
+            while (slots_issued & bit) {
+                if (use_irq) {
+                    cv.wait(cv.mtx());
+                    continue;
+                }
+
+                reap();
+                if (++spins == MAX_SPINS)
+                    reset();
+
+                // let the timer in between the polls
+                types::irq_restore(flags);
+                flags = types::irq_save();
+            }
 
-        char b[512] {};
-        char* orig_buf = buf;
-        size_t start = offset / 512;
-        size_t end = std::min((offset + cnt + 511) / 512, sectors);
+            if (slots_failed & bit)
+                ret = -1;
+            slots_used &= ~bit;
 
-        offset %= 512;
-        for (size_t i = start; i < end; ++i) {
-            int status = send_command(b, i, 512, 0xC8, false);
-            if (status != 0)
+            types::irq_restore(flags);
+        }
+
+        // the issuers waiting for a free slot
+        cv.notify_all();
+
+        if (ret == 0 && !c.write)
+            c.dma.sync_for_cpu();
+
+        __free_raw_page(c.cmdtable_page);
+        return ret;
+    }
+
+    // reap the completed commands and wake up their issuers
+    // called in the irq, with the interrupts disabled
+    void handle_interrupt()
+    {
+        reap();
+        cv.notify_all();
+    }
+
+    // read a packet device, in whole blocks of the medium
+    ssize_t atapi_read(char* buf, std::size_t buf_size, std::size_t offset, std::size_t n)
+    {
+        // the medium might have been inserted after init()
+        if (!block_size && read_capacity() != 0)
+            return -EIO;
+
+        n = std::min(buf_size, n);
+
+        std::vector<char> chunk;
+        std::size_t done = 0;
+        while (done < n) {
+            std::size_t pos = offset + done;
+            std::size_t lba = pos / block_size;
+            std::size_t skip = pos % block_size;
+            if (lba >= blocks)
+                break;
+
+            std::size_t nblocks = (skip + (n - done) + block_size - 1) / block_size;
+            nblocks = std::min(nblocks, MAX_SECTORS * 512 / block_size);
+            nblocks = std::min(nblocks, blocks - lba);
+
+            chunk.resize(nblocks * block_size);
+            kernel::memory::sg_list sg;
+            sg.add_kernel_buf(chunk.data(), chunk.size(), true);
+
+            // READ (10)
+            const uint8_t packet[12] {
+                0x28, 0,
+                (uint8_t)(lba >> 24), (uint8_t)(lba >> 16),
+                (uint8_t)(lba >> 8), (uint8_t)lba,
+                0, (uint8_t)(nblocks >> 8), (uint8_t)nblocks,
+            };
+            if (send_packet(packet, sg) != 0)
                 return -EIO;
 
-            size_t to_copy = 0;
-            if (offset)
-                to_copy = 512 - offset;
-            else
-                to_copy = std::max(cnt, 512U);
-            memcpy(buf, b + offset, to_copy);
-            offset = 0;
-            buf += to_copy;
-            cnt -= to_copy;
+            std::size_t len = std::min(chunk.size() - skip, n - done);
+            memcpy(buf + done, chunk.data() + skip, len);
+            done += len;
         }
-        return buf - orig_buf;
+
+        return done;
+    }
+
+    constexpr bool is_atapi() const { return atapi; }
+
+    // FLUSH CACHE EXT, returns after the drive write cache is on the medium
+    // called by the queue, once the writes queued before are done
+    int flush()
+    {
+        kernel::memory::sg_list sg;
+        command c(sg, 0, 0xEA, false);
+        if (issue(c) != 0 || wait(c) != 0)
+            return -EIO;
+        return 0;
     }
 
     int init()
@@ -419,8 +718,8 @@ public:
         if (stop_command(port) != 0)
             return -1;
         port->interrupt_status = ~0;
-        // TODO: use interrupt
-        // port->interrupt_enable = 1;
+        if (use_irq)
+            port->interrupt_enable = PORT_IRQ_D2H | PORT_IRQ_SDB | PORT_IRQ_TFE;
 
         port->command_list_base = page << 12;
         port->command_list_base_upper = 0;
@@ -428,6 +727,9 @@ public:
         port->fis_base = (page << 12) + 0x400;
         port->fis_base_upper = 0;
 
+        if (atapi)
+            port->command_status = port->command_status | PORT_CMD_ATAPI;
+
         cmd_header = (command_header*)kernel::pmap(page, false);
         fis = (received_fis*)(cmd_header + 1);
 
@@ -437,6 +739,10 @@ public:
         if (identify() != 0)
             return -1;
 
+        // there might be no medium yet
+        if (atapi)
+            read_capacity();
+
         return 0;
     }
 };
@@ -446,6 +752,26 @@ private:
     hba_ghc* ghc { };
     pci_device* dev { };
     std::vector<ahci_port*> ports;
+    bool use_irq { };
+
+    // the ports share the irq of the hba
+    void handle_interrupt()
+    {
+        // the pic is edge triggered, go on till the line is deasserted
+        // or the completions coming in meanwhile would be missed
+        while (uint32_t pending = this->ghc->interrupt_status) {
+            for (int n = 0; n < 32; ++n) {
+                if (!(pending & (1U << n)))
+                    continue;
+
+                if (ports[n])
+                    ports[n]->handle_interrupt();
+                else
+                    port_ptr(this->ghc, n)->interrupt_status = ~0;
+            }
+            this->ghc->interrupt_status = pending;
+        }
+    }
 
 public:
     ahci_module() : module("ahci") { }
@@ -466,6 +792,7 @@ public:
 
     int probe_disks()
     {
+        int ncdroms = 0;
         int ports = this->ghc->ports_implemented;
         for (int n = 0; ports; ports >>= 1, ++n) {
             if (!(ports & 1))
@@ -475,19 +802,47 @@ public:
             if ((ghc_port->sata_status & 0x0f) != 0x03)
                 continue;
 
-            auto* port = new ahci_port(ghc_port);
+            uint32_t cap = this->ghc->capabilities;
+            auto* port = new ahci_port(ghc_port, ((cap >> 8) & 0x1f) + 1,
+                cap & HBA_CAP_SNCQ, use_irq, ghc_port->signature == SATA_SIG_ATAPI);
+
+            // identify() completes through the irq handler
+            this->ports[n] = port;
             if (port->init() != 0) {
+                this->ports[n] = nullptr;
                 delete port;
                 kmsg("An error occurred while configuring an ahci port\n");
                 continue;
             }
 
-            this->ports[n] = port;
+            if (port->is_atapi()) {
+                char name[16];
+                snprintf(name, sizeof(name), "sr%d", ncdroms);
+
+                fs::register_block_device(fs::make_node(11, ncdroms++), {
+                    [port](char* buf, std::size_t buf_size, std::size_t offset, std::size_t cnt) {
+                        return port->atapi_read(buf, buf_size, offset, cnt);
+                    },
+                    [](const char*, std::size_t, std::size_t) -> ssize_t {
+                        return -EROFS;
+                    },
+                    nullptr,
+                    nullptr,
+                }, name);
+                continue;
+            }
 
             fs::register_block_device(fs::make_node(8, n * 8), {
                 [port](char* buf, std::size_t buf_size, std::size_t offset, std::size_t cnt) {
-                    return port->read(buf, buf_size, offset, cnt);
-                }, nullptr
+                    return port->queue.read(buf, buf_size, offset, cnt);
+                },
+                [port](const char* buf, std::size_t offset, std::size_t cnt) {
+                    return port->queue.write(buf, offset, cnt);
+                },
+                [port]() -> int {
+                    return port->queue.flush();
+                },
+                nullptr,
             });
 
             fs::partprobe();
@@ -508,6 +863,12 @@ public:
                 void* base = kernel::pmap(abar_address >> 12, false);
                 this->ghc = (hba_ghc*)base;
 
+                if (dev->interrupt_line < kernel::irq::IRQ_COUNT) {
+                    use_irq = true;
+                    kernel::irq::register_handler(dev->interrupt_line,
+                        [this]() { this->handle_interrupt(); });
+                }
+
                 this->ghc->global_host_control =
                     this->ghc->global_host_control | 2; // set interrupt enable
                 
@@ -1,3 +1,4 @@
+#include <fs/sysfs.hpp>
 #include <kernel/hw/pci.hpp>
 #include <kernel/hw/port.hpp>
 #include <kernel/errno.h>
@@ -6,6 +7,7 @@
 
 #include <assert.h>
 #include <stdint.h>
+#include <types/string.hpp>
 
 using kernel::hw::p32;
 
@@ -72,15 +74,98 @@ pci_device::pci_device(config_reg reg)
 
     tmp = reg[2];
     revision_id = tmp & 0xFF;
+    prog_if = (tmp >> 8) & 0xFF;
     subclass = (tmp >> 16) & 0xFF;
     class_code = tmp >> 24;
 
     tmp = reg[3];
     header_type = (tmp >> 16) & 0xFF;
+
+    // only the general devices (header type 0) have it
+    interrupt_line = 0xFF;
+    if ((header_type & 0x7F) == 0)
+        interrupt_line = reg[15] & 0xFF;
 }
 
 // end class pci_device
 
+enum class attr_type {
+    vendor,
+    device,
+    revision,
+    class_code,
+};
+
+// write the attribute as "0x" followed by fixed width hex digits and a newline
+static size_t show_attr(char* buf, size_t buf_size, const pci_device* dev, attr_type type)
+{
+    uint32_t val = 0;
+    int digits = 0;
+    switch (type) {
+    case attr_type::vendor:
+        val = dev->vendor, digits = 4;
+        break;
+    case attr_type::device:
+        val = dev->device, digits = 4;
+        break;
+    case attr_type::revision:
+        val = dev->revision_id, digits = 2;
+        break;
+    case attr_type::class_code:
+        val = (dev->class_code << 16) | (dev->subclass << 8) | dev->prog_if;
+        digits = 6;
+        break;
+    }
+
+    if (buf_size < (size_t)digits + 3)
+        return 0;
+
+    buf[0] = '0', buf[1] = 'x';
+    for (int i = 0; i < digits; ++i)
+        buf[2 + i] = "0123456789abcdef"[(val >> ((digits - 1 - i) * 4)) & 0xf];
+    buf[2 + digits] = '\n';
+
+    return digits + 3;
+}
+
+// /sys/bus/pci/devices/0000:<bus>:<dev>.<func>
+static void publish_device(pci_device* pcidev, uint8_t bus, uint8_t dev, uint8_t func)
+{
+    char path[] = "bus/pci/devices/0000:bb:dd.f";
+    char* name = path + sizeof("bus/pci/devices/0000:") - 1;
+
+    name[0] = "0123456789abcdef"[bus >> 4];
+    name[1] = "0123456789abcdef"[bus & 0xf];
+    name[3] = "0123456789abcdef"[dev >> 4];
+    name[4] = "0123456789abcdef"[dev & 0xf];
+    name[6] = '0' + func;
+
+    auto* sysfs = fs::sysfs::instance();
+    if (sysfs->mkdir(path) != 0)
+        return;
+
+    const struct {
+        const char* name;
+        attr_type type;
+    } attrs[] = {
+        { "vendor", attr_type::vendor },
+        { "device", attr_type::device },
+        { "revision", attr_type::revision },
+        { "class", attr_type::class_code },
+    };
+
+    for (const auto& attr : attrs) {
+        types::string<> attr_path = path;
+        attr_path += '/';
+        attr_path += attr.name;
+
+        auto type = attr.type;
+        sysfs->add_file(attr_path.c_str(), [pcidev, type](char* buf, size_t buf_size) -> size_t {
+            return show_attr(buf, buf_size, pcidev, type);
+        });
+    }
+}
+
 pci_device* probe_device(uint8_t bus, uint8_t dev, uint8_t func)
 {
     config_reg reg(bus, dev, func);
@@ -96,6 +181,8 @@ pci_device* probe_device(uint8_t bus, uint8_t dev, uint8_t func)
         make_device(vendor, device), reg);
     assert(inserted);
 
+    publish_device(&iter->second, bus, dev, func);
+
     return &iter->second;
 }
 
@@ -2,16 +2,23 @@
 #include <kernel/hw/serial.h>
 #include <kernel/irq.hpp>
 #include <kernel/tty.hpp>
+#include <kernel/vfs.hpp>
 #include <stdio.h>
+#include <types/allocator.hpp>
 #include <types/status.h>
 
-static void serial_receive_data_interrupt(void)
-{
-    while (is_serial_has_data(PORT_SERIAL0)) {
-        uint8_t data = serial_read_data(PORT_SERIAL0);
-        console->recvchar(data);
-    }
-}
+// the isa ports in the order of ttySN
+static constexpr struct {
+    port_id_t port;
+    int irq;
+} s_isa_ports[kernel::hw::serial::MAX_PORTS] = {
+    { PORT_SERIAL0, 4 },
+    { PORT_SERIAL1, 3 },
+    { PORT_SERIAL2, 4 },
+    { PORT_SERIAL3, 3 },
+};
+
+static serial_tty* s_ttys[kernel::hw::serial::MAX_PORTS];
 
 SECTION(".text.kinit")
 int32_t init_serial_port(port_id_t port)
@@ -41,11 +48,60 @@ int32_t init_serial_port(port_id_t port)
 
     asm_outb(port + 1, 0x01); // Enable interrupts #0: Received Data Available
 
-    kernel::irq::register_handler(4, serial_receive_data_interrupt);
-
     return GB_OK;
 }
 
+SECTION(".text.kinit")
+int kernel::hw::serial::init_serial(void)
+{
+    int cnt = 0;
+    for (int i = 0; i < MAX_PORTS; ++i) {
+        // TODO: find the uarts from acpi instead of probing the isa ports
+        if (init_serial_port(s_isa_ports[i].port) != GB_OK)
+            continue;
+
+        auto* tty = types::_new<types::kernel_ident_allocator, serial_tty>(
+            i, s_isa_ports[i].port);
+        s_ttys[i] = tty;
+        kernel::irq::register_handler(s_isa_ports[i].irq,
+            [tty]() { tty->handle_irq(); });
+        ++cnt;
+    }
+
+    return cnt;
+}
+
+serial_tty* kernel::hw::serial::get_tty(int index)
+{
+    if (index < 0 || index >= MAX_PORTS)
+        return nullptr;
+    return s_ttys[index];
+}
+
+SECTION(".text.kinit")
+void kernel::hw::serial::init_serial_devices(void)
+{
+    for (int i = 0; i < MAX_PORTS; ++i) {
+        auto* tty = s_ttys[i];
+        if (!tty)
+            continue;
+
+        fs::register_char_device(fs::make_node(4, 64 + i), {
+            [tty](char* buf, size_t buf_size, size_t n) -> ssize_t {
+                return tty->read(buf, buf_size, n);
+            },
+            [tty](const char* buf, size_t n) -> ssize_t {
+                for (size_t i = 0; i < n; ++i)
+                    tty->putchar(buf[i]);
+                return n;
+            },
+            [tty](unsigned long request, uintptr_t arg) -> int {
+                return tty->ioctl(request, arg);
+            },
+        }, tty->name, 0660);
+    }
+}
+
 int32_t is_serial_has_data(port_id_t port)
 {
     return asm_inb(port + 5) & 1;
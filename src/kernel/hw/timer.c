@@ -24,3 +24,10 @@ size_t current_ticks(void)
 {
     return _current_ticks;
 }
+
+struct timespec current_time(void)
+{
+    // TODO: start from the time in the rtc
+    struct timespec ts = { .tv_sec = 10 + _current_ticks, .tv_nsec = 0 };
+    return ts;
+}
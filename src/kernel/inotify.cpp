@@ -0,0 +1,227 @@
+#include <algorithm>
+#include <list>
+#include <vector>
+
+#include <kernel/errno.h>
+#include <kernel/inotify.hpp>
+#include <kernel/mm.hpp>
+#include <kernel/user/uaccess.hpp>
+#include <stdio.h>
+#include <string.h>
+#include <types/lock.hpp>
+
+fs::inotify_file::inotify_file(file_flags flags, bool nonblock)
+    : file(0, nullptr, flags), anon_file(ANON_NAME, flags, nonblock) { }
+
+void fs::inotify_file::queue_event(int wd, uint32_t mask, const char* name)
+{
+    if (!m_events.empty()) {
+        auto& last = m_events.back();
+
+        if (last.mask == IN_Q_OVERFLOW)
+            return;
+
+        // merge with the last event if they are identical
+        if (last.wd == wd && last.mask == mask
+            && strcmp(last.name.c_str(), name ? name : "") == 0)
+            return;
+    }
+
+    if (m_events.size() >= MAX_QUEUED_EVENTS) {
+        m_events.emplace_back(pending_event { -1, IN_Q_OVERFLOW, 0, "" });
+        return;
+    }
+
+    m_events.emplace_back(pending_event { wd, mask, 0, name ? name : "" });
+}
+
+ssize_t fs::inotify_file::read(char* __user buf, size_t n)
+{
+    if (!flags.read)
+        return -EBADF;
+
+    size_t orig_n = n;
+    {
+        auto& mtx = m_cv.mtx();
+        types::lock_guard lck(mtx);
+
+        while (m_events.empty()) {
+            if (nonblock())
+                return -EAGAIN;
+            if (!m_cv.wait(mtx))
+                return -EINTR;
+        }
+
+        while (!m_events.empty()) {
+            const auto& evt = m_events.front();
+
+            size_t len = 0;
+            if (!evt.name.empty())
+                len = align_up<2>(evt.name.size() + 1);
+
+            size_t reclen = sizeof(inotify_event) + len;
+            if (reclen > n) {
+                if (n == orig_n)
+                    return -EINVAL;
+                break;
+            }
+
+            std::vector<char> rec(reclen, 0x00);
+            auto* ie = (inotify_event*)rec.data();
+            ie->wd = evt.wd;
+            ie->mask = evt.mask;
+            ie->cookie = evt.cookie;
+            ie->len = len;
+            memcpy(ie->name, evt.name.c_str(), evt.name.size());
+
+            // the event stays in the queue if it can't be copied
+            if (kernel::user::copy_to_user(buf, rec.data(), reclen)) {
+                if (n == orig_n)
+                    return -EFAULT;
+                break;
+            }
+
+            buf += reclen;
+            n -= reclen;
+            m_events.pop_front();
+        }
+    }
+
+    return orig_n - n;
+}
+
+int fs::inotify_file::add_watch(inode* ind, uint32_t mask)
+{
+    if (!(mask & IN_ALL_EVENTS))
+        return -EINVAL;
+
+    if ((mask & IN_ONLYDIR) && !S_ISDIR(ind->mode))
+        return -ENOTDIR;
+
+    types::lock_guard lck(m_cv.mtx());
+
+    for (auto& [ wd, w ] : m_watches) {
+        if (w.ind != ind)
+            continue;
+
+        if (mask & IN_MASK_ADD)
+            w.mask |= mask;
+        else
+            w.mask = mask;
+        return wd;
+    }
+
+    int wd = m_next_wd++;
+    m_watches.emplace(wd, watch { ind, mask });
+    return wd;
+}
+
+int fs::inotify_file::rm_watch(int wd)
+{
+    {
+        types::lock_guard lck(m_cv.mtx());
+
+        auto iter = m_watches.find(wd);
+        if (!iter)
+            return -EINVAL;
+
+        m_watches.erase(iter);
+        queue_event(wd, IN_IGNORED, nullptr);
+    }
+
+    m_cv.notify_all();
+    return 0;
+}
+
+void fs::inotify_file::handle_event(inode* ind, uint32_t mask, const char* name)
+{
+    bool queued = false;
+    {
+        types::lock_guard lck(m_cv.mtx());
+
+        for (auto iter = m_watches.begin(); iter != m_watches.end(); ) {
+            auto& [ wd, w ] = *iter;
+            if (w.ind != ind || !(w.mask & mask & IN_ALL_EVENTS)) {
+                ++iter;
+                continue;
+            }
+
+            queue_event(wd, mask, name);
+            queued = true;
+
+            if (w.mask & IN_ONESHOT) {
+                queue_event(wd, IN_IGNORED, nullptr);
+                iter = m_watches.erase(iter);
+                continue;
+            }
+
+            ++iter;
+        }
+    }
+
+    if (queued)
+        m_cv.notify_all();
+}
+
+void fs::inotify_file::handle_inode_removed(inode* ind)
+{
+    bool queued = false;
+    {
+        types::lock_guard lck(m_cv.mtx());
+
+        for (auto iter = m_watches.begin(); iter != m_watches.end(); ) {
+            if (iter->second.ind != ind) {
+                ++iter;
+                continue;
+            }
+
+            queue_event(iter->first, IN_IGNORED, nullptr);
+            queued = true;
+            iter = m_watches.erase(iter);
+        }
+    }
+
+    if (queued)
+        m_cv.notify_all();
+}
+
+size_t fs::inotify_file::describe(char* buf, size_t buf_size)
+{
+    types::lock_guard lck(m_cv.mtx());
+
+    size_t len = 0;
+    for (const auto& [ wd, w ] : m_watches) {
+        if (len + 1 >= buf_size)
+            break;
+
+        int n = snprintf(buf + len, buf_size - len,
+            "inotify wd:%x ino:%x mask:%x ignored_mask:0\n",
+            wd, w.ind->ino, w.mask);
+        if (n > 0)
+            len = std::min(len + n, buf_size - 1);
+    }
+
+    return len;
+}
+
+void fs::inotify_notify(inode* ind, uint32_t mask, const char* name)
+{
+    for (auto* inst : anon_files()) {
+        if (strcmp(inst->name(), inotify_file::ANON_NAME) == 0)
+            static_cast<inotify_file*>(inst)->handle_event(ind, mask, name);
+    }
+}
+
+void fs::inotify_notify_parent(vfs::dentry* dent, uint32_t mask)
+{
+    if (dent->parent)
+        inotify_notify(dent->parent->ind, mask, dent->name.c_str());
+}
+
+void fs::inotify_inode_removed(inode* ind)
+{
+    for (auto* inst : anon_files()) {
+        if (strcmp(inst->name(), inotify_file::ANON_NAME) == 0)
+            static_cast<inotify_file*>(inst)->handle_inode_removed(ind);
+    }
+}
@@ -11,11 +11,15 @@
 #include <kernel/log.hpp>
 #include <kernel/mem.h>
 #include <kernel/mm.hpp>
+#include <kernel/oops.hpp>
 #include <kernel/process.hpp>
+#include <kernel/random.hpp>
+#include <kernel/user/uaccess.hpp>
 #include <kernel/vfs.hpp>
 #include <kernel/vga.hpp>
 #include <stdint.h>
 #include <stdio.h>
+#include <types/lock.hpp>
 #include <types/size.h>
 #include <types/types.h>
 
@@ -62,23 +66,7 @@ static void set_idt_entry(IDT_entry (&idt)[256], int n,
 // [1] bit 16:47 => address
 extern "C" void asm_load_idt(uint16_t idt_descriptor[3], int sti);
 
-static struct IDT_entry IDT[256];
-
-static inline void NORETURN die(regs_32& regs, ptr_t eip)
-{
-    char buf[512] = {};
-    snprintf(
-        buf, sizeof(buf),
-        "***** KERNEL PANIC *****\n"
-        "eax: %x, ebx: %x, ecx: %x, edx: %x\n"
-        "esp: %x, ebp: %x, esi: %x, edi: %x\n"
-        "eip: %x\n",
-        regs.eax, regs.ebx, regs.ecx,
-        regs.edx, regs.esp, regs.ebp,
-        regs.esi, regs.edi, eip);
-    kmsg(buf);
-    freeze();
-}
+static struct IDT_entry IDT[256] __ro_after_init;
 
 SECTION(".text.kinit")
 void init_idt()
@@ -107,9 +95,20 @@ void init_idt()
 
 using kernel::irq::irq_handler_t;
 static std::vector<std::list<irq_handler_t>> s_irq_handlers;
+static types::rw_spinlock s_irq_handlers_lock;
+static size_t s_irq_counts[kernel::irq::IRQ_COUNT];
+// set by the timer, the switch is done after the handlers return so
+// that threads are never switched out with s_irq_handlers_lock held
+static bool s_need_resched;
+
+size_t kernel::irq::irq_count(int irqno)
+{
+    return s_irq_counts[irqno];
+}
 
 void kernel::irq::register_handler(int irqno, irq_handler_t handler)
 {
+    types::write_guard lck(s_irq_handlers_lock);
     s_irq_handlers[irqno].emplace_back(std::move(handler));
 }
 
@@ -118,12 +117,21 @@ void init_pic(void)
 {
     asm_cli();
 
-    s_irq_handlers.resize(16);
+    s_irq_handlers.resize(kernel::irq::IRQ_COUNT);
 
     // TODO: move this to timer driver
     kernel::irq::register_handler(0, []() {
         inc_tick();
-        schedule();
+
+        if (current_thread->attr.system) {
+            ++kernel::tasks::stats.system_ticks;
+            ++current_process->stime;
+        } else {
+            ++kernel::tasks::stats.user_ticks;
+            ++current_process->utime;
+        }
+
+        s_need_resched = true;
     });
 
     asm_outb(PORT_PIC1_COMMAND, 0x11); // edge trigger mode
@@ -169,10 +177,10 @@ extern "C" void int6_handler(
     snprintf(buf, sizeof(buf),
         "[kernel] int6 data: cs: %x, eflags: %x\n", cs, eflags);
     kmsg(buf);
-    if (!current_process->attr.system)
+    if (cs & 3)
         kill_current(-1);
     else
-        die(s_regs, eip);
+        kernel::oops("invalid opcode", &s_regs, eip);
 }
 
 // general protection
@@ -188,10 +196,10 @@ extern "C" void int13_handler(
         "[kernel] int13 data: error_code: %x, cs: %x, eflags: %x\n",
         error_code, cs, eflags);
     kmsg(buf);
-    if (!current_process->attr.system)
+    if (cs & 3)
         kill_current(-1);
     else
-        die(s_regs, eip);
+        kernel::oops("general protection fault", &s_regs, eip);
 }
 
 struct PACKED int14_data {
@@ -203,25 +211,48 @@ struct PACKED int14_data {
     uint32_t eflags;
 };
 
-static inline void _int14_panic(void* eip, void* cr2, struct page_fault_error_code error_code)
+static inline void NORETURN _int14_oops(int14_data* d)
 {
     char buf[128] = {};
     snprintf(buf, sizeof(buf),
-        "[kernel] int14 data: eip: %p, cr2: %p, error_code: %x\n"
-        "[kernel] freezing...\n",
-        eip, cr2, error_code);
+        "[kernel] int14 data: eip: %p, cr2: %p, error_code: %x\n",
+        d->v_eip, d->l_addr, d->error_code);
     kmsg(buf);
-    freeze();
+
+    regs_32 regs = d->s_regs;
+    kernel::oops("unable to handle kernel page fault",
+        &regs, (ptr_t)d->v_eip);
 }
 
 static inline void NORETURN _int14_kill_user(void)
 {
     char buf[256] {};
-    snprintf(buf, 256, "Segmentation Fault (pid%d killed)\n", current_process->pid);
+    int len = snprintf(buf, 256, "Segmentation Fault (pid%d killed", current_process->pid);
+
+    // so that the crash can be matched with the binary
+    const auto& build_id = current_process->build_id;
+    if (!build_id.empty()) {
+        len += snprintf(buf + len, 256 - len, ", build id ");
+        for (auto byte : build_id)
+            len += snprintf(buf + len, 256 - len, "%x%x", byte >> 4, byte & 0xf);
+    }
+    snprintf(buf + len, 256 - len, ")\n");
     kmsg(buf);
     kill_current(-1);
 }
 
+// the kernel faulted at an instruction accessing user memory,
+// continue at its fixup, which makes the access fail with -EFAULT
+static inline bool _int14_fixup(int14_data* d)
+{
+    auto fixup = kernel::user::search_exception_table((uintptr_t)d->v_eip);
+    if (!fixup)
+        return false;
+
+    d->v_eip = (void*)fixup;
+    return true;
+}
+
 // page fault
 extern "C" void int14_handler(int14_data* d)
 {
@@ -233,79 +264,34 @@ extern "C" void int14_handler(int14_data* d)
 
     auto* mm_area = mms->find(d->l_addr);
     if (!mm_area) [[unlikely]] {
-        if (d->error_code.user) {
-            // user access of address that does not exist
+        // user access of address that does not exist
+        if (d->error_code.user)
             _int14_kill_user();
-        } else {
-            _int14_panic(d->v_eip, d->l_addr, d->error_code);
-        }
+        if (_int14_fixup(d))
+            return;
+        _int14_oops(d);
     }
     if (d->error_code.user && mm_area->attr.system)
         _int14_kill_user();
 
-    page* page = &(*mm_area->pgs)[vptrdiff(d->l_addr, mm_area->start) / PAGE_SIZE];
-    kernel::paccess pa(page->pg_pteidx >> 12);
-    auto pt = (pt_t)pa.ptr();
-    assert(pt);
-    pte_t* pte = *pt + (page->pg_pteidx & 0xfff);
-
-    if (unlikely(d->error_code.present == 0 && !mm_area->mapped_file))
-        _int14_panic(d->v_eip, d->l_addr, d->error_code);
-
-    if (page->attr & PAGE_COW) {
-        // if it is a dying page
-        if (*page->ref_count == 1) {
-            page->attr &= ~PAGE_COW;
-            pte->in.p = 1;
-            pte->in.a = 0;
-            pte->in.rw = mm_area->attr.write;
+    // resolve_fault() would leave the page read-only and we would
+    // fault on it again and again, the kernel writes to such areas
+    // in copy_to_user() and the like, which have their fixups
+    if (d->error_code.write && !mm_area->attr.write) [[unlikely]] {
+        if (d->error_code.user)
+            _int14_kill_user();
+        if (_int14_fixup(d))
             return;
-        }
-        // duplicate the page
-        page_t new_page = __alloc_raw_page();
-
-        {
-            kernel::paccess pdst(new_page), psrc(page->phys_page_id);
-            auto* new_page_data = (char*)pdst.ptr();
-            auto* src = psrc.ptr();
-            assert(new_page_data && src);
-            memcpy(new_page_data, src, PAGE_SIZE);
-        }
-
-        pte->in.page = new_page;
-        pte->in.rw = mm_area->attr.write;
-        pte->in.a = 0;
-
-        --*page->ref_count;
-
-        page->ref_count = types::pnew<types::kernel_ident_allocator>(page->ref_count, 1);
-        page->attr &= ~PAGE_COW;
-        page->phys_page_id = new_page;
+        _int14_oops(d);
     }
 
-    if (page->attr & PAGE_MMAP) {
-        pte->in.p = 1;
-
-        size_t offset = align_down<12>((uint32_t)d->l_addr);
-        offset -= (uint32_t)mm_area->start;
-
-        kernel::paccess pa(page->phys_page_id);
-        auto* data = (char*)pa.ptr();
-        assert(data);
-
-        int n = vfs_read(
-            mm_area->mapped_file,
-            data,
-            PAGE_SIZE,
-            mm_area->file_offset + offset,
-            PAGE_SIZE);
-
-        // TODO: send SIGBUS if offset is greater than real size
-        if (n != PAGE_SIZE)
-            memset(data + n, 0x00, PAGE_SIZE - n);
-
-        page->attr &= ~PAGE_MMAP;
+    if (unlikely(d->error_code.present == 0 && !mm_area->mapped_file)) {
+        if (_int14_fixup(d))
+            return;
+        _int14_oops(d);
     }
+
+    mm_area->resolve_fault(d->l_addr);
 }
 
 extern "C" void irq_handler(int irqno)
@@ -314,6 +300,17 @@ extern "C" void irq_handler(int irqno)
     if (irqno >= 8)
         asm_outb(PORT_PIC1_COMMAND, PIC_EOI);
 
-    for (const auto& handler : s_irq_handlers[irqno])
-        handler();
+    ++s_irq_counts[irqno];
+    kernel::random::add_entropy(irqno);
+
+    {
+        types::read_guard lck(s_irq_handlers_lock);
+        for (const auto& handler : s_irq_handlers[irqno])
+            handler();
+    }
+
+    if (s_need_resched) {
+        s_need_resched = false;
+        schedule();
+    }
 }
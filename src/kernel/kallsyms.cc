@@ -0,0 +1,129 @@
+#include <kernel/errno.h>
+#include <kernel/kallsyms.hpp>
+#include <kernel/process.hpp>
+#include <stdio.h>
+#include <string.h>
+
+// made by make_kallsyms.sh and linked in, sorted by address
+extern "C" const uint32_t kallsyms_num;
+extern "C" const uint32_t kallsyms_addrs[];
+extern "C" const char* const kallsyms_names[];
+extern "C" const char kallsyms_types[];
+
+extern "C" char __text_end[];
+
+namespace kernel::kallsyms {
+
+static int s_kptr_restrict = 1;
+
+// @return the index of the last symbol not after addr, -1 if none
+static int find(uintptr_t addr)
+{
+    int lo = 0, hi = kallsyms_num;
+    while (lo < hi) {
+        int mid = lo + (hi - lo) / 2;
+        if (kallsyms_addrs[mid] <= addr)
+            lo = mid + 1;
+        else
+            hi = mid;
+    }
+    return lo - 1;
+}
+
+bool symbol_for_addr(uintptr_t addr, symbol& sym)
+{
+    int i = find(addr);
+    if (i < 0)
+        return false;
+
+    // the last one ends where the text does
+    uintptr_t end = (uintptr_t)__text_end;
+    if ((uint32_t)i + 1 < kallsyms_num)
+        end = kallsyms_addrs[i + 1];
+    if (addr >= end)
+        return false;
+
+    sym.name = kallsyms_names[i];
+    sym.addr = kallsyms_addrs[i];
+    sym.size = end - sym.addr;
+    sym.type = kallsyms_types[i];
+    return true;
+}
+
+int snprint_symbol(char* buf, std::size_t buf_size, uintptr_t addr)
+{
+    symbol sym;
+    if (!symbol_for_addr(addr, sym))
+        return snprintf(buf, buf_size, "%x", addr);
+
+    return snprintf(buf, buf_size, "%s+%x/%x",
+        sym.name, addr - sym.addr, sym.size);
+}
+
+int kptr_restrict(void)
+{
+    return s_kptr_restrict;
+}
+
+int set_kptr_restrict(int val)
+{
+    if (val < 0 || val > 2)
+        return -EINVAL;
+
+    s_kptr_restrict = val;
+    return 0;
+}
+
+static bool addresses_shown(void)
+{
+    switch (s_kptr_restrict) {
+    case 0:
+        return true;
+    case 1:
+        return current_process && current_process->euid == 0;
+    default:
+        return false;
+    }
+}
+
+ssize_t read(char* buf, std::size_t offset, std::size_t n)
+{
+    bool shown = addresses_shown();
+
+    // the position of the line in the file
+    std::size_t pos = 0;
+    std::size_t done = 0;
+    for (uint32_t i = 0; i < kallsyms_num && done < n; ++i) {
+        // "xxxxxxxx t name\n"
+        char head[11];
+        uint32_t addr = shown ? kallsyms_addrs[i] : 0;
+        for (int j = 7; j >= 0; --j, addr >>= 4)
+            head[j] = "0123456789abcdef"[addr & 0xf];
+        head[8] = ' ';
+        head[9] = kallsyms_types[i];
+        head[10] = ' ';
+
+        const char* name = kallsyms_names[i];
+        std::size_t name_len = strlen(name);
+        std::size_t len = sizeof(head) + name_len + 1;
+
+        if (pos + len <= offset) {
+            pos += len;
+            continue;
+        }
+
+        for (std::size_t j = offset + done - pos; j < len && done < n; ++j) {
+            if (j < sizeof(head))
+                buf[done++] = head[j];
+            else if (j < sizeof(head) + name_len)
+                buf[done++] = name[j - sizeof(head)];
+            else
+                buf[done++] = '\n';
+        }
+        pos += len;
+    }
+
+    return done;
+}
+
+} // namespace kernel::kallsyms
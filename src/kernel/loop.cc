@@ -0,0 +1,216 @@
+#include <algorithm>
+#include <map>
+#include <memory>
+
+#include <kernel/errno.h>
+#include <kernel/loop.hpp>
+#include <kernel/module.hpp>
+#include <kernel/process.hpp>
+#include <kernel/vfs.hpp>
+#include <stdio.h>
+#include <types/ida.hpp>
+
+using namespace kernel::module;
+
+namespace loop {
+
+constexpr uint32_t LOOP_MAJOR = 7;
+// the devices created when the module is loaded
+constexpr int LOOP_INITIAL_DEVICES = 8;
+constexpr int LOOP_MAX_DEVICES = 256;
+
+// a block device reading and writing the file it's bound to
+struct loop_device {
+    fs::node_t node;
+    // nullptr if unbound, the reads and writes in progress
+    // hold a reference so the file stays till they are done
+    std::shared_ptr<fs::file> file;
+
+    ssize_t read(char* buf, std::size_t buf_size, std::size_t offset, std::size_t cnt)
+    {
+        auto backing = file;
+        if (!backing)
+            return -ENXIO;
+
+        return backing->pread(buf, std::min(buf_size, cnt), offset);
+    }
+
+    ssize_t write(const char* buf, std::size_t offset, std::size_t cnt)
+    {
+        auto backing = file;
+        if (!backing)
+            return -ENXIO;
+        if (!backing->flags.write)
+            return -EROFS;
+
+        return backing->pwrite(buf, cnt, offset);
+    }
+
+    int flush()
+    {
+        auto backing = file;
+        if (!backing)
+            return -ENXIO;
+
+        return backing->fsync(false);
+    }
+};
+
+class loop_module : public virtual kernel::module::module {
+private:
+    std::map<uint32_t, loop_device*> devices;
+    types::ida<LOOP_MAX_DEVICES> minors;
+
+    loop_device* find(fs::node_t node)
+    {
+        if (NODE_MAJOR(node) != LOOP_MAJOR)
+            return nullptr;
+
+        auto iter = devices.find(NODE_MINOR(node));
+        if (iter == devices.end())
+            return nullptr;
+        return iter->second;
+    }
+
+    // whether binding dev to the device node makes a cycle
+    bool backed_by(fs::node_t node, const loop_device* dev)
+    {
+        for (auto* cur = find(node); cur; ) {
+            if (cur == dev)
+                return true;
+            if (!cur->file)
+                break;
+
+            auto* ind = cur->file->get_dentry()->ind;
+            if (!S_ISBLK(ind->mode))
+                break;
+            cur = find(ind->fs->inode_getnode(ind));
+        }
+        return false;
+    }
+
+    int set_fd(loop_device* dev, int fd)
+    {
+        if (dev->file)
+            return -EBUSY;
+
+        auto file = current_process->files.get(fd);
+        if (!file)
+            return -EBADF;
+        if (!file->flags.read)
+            return -EBADF;
+
+        auto* dent = file->get_dentry();
+        if (!dent)
+            return -EINVAL;
+
+        auto* ind = dent->ind;
+        if (!S_ISREG(ind->mode) && !S_ISBLK(ind->mode))
+            return -EINVAL;
+        if (S_ISBLK(ind->mode) && backed_by(ind->fs->inode_getnode(ind), dev))
+            return -EINVAL;
+
+        dev->file = std::move(file);
+        return 0;
+    }
+
+    int clr_fd(loop_device* dev)
+    {
+        if (!dev->file)
+            return -ENXIO;
+
+        dev->file.reset();
+        return 0;
+    }
+
+    // @return the minor number of the new device or negative error code
+    int add(void)
+    {
+        long minor = minors.alloc();
+        if (minor < 0)
+            return -ENOSPC;
+
+        auto* dev = new loop_device { fs::make_node(LOOP_MAJOR, minor), nullptr };
+
+        char name[16];
+        snprintf(name, sizeof(name), "loop%d", (int)minor);
+
+        int ret = fs::register_block_device(dev->node, {
+            [dev](char* buf, std::size_t buf_size, std::size_t offset, std::size_t cnt) {
+                return dev->read(buf, buf_size, offset, cnt);
+            },
+            [dev](const char* buf, std::size_t offset, std::size_t cnt) {
+                return dev->write(buf, offset, cnt);
+            },
+            [dev]() -> int {
+                return dev->flush();
+            },
+            [this, dev](unsigned long request, uintptr_t arg) -> int {
+                switch (request) {
+                case LOOP_SET_FD:
+                    return set_fd(dev, (int)arg);
+                case LOOP_CLR_FD:
+                    return clr_fd(dev);
+                default:
+                    return -EINVAL;
+                }
+            }
+        }, name);
+        if (ret != 0) {
+            minors.free(minor);
+            delete dev;
+            return ret;
+        }
+        devices.emplace(minor, dev);
+
+        return minor;
+    }
+
+    int get_free(void)
+    {
+        for (auto& [ minor, dev ] : devices) {
+            if (!dev->file)
+                return minor;
+        }
+        return add();
+    }
+
+public:
+    loop_module() : module("loop") { }
+    ~loop_module()
+    {
+        for (auto& [ minor, dev ] : devices)
+            delete dev;
+    }
+
+    virtual int init() override
+    {
+        for (int i = 0; i < LOOP_INITIAL_DEVICES; ++i) {
+            if (add() < 0)
+                return MODULE_FAILED;
+        }
+
+        auto node = fs::make_node(10, 237);
+        int ret = fs::register_char_device(node, {
+            nullptr, nullptr,
+            [this](unsigned long request, uintptr_t) -> int {
+                switch (request) {
+                case LOOP_CTL_GET_FREE:
+                    return get_free();
+                default:
+                    return -EINVAL;
+                }
+            }
+        }, "loop-control", 0600);
+        if (ret != 0)
+            return MODULE_FAILED;
+
+        return MODULE_SUCCESS;
+    }
+};
+
+} // namespace loop
+
+kernel::module::module* loop_module_init()
+{ return new loop::loop_module(); }
+INTERNAL_MODULE(loop_module_loader, loop_module_init);
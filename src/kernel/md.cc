@@ -0,0 +1,267 @@
+#include <algorithm>
+#include <vector>
+
+#include <kernel/errno.h>
+#include <kernel/log.hpp>
+#include <kernel/md.hpp>
+#include <kernel/module.hpp>
+#include <kernel/user/uaccess.hpp>
+#include <kernel/vfs.hpp>
+#include <stdio.h>
+
+using namespace kernel::module;
+
+namespace md {
+
+constexpr uint32_t MD_MAJOR = 9;
+constexpr std::size_t SECTOR_SIZE = 512;
+// 32M, the chunk size in bytes fits easily
+constexpr uint32_t MAX_CHUNK_SECTORS = 1 << 16;
+
+struct md_device {
+    fs::node_t node;
+    uint32_t level;
+    std::size_t chunk_size;
+    std::vector<fs::node_t> devs;
+    // RAID1: members a write failed on, they are out of date
+    // and never read from again
+    std::vector<bool> degraded;
+
+    void degrade(std::size_t i)
+    {
+        char buf[64];
+        snprintf(buf, sizeof(buf), "[md] md%d: member %d failed, degraded\n",
+            (int)NODE_MINOR(node), (int)i);
+        kmsg(buf);
+
+        degraded[i] = true;
+    }
+
+    // RAID0: map offset on the virtual disk to a member and
+    // the offset on it, returns bytes until the end of the chunk
+    std::size_t map(std::size_t offset, fs::node_t& dev, std::size_t& dev_offset) const
+    {
+        std::size_t chunk = offset / chunk_size;
+        std::size_t pos = offset % chunk_size;
+
+        dev = devs[chunk % devs.size()];
+        dev_offset = (chunk / devs.size()) * chunk_size + pos;
+        return chunk_size - pos;
+    }
+
+    ssize_t read(char* buf, std::size_t buf_size, std::size_t offset, std::size_t cnt)
+    {
+        cnt = std::min(buf_size, cnt);
+
+        if (level == MD_RAID1) {
+            // read from the first member that is up to date and works
+            ssize_t ret = -EIO;
+            for (std::size_t i = 0; i < devs.size(); ++i) {
+                if (degraded[i])
+                    continue;
+                ret = fs::block_device_read(devs[i], buf, cnt, offset, cnt);
+                if (ret >= 0)
+                    break;
+            }
+            return ret;
+        }
+
+        std::size_t orig_cnt = cnt;
+        while (cnt) {
+            fs::node_t dev;
+            std::size_t dev_offset;
+            std::size_t n = std::min(cnt, map(offset, dev, dev_offset));
+
+            ssize_t ret = fs::block_device_read(dev, buf, n, dev_offset, n);
+            if (ret < 0)
+                return ret;
+
+            buf += ret, offset += ret, cnt -= ret;
+            if ((std::size_t)ret != n)
+                break;
+        }
+
+        return orig_cnt - cnt;
+    }
+
+    ssize_t write(const char* buf, std::size_t offset, std::size_t cnt)
+    {
+        if (level == MD_RAID1) {
+            // the write succeeds if any of the mirrors has all of it,
+            // those that don't are left behind and dropped from reads
+            ssize_t ret = -EIO;
+            for (std::size_t i = 0; i < devs.size(); ++i) {
+                if (degraded[i])
+                    continue;
+
+                ssize_t n = fs::block_device_write(devs[i], buf, offset, cnt);
+                if (n != (ssize_t)cnt) {
+                    degrade(i);
+                    if (n < 0 && ret < 0)
+                        ret = n;
+                    continue;
+                }
+                ret = n;
+            }
+            return ret;
+        }
+
+        std::size_t orig_cnt = cnt;
+        while (cnt) {
+            fs::node_t dev;
+            std::size_t dev_offset;
+            std::size_t n = std::min(cnt, map(offset, dev, dev_offset));
+
+            ssize_t ret = fs::block_device_write(dev, buf, dev_offset, n);
+            if (ret < 0)
+                return ret;
+
+            buf += ret, offset += ret, cnt -= ret;
+            if ((std::size_t)ret != n)
+                break;
+        }
+
+        return orig_cnt - cnt;
+    }
+
+    int flush()
+    {
+        int ret = 0;
+        for (std::size_t i = 0; i < devs.size(); ++i) {
+            if (level == MD_RAID1 && degraded[i])
+                continue;
+            int n = fs::block_device_flush(devs[i]);
+            if (n != 0)
+                ret = n;
+        }
+        return ret;
+    }
+};
+
+class md_module : public virtual kernel::module::module {
+private:
+    std::vector<md_device*> devices;
+
+    md_device* find(fs::node_t node)
+    {
+        if (NODE_MAJOR(node) != MD_MAJOR)
+            return nullptr;
+
+        auto minor = NODE_MINOR(node);
+        if (minor >= devices.size())
+            return nullptr;
+        return devices[minor];
+    }
+
+    // whether the device node is, or is made of, the device target
+    bool backed_by(fs::node_t node, fs::node_t target)
+    {
+        if (node == target)
+            return true;
+
+        auto* dev = find(node);
+        if (!dev)
+            return false;
+
+        for (auto member : dev->devs) {
+            if (backed_by(member, target))
+                return true;
+        }
+        return false;
+    }
+
+    int create(const md_create* __user ureq)
+    {
+        md_create req;
+        if (kernel::user::copy_from_user(&req, ureq, sizeof(req)) != 0)
+            return -EFAULT;
+
+        if (req.level != MD_RAID0 && req.level != MD_RAID1)
+            return -EINVAL;
+        if (!req.ndevs || req.ndevs > MD_MAX_DEVICES)
+            return -EINVAL;
+        if (req.level == MD_RAID0) {
+            if (!req.chunk_sectors || req.chunk_sectors > MAX_CHUNK_SECTORS)
+                return -EINVAL;
+            if (req.chunk_sectors & (req.chunk_sectors - 1))
+                return -EINVAL;
+        }
+
+        int minor = devices.size();
+        auto node = fs::make_node(MD_MAJOR, minor);
+
+        // the members might name the device before it exists
+        for (uint32_t i = 0; i < req.ndevs; ++i) {
+            if (backed_by(req.devs[i], node))
+                return -EINVAL;
+
+            for (uint32_t k = 0; k < i; ++k) {
+                if (req.devs[k] == req.devs[i])
+                    return -EINVAL;
+            }
+        }
+
+        auto* dev = new md_device { node, req.level, req.chunk_sectors * SECTOR_SIZE, {}, {} };
+        for (uint32_t i = 0; i < req.ndevs; ++i)
+            dev->devs.push_back(req.devs[i]);
+        dev->degraded.resize(req.ndevs);
+
+        char name[16];
+        snprintf(name, sizeof(name), "md%d", minor);
+
+        int ret = fs::register_block_device(node, {
+            [dev](char* buf, std::size_t buf_size, std::size_t offset, std::size_t cnt) {
+                return dev->read(buf, buf_size, offset, cnt);
+            },
+            [dev](const char* buf, std::size_t offset, std::size_t cnt) {
+                return dev->write(buf, offset, cnt);
+            },
+            [dev]() -> int {
+                return dev->flush();
+            },
+            nullptr,
+        }, name);
+        if (ret != 0) {
+            delete dev;
+            return ret;
+        }
+        devices.push_back(dev);
+
+        return minor;
+    }
+
+public:
+    md_module() : module("md") { }
+    ~md_module()
+    {
+        for (auto* dev : devices)
+            delete dev;
+    }
+
+    virtual int init() override
+    {
+        // use a minor number out of the range of md devices
+        auto node = fs::make_node(MD_MAJOR, 0xffff);
+        int ret = fs::register_char_device(node, {
+            nullptr, nullptr,
+            [this](unsigned long request, uintptr_t arg) -> int {
+                switch (request) {
+                case MD_CREATE:
+                    return create((const md_create*)arg);
+                default:
+                    return -EINVAL;
+                }
+            }
+        }, "md", 0600);
+        if (ret != 0)
+            return MODULE_FAILED;
+
+        return MODULE_SUCCESS;
+    }
+};
+
+} // namespace md
+
+kernel::module::module* md_module_init()
+{ return new md::md_module(); }
+INTERNAL_MODULE(md_module_loader, md_module_init);
@@ -1,3 +1,4 @@
+#include <algorithm>
 #include <cstddef>
 
 #include <asm/port_io.h>
@@ -7,6 +8,7 @@
 #include <kernel/mem.h>
 #include <kernel/mm.hpp>
 #include <kernel/process.hpp>
+#include <kernel/sg_list.hpp>
 #include <kernel/task.h>
 #include <kernel/vga.hpp>
 #include <stdint.h>
@@ -112,11 +114,48 @@ page_t __alloc_raw_page(void)
     return -1;
 }
 
+page_t __alloc_raw_pages(size_t n, page_t limit)
+{
+    const size_t size = std::min((size_t)limit, total_raw_pages());
+    size_t run = 0;
+    for (size_t i = 0; i < size; ++i) {
+        if (mem_bitmap.test(i)) {
+            run = 0;
+            continue;
+        }
+        if (++run == n) {
+            page_t start = i + 1 - n;
+            for (size_t j = start; j <= i; ++j)
+                mem_bitmap.set(j);
+            return start;
+        }
+    }
+    return -1;
+}
+
 void __free_raw_page(page_t pg)
 {
     mem_bitmap.clear(pg);
 }
 
+size_t total_raw_pages(void)
+{
+    size_t cnt = mem_size / PAGE_SIZE;
+    if (cnt > mem_bitmap.size())
+        cnt = mem_bitmap.size();
+    return cnt;
+}
+
+size_t free_raw_pages(void)
+{
+    size_t cnt = 0;
+    for (size_t i = 0; i < total_raw_pages(); ++i) {
+        if (mem_bitmap.test(i) == 0)
+            ++cnt;
+    }
+    return cnt;
+}
+
 page allocate_page(void)
 {
     return page {
@@ -227,6 +266,7 @@ mm_list::mm_list(const mm_list& other)
             area.attr.mapped = 1;
             area.mapped_file = src.mapped_file;
             area.file_offset = src.file_offset;
+            fs::inode_get(area.mapped_file);
         }
 
         paccess pa(m_pd);
@@ -351,6 +391,94 @@ int mm_list::unmap(void* start, size_t len, bool system)
     return GB_OK;
 }
 
+int mm_list::protect(void* start, size_t len, bool write)
+{
+    void* end = vptradd(start, align_up<12>(len));
+
+    if ((ptr_t)start % PAGE_SIZE != 0)
+        return -EINVAL;
+    if ((ptr_t)start >= 0xc0000000 || end > (void*)0xc0000000)
+        return -EINVAL;
+
+    // check the whole range before changing anything
+    for (void* cur = start; cur < end; ) {
+        auto* area = find(cur);
+        if (!area || area->is_kernel_space())
+            return -ENOMEM;
+        cur = area->end();
+    }
+
+    for (void* cur = start; cur < end; ) {
+        auto* area = find(cur);
+
+        // split the area so that only the pages in range are changed
+        if (cur != area->start) {
+            bool inserted;
+            std::tie(std::ignore, inserted) = m_areas.emplace(area->split(cur));
+            assert(inserted);
+            area = find(cur);
+        }
+        if (end < area->end()) {
+            bool inserted;
+            std::tie(std::ignore, inserted) = m_areas.emplace(area->split(end));
+            assert(inserted);
+        }
+
+        area->attr.write = write;
+
+        // copy on write pages stay read-only until they are written
+        for (size_t i = 0; i < area->pgs->size(); ++i) {
+            auto& pg = (*area->pgs)[i];
+            kernel::paccess pa(pg.pg_pteidx >> 12);
+            auto pt = (pt_t)pa.ptr();
+            assert(pt);
+            auto* pte = *pt + (pg.pg_pteidx & 0xfff);
+
+            pte->in.rw = write && !(pg.attr & (PAGE_COW | PAGE_MMAP));
+            invalidate_tlb((uint32_t)area->start + i * PAGE_SIZE);
+        }
+
+        cur = area->end();
+    }
+
+    return GB_OK;
+}
+
+ssize_t mm_list::access(void* addr, void* buf, size_t n, bool write)
+{
+    size_t done = 0;
+    while (done < n) {
+        void* cur = vptradd(addr, done);
+        auto* area = find(cur);
+        if (!area || area->is_kernel_space())
+            break;
+        if (write && !area->attr.write)
+            break;
+
+        auto& pg = (*area->pgs)[vptrdiff(cur, area->start) / PAGE_SIZE];
+        if ((pg.attr & PAGE_MMAP) || (write && (pg.attr & PAGE_COW)))
+            area->resolve_fault(cur);
+
+        size_t offset = (uint32_t)cur & (PAGE_SIZE - 1);
+        size_t len = PAGE_SIZE - offset;
+        if (len > n - done)
+            len = n - done;
+
+        kernel::paccess pa(pg.phys_page_id);
+        auto* data = (char*)pa.ptr();
+        assert(data);
+
+        if (write)
+            memcpy(data + offset, (char*)buf + done, len);
+        else
+            memcpy((char*)buf + done, data + offset, len);
+
+        done += len;
+    }
+
+    return done ? (ssize_t)done : -EFAULT;
+}
+
 mm& mm_list::add_empty_area(void *start, std::size_t page_count,
     uint32_t page_attr, bool w, bool system)
 {
@@ -437,6 +565,74 @@ void mm::append_page(pd_t pd, const page& pg, uint32_t attr, bool priv)
     emplaced.attr = attr;
 }
 
+void mm::resolve_fault(void* addr)
+{
+    page* page = &(*pgs)[vptrdiff(addr, start) / PAGE_SIZE];
+    kernel::paccess pa(page->pg_pteidx >> 12);
+    auto pt = (pt_t)pa.ptr();
+    assert(pt);
+    pte_t* pte = *pt + (page->pg_pteidx & 0xfff);
+
+    // the page might be accessed from another address space, so
+    // the stale tlb entry of the current one, if any, is dropped
+    invalidate_tlb(addr);
+
+    if (page->attr & PAGE_COW) {
+        // if it is a dying page
+        if (*page->ref_count == 1) {
+            page->attr &= ~PAGE_COW;
+            pte->in.p = 1;
+            pte->in.a = 0;
+            pte->in.rw = attr.write;
+            return;
+        }
+        // duplicate the page
+        page_t new_page = __alloc_raw_page();
+
+        {
+            kernel::paccess pdst(new_page), psrc(page->phys_page_id);
+            auto* new_page_data = (char*)pdst.ptr();
+            auto* src = psrc.ptr();
+            assert(new_page_data && src);
+            memcpy(new_page_data, src, PAGE_SIZE);
+        }
+
+        pte->in.page = new_page;
+        pte->in.rw = attr.write;
+        pte->in.a = 0;
+
+        --*page->ref_count;
+
+        page->ref_count = types::pnew<types::kernel_ident_allocator>(page->ref_count, 1);
+        page->attr &= ~PAGE_COW;
+        page->phys_page_id = new_page;
+    }
+
+    if (page->attr & PAGE_MMAP) {
+        pte->in.p = 1;
+
+        size_t offset = align_down<12>((uint32_t)addr);
+        offset -= (uint32_t)start;
+
+        kernel::paccess pa(page->phys_page_id);
+        auto* data = (char*)pa.ptr();
+        assert(data);
+
+        int n = fs::vfs_read(
+            mapped_file,
+            data,
+            PAGE_SIZE,
+            file_offset + offset,
+            PAGE_SIZE);
+
+        // TODO: send SIGBUS if offset is greater than real size
+        if (n != PAGE_SIZE)
+            memset(data + n, 0x00, PAGE_SIZE - n);
+
+        page->attr &= ~PAGE_MMAP;
+    }
+}
+
 mm mm::split(void *addr)
 {
     assert(addr > start && addr < end());
@@ -453,6 +649,8 @@ mm mm::split(void *addr)
         .mapped_file = mapped_file,
         .file_offset = attr.mapped ? file_offset + this_count * PAGE_SIZE : 0,
     };
+    if (attr.mapped)
+        fs::inode_get(mapped_file);
 
     for (size_t i = 0; i < new_count; ++i) {
         newmm.pgs->emplace_back(pgs->back());
@@ -495,6 +693,7 @@ int mmap(
         mm.attr.mapped = 1;
         mm.mapped_file = file;
         mm.file_offset = offset;
+        fs::inode_get(file);
     }
     else {
         // private mapping of zero-filled pages
@@ -506,11 +705,64 @@ int mmap(
     return GB_OK;
 }
 
+static bool s_ro_after_init_protected;
+
+static void set_ro_after_init_writable(bool writable)
+{
+    extern char __ro_after_init_start[];
+    extern char __ro_after_init_end[];
+
+    // page tables of the kernel space are shared by all page directories
+    kernel::paccess pa(EARLY_KERNEL_PD_PAGE);
+    pd_t pd = (pd_t)pa.ptr();
+    assert(pd);
+
+    for (char* addr = __ro_after_init_start; addr < __ro_after_init_end; addr += PAGE_SIZE) {
+        pde_t* pde = *pd + v_to_pdi(addr);
+        assert(pde->in.p);
+
+        kernel::paccess pt_pa(pde->in.pt_page);
+        pt_t pt = (pt_t)pt_pa.ptr();
+        assert(pt);
+
+        (*pt)[v_to_pti(addr)].in.rw = writable;
+        invalidate_tlb(addr);
+    }
+}
+
+void kernel::memory::protect_ro_after_init(void)
+{
+    assert(!s_ro_after_init_protected);
+
+    set_ro_after_init_writable(false);
+    s_ro_after_init_protected = true;
+}
+
+kernel::memory::ro_after_init_unprotect::ro_after_init_unprotect(void)
+    : m_protected(s_ro_after_init_protected)
+{
+    if (m_protected) {
+        set_ro_after_init_writable(true);
+        s_ro_after_init_protected = false;
+    }
+}
+
+kernel::memory::ro_after_init_unprotect::~ro_after_init_unprotect()
+{
+    if (m_protected) {
+        set_ro_after_init_writable(false);
+        s_ro_after_init_protected = true;
+    }
+}
+
 SECTION(".text.kinit")
 void init_mem(void)
 {
     init_mem_layout();
 
+    // before anything else takes the low memory
+    kernel::memory::init_swiotlb();
+
     // TODO: replace early kernel pd
     auto* __kernel_mms = types::_new<types::kernel_ident_allocator,
             kernel::memory::mm_list>(EARLY_KERNEL_PD_PAGE);
@@ -0,0 +1,117 @@
+#include <kernel/kallsyms.hpp>
+#include <kernel/log.hpp>
+#include <kernel/mm.hpp>
+#include <kernel/oops.hpp>
+#include <kernel/process.hpp>
+#include <stdio.h>
+#include <types/lock.hpp>
+
+static unsigned s_tainted;
+// set while an oops is being reported
+static bool s_in_oops;
+
+// the number of frames printed at most
+static constexpr int MAX_BACKTRACE = 16;
+
+unsigned kernel::tainted(void)
+{
+    return s_tainted;
+}
+
+void kernel::add_taint(unsigned flags)
+{
+    s_tainted |= flags;
+}
+
+void kernel::print_backtrace(uint32_t ebp)
+{
+    if (!current_thread)
+        return;
+
+    uint32_t top = current_thread->pkstack;
+    uint32_t bottom = top - THREAD_KERNEL_STACK_SIZE;
+
+    char buf[256];
+    char sym[192];
+    kmsg("Call Trace:\n");
+    for (int i = 0; i < MAX_BACKTRACE; ++i) {
+        // saved ebp and the return address
+        if (ebp < bottom || ebp + 8 > top || (ebp & 3))
+            break;
+
+        auto* frame = (const uint32_t*)ebp;
+        if (!frame[1])
+            break;
+
+        kallsyms::snprint_symbol(sym, sizeof(sym), frame[1]);
+        snprintf(buf, sizeof(buf), " [<%x>] %s\n", frame[1], sym);
+        kmsg(buf);
+
+        // frames go up the stack
+        if (frame[0] <= ebp)
+            break;
+        ebp = frame[0];
+    }
+}
+
+static bool can_kill_current(void)
+{
+    if (!current_process || !current_thread)
+        return false;
+    if (current_process->attr.system || current_process->pid == 1)
+        return false;
+
+    // others might be waiting for the locks forever
+    auto& held = current_thread->held_locks;
+    return !held.rw_spinlocks && !held.mutexes;
+}
+
+void NORETURN kernel::oops(const char* reason, const regs_32* regs, ptr_t eip)
+{
+    if (s_in_oops) {
+        kmsg("***** KERNEL PANIC *****\nfault while handling oops\n");
+        freeze();
+    }
+    s_in_oops = true;
+
+    bool recoverable = can_kill_current();
+    char buf[256];
+
+    snprintf(buf, sizeof(buf), "***** %s *****\n%s\n",
+        recoverable ? "KERNEL OOPS" : "KERNEL PANIC", reason);
+    kmsg(buf);
+
+    if (current_process) {
+        snprintf(buf, sizeof(buf), "pid: %d, comm: %s, tainted: %x\n",
+            current_process->pid,
+            current_thread ? current_thread->name.c_str() : "",
+            s_tainted);
+        kmsg(buf);
+    }
+
+    char sym[192];
+    kallsyms::snprint_symbol(sym, sizeof(sym), eip);
+    snprintf(buf, sizeof(buf), "eip: %s\n", sym);
+    kmsg(buf);
+
+    uint32_t ebp = (uint32_t)__builtin_frame_address(0);
+    if (regs) {
+        snprintf(buf, sizeof(buf),
+            "eax: %x, ebx: %x, ecx: %x, edx: %x\n"
+            "esp: %x, ebp: %x, esi: %x, edi: %x\n",
+            regs->eax, regs->ebx, regs->ecx,
+            regs->edx, regs->esp, regs->ebp,
+            regs->esi, regs->edi);
+        kmsg(buf);
+        ebp = regs->ebp;
+    }
+    print_backtrace(ebp);
+
+    if (!recoverable)
+        freeze();
+
+    add_taint(TAINT_DIE);
+    s_in_oops = false;
+
+    kill_current(-1);
+}
@@ -0,0 +1,166 @@
+#include <assert.h>
+#include <kernel/errno.h>
+#include <kernel/pagecache.hpp>
+#include <string.h>
+#include <types/allocator.hpp>
+
+fs::page_cache::cached_page* fs::page_cache::lookup(inode* ind, size_t pgidx)
+{
+    auto iter = m_inodes.find(ind);
+    if (!iter)
+        return nullptr;
+
+    auto pg = iter->second.find(pgidx);
+    if (!pg)
+        return nullptr;
+
+    // move it to the front
+    m_lru.erase(pg->second.lru);
+    m_lru.emplace_front(ind, pgidx);
+    pg->second.lru = m_lru.begin();
+
+    return &pg->second;
+}
+
+fs::page_cache::cached_page* fs::page_cache::fill(inode* ind, size_t pgidx)
+{
+    size_t offset = pgidx * PAGE_SIZE;
+    size_t len = ind->size - offset;
+    if (len > PAGE_SIZE)
+        len = PAGE_SIZE;
+
+    auto* data = new char[PAGE_SIZE];
+    size_t n = ind->fs->inode_read(ind, data, PAGE_SIZE, offset, len);
+    if ((ssize_t)n < 0) {
+        delete[] data;
+        return nullptr;
+    }
+
+    if (m_cnt >= MAX_CACHED_PAGES)
+        evict_one();
+
+    m_lru.emplace_front(ind, pgidx);
+    auto [ iter, inserted ] = m_inodes[ind].emplace(pgidx,
+        cached_page { data, n < len ? n : len, m_lru.begin() });
+    assert(inserted);
+    ++m_cnt;
+
+    return &iter->second;
+}
+
+fs::page_cache::page_map::iterator fs::page_cache::drop(
+    page_map& pages, page_map::iterator pg)
+{
+    delete[] pg->second.data;
+    m_lru.erase(pg->second.lru);
+    --m_cnt;
+
+    return pages.erase(pg);
+}
+
+void fs::page_cache::evict_one(void)
+{
+    if (m_lru.empty())
+        return;
+
+    auto [ ind, pgidx ] = m_lru.back();
+    auto iter = m_inodes.find(ind);
+    auto& pages = iter->second;
+
+    drop(pages, pages.find(pgidx));
+    if (pages.empty())
+        m_inodes.erase(iter);
+}
+
+size_t fs::page_cache::read(inode* ind, char* buf, size_t buf_size, size_t offset, size_t n)
+{
+    if (offset >= ind->size)
+        return 0;
+
+    if (n > ind->size - offset)
+        n = ind->size - offset;
+    if (n > buf_size)
+        n = buf_size;
+
+    size_t orig_n = n;
+    while (n) {
+        size_t pgidx = offset / PAGE_SIZE;
+        size_t pgoff = offset % PAGE_SIZE;
+
+        auto* pg = lookup(ind, pgidx);
+
+        // the file has grown past the end of the page since it was
+        // cached, the part added is not in it
+        if (pg && pg->len != PAGE_SIZE && pgidx * PAGE_SIZE + pg->len < ind->size) {
+            invalidate(ind, pgidx * PAGE_SIZE, PAGE_SIZE);
+            pg = nullptr;
+        }
+
+        if (!pg)
+            pg = fill(ind, pgidx);
+
+        if (!pg) {
+            if (orig_n != n)
+                break;
+            errno = EIO;
+            return -1U;
+        }
+
+        if (pgoff >= pg->len)
+            break;
+
+        size_t cnt = pg->len - pgoff;
+        if (cnt > n)
+            cnt = n;
+
+        memcpy(buf, pg->data + pgoff, cnt);
+        buf += cnt, offset += cnt, n -= cnt;
+
+        // short page, the file ends here
+        if (pg->len != PAGE_SIZE)
+            break;
+    }
+
+    return orig_n - n;
+}
+
+void fs::page_cache::invalidate(inode* ind, size_t offset, size_t n)
+{
+    auto iter = m_inodes.find(ind);
+    if (!iter || !n)
+        return;
+
+    auto& pages = iter->second;
+    size_t first = offset / PAGE_SIZE;
+    size_t last = (offset + n - 1) / PAGE_SIZE;
+
+    // the range might wrap around
+    if (last < first)
+        last = -1;
+
+    for (auto pg = pages.lower_bound(first); pg != pages.end() && pg->first <= last; )
+        pg = drop(pages, pg);
+
+    if (pages.empty())
+        m_inodes.erase(iter);
+}
+
+void fs::page_cache::invalidate(inode* ind)
+{
+    auto iter = m_inodes.find(ind);
+    if (!iter)
+        return;
+
+    for (auto& [ idx, pg ] : iter->second) {
+        delete[] pg.data;
+        m_lru.erase(pg.lru);
+    }
+    m_cnt -= iter->second.size();
+
+    m_inodes.erase(iter);
+}
+
+void fs::init_page_cache(void)
+{
+    pcache = types::pnew<types::kernel_allocator>(pcache);
+}
@@ -0,0 +1,70 @@
+#include <algorithm>
+
+#include <kernel/errno.h>
+#include <kernel/pidfd.hpp>
+#include <stdio.h>
+#include <string.h>
+#include <types/lock.hpp>
+
+fs::pidfd_file::pidfd_file(pid_t pid, file_flags flags, bool nonblock)
+    : file(0, nullptr, flags), anon_file(ANON_NAME, flags, nonblock), m_pid(pid) { }
+
+bool fs::pidfd_file::exited(int* code)
+{
+    types::lock_guard lck(m_cv.mtx());
+    if (m_exited && code)
+        *code = m_exit_code;
+    return m_exited;
+}
+
+bool fs::pidfd_file::reaped(void)
+{
+    types::lock_guard lck(m_cv.mtx());
+    return m_reaped;
+}
+
+void fs::pidfd_file::handle_exit(pid_t pid, int exit_code)
+{
+    {
+        types::lock_guard lck(m_cv.mtx());
+        if (pid != m_pid || m_exited)
+            return;
+
+        m_exited = true;
+        m_exit_code = exit_code;
+    }
+
+    m_cv.notify_all();
+}
+
+void fs::pidfd_file::handle_reap(pid_t pid)
+{
+    types::lock_guard lck(m_cv.mtx());
+    if (pid == m_pid)
+        m_reaped = true;
+}
+
+size_t fs::pidfd_file::describe(char* buf, size_t buf_size)
+{
+    types::lock_guard lck(m_cv.mtx());
+
+    // the pid means nothing once the process is reaped
+    int n = snprintf(buf, buf_size, "Pid:\t%d\n", m_reaped ? -1 : m_pid);
+    return n > 0 ? std::min((size_t)n, buf_size - 1) : 0;
+}
+
+void fs::pidfd_process_exited(pid_t pid, int exit_code)
+{
+    for (auto* inst : anon_files()) {
+        if (strcmp(inst->name(), pidfd_file::ANON_NAME) == 0)
+            static_cast<pidfd_file*>(inst)->handle_exit(pid, exit_code);
+    }
+}
+
+void fs::pidfd_process_reaped(pid_t pid)
+{
+    for (auto* inst : anon_files()) {
+        if (strcmp(inst->name(), pidfd_file::ANON_NAME) == 0)
+            static_cast<pidfd_file*>(inst)->handle_reap(pid);
+    }
+}
@@ -6,13 +6,20 @@
 #include <asm/port_io.h>
 #include <asm/sys.h>
 #include <assert.h>
+#include <fs/devtmpfs.hpp>
 #include <fs/fat.hpp>
+#include <fs/sysfs.hpp>
+#include <kernel/acct.hpp>
+#include <kernel/binfmt.hpp>
+#include <kernel/hw/timer.h>
+#include <kernel/boottime.hpp>
 #include <kernel/interrupt.h>
 #include <kernel/log.hpp>
 #include <kernel/mem.h>
 #include <kernel/mm.hpp>
 #include <kernel/module.hpp>
 #include <kernel/process.hpp>
+#include <kernel/rseq.hpp>
 #include <kernel/signal.hpp>
 #include <kernel/vfs.hpp>
 #include <stdint.h>
@@ -52,18 +59,62 @@ struct no_irq_guard {
 
 static types::bitmap* pkstack_bmp;
 
+// filled into new stacks to find out how deep they have been used
+constexpr uint32_t KSTACK_POISON = 0x6b6b6b6b;
+
+static inline void kstack_get(uint32_t pkstack)
+{
+    auto& st = kernel::tasks::kstack_stats;
+    if (++st.in_use > st.peak_in_use)
+        st.peak_in_use = st.in_use;
+
+#ifndef NDEBUG
+    auto* p = (uint32_t*)(pkstack - THREAD_KERNEL_STACK_SIZE);
+    for (size_t i = 0; i < THREAD_KERNEL_STACK_SIZE / sizeof(uint32_t); ++i)
+        p[i] = KSTACK_POISON;
+#else
+    (void)pkstack;
+#endif
+}
+
+static inline void kstack_put(uint32_t pkstack)
+{
+    auto& st = kernel::tasks::kstack_stats;
+    --st.in_use;
+
+#ifndef NDEBUG
+    // stacks grow downwards, the poison left at the bottom is unused
+    auto* p = (const uint32_t*)(pkstack - THREAD_KERNEL_STACK_SIZE);
+    size_t unused = 0;
+    while (unused < THREAD_KERNEL_STACK_SIZE / sizeof(uint32_t)
+        && p[unused] == KSTACK_POISON)
+        ++unused;
+
+    if (!unused)
+        kmsg("[kernel] warning: kernel stack overflow detected\n");
+
+    size_t depth = THREAD_KERNEL_STACK_SIZE - unused * sizeof(uint32_t);
+    if (depth > st.max_depth)
+        st.max_depth = depth;
+#else
+    (void)pkstack;
+#endif
+}
+
 void kernel::tasks::thread::alloc_kstack(void)
 {
     static int __allocated;
     if (!pkstack_bmp)
         pkstack_bmp = new types::bitmap((0x1000000 - 0xc00000) / 0x2000);
 
+    // reuse the stacks of the dead threads first
     for (int i = 0; i < __allocated; ++i) {
         if (pkstack_bmp->test(i) == 0) {
             pkstack = 0xffc00000 + THREAD_KERNEL_STACK_SIZE * (i + 1);
             esp = reinterpret_cast<uint32_t*>(pkstack);
 
             pkstack_bmp->set(i);
+            kstack_get(pkstack);
             return;
         }
     }
@@ -84,22 +135,29 @@ void kernel::tasks::thread::alloc_kstack(void)
 
     pkstack_bmp->set(__allocated);
     ++__allocated;
+
+    ++kstack_stats.allocated;
+    kstack_get(pkstack);
 }
 
 void kernel::tasks::thread::free_kstack(uint32_t p)
 {
+    kstack_put(p);
+
     p -= 0xffc00000;
     p /= THREAD_KERNEL_STACK_SIZE;
     p -= 1;
     pkstack_bmp->clear(p);
 }
 
-// TODO: file opening permissions check
 int filearr::open(const process &current,
     const types::path& filepath, int flags, mode_t mode)
 {
-    auto* dentry = fs::vfs_open(*current.root, filepath);
+    auto* dentry = fs::vfs_open(*current.root, filepath, !(flags & O_NOFOLLOW));
+    if (!dentry && errno != ENOENT)
+        return -errno;
 
+    bool created = false;
     if (flags & O_CREAT) {
         if (!dentry) {
             // create file
@@ -110,25 +168,28 @@ int filearr::open(const process &current,
             auto* parent = fs::vfs_open(*current.root, parent_path);
             if (!parent)
                 return -EINVAL;
+            // TODO: create the target of dangling symbolic links
+            if (parent->find(filename))
+                return -ENOENT;
             int ret = fs::vfs_mkfile(parent, filename.c_str(), mode);
             if (ret != GB_OK)
                 return ret;
             dentry = fs::vfs_open(*current.root, filepath);
             assert(dentry);
+            created = true;
         } else {
             // file already exists
             if (flags & O_EXCL)
                 return -EEXIST;
-
-            if (flags & O_TRUNC) {
-                // TODO: truncate file
-            }
         }
     } else {
         if (!dentry)
             return -ENOENT;
     }
 
+    if (S_ISLNK(dentry->ind->mode))
+        return -ELOOP;
+
     // check whether dentry is a file if O_DIRECTORY is set
     if (flags & O_DIRECTORY) {
         if (!S_ISDIR(dentry->ind->mode))
@@ -138,15 +199,47 @@ int filearr::open(const process &current,
             return -EISDIR;
     }
 
+    // the mode of a file just created applies to later opens only
+    if (!created) {
+        int may = 0;
+        if (!(flags & O_WRONLY))
+            may |= MAY_READ;
+        if (flags & (O_WRONLY | O_RDWR | O_TRUNC))
+            may |= MAY_WRITE;
+
+        int ret = fs::vfs_permission(dentry->ind, may);
+        if (ret != GB_OK)
+            return ret;
+    }
+
+    if ((flags & O_TRUNC) && (flags & (O_WRONLY | O_RDWR))
+        && S_ISREG(dentry->ind->mode)) {
+        int ret = fs::vfs_truncate(dentry->ind, 0);
+        if (ret != GB_OK)
+            return ret;
+    }
+
+    fs::file::file_flags fflags {
+        .read = !(flags & O_WRONLY),
+        .write = !!(flags & (O_WRONLY | O_RDWR)),
+        .close_on_exec = !!(flags & O_CLOEXEC),
+        .direct = !!(flags & O_DIRECT),
+        .sync = !!(flags & (O_SYNC | O_DSYNC)),
+        .append = !!(flags & O_APPEND),
+    };
+
+    fs::file* file;
+    if (S_ISFIFO(dentry->ind->mode)) {
+        file = fs::fifo_open(dentry, fflags, flags & O_NONBLOCK);
+        if (!file)
+            return -errno;
+    }
+    else {
+        file = new fs::regular_file(dentry, fflags, 0);
+    }
+
     int fd = next_fd();
-    auto [ _, inserted ] = arr.emplace(fd, std::shared_ptr<fs::file> {
-        new fs::regular_file(dentry->parent, {
-            .read = !(flags & O_WRONLY),
-            .write = !!(flags & (O_WRONLY | O_RDWR)),
-            .close_on_exec = !!(flags & O_CLOEXEC),
-            }, 0, dentry->ind
-        )
-    });
+    auto [ _, inserted ] = arr.emplace(fd, std::shared_ptr<fs::file> { file });
     assert(inserted);
     return fd;
 }
@@ -155,14 +248,38 @@ process::process(const process& parent, pid_t pid)
     : mms { parent.mms }, attr { parent.attr } , pwd { parent.pwd }
     , signals { parent.signals } , pid { pid }
     , ppid { parent.pid } , pgid { parent.pgid } , sid { parent.sid }
+    , uid { parent.uid }, euid { parent.euid }, suid { parent.suid }
+    , gid { parent.gid }, egid { parent.egid }, sgid { parent.sgid }
     , control_tty { parent.control_tty }, root { parent.root }
+    , cmdline { parent.cmdline }, exe { parent.exe }
+    , start_ticks { current_ticks() }, forked_only { true }
 {
     this->files.dup_all(parent.files);
 }
 
 process::process(pid_t pid, pid_t ppid)
     : attr { .system = true }
-    , pwd { "/" } , pid { pid } , ppid { ppid } { }
+    , pwd { "/" } , pid { pid } , ppid { ppid }
+    , start_ticks { current_ticks() } { }
+
+std::vector<char> process::pack_args(const char* const* argv)
+{
+    std::vector<char> args;
+    for (; *argv; ++argv) {
+        for (const char* p = *argv; *p; ++p)
+            args.push_back(*p);
+        args.push_back(0);
+    }
+    return args;
+}
+
+void process::set_exec_info(const fs::vfs::dentry* exec, std::vector<char> args)
+{
+    exe.clear();
+    exec->path(*fs::fs_root, exe);
+    cmdline = std::move(args);
+    forked_only = false;
+}
 
 void proclist::kill(pid_t pid, int exit_code)
 {
@@ -177,6 +294,13 @@ void proclist::kill(pid_t pid, int exit_code)
     // write back mmap'ped files and close them
     proc.files.close_all();
 
+    // the session loses its terminal with the leader
+    auto* ctty = proc.control_tty;
+    if (proc.pid == proc.sid && ctty && ctty->get_session() == proc.sid)
+        ctty->hangup();
+
+    kernel::acct::record_exit(proc, exit_code);
+
     // unmap all user memory areas
     proc.mms.clear_user();
 
@@ -221,6 +345,8 @@ void proclist::kill(pid_t pid, int exit_code)
         parent.waitlist.push_back({ pid, exit_code });
     }
     parent.cv_wait.notify();
+
+    fs::pidfd_process_exited(pid, exit_code);
 }
 
 void kernel_threadd_main(void)
@@ -307,6 +433,43 @@ static void create_kthreadd_process()
     readythds->push(&thd);
 }
 
+// the pseudo filesystems are required, we can't go on without them
+static int mount_at(const char* path, int (*mount)(fs::vfs::dentry*))
+{
+    auto* mnt = fs::vfs_open(*fs::fs_root, path);
+    assert(mnt);
+
+    int ret = mount(mnt);
+    assert(ret == GB_OK);
+
+    return ret;
+}
+
+static int init_devtmpfs(void)
+{
+    return mount_at("/dev", fs::devtmpfs::mount);
+}
+INITCALL(1, devtmpfs, init_devtmpfs);
+
+static int init_sysfs(void)
+{
+    return mount_at("/sys", fs::sysfs::mount);
+}
+INITCALL(1, sysfs, init_sysfs);
+
+static int init_binfmt_misc(void)
+{
+    kernel::binfmt::init_misc();
+    return GB_OK;
+}
+INITCALL(2, binfmt_misc, init_binfmt_misc);
+
+static int init_procfs(void)
+{
+    return mount_at("/proc", fs::procfs::mount);
+}
+INITCALL(3, procfs, init_procfs);
+
 void NORETURN _kernel_init(void)
 {
     create_kthreadd_process();
@@ -319,12 +482,21 @@ void NORETURN _kernel_init(void)
     // interrupt enabled
     // ------------------------------------------
 
+    kernel::kinit::boot_phase_calibrate();
+
+    kernel::kinit::run_initcalls();
+
+    kernel::kinit::boot_phase("drivers");
+
+    kernel::memory::protect_ro_after_init();
+
     // load kmods
     for (auto loader = kernel::module::kmod_loaders_start; *loader; ++loader) {
         auto* mod = (*loader)();
         if (!mod)
             continue;
 
+        kernel::memory::ro_after_init_unprotect unprotect;
         auto ret = insmod(mod);
         if (ret == kernel::module::MODULE_SUCCESS)
             continue;
@@ -335,6 +507,8 @@ void NORETURN _kernel_init(void)
         kmsg(buf);
     }
 
+    kernel::kinit::boot_phase("rootfs mount");
+
     // TODO: parse kernel parameters
     auto* drive = fs::vfs_open(*fs::fs_root, "/dev/sda1");
     assert(drive);
@@ -347,10 +521,13 @@ void NORETURN _kernel_init(void)
     current_process->attr.system = 0;
     current_thread->attr.system = 0;
 
+    kernel::kinit::boot_phase("init exec");
+
     const char* argv[] = { "/mnt/init", "/mnt/sh", nullptr };
     const char* envp[] = { nullptr };
 
     types::elf::elf32_load_data d;
+    d.filename = argv[0];
     d.argv = argv;
     d.envp = envp;
     d.system = false;
@@ -361,8 +538,12 @@ void NORETURN _kernel_init(void)
         freeze();
     }
 
-    ret = types::elf::elf32_load(&d);
+    auto args = process::pack_args(argv);
+    ret = kernel::binfmt::exec(&d);
     assert(ret == GB_OK);
+    current_process->set_exec_info(d.exec_dent, std::move(args));
+
+    kernel::kinit::report_boot_phases();
 
     asm volatile(
         "movw $0x23, %%ax\n"
@@ -412,6 +593,7 @@ void NORETURN init_scheduler(void)
 
     current_process = &init;
     current_thread = &thd;
+    types::current_held_locks = &thd.held_locks;
     readythds->push(current_thread);
 
     tss.ss0 = KERNEL_DATA_SEGMENT;
@@ -467,10 +649,14 @@ bool schedule()
     curr_thd = current_thread;
 
     current_thread = thd;
+    types::current_held_locks = &thd->held_locks;
     tss.esp0 = current_thread->pkstack;
+    ++kernel::tasks::stats.ctx_switches;
 
     asm_ctx_switch(&curr_thd->esp, thd->esp);
 
+    kernel::rseq::handle_preempt();
+
 _end:
 
     check_signal();
@@ -485,6 +671,10 @@ void NORETURN schedule_noreturn(void)
 
 void NORETURN freeze(void)
 {
+    // nothing drains the output with the interrupts disabled
+    if (console)
+        console->flush();
+
     asm_cli();
     asm_hlt();
     for (;;)
@@ -497,6 +687,30 @@ void NORETURN kill_current(int exit_code)
     schedule_noreturn();
 }
 
+// whether the current process may signal proc,
+// root may signal all the processes, others only those of the same user
+bool may_signal(const process& proc)
+{
+    auto* cur = current_process;
+    if (cur->euid == 0)
+        return true;
+
+    return cur->uid == proc.uid || cur->uid == proc.euid
+        || cur->euid == proc.uid || cur->euid == proc.euid;
+}
+
+// whether the current process may access the memory of proc,
+// a process that has switched users by a setuid exec is left to root
+bool may_access(const process& proc)
+{
+    auto* cur = current_process;
+    if (cur->euid == 0)
+        return true;
+
+    return cur->uid == proc.uid && cur->uid == proc.euid
+        && cur->uid == proc.suid;
+}
+
 void check_signal()
 {
     switch (current_process->signals.pop()) {
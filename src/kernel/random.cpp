@@ -0,0 +1,92 @@
+#include <kernel/crypto/aes.hpp>
+#include <kernel/boottime.hpp>
+#include <kernel/random.hpp>
+#include <string.h>
+#include <types/lock.hpp>
+
+namespace kernel::random {
+
+static constexpr std::size_t POOL_WORDS = 8;
+
+static uint32_t s_pool[POOL_WORDS];
+static std::size_t s_pool_pos;
+static uint64_t s_counter;
+
+static constexpr uint32_t rotl(uint32_t val, int n)
+{
+    return (val << n) | (val >> (32 - n));
+}
+
+// interrupts MUST be disabled
+static void mix(uint32_t val)
+{
+    uint32_t prev = s_pool[(s_pool_pos + POOL_WORDS - 1) % POOL_WORDS];
+    uint32_t& word = s_pool[s_pool_pos];
+
+    word = rotl(word ^ val, 7) + rotl(prev, 13);
+    word *= 0x9e3779b1;
+
+    s_pool_pos = (s_pool_pos + 1) % POOL_WORDS;
+}
+
+void add_entropy(uint32_t val)
+{
+    uint32_t flags = types::irq_save();
+    mix(val ^ (uint32_t)kinit::rdtsc());
+    types::irq_restore(flags);
+}
+
+void get_random_bytes(void* buf, std::size_t n)
+{
+    uint8_t key[crypto::AES_BLOCK_SIZE];
+    uint64_t counter;
+
+    uint32_t flags = types::irq_save();
+    mix((uint32_t)kinit::rdtsc());
+    // fold the pool into the key
+    for (std::size_t i = 0; i < sizeof(key) / 4; ++i) {
+        uint32_t word = s_pool[i] ^ rotl(s_pool[i + sizeof(key) / 4], 16);
+        memcpy(key + i * 4, &word, 4);
+    }
+    counter = s_counter;
+    s_counter += n / crypto::AES_BLOCK_SIZE + 2;
+    types::irq_restore(flags);
+
+    crypto::aes cipher;
+    cipher.set_key(key, sizeof(key));
+
+    auto* out = (uint8_t*)buf;
+    uint8_t block[crypto::AES_BLOCK_SIZE];
+    while (n) {
+        memset(block, 0x00, sizeof(block));
+        memcpy(block, &counter, sizeof(counter));
+        ++counter;
+        cipher.encrypt(block);
+
+        std::size_t len = n < sizeof(block) ? n : sizeof(block);
+        memcpy(out, block, len);
+        out += len, n -= len;
+    }
+
+    // rekey so that the output given out can't be derived again
+    memset(block, 0x00, sizeof(block));
+    memcpy(block, &counter, sizeof(counter));
+    cipher.encrypt(block);
+
+    flags = types::irq_save();
+    for (std::size_t i = 0; i < sizeof(block) / 4; ++i) {
+        uint32_t word;
+        memcpy(&word, block + i * 4, 4);
+        mix(word);
+    }
+    types::irq_restore(flags);
+}
+
+uint32_t get_random_u32(void)
+{
+    uint32_t val;
+    get_random_bytes(&val, sizeof(val));
+    return val;
+}
+
+} // namespace kernel::random
@@ -0,0 +1,82 @@
+#include <kernel/errno.h>
+#include <kernel/interrupt.h>
+#include <kernel/process.hpp>
+#include <kernel/rseq.hpp>
+#include <types/status.h>
+
+int kernel::rseq::set_area(::rseq* __user area, uint32_t len, int flags, uint32_t sig)
+{
+    auto& thd = *current_thread;
+
+    if (flags & RSEQ_FLAG_UNREGISTER) {
+        if (flags & ~RSEQ_FLAG_UNREGISTER)
+            return -EINVAL;
+        if (area != thd.rseq_area)
+            return -EINVAL;
+        if (sig != thd.rseq_sig)
+            return -EPERM;
+
+        thd.rseq_area = nullptr;
+        return GB_OK;
+    }
+
+    if (flags)
+        return -EINVAL;
+    if (len != RSEQ_SIZE || ((uint32_t)area & (RSEQ_SIZE - 1)))
+        return -EINVAL;
+
+    if (thd.rseq_area)
+        return (area == thd.rseq_area && sig == thd.rseq_sig) ? -EBUSY : -EINVAL;
+
+    // we have only one cpu
+    ::rseq init {};
+    init.cpu_id_start = 0;
+    init.cpu_id = 0;
+    if (current_process->mms.access(area, &init, sizeof(init), true) != sizeof(init))
+        return -EFAULT;
+
+    thd.rseq_area = area;
+    thd.rseq_sig = sig;
+
+    return GB_OK;
+}
+
+void kernel::rseq::handle_preempt(void)
+{
+    auto& thd = *current_thread;
+    auto& mms = current_process->mms;
+
+    if (!thd.rseq_area)
+        return;
+
+    // the user context is saved at the top of the kernel stack
+    auto* frame = (interrupt_stack*)thd.pkstack - 1;
+    if (!(frame->cs & 3))
+        return;
+
+    uint64_t cs_ptr;
+    if (mms.access(&thd.rseq_area->rseq_cs, &cs_ptr, sizeof(cs_ptr), false) != sizeof(cs_ptr))
+        kill_current(-1);
+    if (!cs_ptr)
+        return;
+
+    rseq_cs cs;
+    if (mms.access((void*)(uint32_t)cs_ptr, &cs, sizeof(cs), false) != sizeof(cs))
+        kill_current(-1);
+
+    uint32_t ip = (uint32_t)frame->v_eip;
+    if (ip < cs.start_ip || ip - cs.start_ip >= cs.post_commit_offset)
+        return;
+
+    // the abort handler has to be preceded by the registered signature
+    uint32_t sig;
+    if (mms.access((void*)(uint32_t)(cs.abort_ip - 4), &sig, sizeof(sig), false) != sizeof(sig))
+        kill_current(-1);
+    if (sig != thd.rseq_sig)
+        kill_current(-1);
+
+    cs_ptr = 0;
+    mms.access(&thd.rseq_area->rseq_cs, &cs_ptr, sizeof(cs_ptr), true);
+
+    frame->v_eip = (void*)(uint32_t)cs.abort_ip;
+}
@@ -0,0 +1,263 @@
+#include <algorithm>
+
+#include <assert.h>
+#include <kernel/event/evtqueue.hpp>
+#include <kernel/mm.hpp>
+#include <kernel/sg_list.hpp>
+#include <string.h>
+#include <types/lock.hpp>
+
+namespace kernel::memory {
+
+// 256KiB below 16MiB, where even isa dma engines can reach
+static constexpr std::size_t SWIOTLB_PAGES = 64;
+static constexpr page_t SWIOTLB_LIMIT = 0x1000;
+
+static page_t s_swiotlb_start = -1;
+static bool s_swiotlb_used[SWIOTLB_PAGES];
+// protects s_swiotlb_used, notified when slots are freed
+static kernel::cond_var s_swiotlb_cv;
+
+void init_swiotlb(void)
+{
+    // without the pool, the devices get no bounce pages and the
+    // transfers they can't address fail
+    s_swiotlb_start = __alloc_raw_pages(SWIOTLB_PAGES, SWIOTLB_LIMIT);
+}
+
+// take n contiguous slots, waiting for them if the pool is busy
+//
+// @return the first slot, or -1 if the pool can never satisfy the request
+static std::size_t swiotlb_alloc(std::size_t n, const dma_limits& limits)
+{
+    if (s_swiotlb_start == (page_t)-1 || n > SWIOTLB_PAGES)
+        return -1;
+    if (((s_swiotlb_start + SWIOTLB_PAGES) << 12) - 1 > limits.max_addr)
+        return -1;
+
+    types::lock_guard lck(s_swiotlb_cv.mtx());
+    for (;;) {
+        std::size_t run = 0;
+        for (std::size_t i = 0; i < SWIOTLB_PAGES; ++i) {
+            if (s_swiotlb_used[i]) {
+                run = 0;
+                continue;
+            }
+            if (++run == n) {
+                std::size_t start = i + 1 - n;
+                for (std::size_t j = start; j <= i; ++j)
+                    s_swiotlb_used[j] = true;
+                return start;
+            }
+        }
+
+        // the slots are freed by transfers that are already running,
+        // so there is no point in giving up on signals
+        s_swiotlb_cv.wait(s_swiotlb_cv.mtx());
+    }
+}
+
+static void swiotlb_free(std::size_t slot, std::size_t n)
+{
+    {
+        types::lock_guard lck(s_swiotlb_cv.mtx());
+        for (std::size_t i = slot; i < slot + n; ++i)
+            s_swiotlb_used[i] = false;
+    }
+    s_swiotlb_cv.notify_all();
+}
+
+uint32_t virt_to_phys(void* addr)
+{
+    assert(addr >= (void*)0xc0000000);
+
+    // page tables of the kernel space are shared by all page directories
+    kernel::paccess pa(EARLY_KERNEL_PD_PAGE);
+    pd_t pd = (pd_t)pa.ptr();
+    assert(pd);
+
+    pde_t* pde = *pd + v_to_pdi(addr);
+    assert(pde->in.p);
+
+    kernel::paccess pt_pa(pde->in.pt_page);
+    pt_t pt = (pt_t)pt_pa.ptr();
+    assert(pt);
+
+    pte_t* pte = *pt + v_to_pti(addr);
+    assert(pte->in.p);
+
+    return (pte->in.page << 12) | ((uint32_t)addr & (PAGE_SIZE - 1));
+}
+
+static void add_segment(std::vector<sg_segment>& segs, uint32_t phys, uint32_t len)
+{
+    if (!segs.empty()) {
+        auto& last = segs.back();
+        if (last.phys + last.len == phys) {
+            last.len += len;
+            return;
+        }
+    }
+    segs.push_back({ phys, len });
+}
+
+void sg_list::add_kernel_buf(void* buf, std::size_t len, bool dev_writes)
+{
+    auto* p = (char*)buf;
+    while (len) {
+        std::size_t in_page = PAGE_SIZE - ((uint32_t)p & (PAGE_SIZE - 1));
+        std::size_t n = len < in_page ? len : in_page;
+
+        // the heap pages are mapped to the empty page copy on write
+        // before they are written to, and the device bypasses the mmu
+        if (dev_writes)
+            *(volatile char*)p = *(volatile char*)p;
+
+        add_segment(m_segs, virt_to_phys(p), n);
+        m_len += n;
+
+        p += n, len -= n;
+    }
+}
+
+void sg_list::add_page(page_t pg, uint32_t offset, uint32_t len)
+{
+    assert(offset + len <= PAGE_SIZE);
+    add_segment(m_segs, (pg << 12) + offset, len);
+    m_len += len;
+}
+
+void sg_list::append(const sg_list& other)
+{
+    for (const auto& seg : other.m_segs)
+        add_segment(m_segs, seg.phys, seg.len);
+    m_len += other.m_len;
+}
+
+bool sg_list::fits(const dma_limits& limits) const
+{
+    std::size_t segs = 0;
+    for (const auto& seg : m_segs) {
+        if ((seg.phys | seg.len) & (limits.align - 1))
+            return false;
+        if (seg.phys + (seg.len - 1) > limits.max_addr)
+            return false;
+        segs += (seg.len + limits.max_seg_len - 1) / limits.max_seg_len;
+    }
+    return segs <= limits.max_segs;
+}
+
+void sg_list::split(const dma_limits& limits)
+{
+    std::vector<sg_segment> segs;
+    for (auto seg : m_segs) {
+        while (seg.len > limits.max_seg_len) {
+            segs.push_back({ seg.phys, limits.max_seg_len });
+            seg.phys += limits.max_seg_len;
+            seg.len -= limits.max_seg_len;
+        }
+        segs.push_back(seg);
+    }
+    m_segs = std::move(segs);
+}
+
+void sg_copy(const sg_list& dst, const sg_list& src)
+{
+    auto d = dst.segments().begin(), s = src.segments().begin();
+    uint32_t d_off = 0, s_off = 0;
+
+    while (d != dst.segments().end() && s != src.segments().end()) {
+        uint32_t d_phys = d->phys + d_off, s_phys = s->phys + s_off;
+
+        // copy within a single page of each side at a time
+        uint32_t n = std::min(d->len - d_off, s->len - s_off);
+        n = std::min(n, PAGE_SIZE - (d_phys & (PAGE_SIZE - 1)));
+        n = std::min(n, PAGE_SIZE - (s_phys & (PAGE_SIZE - 1)));
+
+        {
+            kernel::paccess pd(d_phys >> 12), ps(s_phys >> 12);
+            memcpy((char*)pd.ptr() + (d_phys & (PAGE_SIZE - 1)),
+                (char*)ps.ptr() + (s_phys & (PAGE_SIZE - 1)), n);
+        }
+
+        if ((d_off += n) == d->len)
+            ++d, d_off = 0;
+        if ((s_off += n) == s->len)
+            ++s, s_off = 0;
+    }
+}
+
+bounce_buffer::bounce_buffer(const sg_list& orig, const dma_limits& limits)
+    : m_orig(orig)
+{
+    std::size_t len = orig.len();
+    if (!len)
+        return;
+
+    uint32_t ram_end = ((total_raw_pages() - 1) << 12) + (PAGE_SIZE - 1);
+    if (ram_end <= limits.max_addr) {
+        while (len) {
+            page_t pg = __alloc_raw_page();
+
+            uint32_t n = len < PAGE_SIZE ? len : PAGE_SIZE;
+            m_pages.push_back(pg);
+            m_sg.add_page(pg, 0, n);
+            len -= n;
+        }
+    } else {
+        std::size_t npages = (len + PAGE_SIZE - 1) / PAGE_SIZE;
+        m_slot = swiotlb_alloc(npages, limits);
+        if (m_slot == (std::size_t)-1)
+            return;
+        m_nslots = npages;
+
+        for (std::size_t i = 0; i < npages; ++i) {
+            uint32_t n = len < PAGE_SIZE ? len : PAGE_SIZE;
+            m_sg.add_page(s_swiotlb_start + m_slot + i, 0, n);
+            len -= n;
+        }
+    }
+    m_sg.split(limits);
+}
+
+bounce_buffer::~bounce_buffer()
+{
+    for (auto pg : m_pages)
+        __free_raw_page(pg);
+    if (m_slot != (std::size_t)-1)
+        swiotlb_free(m_slot, m_nslots);
+}
+
+void bounce_buffer::fill(void)
+{
+    sg_copy(m_sg, m_orig);
+}
+
+void bounce_buffer::drain(void)
+{
+    sg_copy(m_orig, m_sg);
+}
+
+dma_mapping::dma_mapping(const sg_list& sg, const dma_limits& limits, bool dev_writes)
+    : m_sg(sg)
+{
+    if (sg.fits(limits))
+        return;
+
+    m_bounce = new bounce_buffer(sg, limits);
+    if (m_bounce->valid() && !dev_writes)
+        m_bounce->fill();
+}
+
+dma_mapping::~dma_mapping()
+{
+    delete m_bounce;
+}
+
+void dma_mapping::sync_for_cpu(void)
+{
+    if (m_bounce && m_bounce->valid())
+        m_bounce->drain();
+}
+
+} // namespace kernel::memory
@@ -2,19 +2,29 @@
 #include <asm/sys.h>
 #include <assert.h>
 #include <bits/ioctl.h>
+#include <sys/kcmp.h>
 #include <sys/prctl.h>
 #include <sys/mman.h>
+#include <sys/pidfd.h>
 #include <sys/stat.h>
+#include <sys/uio.h>
+#include <sys/wait.h>
 #include <time.h>
 #include <kernel/user/thread_local.hpp>
+#include <kernel/acct.hpp>
+#include <kernel/binfmt.hpp>
 #include <kernel/errno.h>
+#include <kernel/inotify.hpp>
+#include <kernel/pidfd.hpp>
 #include <kernel/interrupt.h>
 #include <kernel/log.hpp>
 #include <kernel/mem.h>
 #include <kernel/mm.hpp>
 #include <kernel/process.hpp>
+#include <kernel/rseq.hpp>
 #include <kernel/syscall.hpp>
 #include <kernel/tty.hpp>
+#include <kernel/user/uaccess.hpp>
 #include <kernel/vfs.hpp>
 #include <kernel/hw/timer.h>
 #include <stdint.h>
@@ -36,12 +46,15 @@
 #define SYSCALL_ARG5(type, name) type name = (type)((data)->s_regs.edi)
 #define SYSCALL_ARG6(type, name) type name = (type)((data)->s_regs.ebp)
 
-#define SYSCALL_HANDLERS_SIZE (404)
+#define SYSCALL_HANDLERS_SIZE (440)
 syscall_handler syscall_handlers[SYSCALL_HANDLERS_SIZE];
 
 extern "C" void _syscall_stub_fork_return(void);
 int _syscall_fork(interrupt_stack* data)
 {
+    if (!procs->pid_available())
+        return -EAGAIN;
+
     auto& newproc = procs->copy_from(*current_process);
     auto [ iter_newthd, inserted ] = newproc.thds.emplace(*current_thread, newproc.pid);
     assert(inserted);
@@ -110,6 +123,46 @@ int _syscall_read(interrupt_stack* data)
     return file->read(buf, n);
 }
 
+// @param pos_l, pos_h: lower and higher 32 bits of the offset
+int _syscall_pread64(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, fd);
+    SYSCALL_ARG2(char* __user, buf);
+    SYSCALL_ARG3(size_t, n);
+    SYSCALL_ARG4(uint32_t, pos_l);
+    SYSCALL_ARG5(uint32_t, pos_h);
+
+    auto* file = current_process->files[fd];
+    if (!file)
+        return -EBADF;
+
+    off64_t offset = ((off64_t)pos_h << 32) | pos_l;
+    if (offset < 0 || offset > 0xffffffff)
+        return -EINVAL;
+
+    return file->pread(buf, n, offset);
+}
+
+// @param pos_l, pos_h: lower and higher 32 bits of the offset
+int _syscall_pwrite64(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, fd);
+    SYSCALL_ARG2(const char* __user, buf);
+    SYSCALL_ARG3(size_t, n);
+    SYSCALL_ARG4(uint32_t, pos_l);
+    SYSCALL_ARG5(uint32_t, pos_h);
+
+    auto* file = current_process->files[fd];
+    if (!file)
+        return -EBADF;
+
+    off64_t offset = ((off64_t)pos_h << 32) | pos_l;
+    if (offset < 0 || offset > 0xffffffff)
+        return -EINVAL;
+
+    return file->pwrite(buf, n, offset);
+}
+
 // TODO: sleep seconds
 int _syscall_sleep(interrupt_stack*)
 {
@@ -120,17 +173,73 @@ int _syscall_sleep(interrupt_stack*)
     return 0;
 }
 
+// paths longer than this are refused with ENAMETOOLONG
+static constexpr size_t PATH_MAX = 4096;
+
+// copy the path string in from the user
+// @return 0, -EFAULT or -ENAMETOOLONG
+static int copy_path(const char* __user upath, std::vector<char>& out)
+{
+    out.resize(PATH_MAX);
+    long len = kernel::user::strncpy_from_user(out.data(), upath, out.size());
+    if (len < 0)
+        return len;
+    if ((size_t)len == out.size())
+        return -ENAMETOOLONG;
+    return 0;
+}
+
+// the strings of argv and envp together may take no more than this
+static constexpr size_t ARG_MAX = 128 * 1024;
+
+// copy the null terminated array of strings in from the user,
+// a null array is taken as an empty one
+// @return 0, -EFAULT or -E2BIG
+static int copy_strings(const char* const __user* uarr,
+    std::vector<types::string<>>& out, size_t& total)
+{
+    if (!uarr)
+        return 0;
+
+    std::vector<char> buf(PAGE_SIZE);
+    for (;; ++uarr) {
+        uint32_t ptr;
+        if (kernel::user::get_user(ptr, (const uint32_t __user*)uarr))
+            return -EFAULT;
+        if (!ptr)
+            return 0;
+
+        long len = kernel::user::strncpy_from_user(buf.data(),
+            (const char __user*)ptr, buf.size());
+        if (len < 0)
+            return len;
+        if ((size_t)len == buf.size())
+            return -E2BIG;
+
+        total += len + 1;
+        if (total > ARG_MAX)
+            return -E2BIG;
+        out.emplace_back(buf.data());
+    }
+}
+
 int _syscall_chdir(interrupt_stack* data)
 {
-    SYSCALL_ARG1(const char*, path);
+    SYSCALL_ARG1(const char* __user, path);
+
+    std::vector<char> kpath;
+    if (int ret = copy_path(path, kpath); ret != 0)
+        return ret;
 
     auto* dir = fs::vfs_open(*current_process->root,
-        types::make_path(path, current_process->pwd));
+        types::make_path(kpath.data(), current_process->pwd));
     if (!dir)
-        return -ENOENT;
+        return -errno;
 
     if (!S_ISDIR(dir->ind->mode))
         return -ENOTDIR;
+    if (int ret = fs::vfs_permission(dir->ind, MAY_EXEC); ret != GB_OK)
+        return ret;
 
     current_process->pwd.clear();
     dir->path(*current_process->root, current_process->pwd);
@@ -138,33 +247,94 @@ int _syscall_chdir(interrupt_stack* data)
     return 0;
 }
 
+int _syscall_chroot(interrupt_stack* data)
+{
+    SYSCALL_ARG1(const char* __user, path);
+
+    if (current_process->euid != 0)
+        return -EPERM;
+
+    std::vector<char> kpath;
+    if (int ret = copy_path(path, kpath); ret != 0)
+        return ret;
+
+    auto* dir = fs::vfs_open(*current_process->root,
+        types::make_path(kpath.data(), current_process->pwd));
+    if (!dir)
+        return -errno;
+
+    if (!S_ISDIR(dir->ind->mode))
+        return -ENOTDIR;
+    if (int ret = fs::vfs_permission(dir->ind, MAY_EXEC); ret != GB_OK)
+        return ret;
+
+    current_process->root = dir;
+
+    // pwd is kept relative to the root, so the old one means nothing
+    // in the new root, start over from the top of it
+    current_process->pwd = "/";
+
+    return 0;
+}
+
 // syscall_exec(const char* exec, const char** argv)
 // @param exec: the path of program to execute
 // @param argv: arguments end with nullptr
 // @param envp: environment variables end with nullptr
 int _syscall_execve(interrupt_stack* data)
 {
-    SYSCALL_ARG1(const char*, exec);
-    SYSCALL_ARG2(char* const*, argv);
-    SYSCALL_ARG3(char* const*, envp);
+    SYSCALL_ARG1(const char* __user, exec);
+    SYSCALL_ARG2(const char* const __user*, argv);
+    SYSCALL_ARG3(const char* const __user*, envp);
+
+    std::vector<char> kexec;
+    if (int ret = copy_path(exec, kexec); ret != 0)
+        return ret;
+
+    size_t total = 0;
+    std::vector<types::string<>> kargv, kenvp;
+    if (int ret = copy_strings(argv, kargv, total); ret != 0)
+        return ret;
+    if (int ret = copy_strings(envp, kenvp, total); ret != 0)
+        return ret;
+
+    // the loaders take null terminated arrays like the user gives
+    std::vector<const char*> argv_ptrs, envp_ptrs;
+    for (const auto& arg : kargv)
+        argv_ptrs.push_back(arg.c_str());
+    argv_ptrs.push_back(nullptr);
+    for (const auto& env : kenvp)
+        envp_ptrs.push_back(env.c_str());
+    envp_ptrs.push_back(nullptr);
 
     types::elf::elf32_load_data d;
-    d.argv = argv;
-    d.envp = envp;
+    d.filename = kexec.data();
+    d.argv = argv_ptrs.data();
+    d.envp = envp_ptrs.data();
     d.system = false;
 
     d.exec_dent = fs::vfs_open(*current_process->root,
-        types::make_path(exec, current_process->pwd));
-    
+        types::make_path(kexec.data(), current_process->pwd));
+
     if (!d.exec_dent)
-        return -ENOENT;
+        return -errno;
+
+    if (!S_ISREG(d.exec_dent->ind->mode))
+        return -EACCES;
+    if (int ret = fs::vfs_permission(d.exec_dent->ind, MAY_EXEC); ret != GB_OK)
+        return ret;
 
     current_process->files.onexec();
 
-    int ret = types::elf::elf32_load(&d);
+    auto args = process::pack_args(argv_ptrs.data());
+
+    int ret = kernel::binfmt::exec(&d);
     if (ret != GB_OK)
         return -d.errcode;
 
+    current_process->set_exec_info(d.exec_dent, std::move(args));
+    current_thread->rseq_area = nullptr;
+
     data->v_eip = d.eip;
     data->esp = (uint32_t)d.sp;
 
@@ -187,6 +357,48 @@ int NORETURN _syscall_exit(interrupt_stack* data)
     schedule_noreturn();
 }
 
+// wait for a child of the current process to exit
+// @param pid the child to wait for, or -1 for any of them
+// @param nowait leave the child waitable
+// @return pid of the child, 0 if nohang is set and no child has exited
+static int do_wait(pid_t pid, int& code, bool nohang, bool nowait)
+{
+    auto& cv = current_process->cv_wait;
+    auto& mtx = cv.mtx();
+    types::lock_guard lck(mtx);
+
+    auto& waitlist = current_process->waitlist;
+
+    for (;;) {
+        auto iter = waitlist.begin();
+        while (iter != waitlist.end() && pid != -1 && iter->pid != pid)
+            ++iter;
+
+        if (iter != waitlist.end()) {
+            pid_t child = iter->pid;
+
+            code = iter->code;
+
+            if (!nowait) {
+                procs->remove(child);
+                waitlist.erase(iter);
+            }
+            return child;
+        }
+
+        if (pid == -1 && !procs->has_child(current_process->pid))
+            return -ECHILD;
+        if (pid != -1 && !current_process->children.contains(pid))
+            return -ECHILD;
+
+        if (nohang)
+            return 0;
+
+        if (!cv.wait(mtx))
+            return -EINTR;
+    }
+}
+
 // @param pid: pid of the process to wait
 // @param status: the exit code of the exited process
 // @param options: options for waitpid
@@ -194,39 +406,87 @@ int NORETURN _syscall_exit(interrupt_stack* data)
 int _syscall_waitpid(interrupt_stack* data)
 {
     SYSCALL_ARG1(pid_t, pid_to_wait);
-    SYSCALL_ARG2(int*, arg1);
+    SYSCALL_ARG2(int* __user, arg1);
     SYSCALL_ARG3(int, options);
 
     if (pid_to_wait != -1 || options != 0)
         return -EINVAL;
 
-    auto& cv = current_process->cv_wait;
-    auto& mtx = cv.mtx();
-    types::lock_guard lck(mtx);
+    int code = 0;
+    int ret = do_wait(-1, code, false, false);
+    if (ret > 0 && arg1 && kernel::user::put_user(code, (uint32_t __user*)arg1))
+        return -EFAULT;
+    return ret;
+}
 
-    auto& waitlist = current_process->waitlist;
+int _syscall_waitid(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, idtype);
+    SYSCALL_ARG2(int, id);
+    SYSCALL_ARG3(siginfo_t* __user, info);
+    SYSCALL_ARG4(int, options);
 
-    while (waitlist.empty()) {
-        if (!procs->has_child(current_process->pid))
-            return -ECHILD;
+    if (options & ~(WNOHANG | WEXITED | WNOWAIT))
+        return -EINVAL;
+    // stopped and continued children are not reported
+    if (!(options & WEXITED))
+        return -EINVAL;
 
-        if (!cv.wait(mtx))
-            return -EINTR;
-    }
+    bool nohang = options & WNOHANG;
+    pid_t pid = -1;
+
+    switch (idtype) {
+    case P_ALL:
+        break;
+    case P_PID:
+        if (id <= 0)
+            return -EINVAL;
+        pid = id;
+        break;
+    case P_PIDFD: {
+        auto* file = current_process->files[id];
+        if (!file)
+            return -EBADF;
 
-    auto iter = waitlist.begin();
-    assert(iter != waitlist.end());
+        auto* pidfd = fs::anon_file_cast<fs::pidfd_file>(file);
+        if (!pidfd)
+            return -EINVAL;
 
-    auto& obj = *iter;
-    pid_t pid = obj.pid;
+        // the pid belongs to someone else now
+        if (pidfd->reaped())
+            return -ECHILD;
 
-    // TODO: copy_to_user check privilege
-    *arg1 = obj.code;
+        pid = pidfd->pid();
+        if (pidfd->nonblock() && !nohang) {
+            if (!current_process->children.contains(pid))
+                return -ECHILD;
+            if (!pidfd->exited())
+                return -EAGAIN;
+        }
+        break;
+    }
+    default:
+        return -EINVAL;
+    }
 
-    procs->remove(pid);
-    waitlist.erase(iter);
+    int code = 0;
+    int ret = do_wait(pid, code, nohang, options & WNOWAIT);
+    if (ret < 0)
+        return ret;
+
+    if (info) {
+        siginfo_t kinfo {};
+        if (ret) {
+            kinfo.si_signo = 17; // SIGCHLD
+            kinfo.si_code = CLD_EXITED;
+            kinfo.si_pid = ret;
+            kinfo.si_status = code;
+        }
+        if (kernel::user::copy_to_user(info, &kinfo, sizeof(kinfo)))
+            return -EFAULT;
+    }
 
-    return pid;
+    return 0;
 }
 
 int _syscall_getdents(interrupt_stack* data)
@@ -248,8 +508,12 @@ int _syscall_open(interrupt_stack* data)
     SYSCALL_ARG2(int, flags);
     SYSCALL_ARG3(mode_t, mode);
 
+    std::vector<char> kpath;
+    if (int ret = copy_path(path, kpath); ret != 0)
+        return ret;
+
     return current_process->files.open(*current_process,
-        types::make_path(path, current_process->pwd), flags, mode);
+        types::make_path(kpath.data(), current_process->pwd), flags, mode);
 }
 
 int _syscall_getcwd(interrupt_stack* data)
@@ -257,10 +521,12 @@ int _syscall_getcwd(interrupt_stack* data)
     SYSCALL_ARG1(char*, buf);
     SYSCALL_ARG2(size_t, bufsize);
 
-    // TODO: use copy_to_user
     auto path = current_process->pwd.full_path();
-    strncpy(buf, path.c_str(), bufsize);
-    buf[bufsize - 1] = 0;
+    size_t len = path.size() + 1;
+    if (len > bufsize)
+        return -ERANGE;
+    if (kernel::user::copy_to_user(buf, path.c_str(), len))
+        return -EFAULT;
 
     return (uint32_t)buf;
 }
@@ -275,6 +541,7 @@ int _syscall_setsid(interrupt_stack*)
 
     // TODO: get tty* from fd or block device id
     console->set_pgrp(current_process->pid);
+    console->set_session(current_process->sid);
     current_process->control_tty = console;
 
     return current_process->pid;
@@ -358,28 +625,42 @@ int _syscall_ioctl(interrupt_stack* data)
     //       not. and we suppose that stdin will be
     //       either a tty or a pipe.
     auto* file = current_process->files[fd];
-    if (!file || !S_ISCHR(file->mode))
+    if (!file)
+        return -EBADF;
+
+    // requests other than the tty ones are handled by the file itself
+    if (request != TIOCGPGRP && request != TIOCSPGRP && request != TIOCGWINSZ) {
+        SYSCALL_ARG3(uintptr_t, arg);
+        return file->ioctl(request, arg);
+    }
+
+    if (!S_ISCHR(file->mode))
         return -ENOTTY;
 
     switch (request) {
     case TIOCGPGRP: {
-        SYSCALL_ARG3(pid_t*, pgid);
+        SYSCALL_ARG3(pid_t* __user, pgid);
         tty* ctrl_tty = current_process->control_tty;
-        // TODO: copy_to_user
-        *pgid = ctrl_tty->get_pgrp();
+        if (kernel::user::put_user(ctrl_tty->get_pgrp(), (uint32_t __user*)pgid))
+            return -EFAULT;
         break;
     }
     case TIOCSPGRP: {
-        // TODO: copy_from_user
-        SYSCALL_ARG3(const pid_t*, pgid);
+        SYSCALL_ARG3(const pid_t* __user, pgid);
+        uint32_t kpgid;
+        if (kernel::user::get_user(kpgid, (const uint32_t __user*)pgid))
+            return -EFAULT;
         tty* ctrl_tty = current_process->control_tty;
-        ctrl_tty->set_pgrp(*pgid);
+        ctrl_tty->set_pgrp(kpgid);
         break;
     }
     case TIOCGWINSZ: {
-        SYSCALL_ARG3(winsize*, ws);
-        ws->ws_col = 80;
-        ws->ws_row = 10;
+        SYSCALL_ARG3(winsize* __user, ws);
+        winsize kws {};
+        kws.ws_col = 80;
+        kws.ws_row = 10;
+        if (kernel::user::copy_to_user(ws, &kws, sizeof(kws)))
+            return -EFAULT;
         break;
     }
     default:
@@ -405,14 +686,162 @@ int _syscall_set_thread_area(interrupt_stack* data)
     return kernel::user::set_thread_area(ptr);
 }
 
+// processes are single threaded for now, so the only thread
+// shares the id of the process, as the main thread does in linux
+static inline pid_t current_tid(void)
+{
+    return current_process->pid;
+}
+
 int _syscall_set_tid_address(interrupt_stack* data)
 {
     SYSCALL_ARG1(int* __user, tidptr);
     current_thread->set_child_tid = tidptr;
-    return current_thread->tid();
+    return current_tid();
+}
+
+int _syscall_gettid(interrupt_stack*)
+{
+    return current_tid();
+}
+
+static constexpr int IOV_MAX = 1024;
+
+// the vectors are gathered into a buffer of at most this size and
+// transferred by a single read or write, so that the transfers of
+// this size are atomic like the plain ones
+static constexpr size_t IOV_CHUNK_SIZE = 64 * 1024;
+
+// copy the vectors in from the user
+// @return the total length of the vectors, -EINVAL or -EFAULT
+static ssize_t copy_iov(const iovec* __user iov, int iovcnt, std::vector<iovec>& out)
+{
+    if (iovcnt < 0 || iovcnt > IOV_MAX)
+        return -EINVAL;
+
+    out.resize(iovcnt);
+    if (kernel::user::copy_from_user(out.data(), iov, iovcnt * sizeof(iovec)))
+        return -EFAULT;
+
+    size_t total = 0;
+    for (const auto& vec : out) {
+        total += vec.iov_len;
+        if (total > 0x7fffffff)
+            return -EINVAL;
+    }
+
+    return total;
+}
+
+// @param offset where to read from, or -1 to use and update the file offset
+static ssize_t do_readv(fs::file* file,
+    const iovec* __user iov, int iovcnt, off64_t offset)
+{
+    std::vector<iovec> vecs;
+    ssize_t total = copy_iov(iov, iovcnt, vecs);
+    if (total < 0)
+        return total;
+
+    std::vector<char> buf(std::min((size_t)total, IOV_CHUNK_SIZE));
+
+    ssize_t done = 0;
+    int idx = 0;
+    size_t iov_off = 0;
+    while (done < total) {
+        size_t n = std::min((size_t)(total - done), buf.size());
+
+        ssize_t ret = offset < 0
+            ? file->read(buf.data(), n)
+            : file->pread(buf.data(), n, offset + done);
+        if (ret < 0)
+            return done ? done : ret;
+
+        // scatter to the user buffers, what is read after
+        // a bad buffer is lost as the data of a short read is
+        for (ssize_t copied = 0; copied < ret; ) {
+            size_t len = std::min(vecs[idx].iov_len - iov_off, (size_t)(ret - copied));
+            if (kernel::user::copy_to_user((char*)vecs[idx].iov_base + iov_off,
+                    buf.data() + copied, len)) {
+                done += copied;
+                return done ? done : -EFAULT;
+            }
+
+            copied += len;
+            if ((iov_off += len) == vecs[idx].iov_len)
+                ++idx, iov_off = 0;
+        }
+
+        done += ret;
+        if ((size_t)ret < n)
+            break;
+    }
+
+    return done;
+}
+
+// @param offset where to write to, or -1 to use and update the file offset
+static ssize_t do_writev(fs::file* file,
+    const iovec* __user iov, int iovcnt, off64_t offset)
+{
+    std::vector<iovec> vecs;
+    ssize_t total = copy_iov(iov, iovcnt, vecs);
+    if (total < 0)
+        return total;
+
+    std::vector<char> buf(std::min((size_t)total, IOV_CHUNK_SIZE));
+
+    ssize_t done = 0;
+    int idx = 0;
+    size_t iov_off = 0;
+    while (done < total) {
+        size_t n = std::min((size_t)(total - done), buf.size());
+
+        // gather from the user buffers, a bad one ends the write there
+        bool fault = false;
+        for (size_t copied = 0; copied < n; ) {
+            size_t len = std::min(vecs[idx].iov_len - iov_off, n - copied);
+            if (kernel::user::copy_from_user(buf.data() + copied,
+                    (const char*)vecs[idx].iov_base + iov_off, len)) {
+                fault = true;
+                n = copied;
+                break;
+            }
+
+            copied += len;
+            if ((iov_off += len) == vecs[idx].iov_len)
+                ++idx, iov_off = 0;
+        }
+
+        if (!n)
+            return done ? done : -EFAULT;
+
+        ssize_t ret = offset < 0
+            ? file->write(buf.data(), n)
+            : file->pwrite(buf.data(), n, offset + done);
+        if (ret < 0)
+            return done ? done : ret;
+
+        done += ret;
+        if (fault || (size_t)ret < n)
+            break;
+    }
+
+    return done;
+}
+
+ssize_t _syscall_readv(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, fd);
+    SYSCALL_ARG2(const iovec* __user, iov);
+    SYSCALL_ARG3(int, iovcnt);
+
+    auto* file = current_process->files[fd];
+    if (!file)
+        return -EBADF;
+
+    return do_readv(file, iov, iovcnt, -1);
 }
 
-// TODO: this operation SHOULD be atomic
 ssize_t _syscall_writev(interrupt_stack* data)
 {
     SYSCALL_ARG1(int, fd);
@@ -420,21 +849,50 @@ ssize_t _syscall_writev(interrupt_stack* data)
     SYSCALL_ARG3(int, iovcnt);
 
     auto* file = current_process->files[fd];
+    if (!file)
+        return -EBADF;
 
+    return do_writev(file, iov, iovcnt, -1);
+}
+
+// @param pos_l, pos_h: lower and higher 32 bits of the offset
+ssize_t _syscall_preadv(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, fd);
+    SYSCALL_ARG2(const iovec* __user, iov);
+    SYSCALL_ARG3(int, iovcnt);
+    SYSCALL_ARG4(uint32_t, pos_l);
+    SYSCALL_ARG5(uint32_t, pos_h);
+
+    auto* file = current_process->files[fd];
     if (!file)
         return -EBADF;
 
-    ssize_t totn = 0;
-    for (int i = 0; i < iovcnt; ++i) {
-        ssize_t ret = file->write(
-            (const char*)iov[i].iov_base, iov[i].iov_len);
+    off64_t offset = ((off64_t)pos_h << 32) | pos_l;
+    if (offset < 0 || offset > 0xffffffff)
+        return -EINVAL;
 
-        if (ret < 0)
-            return ret;
-        totn += ret;
-    }
+    return do_readv(file, iov, iovcnt, offset);
+}
 
-    return totn;
+// @param pos_l, pos_h: lower and higher 32 bits of the offset
+ssize_t _syscall_pwritev(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, fd);
+    SYSCALL_ARG2(const iovec* __user, iov);
+    SYSCALL_ARG3(int, iovcnt);
+    SYSCALL_ARG4(uint32_t, pos_l);
+    SYSCALL_ARG5(uint32_t, pos_h);
+
+    auto* file = current_process->files[fd];
+    if (!file)
+        return -EBADF;
+
+    off64_t offset = ((off64_t)pos_h << 32) | pos_l;
+    if (offset < 0 || offset > 0xffffffff)
+        return -EINVAL;
+
+    return do_writev(file, iov, iovcnt, offset);
 }
 
 int _syscall_prctl(interrupt_stack* data)
@@ -467,19 +925,70 @@ int _syscall_clock_gettime64(interrupt_stack* data)
     SYSCALL_ARG1(clockid_t, clk_id);
     SYSCALL_ARG2(timespec* __user, tp);
 
-    // TODO: check privilege of tp
     if (clk_id != CLOCK_REALTIME || !tp)
         return -EINVAL;
 
-    tp->tv_sec = 10 + current_ticks();
-    tp->tv_nsec = 0;
+    auto now = current_time();
+    if (kernel::user::copy_to_user(tp, &now, sizeof(now)))
+        return -EFAULT;
 
     return 0;
 }
 
 int _syscall_getuid(interrupt_stack*)
 {
-    return 0; // all user is root for now
+    return current_process->uid;
+}
+
+int _syscall_getgid(interrupt_stack*)
+{
+    return current_process->gid;
+}
+
+int _syscall_geteuid(interrupt_stack*)
+{
+    return current_process->euid;
+}
+
+int _syscall_getegid(interrupt_stack*)
+{
+    return current_process->egid;
+}
+
+int _syscall_setuid(interrupt_stack* data)
+{
+    SYSCALL_ARG1(uid_t, uid);
+
+    auto* proc = current_process;
+    if (proc->euid == 0) {
+        proc->uid = proc->euid = proc->suid = uid;
+        return 0;
+    }
+
+    // unprivileged processes can only switch between the real
+    // and the saved ids
+    if (uid != proc->uid && uid != proc->suid)
+        return -EPERM;
+
+    proc->euid = uid;
+    return 0;
+}
+
+int _syscall_setgid(interrupt_stack* data)
+{
+    SYSCALL_ARG1(gid_t, gid);
+
+    auto* proc = current_process;
+    if (proc->euid == 0) {
+        proc->gid = proc->egid = proc->sgid = gid;
+        return 0;
+    }
+
+    if (gid != proc->gid && gid != proc->sgid)
+        return -EPERM;
+
+    proc->egid = gid;
+    return 0;
 }
 
 int _syscall_brk(interrupt_stack* data)
@@ -554,50 +1063,431 @@ int _syscall_munmap(interrupt_stack* data)
     return current_process->mms.unmap(addr, len, false);
 }
 
+int _syscall_mprotect(interrupt_stack* data)
+{
+    SYSCALL_ARG1(void*, addr);
+    SYSCALL_ARG2(size_t, len);
+    SYSCALL_ARG3(int, prot);
+
+    if (prot & ~(PROT_READ | PROT_WRITE | PROT_EXEC))
+        return -EINVAL;
+
+    // TODO: PROT_NONE and PROT_EXEC can't be enforced without PAE
+    return current_process->mms.protect(addr, len, prot & PROT_WRITE);
+}
+
 [[noreturn]] static void not_implemented()
 {
     console->print("\n[kernel] this function is not implemented\n");
     kill_current(-1);
 }
 
-int _syscall_sendfile64(interrupt_stack* data)
+// read from the in side of splice() or sendfile(), pipes are read
+// without waiting for all of n
+//
+// offset is the kernel copy of the one given by the user
+static ssize_t splice_read(fs::file* file, char* buf, size_t n,
+    off64_t* offset, bool nonblock)
 {
-    SYSCALL_ARG1(int, out_fd);
-    SYSCALL_ARG2(int, in_fd);
-    SYSCALL_ARG3(off64_t*, offset);
-    SYSCALL_ARG4(size_t, count);
+    if (!file->flags.read)
+        return -EBADF;
 
-    auto* out_file = current_process->files[out_fd];
-    auto* in_file = current_process->files[in_fd];
+    if (auto* ppipe = file->get_pipe())
+        return ppipe->read_some(buf, n, nonblock);
 
-    if (!out_file || !in_file)
-        return -EBADF;
+    if (!offset)
+        return file->read(buf, n);
 
-    // TODO: check whether in_fd supports mmapping (for example,
-    //       whether it is a char device) if not, return -EINVAL
+    ssize_t ret = file->pread(buf, n, *offset);
+    if (ret > 0)
+        *offset += ret;
+    return ret;
+}
 
-    if (offset)
-        not_implemented();
+static ssize_t splice_write(fs::file* file, const char* buf, size_t n,
+    off64_t* offset)
+{
+    if (!offset)
+        return file->write(buf, n);
 
-    constexpr size_t bufsize = 512;
-    std::vector<char> buf(bufsize);
-    size_t totn = 0;
-    while (totn < count) {
-        size_t n = std::min(count - totn, bufsize);
-        ssize_t ret = in_file->read(buf.data(), n);
-        if (ret < 0)
-            return ret;
+    ssize_t ret = file->pwrite(buf, n, *offset);
+    if (ret > 0)
+        *offset += ret;
+    return ret;
+}
+
+// move at most count bytes from in to out a pipe buffer at a time
+//
+// only the first chunk waits for the pipes, so the transfer stops
+// when the pipe at either side runs dry instead of blocking forever
+static ssize_t do_splice(fs::file* in, off64_t* off_in,
+    fs::file* out, off64_t* off_out, size_t count, bool nonblock)
+{
+    if (!out->flags.write)
+        return -EBADF;
+
+    auto* out_pipe = out->get_pipe();
+
+    char buf[512];
+    size_t totn = 0;
+    while (totn < count) {
+        bool dont_wait = nonblock || totn;
+        size_t n = std::min(count - totn, sizeof(buf));
+
+        // never read what can't be written to the pipe
+        if (out_pipe) {
+            int space = out_pipe->wait_space(dont_wait);
+            if (space < 0)
+                return totn ? totn : space;
+            n = std::min(n, (size_t)space);
+        }
+
+        ssize_t ret = splice_read(in, buf, n, off_in, dont_wait);
+        if (ret < 0)
+            return totn ? totn : ret;
         if (ret == 0)
             break;
-        ret = out_file->write(buf.data(), ret);
-        if (ret < 0)
-            return ret;
-        totn += ret;
+
+        // pipe writes return all or an error
+        ssize_t wret = splice_write(out, buf, ret, off_out);
+        if (wret < 0)
+            return totn ? totn : wret;
+        totn += wret;
+        if (wret < ret)
+            break;
+    }
+
+    return totn;
+}
+
+int _syscall_sendfile64(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, out_fd);
+    SYSCALL_ARG2(int, in_fd);
+    SYSCALL_ARG3(off64_t* __user, offset);
+    SYSCALL_ARG4(size_t, count);
+
+    auto* out_file = current_process->files[out_fd];
+    auto* in_file = current_process->files[in_fd];
+
+    if (!out_file || !in_file)
+        return -EBADF;
+
+    // in_fd must be something that can be read at an offset
+    if (in_file->get_pipe())
+        return -EINVAL;
+
+    off64_t koff = 0;
+    if (offset && kernel::user::copy_from_user(&koff, offset, sizeof(koff)))
+        return -EFAULT;
+    if (koff < 0 || koff > 0xffffffff)
+        return -EINVAL;
+
+    ssize_t ret = do_splice(in_file, offset ? &koff : nullptr,
+        out_file, nullptr, count, false);
+
+    // the data is sent already, a bad offset pointer can't undo it
+    if (offset && ret > 0)
+        kernel::user::copy_to_user(offset, &koff, sizeof(koff));
+
+    return ret;
+}
+
+int _syscall_splice(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, fd_in);
+    SYSCALL_ARG2(off64_t* __user, off_in);
+    SYSCALL_ARG3(int, fd_out);
+    SYSCALL_ARG4(off64_t* __user, off_out);
+    SYSCALL_ARG5(size_t, len);
+    SYSCALL_ARG6(unsigned int, flags);
+
+    if (flags & ~(SPLICE_F_MOVE | SPLICE_F_NONBLOCK | SPLICE_F_MORE | SPLICE_F_GIFT))
+        return -EINVAL;
+
+    auto* in_file = current_process->files[fd_in];
+    auto* out_file = current_process->files[fd_out];
+    if (!in_file || !out_file)
+        return -EBADF;
+
+    auto* in_pipe = in_file->get_pipe();
+    auto* out_pipe = out_file->get_pipe();
+    if (!in_pipe && !out_pipe)
+        return -EINVAL;
+    if (in_pipe && in_pipe == out_pipe)
+        return -EINVAL;
+    if ((in_pipe && off_in) || (out_pipe && off_out))
+        return -ESPIPE;
+
+    off64_t koff_in = 0, koff_out = 0;
+    if (off_in && kernel::user::copy_from_user(&koff_in, off_in, sizeof(koff_in)))
+        return -EFAULT;
+    if (off_out && kernel::user::copy_from_user(&koff_out, off_out, sizeof(koff_out)))
+        return -EFAULT;
+    if (koff_in < 0 || koff_in > 0xffffffff)
+        return -EINVAL;
+    if (koff_out < 0 || koff_out > 0xffffffff)
+        return -EINVAL;
+
+    // TODO: move the pages instead of copying them when the pipes
+    //       are made of pages, SPLICE_F_MOVE and SPLICE_F_MORE are hints
+    ssize_t ret = do_splice(in_file, off_in ? &koff_in : nullptr,
+        out_file, off_out ? &koff_out : nullptr,
+        len, flags & SPLICE_F_NONBLOCK);
+
+    // the data is moved already, a bad offset pointer can't undo it
+    if (ret > 0) {
+        if (off_in)
+            kernel::user::copy_to_user(off_in, &koff_in, sizeof(koff_in));
+        if (off_out)
+            kernel::user::copy_to_user(off_out, &koff_out, sizeof(koff_out));
+    }
+
+    return ret;
+}
+
+int _syscall_tee(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, fd_in);
+    SYSCALL_ARG2(int, fd_out);
+    SYSCALL_ARG3(size_t, len);
+    SYSCALL_ARG4(unsigned int, flags);
+
+    if (flags & ~(SPLICE_F_MOVE | SPLICE_F_NONBLOCK | SPLICE_F_MORE | SPLICE_F_GIFT))
+        return -EINVAL;
+
+    auto* in_file = current_process->files[fd_in];
+    auto* out_file = current_process->files[fd_out];
+    if (!in_file || !out_file)
+        return -EBADF;
+    if (!in_file->flags.read || !out_file->flags.write)
+        return -EBADF;
+
+    auto* in_pipe = in_file->get_pipe();
+    auto* out_pipe = out_file->get_pipe();
+    if (!in_pipe || !out_pipe || in_pipe == out_pipe)
+        return -EINVAL;
+
+    bool nonblock = flags & SPLICE_F_NONBLOCK;
+
+    // a pipe holds no more than a page, so a single copy duplicates
+    // all that can fit into out
+    int space = out_pipe->wait_space(nonblock);
+    if (space < 0)
+        return space;
+
+    char buf[512];
+    size_t n = std::min(std::min(len, sizeof(buf)), (size_t)space);
+    int ret = in_pipe->read_some(buf, n, nonblock, true);
+    if (ret <= 0)
+        return ret;
+
+    return out_pipe->write(buf, ret);
+}
+
+int _syscall_vmsplice(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, fd);
+    SYSCALL_ARG2(const iovec* __user, iov);
+    SYSCALL_ARG3(unsigned long, nr_segs);
+    SYSCALL_ARG4(unsigned int, flags);
+
+    if (flags & ~(SPLICE_F_MOVE | SPLICE_F_NONBLOCK | SPLICE_F_MORE | SPLICE_F_GIFT))
+        return -EINVAL;
+
+    auto* file = current_process->files[fd];
+    if (!file)
+        return -EBADF;
+
+    auto* ppipe = file->get_pipe();
+    if (!ppipe)
+        return -EBADF;
+
+    std::vector<iovec> vecs;
+    ssize_t total = copy_iov(iov, nr_segs, vecs);
+    if (total < 0)
+        return total;
+
+    bool nonblock = flags & SPLICE_F_NONBLOCK;
+
+    // TODO: SPLICE_F_GIFT could map the user pages into the pipe
+    //       if the pipes were made of pages
+    char buf[512];
+    size_t totn = 0;
+    for (const auto& vec : vecs) {
+        char* base = (char*)vec.iov_base;
+        size_t len = vec.iov_len;
+
+        while (len) {
+            bool dont_wait = nonblock || totn;
+            size_t n = std::min(len, sizeof(buf));
+            int ret;
+            if (file->flags.write) {
+                ret = ppipe->wait_space(dont_wait);
+                if (ret > 0) {
+                    n = std::min(n, (size_t)ret);
+                    if (kernel::user::copy_from_user(buf, base, n))
+                        return totn ? totn : -EFAULT;
+                    ret = ppipe->write(buf, n);
+                }
+            } else {
+                ret = ppipe->read_some(buf, n, dont_wait);
+                // what is read is lost as the data of a short read is
+                if (ret > 0 && kernel::user::copy_to_user(base, buf, ret))
+                    return totn ? totn : -EFAULT;
+            }
+
+            if (ret < 0)
+                return totn ? totn : ret;
+            if (ret == 0)
+                return totn;
+
+            base += ret, len -= ret, totn += ret;
+        }
     }
 
     return totn;
 }
 
+// copy len bytes between the regular files in and out at the offsets
+static ssize_t do_copy_file_range(fs::file* in_file, size_t src_off,
+    fs::file* out_file, size_t dst_off, size_t len)
+{
+    auto* in_ind = in_file->get_dentry()->ind;
+    auto* out_ind = out_file->get_dentry()->ind;
+
+    ssize_t ret = fs::vfs_copy_file_range(
+        in_ind, src_off, out_ind, dst_off, len);
+
+    // the filesystem can't do it by itself, copy through memory
+    if (ret == -EOPNOTSUPP || ret == -EXDEV) {
+        char buf[512];
+        size_t totn = 0;
+        ret = 0;
+        while (totn < len) {
+            size_t n = std::min(len - totn, sizeof(buf));
+            ssize_t nread = in_file->pread(buf, n, src_off + totn);
+            if (nread <= 0) {
+                ret = nread;
+                break;
+            }
+
+            ssize_t nwrote = out_file->pwrite(buf, nread, dst_off + totn);
+            if (nwrote < 0) {
+                ret = nwrote;
+                break;
+            }
+
+            totn += nwrote;
+            if (nwrote < nread)
+                break;
+        }
+
+        if (totn)
+            ret = totn;
+    } else if (ret > 0) {
+        fs::inotify_notify_parent(out_file->get_dentry(), IN_MODIFY);
+
+        if (out_file->flags.sync) {
+            int sret = fs::vfs_sync(out_ind);
+            if (sret != 0)
+                return sret;
+        }
+    }
+
+    return ret;
+}
+
+int _syscall_copy_file_range(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, fd_in);
+    SYSCALL_ARG2(off64_t* __user, off_in);
+    SYSCALL_ARG3(int, fd_out);
+    SYSCALL_ARG4(off64_t* __user, off_out);
+    SYSCALL_ARG5(size_t, len);
+    SYSCALL_ARG6(unsigned int, flags);
+
+    if (flags)
+        return -EINVAL;
+
+    auto* in_file = current_process->files[fd_in];
+    auto* out_file = current_process->files[fd_out];
+    if (!in_file || !out_file)
+        return -EBADF;
+    if (!in_file->flags.read || !out_file->flags.write)
+        return -EBADF;
+    // the data would go to where the offset says instead of the end
+    if (out_file->flags.append)
+        return -EBADF;
+
+    auto* in_dent = in_file->get_dentry();
+    auto* out_dent = out_file->get_dentry();
+    if (!in_dent || !out_dent)
+        return -EINVAL;
+    if (S_ISDIR(in_dent->ind->mode) || S_ISDIR(out_dent->ind->mode))
+        return -EISDIR;
+    if (!S_ISREG(in_dent->ind->mode) || !S_ISREG(out_dent->ind->mode))
+        return -EINVAL;
+
+    off64_t koff_in = 0, koff_out = 0;
+    if (off_in && kernel::user::copy_from_user(&koff_in, off_in, sizeof(koff_in)))
+        return -EFAULT;
+    if (off_out && kernel::user::copy_from_user(&koff_out, off_out, sizeof(koff_out)))
+        return -EFAULT;
+
+    if (koff_in < 0 || koff_in > 0xffffffff)
+        return -EINVAL;
+    if (koff_out < 0 || koff_out > 0xffffffff)
+        return -EINVAL;
+    // the offsets of the files used are held until they are updated
+    bool lock_in = !off_in;
+    bool lock_out = !off_out && out_file != in_file;
+    if (lock_in && !in_file->lock_pos())
+        return -EINTR;
+    if (lock_out && !out_file->lock_pos()) {
+        if (lock_in)
+            in_file->unlock_pos();
+        return -EINTR;
+    }
+
+    ssize_t ret = -EINVAL;
+    size_t* in_pos = off_in ? nullptr : in_file->pos();
+    size_t* out_pos = off_out ? nullptr : out_file->pos();
+    if ((off_in || in_pos) && (off_out || out_pos)) {
+        size_t src_off = off_in ? koff_in : *in_pos;
+        size_t dst_off = off_out ? koff_out : *out_pos;
+
+        // the ranges may not overlap, written so that it can't wrap around
+        if (in_dent->ind != out_dent->ind
+            || (src_off >= dst_off && src_off - dst_off >= len)
+            || (dst_off >= src_off && dst_off - src_off >= len))
+            ret = do_copy_file_range(in_file, src_off, out_file, dst_off, len);
+    }
+
+    // the data is copied already, a bad offset pointer can't undo it
+    if (ret > 0) {
+        if (off_in) {
+            koff_in += ret;
+            kernel::user::copy_to_user(off_in, &koff_in, sizeof(koff_in));
+        } else {
+            *in_pos += ret;
+        }
+        if (off_out) {
+            koff_out += ret;
+            kernel::user::copy_to_user(off_out, &koff_out, sizeof(koff_out));
+        } else {
+            *out_pos += ret;
+        }
+    }
+
+    if (lock_out)
+        out_file->unlock_pos();
+    if (lock_in)
+        in_file->unlock_pos();
+
+    return ret;
+}
+
 int _syscall_statx(interrupt_stack* data)
 {
     SYSCALL_ARG1(int, dirfd);
@@ -613,16 +1503,154 @@ int _syscall_statx(interrupt_stack* data)
     if (dirfd != AT_FDCWD)
         not_implemented();
 
+    std::vector<char> kpath;
+    if (int ret = copy_path(path, kpath); ret != 0)
+        return ret;
+
     auto* dent = fs::vfs_open(*current_process->root,
-        types::make_path(path, current_process->pwd));
+        types::make_path(kpath.data(), current_process->pwd),
+        !(flags & AT_SYMLINK_NOFOLLOW));
 
     if (!dent)
-        return -ENOENT;
+        return -errno;
+
+    statx kbuf {};
+    auto ret = fs::vfs_stat(dent, &kbuf, mask);
+    if (ret != GB_OK)
+        return ret;
 
-    // TODO: copy to user
-    auto ret = fs::vfs_stat(dent, statxbuf, mask);
+    if (kernel::user::copy_to_user(statxbuf, &kbuf, sizeof(kbuf)))
+        return -EFAULT;
+    return 0;
+}
 
-    return ret;
+int _syscall_utimensat_time64(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, dirfd);
+    SYSCALL_ARG2(const char* __user, path);
+    SYSCALL_ARG3(const timespec* __user, times);
+    SYSCALL_ARG4(int, flags);
+
+    if (flags & ~AT_SYMLINK_NOFOLLOW)
+        return -EINVAL;
+
+    fs::vfs::dentry* dent;
+    if (!path) {
+        // futimens(), dirfd is the file itself
+        auto* file = current_process->files[dirfd];
+        if (!file)
+            return -EBADF;
+
+        dent = file->get_dentry();
+        if (!dent)
+            return -EINVAL;
+    } else {
+        if (dirfd != AT_FDCWD)
+            not_implemented();
+
+        std::vector<char> kpath;
+        if (int ret = copy_path(path, kpath); ret != 0)
+            return ret;
+
+        dent = fs::vfs_open(*current_process->root,
+            types::make_path(kpath.data(), current_process->pwd),
+            !(flags & AT_SYMLINK_NOFOLLOW));
+
+        if (!dent)
+            return -errno;
+    }
+
+    timespec ktimes[2];
+    if (times && kernel::user::copy_from_user(ktimes, times, sizeof(ktimes)))
+        return -EFAULT;
+
+    return fs::vfs_utimens(dent->ind, times ? ktimes : nullptr);
+}
+
+int _syscall_statfs(interrupt_stack* data)
+{
+    SYSCALL_ARG1(const char* __user, path);
+    SYSCALL_ARG2(struct statfs* __user, buf);
+
+    std::vector<char> kpath;
+    if (int ret = copy_path(path, kpath); ret != 0)
+        return ret;
+
+    auto* dent = fs::vfs_open(*current_process->root,
+        types::make_path(kpath.data(), current_process->pwd));
+
+    if (!dent)
+        return -errno;
+
+    struct statfs kbuf {};
+    int ret = fs::vfs_statfs(dent->ind, &kbuf);
+    if (ret != GB_OK)
+        return ret;
+
+    if (kernel::user::copy_to_user(buf, &kbuf, sizeof(kbuf)))
+        return -EFAULT;
+    return 0;
+}
+
+int _syscall_fstatfs(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, fd);
+    SYSCALL_ARG2(struct statfs* __user, buf);
+
+    auto* file = current_process->files[fd];
+    if (!file)
+        return -EBADF;
+
+    auto* dent = file->get_dentry();
+    if (!dent)
+        return -ENOSYS;
+
+    struct statfs kbuf {};
+    int ret = fs::vfs_statfs(dent->ind, &kbuf);
+    if (ret != GB_OK)
+        return ret;
+
+    if (kernel::user::copy_to_user(buf, &kbuf, sizeof(kbuf)))
+        return -EFAULT;
+    return 0;
+}
+
+int _syscall_fsync(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, fd);
+
+    auto* file = current_process->files[fd];
+    if (!file)
+        return -EBADF;
+
+    return file->fsync(false);
+}
+
+int _syscall_fdatasync(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, fd);
+
+    auto* file = current_process->files[fd];
+    if (!file)
+        return -EBADF;
+
+    return file->fsync(true);
+}
+
+int _syscall_syncfs(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, fd);
+
+    auto* file = current_process->files[fd];
+    if (!file)
+        return -EBADF;
+
+    // pipes and other anonymous files have nothing to sync
+    auto* dent = file->get_dentry();
+    if (!dent)
+        return 0;
+
+    return dent->ind->fs->sync_fs();
 }
 
 int _syscall_fcntl64(interrupt_stack* data)
@@ -645,77 +1673,933 @@ int _syscall_fcntl64(interrupt_stack* data)
     }
 }
 
-int _syscall_getdents64(interrupt_stack* data)
+// resolve pathname relative to the directory dirfd refers to
+static int make_at_path(int dirfd, const char* __user upathname, types::path& out)
 {
-    SYSCALL_ARG1(int, fd);
-    SYSCALL_ARG2(char* __user, buf);
-    SYSCALL_ARG3(size_t, cnt);
+    std::vector<char> kpath;
+    if (int ret = copy_path(upathname, kpath); ret != 0)
+        return ret;
+    const char* pathname = kpath.data();
 
-    auto* dir = current_process->files[fd];
-    if (!dir)
+    if (pathname[0] == '/' || dirfd == AT_FDCWD) {
+        out = types::make_path(pathname, current_process->pwd);
+        return 0;
+    }
+
+    auto* file = current_process->files[dirfd];
+    if (!file)
         return -EBADF;
 
-    return dir->getdents64(buf, cnt);
+    auto* dent = file->get_dentry();
+    if (!dent || !S_ISDIR(dent->ind->mode))
+        return -ENOTDIR;
+
+    types::path dir;
+    dent->path(*current_process->root, dir);
+    out = types::make_path(pathname, dir);
+
+    return 0;
 }
 
-extern "C" void syscall_entry(interrupt_stack* data)
+static int do_linkat(int olddirfd, const char* __user oldpath,
+    int newdirfd, const char* __user newpath, int flags)
 {
-    int syscall_no = SYSCALL_NO;
+    if (flags & ~AT_SYMLINK_FOLLOW)
+        return -EINVAL;
 
-    if (syscall_no >= SYSCALL_HANDLERS_SIZE
-        || !syscall_handlers[syscall_no]) {
-        char buf[64];
-        snprintf(buf, 64,
-            "[kernel] syscall %x not implemented\n", syscall_no);
-        console->print(buf);
-        kill_current(-1);
-    }
+    types::path src, dst;
+    int ret = make_at_path(olddirfd, oldpath, src);
+    if (ret)
+        return ret;
+    ret = make_at_path(newdirfd, newpath, dst);
+    if (ret)
+        return ret;
 
-    int ret = syscall_handlers[syscall_no](data);
+    auto* target = fs::vfs_open(*current_process->root, src,
+        flags & AT_SYMLINK_FOLLOW);
+    if (!target)
+        return -errno;
 
-    SYSCALL_RETVAL = ret;
+    auto filename = dst.last_name();
+    dst.remove_last();
 
-    check_signal();
+    auto* dir = fs::vfs_open(*current_process->root, dst);
+    if (!dir)
+        return -errno;
+    if (!S_ISDIR(dir->ind->mode))
+        return -ENOTDIR;
+
+    if (dir->find(filename))
+        return -EEXIST;
+
+    return fs::vfs_link(dir, filename.c_str(), target->ind);
 }
 
-SECTION(".text.kinit")
-void init_syscall(void)
+int _syscall_link(interrupt_stack* data)
 {
-    memset(syscall_handlers, 0x00, sizeof(syscall_handlers));
+    SYSCALL_ARG1(const char* __user, oldpath);
+    SYSCALL_ARG2(const char* __user, newpath);
+
+    return do_linkat(AT_FDCWD, oldpath, AT_FDCWD, newpath, 0);
+}
+
+int _syscall_linkat(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, olddirfd);
+    SYSCALL_ARG2(const char* __user, oldpath);
+    SYSCALL_ARG3(int, newdirfd);
+    SYSCALL_ARG4(const char* __user, newpath);
+    SYSCALL_ARG5(int, flags);
+
+    return do_linkat(olddirfd, oldpath, newdirfd, newpath, flags);
+}
+
+int _syscall_unlink(interrupt_stack* data)
+{
+    SYSCALL_ARG1(const char* __user, pathname);
+
+    std::vector<char> kpath;
+    if (int ret = copy_path(pathname, kpath); ret != 0)
+        return ret;
+
+    auto path = types::make_path(kpath.data(), current_process->pwd);
+    auto filename = path.last_name();
+    path.remove_last();
 
-    syscall_handlers[0x01] = _syscall_exit;
-    syscall_handlers[0x02] = _syscall_fork;
-    syscall_handlers[0x03] = _syscall_read;
-    syscall_handlers[0x04] = _syscall_write;
-    syscall_handlers[0x05] = _syscall_open;
-    syscall_handlers[0x06] = _syscall_close;
-    syscall_handlers[0x07] = _syscall_waitpid;
-    syscall_handlers[0x0b] = _syscall_execve;
-    syscall_handlers[0x0c] = _syscall_chdir;
-    syscall_handlers[0x14] = _syscall_getpid;
-    syscall_handlers[0x29] = _syscall_dup;
-    syscall_handlers[0x2a] = _syscall_pipe;
-    syscall_handlers[0x2d] = _syscall_brk;
-    syscall_handlers[0x36] = _syscall_ioctl;
-    syscall_handlers[0x39] = _syscall_setpgid;
-    syscall_handlers[0x3f] = _syscall_dup2;
-    syscall_handlers[0x40] = _syscall_getppid;
-    syscall_handlers[0x42] = _syscall_setsid;
-    syscall_handlers[0x5b] = _syscall_munmap;
-    syscall_handlers[0x84] = _syscall_getdents;
-    syscall_handlers[0x92] = _syscall_writev;
-    syscall_handlers[0x93] = _syscall_getsid;
-    syscall_handlers[0xac] = _syscall_prctl;
-    syscall_handlers[0xb7] = _syscall_getcwd;
-    syscall_handlers[0xc0] = _syscall_mmap_pgoff;
-    syscall_handlers[0xc7] = _syscall_getuid;
-    syscall_handlers[0xdc] = _syscall_getdents64;
-    syscall_handlers[0xdd] = _syscall_fcntl64;
-    syscall_handlers[0xef] = _syscall_sendfile64;
-    syscall_handlers[0xf3] = _syscall_set_thread_area;
-    syscall_handlers[0xfc] = _syscall_exit; // we implement exit_group as exit for now
-    syscall_handlers[0x102] = _syscall_set_tid_address;
-    syscall_handlers[0x17f] = _syscall_statx;
-    syscall_handlers[0x193] = _syscall_clock_gettime64;
-    // syscall_handlers[35] = _syscall_sleep;
+    auto* dir = fs::vfs_open(*current_process->root, path);
+    if (!dir)
+        return -errno;
+
+    auto* ent = dir->find(filename);
+    if (!ent)
+        return -ENOENT;
+    if (S_ISDIR(ent->ind->mode))
+        return -EISDIR;
+
+    return fs::vfs_rmfile(dir, filename.c_str());
+}
+
+int _syscall_mknod(interrupt_stack* data)
+{
+    SYSCALL_ARG1(const char* __user, pathname);
+    SYSCALL_ARG2(mode_t, mode);
+    SYSCALL_ARG3(unsigned int, dev);
+
+    std::vector<char> kpath;
+    if (int ret = copy_path(pathname, kpath); ret != 0)
+        return ret;
+
+    auto path = types::make_path(kpath.data(), current_process->pwd);
+    auto filename = path.last_name();
+    path.remove_last();
+
+    auto* dir = fs::vfs_open(*current_process->root, path);
+    if (!dir)
+        return -errno;
+    if (!S_ISDIR(dir->ind->mode))
+        return -ENOTDIR;
+
+    if (dir->find(filename))
+        return -EEXIST;
+
+    // dev is encoded as in linux: minor bits are split around the major
+    uint32_t major = (dev >> 8) & 0xfff;
+    uint32_t minor = (dev & 0xff) | ((dev >> 12) & 0xfff00);
+
+    switch (mode & S_IFMT) {
+    case 0:
+    case S_IFREG:
+        return fs::vfs_mkfile(dir, filename.c_str(), mode & 07777);
+    case S_IFCHR:
+    case S_IFBLK:
+        return fs::vfs_mknode(dir, filename.c_str(), mode, fs::make_node(major, minor));
+    case S_IFIFO:
+        return fs::vfs_mknode(dir, filename.c_str(), mode, fs::NODE_INVALID);
+    default:
+        return -EINVAL;
+    }
+}
+
+int _syscall_symlink(interrupt_stack* data)
+{
+    SYSCALL_ARG1(const char* __user, target);
+    SYSCALL_ARG2(const char* __user, linkpath);
+
+    std::vector<char> ktarget, kpath;
+    if (int ret = copy_path(target, ktarget); ret != 0)
+        return ret;
+    if (int ret = copy_path(linkpath, kpath); ret != 0)
+        return ret;
+
+    auto path = types::make_path(kpath.data(), current_process->pwd);
+    auto linkname = path.last_name();
+    path.remove_last();
+
+    auto* dir = fs::vfs_open(*current_process->root, path);
+    if (!dir)
+        return -errno;
+    if (!S_ISDIR(dir->ind->mode))
+        return -ENOTDIR;
+
+    if (dir->find(linkname))
+        return -EEXIST;
+
+    return fs::vfs_symlink(dir, linkname.c_str(), ktarget.data());
+}
+
+int _syscall_readlink(interrupt_stack* data)
+{
+    SYSCALL_ARG1(const char* __user, pathname);
+    SYSCALL_ARG2(char* __user, buf);
+    SYSCALL_ARG3(size_t, bufsize);
+
+    if (!bufsize)
+        return -EINVAL;
+
+    std::vector<char> kpath;
+    if (int ret = copy_path(pathname, kpath); ret != 0)
+        return ret;
+
+    auto* dent = fs::vfs_open(*current_process->root,
+        types::make_path(kpath.data(), current_process->pwd), false);
+    if (!dent)
+        return -errno;
+
+    // a link longer than a page is cut short as if buf was that small
+    std::vector<char> kbuf(std::min(bufsize, (size_t)PAGE_SIZE));
+    int ret = fs::vfs_readlink(dent->ind, kbuf.data(), kbuf.size());
+    if (ret <= 0)
+        return ret;
+
+    if (kernel::user::copy_to_user(buf, kbuf.data(), ret))
+        return -EFAULT;
+    return ret;
+}
+
+int _syscall_getdents64(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, fd);
+    SYSCALL_ARG2(char* __user, buf);
+    SYSCALL_ARG3(size_t, cnt);
+
+    auto* dir = current_process->files[fd];
+    if (!dir)
+        return -EBADF;
+
+    return dir->getdents64(buf, cnt);
+}
+
+// @param filename: the file to append accounting records to,
+//                  nullptr to turn accounting off
+int _syscall_acct(interrupt_stack* data)
+{
+    SYSCALL_ARG1(const char* __user, filename);
+
+    if (current_process->euid != 0)
+        return -EPERM;
+
+    if (!filename)
+        return kernel::acct::set_file(nullptr);
+
+    std::vector<char> kpath;
+    if (int ret = copy_path(filename, kpath); ret != 0)
+        return ret;
+
+    auto* dent = fs::vfs_open(*current_process->root,
+        types::make_path(kpath.data(), current_process->pwd));
+    if (!dent)
+        return -ENOENT;
+
+    return kernel::acct::set_file(dent->ind);
+}
+
+static process* find_process(pid_t pid)
+{
+    if (!procs->try_find(pid))
+        return nullptr;
+
+    auto& proc = procs->find(pid);
+    if (proc.is_zombie())
+        return nullptr;
+    return &proc;
+}
+
+// only the signals the kernel knows about can be sent
+// @return 0 if signo is not supported
+static kernel::sig_t signal_from_user(int signo)
+{
+    switch (signo) {
+    case 2:
+        return kernel::SIGINT;
+    case 3:
+        return kernel::SIGQUIT;
+    case 13:
+        return kernel::SIGPIPE;
+    case 19:
+        return kernel::SIGSTOP;
+    default:
+        return 0;
+    }
+}
+
+static int do_tkill(process* proc, int signo)
+{
+    if (!proc)
+        return -ESRCH;
+    if (!may_signal(*proc))
+        return -EPERM;
+
+    // signal 0 only checks whether the thread exists
+    if (!signo)
+        return 0;
+
+    auto signal = signal_from_user(signo);
+    if (!signal)
+        return -EINVAL;
+
+    procs->send_signal(proc->pid, signal);
+    return 0;
+}
+
+int _syscall_pidfd_open(interrupt_stack* data)
+{
+    SYSCALL_ARG1(pid_t, pid);
+    SYSCALL_ARG2(unsigned int, flags);
+
+    if (flags & ~PIDFD_NONBLOCK)
+        return -EINVAL;
+    if (pid <= 0 || !procs->try_find(pid))
+        return -ESRCH;
+
+    auto* file = new fs::pidfd_file(pid, {
+        .read = 1,
+        .write = 0,
+        .close_on_exec = 1,
+        .direct = 0,
+        .sync = 0,
+        .append = 0,
+        }, !!(flags & PIDFD_NONBLOCK));
+
+    // the process has exited already, find its exit code
+    // in the waitlist of its parent
+    auto& proc = procs->find(pid);
+    if (proc.is_zombie()) {
+        auto& waitlist = procs->find(proc.ppid).waitlist;
+        for (const auto& item : waitlist) {
+            if (item.pid == pid)
+                file->handle_exit(pid, item.code);
+        }
+    }
+
+    return fs::anon_inode_getfd(file);
+}
+
+int _syscall_pidfd_send_signal(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, pidfd);
+    SYSCALL_ARG2(int, signo);
+    SYSCALL_ARG3(void* __user, info);
+    SYSCALL_ARG4(unsigned int, flags);
+
+    if (info || flags)
+        return -EINVAL;
+
+    auto* file = current_process->files[pidfd];
+    if (!file)
+        return -EBADF;
+
+    auto* inst = fs::anon_file_cast<fs::pidfd_file>(file);
+    if (!inst)
+        return -EBADF;
+
+    if (inst->exited())
+        return -ESRCH;
+
+    return do_tkill(find_process(inst->pid()), signo);
+}
+
+int _syscall_tkill(interrupt_stack* data)
+{
+    SYSCALL_ARG1(pid_t, tid);
+    SYSCALL_ARG2(int, signo);
+
+    if (tid <= 0)
+        return -EINVAL;
+
+    return do_tkill(find_process(tid), signo);
+}
+
+int _syscall_tgkill(interrupt_stack* data)
+{
+    SYSCALL_ARG1(pid_t, tgid);
+    SYSCALL_ARG2(pid_t, tid);
+    SYSCALL_ARG3(int, signo);
+
+    if (tgid <= 0 || tid <= 0)
+        return -EINVAL;
+
+    // the thread must belong to the thread group tgid
+    auto* proc = find_process(tid);
+    if (proc && proc->pid != tgid)
+        proc = nullptr;
+
+    return do_tkill(proc, signo);
+}
+
+// copy between the iovecs of the current process and those of proc
+// through a kernel buffer so that both sides are checked
+static ssize_t do_process_vm_rw(process& proc,
+    const iovec* __user local_iov, unsigned long liovcnt,
+    const iovec* __user remote_iov, unsigned long riovcnt, bool write)
+{
+    if (liovcnt > IOV_MAX || riovcnt > IOV_MAX)
+        return -EINVAL;
+
+    std::vector<iovec> local_vecs, remote_vecs;
+    ssize_t ret = copy_iov(local_iov, liovcnt, local_vecs);
+    if (ret < 0)
+        return ret;
+    ret = copy_iov(remote_iov, riovcnt, remote_vecs);
+    if (ret < 0)
+        return ret;
+
+    auto& local = current_process->mms;
+    auto& remote = proc.mms;
+
+    std::vector<char> buf(PAGE_SIZE);
+    ssize_t total = 0;
+
+    size_t li = 0, ri = 0;
+    size_t loff = 0, roff = 0;
+    while (li < local_vecs.size() && ri < remote_vecs.size()) {
+        const auto& lvec = local_vecs[li];
+        const auto& rvec = remote_vecs[ri];
+
+        if (loff == lvec.iov_len) {
+            ++li, loff = 0;
+            continue;
+        }
+        if (roff == rvec.iov_len) {
+            ++ri, roff = 0;
+            continue;
+        }
+
+        size_t n = buf.size();
+        if (n > lvec.iov_len - loff)
+            n = lvec.iov_len - loff;
+        if (n > rvec.iov_len - roff)
+            n = rvec.iov_len - roff;
+
+        void* laddr = (char*)lvec.iov_base + loff;
+        void* raddr = (char*)rvec.iov_base + roff;
+
+        if (write) {
+            ret = local.access(laddr, buf.data(), n, false);
+            if (ret > 0)
+                ret = remote.access(raddr, buf.data(), ret, true);
+        } else {
+            ret = remote.access(raddr, buf.data(), n, false);
+            if (ret > 0)
+                ret = local.access(laddr, buf.data(), ret, true);
+        }
+
+        if (ret < 0)
+            return total ? total : ret;
+
+        total += ret;
+        loff += ret, roff += ret;
+
+        // stop at the first partial transfer
+        if ((size_t)ret < n)
+            break;
+    }
+
+    return total;
+}
+
+int _syscall_process_vm_readv(interrupt_stack* data)
+{
+    SYSCALL_ARG1(pid_t, pid);
+    SYSCALL_ARG2(const iovec* __user, local_iov);
+    SYSCALL_ARG3(unsigned long, liovcnt);
+    SYSCALL_ARG4(const iovec* __user, remote_iov);
+    SYSCALL_ARG5(unsigned long, riovcnt);
+    SYSCALL_ARG6(unsigned long, flags);
+
+    if (flags)
+        return -EINVAL;
+
+    auto* proc = find_process(pid);
+    if (!proc)
+        return -ESRCH;
+    if (!may_access(*proc))
+        return -EPERM;
+
+    return do_process_vm_rw(*proc, local_iov, liovcnt, remote_iov, riovcnt, false);
+}
+
+int _syscall_process_vm_writev(interrupt_stack* data)
+{
+    SYSCALL_ARG1(pid_t, pid);
+    SYSCALL_ARG2(const iovec* __user, local_iov);
+    SYSCALL_ARG3(unsigned long, liovcnt);
+    SYSCALL_ARG4(const iovec* __user, remote_iov);
+    SYSCALL_ARG5(unsigned long, riovcnt);
+    SYSCALL_ARG6(unsigned long, flags);
+
+    if (flags)
+        return -EINVAL;
+
+    auto* proc = find_process(pid);
+    if (!proc)
+        return -ESRCH;
+    if (!may_access(*proc))
+        return -EPERM;
+
+    return do_process_vm_rw(*proc, local_iov, liovcnt, remote_iov, riovcnt, true);
+}
+
+// 0 if equal, otherwise 1 or 2 to give an ordering
+static int kcmp_ptr(const void* p1, const void* p2)
+{
+    if (p1 == p2)
+        return 0;
+    return p1 < p2 ? 1 : 2;
+}
+
+int _syscall_kcmp(interrupt_stack* data)
+{
+    SYSCALL_ARG1(pid_t, pid1);
+    SYSCALL_ARG2(pid_t, pid2);
+    SYSCALL_ARG3(int, type);
+    SYSCALL_ARG4(unsigned long, idx1);
+    SYSCALL_ARG5(unsigned long, idx2);
+
+    auto* proc1 = find_process(pid1);
+    auto* proc2 = find_process(pid2);
+    if (!proc1 || !proc2)
+        return -ESRCH;
+    if (!may_access(*proc1) || !may_access(*proc2))
+        return -EPERM;
+
+    // address spaces, file tables and such are never shared
+    // between processes for now, so they are compared as is
+    switch (type) {
+    case KCMP_FILE: {
+        auto* file1 = proc1->files[idx1];
+        auto* file2 = proc2->files[idx2];
+        if (!file1 || !file2)
+            return -EBADF;
+        return kcmp_ptr(file1, file2);
+    }
+    case KCMP_VM:
+        return kcmp_ptr(&proc1->mms, &proc2->mms);
+    case KCMP_FILES:
+        return kcmp_ptr(&proc1->files, &proc2->files);
+    case KCMP_FS:
+        return kcmp_ptr(proc1, proc2);
+    case KCMP_SIGHAND:
+        return kcmp_ptr(&proc1->signals, &proc2->signals);
+    default:
+        return -EINVAL;
+    }
+}
+
+int _syscall_getcpu(interrupt_stack* data)
+{
+    SYSCALL_ARG1(unsigned int* __user, cpu);
+    SYSCALL_ARG2(unsigned int* __user, node);
+
+    if (cpu && kernel::user::put_user(0, cpu))
+        return -EFAULT;
+    if (node && kernel::user::put_user(0, node))
+        return -EFAULT;
+
+    return 0;
+}
+
+int _syscall_rseq(interrupt_stack* data)
+{
+    SYSCALL_ARG1(rseq* __user, area);
+    SYSCALL_ARG2(uint32_t, len);
+    SYSCALL_ARG3(int, flags);
+    SYSCALL_ARG4(uint32_t, sig);
+
+    return kernel::rseq::set_area(area, len, flags, sig);
+}
+
+static int do_inotify_init(int flags)
+{
+    if (flags & ~(IN_NONBLOCK | IN_CLOEXEC))
+        return -EINVAL;
+
+    return fs::anon_inode_getfd(new fs::inotify_file({
+        .read = 1,
+        .write = 0,
+        .close_on_exec = !!(flags & IN_CLOEXEC),
+        .direct = 0,
+        .sync = 0,
+        .append = 0,
+        }, !!(flags & IN_NONBLOCK)));
+}
+
+int _syscall_inotify_init(interrupt_stack*)
+{
+    return do_inotify_init(0);
+}
+
+int _syscall_inotify_init1(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, flags);
+    return do_inotify_init(flags);
+}
+
+int _syscall_inotify_add_watch(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, fd);
+    SYSCALL_ARG2(const char* __user, pathname);
+    SYSCALL_ARG3(uint32_t, mask);
+
+    auto* file = current_process->files[fd];
+    if (!file)
+        return -EBADF;
+
+    auto* inst = fs::anon_file_cast<fs::inotify_file>(file);
+    if (!inst)
+        return -EINVAL;
+
+    std::vector<char> kpath;
+    if (int ret = copy_path(pathname, kpath); ret != 0)
+        return ret;
+
+    auto* dent = fs::vfs_open(*current_process->root,
+        types::make_path(kpath.data(), current_process->pwd));
+    if (!dent)
+        return -ENOENT;
+
+    return inst->add_watch(dent->ind, mask);
+}
+
+int _syscall_inotify_rm_watch(interrupt_stack* data)
+{
+    SYSCALL_ARG1(int, fd);
+    SYSCALL_ARG2(int, wd);
+
+    auto* file = current_process->files[fd];
+    if (!file)
+        return -EBADF;
+
+    auto* inst = fs::anon_file_cast<fs::inotify_file>(file);
+    if (!inst)
+        return -EINVAL;
+
+    return inst->rm_watch(wd);
+}
+
+struct syscall_table_entry {
+    int no;
+    syscall_handler handler;
+    const char* name;
+    // how each argument is shown in the trace, see format_syscall_arg()
+    const char* args;
+    // the handler never returns, so the trace is printed before it runs
+    bool noreturn {};
+};
+
+// the entries of syscall_table by number, for the trace
+static const syscall_table_entry* syscall_entries[SYSCALL_HANDLERS_SIZE];
+
+static bool s_syscall_trace;
+
+int syscall_trace(void)
+{
+    return s_syscall_trace;
+}
+
+int set_syscall_trace(int val)
+{
+    if (val < 0 || val > 1)
+        return -EINVAL;
+
+    s_syscall_trace = val;
+    return 0;
+}
+
+// strings in user memory longer than this are cut short in the trace
+static constexpr size_t TRACE_STR_MAX = 32;
+
+// show an argument of a syscall in the trace as given by kind
+//   d: signed decimal, u: unsigned decimal, x: hex, o: octal,
+//   p: pointer, s: string in user memory
+// @return the same as snprintf
+static int format_syscall_arg(char* buf, size_t buf_size, char kind, uint32_t val)
+{
+    switch (kind) {
+    case 'd':
+        return snprintf(buf, buf_size, "%d", (int)val);
+    case 'u':
+        return snprintf(buf, buf_size, "%lld", (long long)val);
+    case 'o': {
+        char digits[12];
+        int n = 0;
+        do {
+            digits[n++] = '0' + (val & 7);
+            val >>= 3;
+        } while (val);
+
+        size_t len = 0;
+        for (int i = -1; i < n; ++i) {
+            if (len + 1 < buf_size)
+                buf[len] = i < 0 ? '0' : digits[n - 1 - i];
+            ++len;
+        }
+        if (buf_size)
+            buf[std::min(len, buf_size - 1)] = 0;
+        return len;
+    }
+    case 's': {
+        char str[TRACE_STR_MAX + 1];
+        long len = val
+            ? kernel::user::strncpy_from_user(str, (const char __user*)val, sizeof(str))
+            : -EFAULT;
+        // not readable, show where it is
+        if (len < 0)
+            return snprintf(buf, buf_size, "%x", val);
+
+        // the string is copied as is, since snprintf takes
+        // the string given to %s as a format
+        size_t out = 0;
+        auto putc = [&](char c) {
+            if (out + 1 < buf_size)
+                buf[out] = c;
+            ++out;
+        };
+
+        putc('"');
+        for (long i = 0; i < len && i < (long)TRACE_STR_MAX; ++i) {
+            char c = str[i];
+            if (c == '"' || c == '\\') {
+                putc('\\');
+                putc(c);
+            } else if (c == '\n') {
+                putc('\\');
+                putc('n');
+            } else {
+                putc(c >= 0x20 && c < 0x7f ? c : '?');
+            }
+        }
+        putc('"');
+        if (len > (long)TRACE_STR_MAX) {
+            putc('.');
+            putc('.');
+            putc('.');
+        }
+
+        if (buf_size)
+            buf[std::min(out, buf_size - 1)] = 0;
+        return out;
+    }
+    case 'x':
+    case 'p':
+    default:
+        return snprintf(buf, buf_size, "%x", val);
+    }
+}
+
+// "pid 3: open("/etc/passwd", 0x0, 0644)", the arguments are read
+// before the syscall runs, as it may change or unmap them
+static void format_syscall(char* buf, size_t buf_size,
+    const syscall_table_entry& ent, const interrupt_stack* data)
+{
+    const uint32_t args[] = {
+        data->s_regs.ebx, data->s_regs.ecx, data->s_regs.edx,
+        data->s_regs.esi, data->s_regs.edi, data->s_regs.ebp,
+    };
+
+    size_t len = snprintf(buf, buf_size, "pid %d: %s(",
+        current_process->pid, ent.name);
+
+    for (int i = 0; ent.args[i] && len < buf_size; ++i) {
+        if (i)
+            len += snprintf(buf + len, buf_size - len, ", ");
+        if (len < buf_size)
+            len += format_syscall_arg(buf + len, buf_size - len, ent.args[i], args[i]);
+    }
+
+    if (len < buf_size)
+        snprintf(buf + len, buf_size - len, ")");
+}
+
+extern "C" void syscall_entry(interrupt_stack* data)
+{
+    int syscall_no = SYSCALL_NO;
+
+    if (syscall_no >= SYSCALL_HANDLERS_SIZE
+        || !syscall_handlers[syscall_no]) {
+        char buf[64];
+        snprintf(buf, 64,
+            "[kernel] syscall %x not implemented\n", syscall_no);
+        console->print(buf);
+        kill_current(-1);
+    }
+
+    char trace[256];
+    auto* ent = syscall_entries[syscall_no];
+    bool traced = s_syscall_trace && ent;
+    if (traced) {
+        format_syscall(trace, sizeof(trace), *ent, data);
+
+        if (ent->noreturn) {
+            kmsg("[strace] ");
+            kmsg(trace);
+            kmsg(" = ?\n");
+        }
+    }
+
+    int ret = syscall_handlers[syscall_no](data);
+
+    if (traced) {
+        char retbuf[24];
+        snprintf(retbuf, sizeof(retbuf), " = %d\n", ret);
+        kmsg("[strace] ");
+        kmsg(trace);
+        kmsg(retbuf);
+    }
+
+    SYSCALL_RETVAL = ret;
+
+    check_signal();
+}
+
+// syscall numbers follow the i386 linux abi
+static constexpr syscall_table_entry syscall_table[] = {
+    { 0x01, _syscall_exit, "exit", "d", true },
+    { 0x02, _syscall_fork, "fork", "" },
+    { 0x03, _syscall_read, "read", "dpu" },
+    { 0x04, _syscall_write, "write", "dpu" },
+    { 0x05, _syscall_open, "open", "sxo" },
+    { 0x06, _syscall_close, "close", "d" },
+    { 0x07, _syscall_waitpid, "waitpid", "dpx" },
+    { 0x09, _syscall_link, "link", "ss" },
+    { 0x0a, _syscall_unlink, "unlink", "s" },
+    { 0x0b, _syscall_execve, "execve", "spp" },
+    { 0x0c, _syscall_chdir, "chdir", "s" },
+    { 0x0e, _syscall_mknod, "mknod", "sou" },
+    { 0x14, _syscall_getpid, "getpid", "" },
+    { 0x29, _syscall_dup, "dup", "d" },
+    { 0x2a, _syscall_pipe, "pipe", "p" },
+    { 0x2d, _syscall_brk, "brk", "p" },
+    { 0x33, _syscall_acct, "acct", "s" },
+    { 0x36, _syscall_ioctl, "ioctl", "dxx" },
+    { 0x39, _syscall_setpgid, "setpgid", "dd" },
+    { 0x3d, _syscall_chroot, "chroot", "s" },
+    { 0x3f, _syscall_dup2, "dup2", "dd" },
+    { 0x40, _syscall_getppid, "getppid", "" },
+    { 0x42, _syscall_setsid, "setsid", "" },
+    { 0x53, _syscall_symlink, "symlink", "ss" },
+    { 0x55, _syscall_readlink, "readlink", "spu" },
+    { 0x5b, _syscall_munmap, "munmap", "pu" },
+    { 0x63, _syscall_statfs, "statfs", "sp" },
+    { 0x64, _syscall_fstatfs, "fstatfs", "dp" },
+    { 0x76, _syscall_fsync, "fsync", "d" },
+    { 0x7d, _syscall_mprotect, "mprotect", "pux" },
+    { 0x84, _syscall_getdents, "getdents", "dpu" },
+    { 0x91, _syscall_readv, "readv", "dpd" },
+    { 0x92, _syscall_writev, "writev", "dpd" },
+    { 0x93, _syscall_getsid, "getsid", "d" },
+    { 0x94, _syscall_fdatasync, "fdatasync", "d" },
+    { 0xac, _syscall_prctl, "prctl", "dp" },
+    { 0xb4, _syscall_pread64, "pread64", "dpuuu" },
+    { 0xb5, _syscall_pwrite64, "pwrite64", "dpuuu" },
+    { 0xb7, _syscall_getcwd, "getcwd", "pu" },
+    { 0xc0, _syscall_mmap_pgoff, "mmap_pgoff", "puxxdd" },
+    { 0xc7, _syscall_getuid, "getuid", "" },
+    { 0xc8, _syscall_getgid, "getgid", "" },
+    { 0xc9, _syscall_geteuid, "geteuid", "" },
+    { 0xca, _syscall_getegid, "getegid", "" },
+    { 0xd5, _syscall_setuid, "setuid", "u" },
+    { 0xd6, _syscall_setgid, "setgid", "u" },
+    { 0xdc, _syscall_getdents64, "getdents64", "dpu" },
+    { 0xdd, _syscall_fcntl64, "fcntl64", "ddx" },
+    { 0xe0, _syscall_gettid, "gettid", "" },
+    { 0xee, _syscall_tkill, "tkill", "dd" },
+    { 0xef, _syscall_sendfile64, "sendfile64", "ddpu" },
+    { 0xf3, _syscall_set_thread_area, "set_thread_area", "p" },
+    { 0xfc, _syscall_exit, "exit_group", "d", true }, // we implement exit_group as exit for now
+    { 0x102, _syscall_set_tid_address, "set_tid_address", "p" },
+    { 0x10e, _syscall_tgkill, "tgkill", "ddd" },
+    { 0x11c, _syscall_waitid, "waitid", "ddpx" },
+    { 0x123, _syscall_inotify_init, "inotify_init", "" },
+    { 0x124, _syscall_inotify_add_watch, "inotify_add_watch", "dsx" },
+    { 0x125, _syscall_inotify_rm_watch, "inotify_rm_watch", "dd" },
+    { 0x12f, _syscall_linkat, "linkat", "dsdsx" },
+    { 0x139, _syscall_splice, "splice", "dpdpux" },
+    { 0x13b, _syscall_tee, "tee", "ddux" },
+    { 0x13c, _syscall_vmsplice, "vmsplice", "dpux" },
+    { 0x13e, _syscall_getcpu, "getcpu", "pp" },
+    { 0x14c, _syscall_inotify_init1, "inotify_init1", "x" },
+    { 0x14d, _syscall_preadv, "preadv", "dpduu" },
+    { 0x14e, _syscall_pwritev, "pwritev", "dpduu" },
+    { 0x158, _syscall_syncfs, "syncfs", "d" },
+    { 0x15b, _syscall_process_vm_readv, "process_vm_readv", "dpupux" },
+    { 0x15c, _syscall_process_vm_writev, "process_vm_writev", "dpupux" },
+    { 0x15d, _syscall_kcmp, "kcmp", "ddduu" },
+    { 0x179, _syscall_copy_file_range, "copy_file_range", "dpdpux" },
+    { 0x17f, _syscall_statx, "statx", "dsxxp" },
+    { 0x182, _syscall_rseq, "rseq", "puxx" },
+    { 0x193, _syscall_clock_gettime64, "clock_gettime64", "dp" },
+    { 0x19c, _syscall_utimensat_time64, "utimensat_time64", "dspx" },
+    { 0x1a8, _syscall_pidfd_send_signal, "pidfd_send_signal", "ddpx" },
+    { 0x1b2, _syscall_pidfd_open, "pidfd_open", "dx" },
+    // { 35, _syscall_sleep },
+};
+
+static constexpr std::size_t SYSCALL_TABLE_SIZE =
+    sizeof(syscall_table) / sizeof(syscall_table[0]);
+
+static consteval bool syscall_args_valid(const char* args)
+{
+    int cnt = 0;
+    for (; *args; ++args, ++cnt) {
+        switch (*args) {
+        case 'd': case 'u': case 'x': case 'o': case 'p': case 's':
+            break;
+        default:
+            return false;
+        }
+    }
+    return cnt <= 6;
+}
+
+static consteval bool syscall_table_valid(void)
+{
+    for (const auto& ent : syscall_table) {
+        if (ent.no < 0 || ent.no >= SYSCALL_HANDLERS_SIZE || !ent.handler)
+            return false;
+        if (!ent.name || !ent.args || !syscall_args_valid(ent.args))
+            return false;
+    }
+
+    for (std::size_t i = 0; i < SYSCALL_TABLE_SIZE; ++i) {
+        for (std::size_t j = i + 1; j < SYSCALL_TABLE_SIZE; ++j) {
+            if (syscall_table[i].no == syscall_table[j].no)
+                return false;
+        }
+    }
+
+    return true;
+}
+static_assert(syscall_table_valid(),
+    "duplicate or out of range syscall number, or bad argument "
+    "formats in syscall_table");
+
+SECTION(".text.kinit")
+void init_syscall(void)
+{
+    memset(syscall_handlers, 0x00, sizeof(syscall_handlers));
+
+    for (const auto& ent : syscall_table) {
+        syscall_handlers[ent.no] = ent.handler;
+        syscall_entries[ent.no] = &ent;
+    }
 }
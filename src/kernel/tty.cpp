@@ -1,15 +1,36 @@
+#include <asm/port_io.h>
+#include <bits/ioctl.h>
+#include <kernel/errno.h>
 #include <kernel/event/evtqueue.hpp>
 #include <kernel/hw/serial.h>
 #include <kernel/process.hpp>
 #include <kernel/tty.hpp>
+#include <kernel/user/uaccess.hpp>
 #include <kernel/vga.hpp>
 #include <stdint.h>
 #include <stdio.h>
 #include <types/lock.hpp>
 
+tty* console __ro_after_init;
+
 tty::tty()
     : buf(BUFFER_SIZE)
+    , m_termios {
+        .c_iflag = ICRNL,
+        .c_oflag = 0,
+        .c_cflag = B38400 | CS8 | CREAD,
+        .c_lflag = ISIG | ICANON | ECHO | ECHOE | ECHOK,
+        .c_line = 0,
+        .c_cc = {},
+    }
 {
+    m_termios.c_cc[VINTR] = 0x03;
+    m_termios.c_cc[VQUIT] = 0x1c;
+    m_termios.c_cc[VERASE] = 0x7f;
+    m_termios.c_cc[VKILL] = 0x15;
+    m_termios.c_cc[VEOF] = 0x04;
+    m_termios.c_cc[VMIN] = 1;
+    m_termios.c_cc[VSUSP] = 0x1a;
 }
 
 void tty::print(const char* str)
@@ -18,30 +39,120 @@ void tty::print(const char* str)
         this->putchar(*(str++));
 }
 
-size_t tty::read(char* buf, size_t buf_size, size_t n)
+bool tty::is_line_end(char c) const
+{
+    return c == '\n' || c == (char)m_termios.c_cc[VEOF];
+}
+
+void tty::count_lines(void)
 {
-    size_t orig_n = n;
+    m_lines = 0;
+    if (!lflag(ICANON))
+        return;
+
+    for (size_t i = 0; i < buf.size(); ++i) {
+        if (is_line_end(buf.peek(i)))
+            ++m_lines;
+    }
+}
+
+void tty::set_session(pid_t sid)
+{
+    types::spin_guard lck(m_lock);
+    types::lock_guard cv_lck(m_cv.mtx());
+
+    m_sid = sid;
+    m_cv.reopen();
+}
+
+void tty::hangup(void)
+{
+    types::spin_guard lck(m_lock);
+    types::lock_guard cv_lck(m_cv.mtx());
+
+    while (!buf.empty())
+        buf.get();
+    m_lines = 0;
+
+    m_sid = 0;
+    m_cv.close();
+}
+
+ssize_t tty::read(char* buf, size_t buf_size, size_t n)
+{
+    uint32_t flags = m_lock.lock();
+
+    // in canonical mode, wait for a whole line or an EOF
+    while (lflag(ICANON) ? !m_lines : this->buf.empty()) {
+        if (!this->m_cv.wait(m_lock, flags)) {
+            bool hung_up = m_cv.closed();
+            m_lock.unlock(flags);
+            return hung_up ? 0 : -EINTR;
+        }
+    }
 
-    while (buf_size && n) {
-        auto& mtx = this->m_cv.mtx();
-        types::lock_guard lck(mtx);
+    size_t cnt = 0;
+    while (cnt < buf_size && cnt < n && !this->buf.empty()) {
+        char c = this->buf.get();
 
-        if (this->buf.empty()) {
-            bool intr = !this->m_cv.wait(mtx);
+        if (lflag(ICANON) && is_line_end(c)) {
+            --m_lines;
 
-            if (intr || this->buf.empty())
+            // the EOF character itself is not passed on, and a read
+            // that gets nothing but it returns 0
+            if (c != '\n')
                 break;
         }
 
-        *buf = this->buf.get();
-        --buf_size;
-        --n;
+        buf[cnt++] = c;
 
-        if (*(buf++) == '\n')
+        if (lflag(ICANON) && c == '\n')
             break;
     }
 
-    return orig_n - n;
+    m_lock.unlock(flags);
+    return cnt;
+}
+
+int tty::ioctl(unsigned long request, uintptr_t arg)
+{
+    switch (request) {
+    case TCGETS: {
+        struct termios termios;
+        {
+            types::spin_guard lck(m_lock);
+            termios = m_termios;
+        }
+
+        if (kernel::user::copy_to_user((void*)arg, &termios, sizeof(termios)))
+            return -EFAULT;
+        return 0;
+    }
+    case TCSETSW:
+    case TCSETSF:
+    case TCSETS: {
+        struct termios termios;
+        if (kernel::user::copy_from_user(&termios, (const void*)arg, sizeof(termios)))
+            return -EFAULT;
+
+        // there's no output queued anywhere but in the driver
+        if (request != TCSETS)
+            flush();
+        {
+            types::spin_guard lck(m_lock);
+            if (request == TCSETSF) {
+                while (!buf.empty())
+                    buf.get();
+            }
+            m_termios = termios;
+            count_lines();
+        }
+        m_cv.notify_all();
+        return 0;
+    }
+    default:
+        return -ENOTTY;
+    }
 }
 
 vga_tty::vga_tty()
@@ -49,15 +160,82 @@ vga_tty::vga_tty()
     snprintf(this->name, sizeof(this->name), "ttyVGA");
 }
 
-serial_tty::serial_tty(int id)
-    : id(id)
+serial_tty::serial_tty(int index, uint16_t port)
+    : m_tx(TX_BUFFER_SIZE)
+    , id(port)
 {
-    snprintf(this->name, sizeof(this->name), "ttyS%x", (int)id);
+    snprintf(this->name, sizeof(this->name), "ttyS%d", index);
+
+    // IIR bits 6 and 7 are set if the fifo is enabled (16550A)
+    m_fifo_size = (asm_inb(id + 2) & 0xc0) == 0xc0 ? 16 : 1;
 }
 
 void serial_tty::putchar(char c)
 {
-    serial_send_data(id, c);
+    uint32_t flags = types::irq_save();
+
+    // make room by sending the oldest character ourselves, so the
+    // writers only have to wait for the uart when the ring is full
+    if (m_tx.full())
+        serial_send_data(id, m_tx.get());
+    m_tx.put(c);
+
+    // the transmitter interrupts right away if it's idle
+    if (!m_tx_irq) {
+        m_tx_irq = true;
+        asm_outb(id + 1, 0x03);
+    }
+
+    types::irq_restore(flags);
+}
+
+void serial_tty::tx_ready(void)
+{
+    for (size_t i = 0; i < m_fifo_size && !m_tx.empty(); ++i)
+        asm_outb(id, m_tx.get());
+
+    // keep only the received data interrupt until more is queued
+    if (m_tx.empty() && m_tx_irq) {
+        m_tx_irq = false;
+        asm_outb(id + 1, 0x01);
+    }
+}
+
+void serial_tty::flush(void)
+{
+    uint32_t flags = types::irq_save();
+    while (!m_tx.empty())
+        serial_send_data(id, m_tx.get());
+    tx_ready();
+    types::irq_restore(flags);
+}
+
+void serial_tty::handle_irq(void)
+{
+    // IIR bit 0 is cleared as long as there are interrupts pending
+    uint8_t iir;
+    while (!((iir = asm_inb(id + 2)) & 1)) {
+        switch ((iir >> 1) & 0x7) {
+        // modem status changed
+        case 0:
+            asm_inb(id + 6);
+            break;
+        // transmitter holding register empty
+        case 1:
+            tx_ready();
+            break;
+        // received data available, or timed out in the fifo
+        case 2:
+        case 6:
+            while (is_serial_has_data(id))
+                recvchar(serial_read_data(id));
+            break;
+        // line status changed
+        case 3:
+            asm_inb(id + 5);
+            break;
+        }
+    }
 }
 
 void vga_tty::putchar(char c)
@@ -70,83 +248,107 @@ void vga_tty::putchar(char c)
 void vga_tty::recvchar(char c)
 {
     // TODO: keyboard scan code
+    types::spin_guard lck(m_lock);
     buf.put(c);
 }
 
+void serial_tty::echo(char c)
+{
+    if (lflag(ECHO))
+        putchar(c);
+}
+
 void serial_tty::recvchar(char c)
 {
-    switch (c) {
-    case '\r':
-        buf.put('\n');
-        if (echo) {
-            serial_send_data(id, '\r');
-            serial_send_data(id, '\n');
+    types::spin_guard lck(m_lock);
+
+    if (c == '\r') {
+        if (iflag(IGNCR))
+            return;
+        if (iflag(ICRNL))
+            c = '\n';
+    }
+
+    if (lflag(ISIG)) {
+        int signo = 0;
+        if (c == m_termios.c_cc[VINTR])
+            signo = kernel::SIGINT;
+        else if (c == m_termios.c_cc[VQUIT])
+            signo = kernel::SIGQUIT;
+        else if (c == m_termios.c_cc[VSUSP])
+            signo = kernel::SIGSTOP;
+
+        if (signo) {
+            procs->send_signal_grp(fg_pgroup, signo);
+            this->m_cv.notify();
+            return;
         }
-        this->m_cv.notify();
-        break;
-    // ^?: backspace
-    case 0x7f:
-        if (!buf.empty() && buf.back() != '\n') {
-            buf.pop();
-
-            if (echo) {
-                serial_send_data(id, 0x08);
-                serial_send_data(id, '\x1b');
-                serial_send_data(id, '[');
-                serial_send_data(id, 'K');
+    }
+
+    if (lflag(ICANON)) {
+        if (c == '\n') {
+            if (buf.put('\n') != EOF)
+                ++m_lines;
+            if (lflag(ECHO) || lflag(ECHONL)) {
+                putchar('\r');
+                putchar('\n');
             }
+            this->m_cv.notify();
+            return;
         }
-        break;
-    // ^U: clear the line
-    case 0x15:
-        while (!buf.empty() && buf.back() != '\n') {
-            buf.pop();
-
-            if (echo) {
-                // clear the line
-                // serial_send_data(id, '\r');
-                // serial_send_data(id, '\x1b');
-                // serial_send_data(id, '[');
-                // serial_send_data(id, '2');
-                // serial_send_data(id, 'K');
-                serial_send_data(id, 0x08);
-                serial_send_data(id, '\x1b');
-                serial_send_data(id, '[');
-                serial_send_data(id, 'K');
+
+        if (c == m_termios.c_cc[VERASE]) {
+            if (!buf.empty() && !is_line_end(buf.back())) {
+                buf.pop();
+
+                if (lflag(ECHOE)) {
+                    echo(0x08);
+                    echo('\x1b');
+                    echo('[');
+                    echo('K');
+                }
             }
+            return;
         }
-        break;
-    // ^C: SIGINT
-    case 0x03:
-        procs->send_signal_grp(fg_pgroup, kernel::SIGINT);
-        this->m_cv.notify();
-        break;
-    // ^D: EOF
-    case 0x04:
-        this->m_cv.notify();
-        break;
-    // ^Z: SIGSTOP
-    case 0x1a:
-        procs->send_signal_grp(fg_pgroup, kernel::SIGSTOP);
-        this->m_cv.notify();
-        break;
-    // ^[: ESCAPE
-    case 0x1b:
-        buf.put('\x1b');
-        if (echo) {
-            serial_send_data(id, '^');
-            serial_send_data(id, '[');
+
+        // clear the line
+        if (c == m_termios.c_cc[VKILL]) {
+            while (!buf.empty() && !is_line_end(buf.back())) {
+                buf.pop();
+
+                if (lflag(ECHOK)) {
+                    echo(0x08);
+                    echo('\x1b');
+                    echo('[');
+                    echo('K');
+                }
+            }
+            return;
         }
-        break;
-    // ^\: SIGQUIT
-    case 0x1c:
-        procs->send_signal_grp(fg_pgroup, kernel::SIGQUIT);
-        this->m_cv.notify();
-        break;
-    default:
-        buf.put(c);
-        if (echo)
-            serial_send_data(id, c);
-        break;
+
+        // ^D: pass on the current line without a newline,
+        // or make the read return 0 if the line is empty
+        if (c == m_termios.c_cc[VEOF]) {
+            if (buf.put(c) != EOF)
+                ++m_lines;
+            this->m_cv.notify();
+            return;
+        }
+    }
+
+    // keep the last slot for the line end, so that a line
+    // filling up the buffer can still be completed
+    if (lflag(ICANON) && buf.avail() <= 1)
+        return;
+
+    buf.put(c);
+    if (c == '\x1b') {
+        echo('^');
+        echo('[');
+    } else {
+        echo(c);
     }
+
+    if (!lflag(ICANON))
+        this->m_cv.notify();
 }
@@ -0,0 +1,152 @@
+#include <kernel/errno.h>
+#include <kernel/mem.h>
+#include <kernel/user/uaccess.hpp>
+
+// provided by the linker script
+extern "C" const kernel::user::exception_table_entry __ex_table_start[];
+extern "C" const kernel::user::exception_table_entry __ex_table_end[];
+
+namespace kernel::user {
+
+uintptr_t search_exception_table(uintptr_t eip)
+{
+    for (auto* ent = __ex_table_start; ent < __ex_table_end; ++ent) {
+        if (ent->insn == eip)
+            return ent->fixup;
+    }
+    return 0;
+}
+
+// a fault in rep movsb continues right after it with
+// the number of bytes left in ecx
+static std::size_t raw_copy(void* dst, const void* src, std::size_t n)
+{
+    asm volatile(
+        "1: rep movsb\n"
+        "2:\n"
+        ".pushsection __ex_table, \"a\"\n"
+        ".balign 4\n"
+        ".long 1b, 2b\n"
+        ".popsection\n"
+        : "+c"(n), "+D"(dst), "+S"(src)
+        :
+        : "memory");
+    return n;
+}
+
+std::size_t copy_from_user(void* dst, const void __user* src, std::size_t n)
+{
+    if (!access_ok(src, n))
+        return n;
+    return raw_copy(dst, src, n);
+}
+
+std::size_t copy_to_user(void __user* dst, const void* src, std::size_t n)
+{
+    if (!access_ok(dst, n))
+        return n;
+    return raw_copy(dst, src, n);
+}
+
+long strncpy_from_user(char* dst, const char __user* src, std::size_t n)
+{
+    // copy no further than the end of a page at a time,
+    // the string may end right before an unmapped one
+    std::size_t copied = 0;
+    while (copied < n) {
+        auto addr = (uintptr_t)src + copied;
+        std::size_t len = PAGE_SIZE - (addr & (PAGE_SIZE - 1));
+        if (len > n - copied)
+            len = n - copied;
+
+        if (copy_from_user(dst + copied, (const char __user*)addr, len))
+            return -EFAULT;
+
+        for (std::size_t i = 0; i < len; ++i) {
+            if (!dst[copied + i])
+                return copied + i;
+        }
+        copied += len;
+    }
+
+    return n;
+}
+
+int get_user(uint32_t& val, const uint32_t __user* addr)
+{
+    if (!access_ok(addr, sizeof(*addr)))
+        return -EFAULT;
+
+    int ret = 0;
+    uint32_t tmp;
+    asm volatile(
+        "1: movl (%[addr]), %[val]\n"
+        "2:\n"
+        ".pushsection .text.fixup, \"ax\"\n"
+        "3: movl %[efault], %[ret]\n"
+        "   jmp 2b\n"
+        ".popsection\n"
+        ".pushsection __ex_table, \"a\"\n"
+        ".balign 4\n"
+        ".long 1b, 3b\n"
+        ".popsection\n"
+        : [ret] "+r"(ret), [val] "=&r"(tmp)
+        : [addr] "r"(addr), [efault] "i"(-EFAULT)
+        : "memory");
+
+    if (ret == 0)
+        val = tmp;
+    return ret;
+}
+
+int put_user(uint32_t val, uint32_t __user* addr)
+{
+    if (!access_ok(addr, sizeof(*addr)))
+        return -EFAULT;
+
+    int ret = 0;
+    asm volatile(
+        "1: movl %[val], (%[addr])\n"
+        "2:\n"
+        ".pushsection .text.fixup, \"ax\"\n"
+        "3: movl %[efault], %[ret]\n"
+        "   jmp 2b\n"
+        ".popsection\n"
+        ".pushsection __ex_table, \"a\"\n"
+        ".balign 4\n"
+        ".long 1b, 3b\n"
+        ".popsection\n"
+        : [ret] "+r"(ret)
+        : [val] "r"(val), [addr] "r"(addr), [efault] "i"(-EFAULT)
+        : "memory");
+
+    return ret;
+}
+
+int cmpxchg_user(uint32_t __user* addr,
+    uint32_t expected, uint32_t desired, uint32_t& cur)
+{
+    if (!access_ok(addr, sizeof(*addr)))
+        return -EFAULT;
+
+    int ret = 0;
+    asm volatile(
+        "1: lock cmpxchgl %[desired], (%[addr])\n"
+        "2:\n"
+        ".pushsection .text.fixup, \"ax\"\n"
+        "3: movl %[efault], %[ret]\n"
+        "   jmp 2b\n"
+        ".popsection\n"
+        ".pushsection __ex_table, \"a\"\n"
+        ".balign 4\n"
+        ".long 1b, 3b\n"
+        ".popsection\n"
+        : [ret] "+r"(ret), "+a"(expected)
+        : [desired] "r"(desired), [addr] "r"(addr), [efault] "i"(-EFAULT)
+        : "memory");
+
+    cur = expected;
+    return ret;
+}
+
+} // namespace kernel::user
@@ -5,13 +5,20 @@
 #include <utility>
 
 #include <bits/alltypes.h>
+#include <bits/ioctl.h>
 
 #include <assert.h>
+#include <fs/devtmpfs.hpp>
+#include <fs/sysfs.hpp>
 #include <kernel/errno.h>
+#include <kernel/hw/timer.h>
+#include <kernel/inotify.hpp>
 #include <kernel/log.hpp>
 #include <kernel/mem.h>
+#include <kernel/pagecache.hpp>
 #include <kernel/process.hpp>
 #include <kernel/tty.hpp>
+#include <kernel/user/uaccess.hpp>
 #include <kernel/vfs.hpp>
 #include <stdint.h>
 #include <stdio.h>
@@ -29,52 +36,131 @@ struct tmpfs_file_entry {
 };
 
 fs::vfs::dentry::dentry(dentry* _parent, inode* _ind, name_type _name)
-    : parent(_parent) , ind(_ind) , flags { } , name(_name)
+    : parent(_parent) , ind(_ind) , type(_ind ? IFTODT(_ind->mode) : DT_UNKNOWN)
+    , flags { } , name(std::move(_name))
 {
-    if (!_ind || S_ISDIR(ind->mode)) {
-        children = types::pnew<allocator_type>(children);
-        idx_children = types::pnew<allocator_type>(idx_children);
-    }
+}
+
+void fs::vfs::dentry::alloc_children(void)
+{
+    children = types::pnew<allocator_type>(children);
+    idx_children = types::pnew<allocator_type>(idx_children, INITIAL_INDEX_BUCKETS);
 }
 
 fs::vfs::dentry* fs::vfs::dentry::append(inode* ind, const name_type& name, bool set_dirty)
 {
-    auto& ent = children->emplace_back(this, ind, name);
-    idx_children->emplace(ent.name, &ent);
-    if (set_dirty)
-        this->flags.in.dirty = 1;
-    return &ent;
+    return append(ind, name_type { name }, set_dirty);
 }
 fs::vfs::dentry* fs::vfs::dentry::append(inode* ind, name_type&& name, bool set_dirty)
 {
+    if (!children)
+        alloc_children();
+
     auto& ent = children->emplace_back(this, ind, std::move(name));
     idx_children->emplace(ent.name, &ent);
+
+    // keep the chains short for large directories
+    auto nbuckets = idx_children->bucket_count();
+    if (idx_children->size() > nbuckets * 2)
+        idx_children->rehash(nbuckets * 2);
+
     if (set_dirty)
         this->flags.in.dirty = 1;
     return &ent;
 }
 fs::vfs::dentry* fs::vfs::dentry::find(const name_type& name)
 {
-    if (!S_ISDIR(ind->mode))
+    return find(name.c_str(), name.size());
+}
+fs::vfs::dentry* fs::vfs::dentry::find(const char* name, std::size_t len)
+{
+    if (type != DT_DIR)
         return nullptr;
 
-    if (name[0] == '.') {
-        if (!name[1])
+    if (len && name[0] == '.') {
+        if (len == 1)
             return this;
-        if (name[1] == '.' && !name[2])
+        if (len == 2 && name[1] == '.')
             return parent ? parent : this;
     }
 
     if (!flags.in.present)
         ind->fs->load_dentry(this);
 
+    // empty directories have no index
+    if (idx_children) {
+        auto iter = idx_children->find_as(types::string_view { name, len });
+        if (iter)
+            return iter->second;
+    }
+
+    errno = ENOTFOUND;
+    return nullptr;
+}
+void fs::vfs::dentry::erase_child(dentry* ent)
+{
+    for (auto child = children->begin(); child != children->end(); ++child) {
+        if (&*child == ent) {
+            children->erase(child);
+            break;
+        }
+    }
+}
+bool fs::vfs::dentry::remove(const name_type& name)
+{
+    if (!flags.in.present || !idx_children)
+        return true;
+
     auto iter = idx_children->find(name);
-    if (!iter) {
-        errno = ENOTFOUND;
-        return nullptr;
+    if (!iter)
+        return true;
+
+    auto* ent = iter->second;
+    idx_children->remove(iter);
+
+    // out of the index, it can't be found anymore
+    if (ent->in_use()) {
+        ent->removed = true;
+        return false;
+    }
+
+    erase_child(ent);
+    return true;
+}
+void fs::vfs::dentry::get(void)
+{
+    ++refs;
+    ++ind->refs;
+}
+void fs::vfs::dentry::put(void)
+{
+    --refs;
+    --ind->refs;
+
+    // free the entries removed while in use, this one or the
+    // directories it's in, once nothing is opened in them
+    for (auto* ent = this; ent; ) {
+        auto* parent = ent->parent;
+        if (ent->removed && !ent->in_use()) {
+            auto* ind = ent->ind;
+            parent->erase_child(ent);
+            ind->fs->inode_release(ind);
+        }
+        ent = parent;
     }
+}
+bool fs::vfs::dentry::in_use(void) const
+{
+    if (refs)
+        return true;
 
-    return iter->second;
+    if (children) {
+        for (const auto& child : *children) {
+            if (child.in_use())
+                return true;
+        }
+    }
+    return false;
 }
 fs::vfs::dentry* fs::vfs::dentry::replace(dentry* val)
 {
@@ -86,8 +172,10 @@ void fs::vfs::dentry::invalidate(void)
 {
     // TODO: write back
     flags.in.dirty = 0;
-    children->clear();
-    idx_children->clear();
+    if (children) {
+        children->clear();
+        idx_children->clear();
+    }
     flags.in.present = 0;
 }
 fs::vfs::vfs(void)
@@ -118,6 +206,10 @@ fs::inode* fs::vfs::cache_inode(size_t size, ino_t ino,
 {
     auto [ iter, inserted ] =
         _inodes.try_emplace(ino, inode { ino, this, size, mode, uid, gid });
+    if (inserted) {
+        auto now = current_time();
+        iter->second.atime = iter->second.mtime = iter->second.ctime = now;
+    }
     return &iter->second;
 }
 fs::inode* fs::vfs::get_inode(ino_t ino)
@@ -129,10 +221,23 @@ fs::inode* fs::vfs::get_inode(ino_t ino)
     else
         return nullptr;
 }
+void fs::vfs::free_inode(ino_t ino)
+{
+    auto iter = _inodes.find(ino);
+    if (!iter)
+        return;
+
+    // another inode may be put at the same address
+    if (pcache)
+        pcache->invalidate(&iter->second);
+    _inodes.erase(iter);
+}
 void fs::vfs::register_root_node(inode* root)
 {
-    if (!_root.ind)
+    if (!_root.ind) {
         _root.ind = root;
+        _root.type = IFTODT(root->mode);
+    }
 }
 int fs::vfs::load_dentry(dentry* ent)
 {
@@ -161,7 +266,7 @@ int fs::vfs::load_dentry(dentry* ent)
 
     return GB_OK;
 }
-int fs::vfs::mount(dentry* mnt, vfs* new_fs)
+int fs::vfs::mount(dentry* mnt, vfs* new_fs, unsigned long flags)
 {
     if (!S_ISDIR(mnt->ind->mode)) {
         errno = ENOTDIR;
@@ -176,27 +281,111 @@ int fs::vfs::mount(dentry* mnt, vfs* new_fs)
     auto* orig_ent = mnt->replace(new_ent);
     _mount_recover_list.emplace(new_ent, orig_ent);
 
+    new_fs->m_mount_flags = flags;
+
+    return GB_OK;
+}
+int fs::vfs::freeze(void)
+{
+    {
+        types::lock_guard lck(m_freeze_cv.mtx());
+        if (m_frozen)
+            return -EBUSY;
+        m_frozen = true;
+
+        while (m_writers) {
+            if (!m_freeze_cv.wait(m_freeze_cv.mtx())) {
+                m_frozen = false;
+                break;
+            }
+        }
+    }
+
+    if (!m_frozen) {
+        m_freeze_cv.notify_all();
+        return -EINTR;
+    }
+
+    int ret = sync_fs();
+    if (ret != GB_OK)
+        thaw();
+    return ret;
+}
+int fs::vfs::thaw(void)
+{
+    {
+        types::lock_guard lck(m_freeze_cv.mtx());
+        if (!m_frozen)
+            return -EINVAL;
+        m_frozen = false;
+    }
+
+    m_freeze_cv.notify_all();
     return GB_OK;
 }
+bool fs::vfs::begin_write(void)
+{
+    types::lock_guard lck(m_freeze_cv.mtx());
+    while (m_frozen) {
+        if (!m_freeze_cv.wait(m_freeze_cv.mtx()))
+            return false;
+    }
+
+    ++m_writers;
+    return true;
+}
+void fs::vfs::end_write(void)
+{
+    bool last;
+    {
+        types::lock_guard lck(m_freeze_cv.mtx());
+        last = --m_writers == 0 && m_frozen;
+    }
+
+    // wake up freeze() waiting for us
+    if (last)
+        m_freeze_cv.notify_all();
+}
+int fs::vfs::sync_fs(void)
+{ return GB_OK; }
+int fs::vfs::inode_sync(inode*, bool)
+{ return sync_fs(); }
+int fs::vfs::statfs(struct statfs*)
+{ return -ENOSYS; }
 size_t fs::vfs::inode_read(inode*, char*, size_t, size_t, size_t)
 { return -EINVAL; }
 size_t fs::vfs::inode_write(inode*, const char*, size_t, size_t)
 { return -EINVAL; }
+ssize_t fs::vfs::inode_copy_range(inode*, size_t, inode*, size_t, size_t)
+{ return -EOPNOTSUPP; }
 int fs::vfs::inode_mkfile(dentry*, const char*, mode_t)
 { return -EINVAL; }
 int fs::vfs::inode_mknode(dentry*, const char*, mode_t, node_t)
 { return -EINVAL; }
 int fs::vfs::inode_rmfile(dentry*, const char*)
 { return -EINVAL; }
+int fs::vfs::inode_link(dentry*, const char*, inode*)
+{ return -EPERM; }
 int fs::vfs::inode_mkdir(dentry*, const char*)
 { return -EINVAL; }
+int fs::vfs::inode_symlink(dentry*, const char*, const char*)
+{ return -EPERM; }
+int fs::vfs::inode_readlink(inode*, char*, size_t)
+{ return -EINVAL; }
 int fs::vfs::inode_stat(dentry*, statx*, unsigned int)
 { return -EINVAL; }
+int fs::vfs::inode_truncate(inode*, size_t)
+{ return -EINVAL; }
 uint32_t fs::vfs::inode_getnode(fs::inode*)
 {
     assert(false);
     return 0xffffffff;
 }
+void fs::vfs::inode_release(fs::inode*)
+{
+}
+bool fs::vfs::use_page_cache(void) const
+{ return false; }
 
 class tmpfs : public virtual fs::vfs {
 private:
@@ -279,8 +468,8 @@ protected:
             const auto& entry = entries[off];
             auto* ind = get_inode(entry.ino);
 
-            // inode mode filetype is compatible with user dentry filetype
-            auto ret = filldir(entry.filename, 0, entry.ino, ind->mode & S_IFMT);
+            // the type in mode shifted down is the user dentry type
+            auto ret = filldir(entry.filename, 0, entry.ino, IFTODT(ind->mode));
             if (ret != GB_OK)
                 break;
         }
@@ -293,6 +482,7 @@ public:
         : _next_ino(1)
     {
         auto& in = *cache_inode(0, _savedata(mk_fe_vector()), S_IFDIR | 0777, 0, 0);
+        in.nlink = 2;
 
         mklink(&in, &in, ".");
         mklink(&in, &in, "..");
@@ -310,7 +500,7 @@ public:
 
     virtual int inode_mknode(dentry* dir, const char* filename, mode_t mode, fs::node_t sn) override
     {
-        if (!S_ISBLK(mode) && !S_ISCHR(mode))
+        if (!S_ISBLK(mode) && !S_ISCHR(mode) && !S_ISFIFO(mode))
             return -EINVAL;
 
         auto& node = *cache_inode(0, _savedata(sn), mode, 0, 0);
@@ -323,14 +513,92 @@ public:
     {
         auto new_dir = cache_inode(0, _savedata(mk_fe_vector()), S_IFDIR | 0777, 0, 0);
         mklink(new_dir, new_dir, ".");
+        new_dir->nlink = 2;
 
         mklink(dir->ind, new_dir, dirname);
         mklink(new_dir, dir->ind, "..");
+        ++dir->ind->nlink;
 
         dir->append(new_dir, dirname, true);
         return GB_OK;
     }
 
+    virtual int inode_link(dentry* dir, const char* filename, fs::inode* target) override
+    {
+        mklink(dir->ind, target, filename);
+        ++target->nlink;
+        dir->append(target, filename, true);
+        return GB_OK;
+    }
+
+    virtual int inode_rmfile(dentry* dir, const char* filename) override
+    {
+        auto* fes = as_vfe(_getdata(dir->ind->ino));
+        for (auto iter = fes->begin(); iter != fes->end(); ++iter) {
+            if (strcmp(iter->filename, filename) != 0)
+                continue;
+
+            auto* ind = get_inode(iter->ino);
+            if (S_ISDIR(ind->mode))
+                return -EISDIR;
+
+            fes->erase(iter);
+            dir->ind->size -= sizeof(fe_t);
+            --ind->nlink;
+
+            // the entry in use is freed by its last put(), which
+            // releases the inode then, otherwise we do it now
+            if (dir->remove(filename))
+                inode_release(ind);
+
+            return GB_OK;
+        }
+
+        return -ENOENT;
+    }
+
+    // free the inode and its data once it has no links and no users
+    virtual void inode_release(fs::inode* ind) override
+    {
+        if (ind->nlink || ind->refs)
+            return;
+
+        auto iter = inode_data.find(ind->ino);
+        if (iter == inode_data.end())
+            return;
+
+        if (S_ISDIR(ind->mode))
+            allocator_traits<kernel_allocator<vfe_t>>::deconstruct_and_deallocate(as_vfe(iter->second));
+        else if (S_ISREG(ind->mode) || S_ISLNK(ind->mode))
+            allocator_traits<kernel_allocator<fdata_t>>::deconstruct_and_deallocate(as_fdata(iter->second));
+        inode_data.erase(iter);
+
+        free_inode(ind->ino);
+    }
+
+    virtual int inode_symlink(dentry* dir, const char* linkname, const char* target) override
+    {
+        auto* data = mk_data_vector();
+        data->insert(data->end(), target, target + strlen(target));
+
+        auto& link = *cache_inode(data->size(), _savedata(data), S_IFLNK | 0777, 0, 0);
+        mklink(dir->ind, &link, linkname);
+        dir->append(get_inode(link.ino), linkname, true);
+        return GB_OK;
+    }
+
+    virtual int inode_readlink(fs::inode* link, char* buf, size_t buf_size) override
+    {
+        auto* data = as_fdata(_getdata(link->ino));
+
+        size_t n = data->size();
+        if (n > buf_size)
+            n = buf_size;
+
+        memcpy(buf, data->data(), n);
+        return n;
+    }
+
     virtual size_t inode_read(fs::inode* file, char* buf, size_t buf_size, size_t offset, size_t n) override
     {
         if (!S_ISREG(file->mode))
@@ -367,6 +635,42 @@ public:
         return n;
     }
 
+    virtual ssize_t inode_copy_range(fs::inode* src, size_t src_off,
+        fs::inode* dst, size_t dst_off, size_t n) override
+    {
+        auto* src_data = as_fdata(_getdata(src->ino));
+        auto* dst_data = as_fdata(_getdata(dst->ino));
+
+        if (dst_data->size() < dst_off + n)
+            dst_data->resize(dst_off + n);
+        // src and dst might be overlapping parts of the same file
+        char* to = dst_data->data() + dst_off;
+        const char* from = src_data->data() + src_off;
+        if (to < from) {
+            for (size_t i = 0; i < n; ++i)
+                to[i] = from[i];
+        } else {
+            for (size_t i = n; i > 0; --i)
+                to[i - 1] = from[i - 1];
+        }
+
+        dst->size = dst_data->size();
+
+        return n;
+    }
+
+    virtual int inode_truncate(fs::inode* file, size_t size) override
+    {
+        if (!S_ISREG(file->mode))
+            return -EINVAL;
+
+        auto* data = as_fdata(_getdata(file->ino));
+        data->resize(size);
+        file->size = size;
+
+        return GB_OK;
+    }
+
     virtual int inode_stat(dentry* dent, statx* st, unsigned int mask) override
     {
         auto* ind = dent->ind;
@@ -421,13 +725,100 @@ public:
     {
         return as_val(_getdata(file->ino));
     }
+
+    // files live in the kernel heap, so the whole memory is the limit
+    virtual int statfs(struct statfs* buf) override
+    {
+        buf->f_type = TMPFS_MAGIC;
+        buf->f_bsize = PAGE_SIZE;
+        buf->f_blocks = total_raw_pages();
+        buf->f_bfree = free_raw_pages();
+        buf->f_bavail = buf->f_bfree;
+        buf->f_files = inode_data.size();
+        buf->f_namelen = 255;
+
+        return GB_OK;
+    }
 };
 
-fs::regular_file::regular_file(vfs::dentry* parent,
-    file_flags flags, size_t cursor, inode* ind)
-    : file(S_IFREG, parent, flags), cursor(cursor), ind(ind) { }
+// the kernel acts as root before the first process is created
+static inline uid_t current_euid(void)
+{
+    return current_process ? current_process->euid : 0;
+}
 
-ssize_t fs::regular_file::read(char* __user buf, size_t n)
+static inline gid_t current_egid(void)
+{
+    return current_process ? current_process->egid : 0;
+}
+
+// O_DIRECT transfers are done in whole sectors
+static inline bool direct_io_aligned(const void* buf, size_t offset, size_t n)
+{
+    constexpr size_t DIRECT_IO_ALIGN = 512;
+    return !(((uintptr_t)buf | offset | n) & (DIRECT_IO_ALIGN - 1));
+}
+
+fs::regular_file::regular_file(vfs::dentry* dent,
+    file_flags flags, size_t cursor)
+    : file(S_IFREG, dent->parent, flags), cursor(cursor), ind(dent->ind), dent(dent)
+{
+    dent->get();
+}
+
+fs::regular_file::~regular_file()
+{
+    dent->put();
+}
+
+// atime older than this is updated anyway with relatime
+static constexpr time_t RELATIME_INTERVAL = 24 * 60 * 60;
+
+static inline bool timespec_before(const timespec& a, const timespec& b)
+{
+    return a.tv_sec < b.tv_sec
+        || (a.tv_sec == b.tv_sec && a.tv_nsec < b.tv_nsec);
+}
+
+// with relatime, atime is only updated if it doesn't tell
+// whether the file has been read since it was last changed
+static inline bool relatime_need_update(fs::inode* ind, const timespec& now)
+{
+    if (!timespec_before(ind->mtime, ind->atime))
+        return true;
+    if (!timespec_before(ind->ctime, ind->atime))
+        return true;
+
+    return now.tv_sec - ind->atime.tv_sec >= RELATIME_INTERVAL;
+}
+
+// the data of ind has been read, atime is updated as the mount says
+static inline void touch_atime(fs::inode* ind)
+{
+    auto flags = ind->fs->mount_flags();
+    if (flags & MS_NOATIME)
+        return;
+
+    auto now = current_time();
+    if (!(flags & MS_STRICTATIME) && !relatime_need_update(ind, now))
+        return;
+
+    ind->atime = now;
+}
+
+// the data of ind has changed, which changes the inode too
+static inline void touch_mtime(fs::inode* ind)
+{
+    ind->mtime = ind->ctime = current_time();
+}
+
+// the inode itself has changed, e.g. its links or flags
+static inline void touch_ctime(fs::inode* ind)
+{
+    ind->ctime = current_time();
+}
+
+ssize_t fs::regular_file::do_read(char* __user buf, size_t n, size_t offset)
 {
     if (!flags.read)
         return -EBADF;
@@ -435,15 +826,32 @@ ssize_t fs::regular_file::read(char* __user buf, size_t n)
     if (S_ISDIR(ind->mode))
         return -EISDIR;
 
-    // TODO: copy to user function !IMPORTANT
-    ssize_t n_wrote = fs::vfs_read(ind, buf, n, cursor, n);
-    if (n_wrote >= 0)
-        cursor += n_wrote;
+    if (flags.direct) {
+        if (!direct_io_aligned(buf, offset, n))
+            return -EINVAL;
 
-    return n_wrote;
+        // bypass the page cache, even if the fs uses one
+        // TODO: copy to user function !IMPORTANT
+        if (S_ISREG(ind->mode)) {
+            // the filesystem reads whole clusters, what is after
+            // the end of file is left from whatever was there
+            if (offset >= ind->size)
+                return 0;
+            n = std::min(n, ind->size - offset);
+
+            ssize_t ret = ind->fs->inode_read(ind, buf, n, offset, n);
+            if (ret > 0)
+                touch_atime(ind);
+            return ret;
+        }
+        return fs::vfs_read(ind, buf, n, offset, n);
+    }
+
+    // TODO: copy to user function !IMPORTANT
+    return fs::vfs_read(ind, buf, n, offset, n);
 }
 
-ssize_t fs::regular_file::write(const char* __user buf, size_t n)
+ssize_t fs::regular_file::do_write(const char* __user buf, size_t n, size_t& offset, bool append)
 {
     if (!flags.write)
         return -EBADF;
@@ -451,16 +859,157 @@ ssize_t fs::regular_file::write(const char* __user buf, size_t n)
     if (S_ISDIR(ind->mode))
         return -EISDIR;
 
+    // writes never go through the page cache, the range is dropped
+    // from it in vfs_write() so O_DIRECT only needs the alignment check
+    if (flags.direct && !direct_io_aligned(buf, append ? ind->size : offset, n))
+        return -EINVAL;
+
     // TODO: check privilege of user ptr
-    ssize_t n_wrote = fs::vfs_write(ind, buf, cursor, n);
+    ssize_t n_wrote = append
+        ? fs::vfs_append(ind, buf, n, offset)
+        : fs::vfs_write(ind, buf, offset, n);
+    if (n_wrote < 0)
+        return n_wrote;
+
+    // vfs_write() only knows the inode, the directory is told here
+    if (n_wrote > 0 && S_ISREG(ind->mode))
+        inotify_notify_parent(dent, IN_MODIFY);
+
+    if (flags.sync) {
+        int ret = fs::vfs_sync(ind);
+        if (ret != 0)
+            return ret;
+    }
+
+    return n_wrote;
+}
+
+ssize_t fs::regular_file::read(char* __user buf, size_t n)
+{
+    if (!lock_pos())
+        return -EINTR;
+
+    ssize_t n_read = do_read(buf, n, cursor);
+    if (n_read >= 0)
+        cursor += n_read;
+
+    unlock_pos();
+    return n_read;
+}
+
+ssize_t fs::regular_file::write(const char* __user buf, size_t n)
+{
+    if (!lock_pos())
+        return -EINTR;
+
+    ssize_t n_wrote = do_write(buf, n, cursor, flags.append);
     if (n_wrote >= 0)
         cursor += n_wrote;
 
+    unlock_pos();
     return n_wrote;
 }
 
+ssize_t fs::regular_file::pread(char* __user buf, size_t n, size_t offset)
+{
+    if (S_ISCHR(ind->mode))
+        return -ESPIPE;
+
+    return do_read(buf, n, offset);
+}
+
+// the offset given is used even with O_APPEND, the cursor
+// is left alone either way
+ssize_t fs::regular_file::pwrite(const char* __user buf, size_t n, size_t offset)
+{
+    if (S_ISCHR(ind->mode))
+        return -ESPIPE;
+
+    return do_write(buf, n, offset, false);
+}
+
 void fs::regular_file::close(void) { } // TODO: mark inode as free
 
+int fs::regular_file::ioctl(unsigned long request, uintptr_t arg)
+{
+    switch (request) {
+    case FS_IOC_GETFLAGS:
+        return kernel::user::put_user(ind->attr_flags, (uint32_t __user*)arg);
+    case FS_IOC_SETFLAGS: {
+        uint32_t attr;
+        if (int ret = kernel::user::get_user(attr, (const uint32_t __user*)arg); ret != 0)
+            return ret;
+        return fs::vfs_setflags(ind, attr);
+    }
+    case FIFREEZE:
+        if (S_ISREG(ind->mode) || S_ISDIR(ind->mode)) {
+            // writers all over the filesystem would wait for the thaw
+            if (current_euid() != 0)
+                return -EPERM;
+            return ind->fs->freeze();
+        }
+        break;
+    case FITHAW:
+        if (S_ISREG(ind->mode) || S_ISDIR(ind->mode)) {
+            if (current_euid() != 0)
+                return -EPERM;
+            return ind->fs->thaw();
+        }
+        break;
+    }
+
+    if (S_ISBLK(ind->mode))
+        return fs::block_device_ioctl(ind->fs->inode_getnode(ind), request, arg);
+    if (!S_ISCHR(ind->mode))
+        return -ENOTTY;
+
+    return fs::char_device_ioctl(ind->fs->inode_getnode(ind), request, arg);
+}
+
+int fs::regular_file::fsync(bool datasync)
+{
+    return fs::vfs_sync(ind, datasync);
+}
+
+fs::vfs::dentry* fs::regular_file::get_dentry(void) const
+{
+    return dent;
+}
+
+size_t* fs::regular_file::pos(void)
+{
+    return &cursor;
+}
+
+bool fs::regular_file::lock_pos(void)
+{
+    // character devices have no offset to keep, and a read of a tty
+    // would keep the writes to the same file waiting for the input
+    if (S_ISCHR(ind->mode))
+        return true;
+
+    types::lock_guard lck(m_pos_cv.mtx());
+    while (m_pos_locked) {
+        if (!m_pos_cv.wait(m_pos_cv.mtx()))
+            return false;
+    }
+
+    m_pos_locked = true;
+    return true;
+}
+
+void fs::regular_file::unlock_pos(void)
+{
+    if (S_ISCHR(ind->mode))
+        return;
+
+    {
+        types::lock_guard lck(m_pos_cv.mtx());
+        m_pos_locked = false;
+    }
+    m_pos_cv.notify();
+}
+
 int fs::regular_file::getdents(char* __user buf, size_t cnt)
 {
     if (!S_ISDIR(ind->mode))
@@ -532,6 +1081,9 @@ int fs::regular_file::getdents64(char* __user buf, size_t cnt)
     return orig_cnt - cnt;
 }
 
+// pipes of the named pipes opened, removed when the last end is closed
+static std::map<fs::inode*, std::shared_ptr<fs::pipe>> s_fifos;
+
 fs::fifo_file::fifo_file(vfs::dentry* parent, file_flags flags,
     std::shared_ptr<fs::pipe> ppipe)
     : file(S_IFIFO, parent, flags), ppipe(ppipe) { }
@@ -554,15 +1106,63 @@ ssize_t fs::fifo_file::write(const char* __user buf, size_t n)
 
 void fs::fifo_file::close(void)
 {
-    assert(flags.read ^ flags.write);
+    assert(flags.read || flags.write);
     if (flags.read)
         ppipe->close_read();
-    else
+    if (flags.write)
         ppipe->close_write();
 
+    if (dent && ppipe->is_free())
+        s_fifos.erase(dent->ind);
+
     ppipe.reset();
 }
 
+fs::vfs::dentry* fs::fifo_file::get_dentry(void) const
+{
+    return dent;
+}
+
+fs::pipe* fs::fifo_file::get_pipe(void) const
+{
+    return ppipe.get();
+}
+
+fs::fifo_file* fs::fifo_open(vfs::dentry* dent, file::file_flags flags, bool nonblock)
+{
+    auto iter = s_fifos.find(dent->ind);
+    if (!iter) {
+        std::tie(iter, std::ignore) = s_fifos.emplace(
+            dent->ind, std::shared_ptr<pipe> { new pipe(0, 0) });
+    }
+    auto ppipe = iter->second;
+
+    int ret;
+    if (flags.read && flags.write) {
+        // we are the reader ourselves, so don't wait for one
+        ret = ppipe->open_read(true);
+        if (ret == 0)
+            ret = ppipe->open_write(true);
+    }
+    else if (flags.read) {
+        ret = ppipe->open_read(nonblock);
+    }
+    else {
+        ret = ppipe->open_write(nonblock);
+    }
+
+    if (ret != 0) {
+        if (ppipe->is_free())
+            s_fifos.erase(dent->ind);
+        errno = -ret;
+        return nullptr;
+    }
+
+    auto* file = new fifo_file(dent->parent, flags, std::move(ppipe));
+    file->dent = dent;
+    return file;
+}
+
 static std::map<fs::node_t, fs::blkdev_ops> blkdevs;
 static std::map<fs::node_t, fs::chrdev_ops> chrdevs;
 
@@ -573,8 +1173,17 @@ size_t fs::vfs_read(fs::inode* file, char* buf, size_t buf_size, size_t offset,
         return -1U;
     }
 
-    if (S_ISREG(file->mode))
-        return file->fs->inode_read(file, buf, buf_size, offset, n);
+    if (S_ISREG(file->mode)) {
+        size_t ret;
+        if (file->fs->use_page_cache())
+            ret = pcache->read(file, buf, buf_size, offset, n);
+        else
+            ret = file->fs->inode_read(file, buf, buf_size, offset, n);
+
+        if ((ssize_t)ret > 0)
+            touch_atime(file);
+        return ret;
+    }
 
     if (S_ISBLK(file->mode) || S_ISCHR(file->mode)) {
         node_t sn = file->fs->inode_getnode(file);
@@ -596,15 +1205,33 @@ size_t fs::vfs_read(fs::inode* file, char* buf, size_t buf_size, size_t offset,
     errno = EINVAL;
     return -1U;
 }
-size_t fs::vfs_write(fs::inode* file, const char* buf, size_t offset, size_t n)
+ssize_t fs::vfs_write(fs::inode* file, const char* buf, size_t offset, size_t n)
 {
-    if (S_ISDIR(file->mode)) {
-        errno = EISDIR;
-        return -1U;
-    }
+    if (S_ISDIR(file->mode))
+        return -EISDIR;
+
+    if (S_ISREG(file->mode)) {
+        if (file->attr_flags & FS_IMMUTABLE_FL)
+            return -EPERM;
+
+        // append-only files can only be written at the end
+        if ((file->attr_flags & FS_APPEND_FL) && offset != file->size)
+            return -EPERM;
+
+        if (!file->fs->begin_write())
+            return -EINTR;
+
+        ssize_t ret = file->fs->inode_write(file, buf, offset, n);
+        if (file->fs->use_page_cache())
+            pcache->invalidate(file, offset, n);
+        file->fs->end_write();
 
-    if (S_ISREG(file->mode))
-        return file->fs->inode_write(file, buf, offset, n);
+        if (ret > 0) {
+            touch_mtime(file);
+            inotify_notify(file, IN_MODIFY);
+        }
+        return ret;
+    }
 
     if (S_ISBLK(file->mode) || S_ISCHR(file->mode)) {
         node_t sn = file->fs->inode_getnode(file);
@@ -615,42 +1242,375 @@ size_t fs::vfs_write(fs::inode* file, const char* buf, size_t offset, size_t n)
         else
             ret = char_device_write(sn, buf, n);
 
-        if (ret < 0) {
-            errno = -ret;
-            return -1U;
+        return ret;
+    }
+
+    return -EINVAL;
+}
+
+// notified when an append is done
+static kernel::cond_var s_append_cv;
+ssize_t fs::vfs_append(fs::inode* file, const char* buf, size_t n, size_t& offset)
+{
+    {
+        types::lock_guard lck(s_append_cv.mtx());
+        while (file->appending) {
+            if (!s_append_cv.wait(s_append_cv.mtx()))
+                return -EINTR;
         }
+        file->appending = true;
+    }
 
-        return ret;
+    // other appends wait for us, so the end stays put even if we sleep
+    offset = file->size;
+    ssize_t ret = vfs_write(file, buf, offset, n);
+
+    {
+        types::lock_guard lck(s_append_cv.mtx());
+        file->appending = false;
     }
+    s_append_cv.notify_all();
 
-    errno = EINVAL;
-    return -1U;
+    return ret;
+}
+
+ssize_t fs::vfs_copy_file_range(inode* src, size_t src_off,
+    inode* dst, size_t dst_off, size_t n)
+{
+    if (!S_ISREG(src->mode) || !S_ISREG(dst->mode))
+        return -EINVAL;
+    if (src->fs != dst->fs)
+        return -EXDEV;
+
+    if (dst->attr_flags & (FS_IMMUTABLE_FL | FS_APPEND_FL))
+        return -EPERM;
+
+    if (src_off >= src->size)
+        return 0;
+    n = std::min(n, src->size - src_off);
+
+    // the end of the copy must be addressable
+    if (dst_off + n < dst_off)
+        return -EFBIG;
+
+    auto* fs = dst->fs;
+    if (!fs->begin_write())
+        return -EINTR;
+
+    ssize_t ret = fs->inode_copy_range(src, src_off, dst, dst_off, n);
+    if (ret > 0 && fs->use_page_cache())
+        pcache->invalidate(dst, dst_off, ret);
+    fs->end_write();
+
+    if (ret > 0) {
+        touch_atime(src);
+        touch_mtime(dst);
+        inotify_notify(dst, IN_MODIFY);
+    }
+    return ret;
+}
+
+int fs::vfs_permission(inode* file, int mask)
+{
+    uid_t euid = current_euid();
+
+    if (euid == 0) {
+        if (!(mask & MAY_EXEC) || S_ISDIR(file->mode))
+            return GB_OK;
+        if (file->mode & (S_IXUSR | S_IXGRP | S_IXOTH))
+            return GB_OK;
+        return -EACCES;
+    }
+
+    mode_t bits = file->mode;
+    if (euid == file->uid)
+        bits >>= 6;
+    else if (current_egid() == file->gid)
+        bits >>= 3;
+
+    if ((bits & mask) != (mode_t)mask)
+        return -EACCES;
+    return GB_OK;
+}
+
+// entries are about to be added to or removed from dir
+static inline int may_modify_dir(fs::inode* dir)
+{
+    return fs::vfs_permission(dir, MAY_WRITE | MAY_EXEC);
 }
+
+// entries of a sticky directory, e.g. /tmp, can only be removed
+// by the owner of the entry or of the directory
+static inline bool sticky_denied(fs::inode* dir, fs::inode* ind)
+{
+    if (!ind || !(dir->mode & S_ISVTX))
+        return false;
+
+    uid_t euid = current_euid();
+    return euid != 0 && euid != ind->uid && euid != dir->uid;
+}
+
+// new inodes belong to the effective ids of their creator
+static void set_owner(fs::vfs::dentry* dir, const char* filename)
+{
+    auto* ent = dir->find(filename);
+    if (!ent)
+        return;
+
+    ent->ind->uid = current_euid();
+    ent->ind->gid = current_egid();
+}
+
 int fs::vfs_mkfile(fs::vfs::dentry* dir, const char* filename, mode_t mode)
 {
-    return dir->ind->fs->inode_mkfile(dir, filename, mode);
+    if (dir->ind->attr_flags & FS_IMMUTABLE_FL)
+        return -EPERM;
+    if (int ret = may_modify_dir(dir->ind); ret != GB_OK)
+        return ret;
+
+    auto* fs = dir->ind->fs;
+    if (!fs->begin_write())
+        return -EINTR;
+    int ret = fs->inode_mkfile(dir, filename, mode);
+    fs->end_write();
+    if (ret == GB_OK) {
+        set_owner(dir, filename);
+        touch_mtime(dir->ind);
+        inotify_notify(dir->ind, IN_CREATE, filename);
+    }
+    return ret;
 }
 int fs::vfs_mknode(fs::vfs::dentry* dir, const char* filename, mode_t mode, fs::node_t sn)
 {
-    return dir->ind->fs->inode_mknode(dir, filename, mode, sn);
+    // the node would belong to the creator and give access to the device
+    if ((S_ISCHR(mode) || S_ISBLK(mode)) && current_euid() != 0)
+        return -EPERM;
+    if (dir->ind->attr_flags & FS_IMMUTABLE_FL)
+        return -EPERM;
+    if (int ret = may_modify_dir(dir->ind); ret != GB_OK)
+        return ret;
+
+    auto* fs = dir->ind->fs;
+    if (!fs->begin_write())
+        return -EINTR;
+    int ret = fs->inode_mknode(dir, filename, mode, sn);
+    fs->end_write();
+    if (ret == GB_OK) {
+        set_owner(dir, filename);
+        touch_mtime(dir->ind);
+        inotify_notify(dir->ind, IN_CREATE, filename);
+    }
+    return ret;
 }
 int fs::vfs_rmfile(fs::vfs::dentry* dir, const char* filename)
 {
-    return dir->ind->fs->inode_rmfile(dir, filename);
+    auto* ent = dir->find(filename);
+    auto* ind = ent ? ent->ind : nullptr;
+
+    // entries of append-only directories can't be removed either
+    if (dir->ind->attr_flags & (FS_IMMUTABLE_FL | FS_APPEND_FL))
+        return -EPERM;
+    if (ind && (ind->attr_flags & (FS_IMMUTABLE_FL | FS_APPEND_FL)))
+        return -EPERM;
+    if (int ret = may_modify_dir(dir->ind); ret != GB_OK)
+        return ret;
+    if (sticky_denied(dir->ind, ind))
+        return -EPERM;
+
+    auto* fs = dir->ind->fs;
+    if (!fs->begin_write())
+        return -EINTR;
+
+    // the entry is held so that the inode, if it has no links left,
+    // is only released by the put() after we are done with it
+    if (ent)
+        ent->get();
+
+    int ret = fs->inode_rmfile(dir, filename);
+    fs->end_write();
+    if (ret == GB_OK) {
+        touch_mtime(dir->ind);
+        inotify_notify(dir->ind, IN_DELETE, filename);
+        if (ind && ind->nlink) {
+            // other hard links to the inode are still there
+            touch_ctime(ind);
+            inotify_notify(ind, IN_ATTRIB);
+        } else if (ind) {
+            inotify_notify(ind, IN_DELETE_SELF);
+            inotify_inode_removed(ind);
+        }
+    }
+
+    if (ent)
+        ent->put();
+    return ret;
+}
+int fs::vfs_link(fs::vfs::dentry* dir, const char* filename, inode* target)
+{
+    if (dir->ind->fs != target->fs)
+        return -EXDEV;
+    if (S_ISDIR(target->mode))
+        return -EPERM;
+
+    if (dir->ind->attr_flags & FS_IMMUTABLE_FL)
+        return -EPERM;
+    if (int ret = may_modify_dir(dir->ind); ret != GB_OK)
+        return ret;
+    if (target->attr_flags & (FS_IMMUTABLE_FL | FS_APPEND_FL))
+        return -EPERM;
+
+    auto* fs = dir->ind->fs;
+    if (!fs->begin_write())
+        return -EINTR;
+    int ret = fs->inode_link(dir, filename, target);
+    fs->end_write();
+    if (ret == GB_OK) {
+        touch_mtime(dir->ind);
+        touch_ctime(target);
+        inotify_notify(dir->ind, IN_CREATE, filename);
+        inotify_notify(target, IN_ATTRIB);
+    }
+    return ret;
 }
 int fs::vfs_mkdir(fs::vfs::dentry* dir, const char* dirname)
 {
-    return dir->ind->fs->inode_mkdir(dir, dirname);
+    if (dir->ind->attr_flags & FS_IMMUTABLE_FL)
+        return -EPERM;
+    if (int ret = may_modify_dir(dir->ind); ret != GB_OK)
+        return ret;
+
+    auto* fs = dir->ind->fs;
+    if (!fs->begin_write())
+        return -EINTR;
+    int ret = fs->inode_mkdir(dir, dirname);
+    fs->end_write();
+    if (ret == GB_OK) {
+        set_owner(dir, dirname);
+        touch_mtime(dir->ind);
+        inotify_notify(dir->ind, IN_CREATE | IN_ISDIR, dirname);
+    }
+    return ret;
+}
+
+int fs::vfs_symlink(fs::vfs::dentry* dir, const char* linkname, const char* target)
+{
+    if (dir->ind->attr_flags & FS_IMMUTABLE_FL)
+        return -EPERM;
+    if (int ret = may_modify_dir(dir->ind); ret != GB_OK)
+        return ret;
+    if (!*target)
+        return -ENOENT;
+
+    auto* fs = dir->ind->fs;
+    if (!fs->begin_write())
+        return -EINTR;
+    int ret = fs->inode_symlink(dir, linkname, target);
+    fs->end_write();
+    if (ret == GB_OK) {
+        set_owner(dir, linkname);
+        touch_mtime(dir->ind);
+        inotify_notify(dir->ind, IN_CREATE, linkname);
+    }
+    return ret;
+}
+
+int fs::vfs_readlink(inode* link, char* buf, size_t buf_size)
+{
+    if (!S_ISLNK(link->mode))
+        return -EINVAL;
+
+    return link->fs->inode_readlink(link, buf, buf_size);
+}
+
+// the max number of symbolic links followed in a single path lookup
+static constexpr int MAX_SYMLINKS = 40;
+static constexpr size_t MAX_SYMLINK_TARGET = 4096;
+
+static fs::vfs::dentry* _vfs_walk(fs::vfs::dentry& root, fs::vfs::dentry* cur,
+    const char* path, size_t len, bool follow_symlinks, int& nlinks);
+
+// step from cur to its child name[0, len), following symbolic links
+// @param last whether it's the last component of the path
+static fs::vfs::dentry* _vfs_step(fs::vfs::dentry& root, fs::vfs::dentry* cur,
+    const char* name, size_t len, bool last, bool follow_symlinks, int& nlinks)
+{
+    // never walk above the root, which might be a chroot jail
+    if (cur == &root && len == 2 && name[0] == '.' && name[1] == '.')
+        return cur;
+
+    // directories on the way need to be searchable
+    if (cur->type == DT_DIR
+        && fs::vfs_permission(cur->ind, MAY_EXEC) != GB_OK) {
+        errno = EACCES;
+        return nullptr;
+    }
+
+    cur = cur->find(name, len);
+    if (!cur) {
+        errno = ENOENT;
+        return nullptr;
+    }
+
+    if (cur->type != DT_LNK)
+        return cur;
+    if (!follow_symlinks && last)
+        return cur;
+
+    if (++nlinks > MAX_SYMLINKS) {
+        errno = ELOOP;
+        return nullptr;
+    }
+
+    // keep the target off the stack since we are recursive
+    // the size of the link might not be known until it's read
+    std::vector<char> buf(MAX_SYMLINK_TARGET);
+    int target_len = fs::vfs_readlink(cur->ind, buf.data(), buf.size());
+    if (target_len <= 0) {
+        errno = ENOENT;
+        return nullptr;
+    }
+
+    return _vfs_walk(root, buf[0] == '/' ? &root : cur->parent,
+        buf.data(), target_len, true, nlinks);
 }
 
-fs::vfs::dentry* fs::vfs_open(fs::vfs::dentry& root, const types::path& path)
+// resolve path[0, len) from cur a component at a time,
+// without making strings of them
+static fs::vfs::dentry* _vfs_walk(fs::vfs::dentry& root, fs::vfs::dentry* cur,
+    const char* path, size_t len, bool follow_symlinks, int& nlinks)
 {
-    fs::vfs::dentry* cur = &root;
+    const char* end = path + len;
+    while (path < end) {
+        if (*path == '/') {
+            ++path;
+            continue;
+        }
+
+        const char* name = path;
+        while (path < end && *path != '/')
+            ++path;
+
+        // with a trailing slash, the last component is always followed
+        cur = _vfs_step(root, cur, name, path - name,
+            path == end, follow_symlinks, nlinks);
+        if (!cur)
+            return nullptr;
+    }
 
-    for (const auto& item : path) {
-        if (item.empty())
+    return cur;
+}
+
+fs::vfs::dentry* fs::vfs_open(fs::vfs::dentry& root,
+    const types::path& path, bool follow_symlinks)
+{
+    int nlinks = 0;
+    auto* cur = &root;
+    for (auto iter = path.begin(); iter != path.end(); ++iter) {
+        if (iter->empty())
             continue;
-        cur = cur->find(item);
+
+        cur = _vfs_step(root, cur, iter->c_str(), iter->size(),
+            iter + 1 == path.end(), follow_symlinks, nlinks);
         if (!cur)
             return nullptr;
     }
@@ -658,30 +1618,197 @@ fs::vfs::dentry* fs::vfs_open(fs::vfs::dentry& root, const types::path& path)
     return cur;
 }
 
+fs::vfs::dentry* fs::vfs_open(fs::vfs::dentry& root,
+    const char* path, bool follow_symlinks)
+{
+    int nlinks = 0;
+    return _vfs_walk(root, &root, path, strlen(path), follow_symlinks, nlinks);
+}
+
 int fs::vfs_stat(fs::vfs::dentry* ent, statx* stat, unsigned int mask)
 {
-    return ent->ind->fs->inode_stat(ent, stat, mask);
+    int ret = ent->ind->fs->inode_stat(ent, stat, mask);
+    if (ret != GB_OK)
+        return ret;
+
+    if (mask & STATX_NLINK) {
+        stat->stx_nlink = ent->ind->nlink;
+        stat->stx_mask |= STATX_NLINK;
+    }
+
+    auto to_statx = [](const timespec& ts) -> statx_timestamp {
+        return { (int64_t)ts.tv_sec, (uint32_t)ts.tv_nsec, 0 };
+    };
+    if (mask & STATX_ATIME) {
+        stat->stx_atime = to_statx(ent->ind->atime);
+        stat->stx_mask |= STATX_ATIME;
+    }
+    if (mask & STATX_MTIME) {
+        stat->stx_mtime = to_statx(ent->ind->mtime);
+        stat->stx_mask |= STATX_MTIME;
+    }
+    if (mask & STATX_CTIME) {
+        stat->stx_ctime = to_statx(ent->ind->ctime);
+        stat->stx_mask |= STATX_CTIME;
+    }
+
+    return GB_OK;
+}
+
+int fs::vfs_truncate(inode* file, size_t size)
+{
+    if (file->attr_flags & (FS_IMMUTABLE_FL | FS_APPEND_FL))
+        return -EPERM;
+
+    if (!file->fs->begin_write())
+        return -EINTR;
+
+    int ret = file->fs->inode_truncate(file, size);
+    if (ret == GB_OK && file->fs->use_page_cache())
+        pcache->invalidate(file);
+    file->fs->end_write();
+
+    if (ret != GB_OK)
+        return ret;
+    touch_mtime(file);
+    inotify_notify(file, IN_MODIFY);
+
+    return GB_OK;
+}
+
+int fs::vfs_sync(inode* file, bool datasync)
+{
+    if (S_ISBLK(file->mode))
+        return fs::block_device_flush(file->fs->inode_getnode(file));
+
+    return file->fs->inode_sync(file, datasync);
+}
+
+int fs::vfs_statfs(inode* file, struct statfs* buf)
+{
+    memset(buf, 0x00, sizeof(struct statfs));
+
+    int ret = file->fs->statfs(buf);
+    if (ret != GB_OK)
+        return ret;
+
+    if (!buf->f_frsize)
+        buf->f_frsize = buf->f_bsize;
+
+    auto mount_flags = file->fs->mount_flags();
+    if (mount_flags & MS_NOATIME)
+        buf->f_flags |= ST_NOATIME;
+    else if (!(mount_flags & MS_STRICTATIME))
+        buf->f_flags |= ST_RELATIME;
+
+    return GB_OK;
+}
+
+int fs::vfs_setflags(inode* file, uint32_t flags)
+{
+    // the flags protect files from their owners too
+    if (current_euid() != 0)
+        return -EPERM;
+    if (flags & ~(FS_IMMUTABLE_FL | FS_APPEND_FL))
+        return -EOPNOTSUPP;
+
+    file->attr_flags = flags;
+    touch_ctime(file);
+    inotify_notify(file, IN_ATTRIB);
+
+    return GB_OK;
+}
+
+static inline bool valid_utime(const timespec& ts)
+{
+    if (ts.tv_nsec == UTIME_NOW || ts.tv_nsec == UTIME_OMIT)
+        return true;
+    return ts.tv_nsec >= 0 && ts.tv_nsec < 1000000000;
+}
+
+int fs::vfs_utimens(inode* file, const timespec* times)
+{
+    if (times && (!valid_utime(times[0]) || !valid_utime(times[1])))
+        return -EINVAL;
+
+    bool to_now = !times
+        || (times[0].tv_nsec == UTIME_NOW && times[1].tv_nsec == UTIME_NOW);
+
+    // others than the owner can only touch files they can write to
+    uid_t euid = current_euid();
+    if (euid != 0 && euid != file->uid) {
+        if (!to_now)
+            return -EPERM;
+        if (int ret = vfs_permission(file, MAY_WRITE); ret != GB_OK)
+            return ret;
+    }
+
+    if (file->attr_flags & FS_IMMUTABLE_FL)
+        return -EPERM;
+    // append-only files can only be touched
+    if ((file->attr_flags & FS_APPEND_FL) && !to_now)
+        return -EPERM;
+
+    auto now = current_time();
+    if (!times || times[0].tv_nsec == UTIME_NOW)
+        file->atime = now;
+    else if (times[0].tv_nsec != UTIME_OMIT)
+        file->atime = times[0];
+
+    if (!times || times[1].tv_nsec == UTIME_NOW)
+        file->mtime = now;
+    else if (times[1].tv_nsec != UTIME_OMIT)
+        file->mtime = times[1];
+
+    file->ctime = now;
+    inotify_notify(file, IN_ATTRIB);
+
+    return GB_OK;
 }
 
 static std::list<fs::vfs*>* fs_es;
 
-int fs::register_block_device(fs::node_t node, fs::blkdev_ops ops)
+// create the device node and the sysfs entries of a block device
+static void publish_block_device(const char* name, fs::node_t node, mode_t perm)
+{
+    fs::devtmpfs::add(name, (perm & 07777) | S_IFBLK, node);
+
+    auto* sysfs = fs::sysfs::instance();
+    types::string<> path = "block/";
+    path += name;
+    if (sysfs->mkdir(path.c_str()) != GB_OK)
+        return;
+
+    path += "/dev";
+    sysfs->add_file(path.c_str(), [node](char* buf, size_t buf_size) -> size_t {
+        int n = snprintf(buf, buf_size, "%d:%d\n", NODE_MAJOR(node), NODE_MINOR(node));
+        return n < 0 ? 0 : n;
+    });
+}
+
+int fs::register_block_device(fs::node_t node, fs::blkdev_ops ops, const char* name, mode_t perm)
 {
     auto iter = blkdevs.find(node);
     if (iter)
         return -EEXIST;
 
     std::tie(iter, std::ignore) = blkdevs.emplace(node, std::move(ops));
+
+    if (name)
+        publish_block_device(name, node, perm);
     return 0;
 }
 
-int fs::register_char_device(fs::node_t node, fs::chrdev_ops ops)
+int fs::register_char_device(fs::node_t node, fs::chrdev_ops ops, const char* name, mode_t perm)
 {
     auto iter = chrdevs.find(node);
     if (iter)
         return -EEXIST;
 
     std::tie(iter, std::ignore) = chrdevs.emplace(node, std::move(ops));
+
+    if (name)
+        devtmpfs::add(name, (perm & 07777) | S_IFCHR, node);
     return 0;
 }
 
@@ -707,10 +1834,6 @@ struct PACKED mbr {
 static inline void mbr_part_probe(fs::node_t node, char ch)
 {
     mbr buf_mbr;
-    // TODO: devtmpfs
-    auto* dev = fs::vfs_open(*fs::fs_root, "/dev");
-    if (!dev)
-        return;
 
     char label[] = "sda1";
     label[2] = ch;
@@ -731,7 +1854,7 @@ static inline void mbr_part_probe(fs::node_t node, char ch)
         std::size_t part_offset = part.lba_start * 512;
 
         // TODO: add partition offset limit
-        fs::register_block_device(node + n, {
+        ret = fs::register_block_device(node + n, {
             [=](char* buf, size_t buf_size, size_t offset, size_t n) -> ssize_t {
                 offset += part_offset;
                 return fs::block_device_read(node, buf, buf_size, offset, n);
@@ -739,36 +1862,29 @@ static inline void mbr_part_probe(fs::node_t node, char ch)
             [=](const char* buf, size_t offset, size_t n) -> ssize_t {
                 offset += part_offset;
                 return fs::block_device_write(node, buf, offset, n);
-            }
-        });
+            },
+            [=]() -> int {
+                return fs::block_device_flush(node);
+            },
+            nullptr,
+        }, label);
 
-        ret = fs::vfs_mknode(dev, label, 0660 | S_IFBLK, node + n);
         ++n, ++label[3];
     }
 }
 
 void fs::partprobe()
 {
-    auto* dev = fs::vfs_open(*fs::fs_root, "/dev");
-    if (!dev)
-        return;
-
     char ch = 'a';
     char name[] = "sd*";
-    types::string<> path = "/dev/sd*";
     for (const auto& device : blkdevs) {
-        // only the devices whose minor number is a multiple of 8
+        // only the scsi disks whose minor number is a multiple of 8
         // are considered as a disk instead of partitions
-        if (NODE_MINOR(device.first) % 8 != 0)
+        if (NODE_MAJOR(device.first) != 8 || NODE_MINOR(device.first) % 8 != 0)
             continue;
 
-        path.pop();
-        path += ch;
         name[2] = ch;
-
-        auto* blkfile = fs::vfs_open(*fs::fs_root, path.c_str());
-        if (!blkfile)
-            vfs_mknode(dev, name, 0660 | S_IFBLK, device.first);
+        publish_block_device(name, device.first, 0660);
 
         mbr_part_probe(device.first, ch);
 
@@ -800,6 +1916,37 @@ ssize_t fs::block_device_write(fs::node_t node, const char* buf, size_t offset,
     return iter->second.write(buf, offset, n);
 }
 
+int fs::block_device_flush(fs::node_t node)
+{
+    if (node == fs::NODE_INVALID)
+        return -EINVAL;
+
+    auto iter = blkdevs.find(node);
+    if (!iter)
+        return -EINVAL;
+
+    // devices without a volatile cache complete writes synchronously
+    if (!iter->second.flush)
+        return 0;
+
+    return iter->second.flush();
+}
+
+int fs::block_device_ioctl(fs::node_t node, unsigned long request, uintptr_t arg)
+{
+    if (node == fs::NODE_INVALID)
+        return -EINVAL;
+
+    auto iter = blkdevs.find(node);
+    if (!iter)
+        return -EINVAL;
+
+    if (!iter->second.ioctl)
+        return -ENOTTY;
+
+    return iter->second.ioctl(request, arg);
+}
+
 ssize_t fs::char_device_read(fs::node_t node, char* buf, size_t buf_size, size_t n)
 {
     if (node == fs::NODE_INVALID)
@@ -824,12 +1971,40 @@ ssize_t fs::char_device_write(fs::node_t node, const char* buf, size_t n)
     return iter->second.write(buf, n);
 }
 
+int fs::char_device_ioctl(fs::node_t node, unsigned long request, uintptr_t arg)
+{
+    if (node == fs::NODE_INVALID)
+        return -EINVAL;
+
+    auto iter = chrdevs.find(node);
+    if (!iter || !iter->second.ioctl)
+        return -ENOTTY;
+
+    return iter->second.ioctl(request, arg);
+}
+
+void fs::inode_get(inode* ind)
+{
+    ++ind->refs;
+}
+
+void fs::inode_put(inode* ind)
+{
+    if (--ind->refs == 0)
+        ind->fs->inode_release(ind);
+}
+
 fs::vfs* fs::register_fs(vfs* fs)
 {
     fs_es->push_back(fs);
     return fs;
 }
 
+fs::vfs* fs::make_tmpfs(void)
+{
+    return new tmpfs;
+}
+
 ssize_t b_null_read(char* buf, size_t buf_size, size_t n)
 {
     if (n >= buf_size)
@@ -853,18 +2028,85 @@ static ssize_t console_write(const char* buf, size_t n)
 
     return orig_n;
 }
+static int console_ioctl(unsigned long request, uintptr_t arg)
+{
+    return console->ioctl(request, arg);
+}
 
-fs::pipe::pipe(void)
+fs::pipe::pipe(size_t readers, size_t writers)
     : buf { PIPE_SIZE }
-    , flags { READABLE | WRITABLE }
+    , m_readers { readers }
+    , m_writers { writers }
+{
+}
+
+int fs::pipe::open_read(bool nonblock)
+{
+    {
+        types::lock_guard lck(m_cv.mtx());
+        ++m_readers;
+        m_cv.reopen();
+    }
+    m_cv.notify_all();
+
+    if (nonblock)
+        return 0;
+
+    auto& mtx = m_cv.mtx();
+    types::lock_guard lck(mtx);
+    while (!m_writers) {
+        if (!m_cv.wait(mtx)) {
+            // a writer has come and gone in the meantime
+            if (m_cv.closed())
+                break;
+            --m_readers;
+            return -EINTR;
+        }
+    }
+
+    return 0;
+}
+
+int fs::pipe::open_write(bool nonblock)
 {
+    {
+        types::lock_guard lck(m_cv.mtx());
+        // there's no one to read what we write
+        if (nonblock && !m_readers)
+            return -ENXIO;
+        ++m_writers;
+        m_cv.reopen();
+    }
+    m_cv.notify_all();
+
+    if (nonblock)
+        return 0;
+
+    auto& mtx = m_cv.mtx();
+    types::lock_guard lck(mtx);
+    while (!m_readers) {
+        if (!m_cv.wait(mtx)) {
+            // a reader has come and gone in the meantime
+            if (m_cv.closed())
+                break;
+            --m_writers;
+            return -EINTR;
+        }
+    }
+
+    return 0;
 }
 
 void fs::pipe::close_read(void)
 {
     {
         types::lock_guard lck(m_cv.mtx());
-        flags &= (~READABLE);
+        assert(m_readers);
+        // fail the waits of the other side, which then finds us gone
+        if (!--m_readers) {
+            m_cv.close();
+            return;
+        }
     }
     m_cv.notify_all();
 }
@@ -873,7 +2115,12 @@ void fs::pipe::close_write(void)
 {
     {
         types::lock_guard lck(m_cv.mtx());
-        flags &= (~WRITABLE);
+        assert(m_writers);
+        // fail the waits of the other side, which then finds us gone
+        if (!--m_writers) {
+            m_cv.close();
+            return;
+        }
     }
     m_cv.notify_all();
 }
@@ -892,7 +2139,7 @@ int fs::pipe::write(const char* buf, size_t n)
         }
 
         while (this->buf.avail() < n) {
-            if (!m_cv.wait(mtx))
+            if (!m_cv.wait(mtx) && !m_cv.closed())
                 return -EINTR;
 
             if (!is_readable()) {
@@ -925,7 +2172,7 @@ int fs::pipe::read(char* buf, size_t n)
         }
 
         while (this->buf.size() < n) {
-            if (!m_cv.wait(mtx))
+            if (!m_cv.wait(mtx) && !m_cv.closed())
                 return -EINTR;
 
             if (!is_writeable()) {
@@ -945,23 +2192,71 @@ int fs::pipe::read(char* buf, size_t n)
     return n;
 }
 
+int fs::pipe::read_some(char* buf, size_t n, bool nonblock, bool peek)
+{
+    size_t cnt = 0;
+    {
+        auto& mtx = m_cv.mtx();
+        types::lock_guard lck(mtx);
+
+        while (this->buf.empty()) {
+            if (!is_writeable())
+                return 0;
+            if (nonblock)
+                return -EAGAIN;
+            if (!m_cv.wait(mtx) && !m_cv.closed())
+                return -EINTR;
+        }
+
+        for (; cnt < n && cnt < this->buf.size(); ++cnt)
+            buf[cnt] = peek ? this->buf.peek(cnt) : this->buf.get();
+    }
+
+    if (!peek)
+        m_cv.notify();
+    return cnt;
+}
+
+int fs::pipe::wait_space(bool nonblock)
+{
+    auto& mtx = m_cv.mtx();
+    types::lock_guard lck(mtx);
+
+    while (is_readable() && this->buf.full()) {
+        if (nonblock)
+            return -EAGAIN;
+        if (!m_cv.wait(mtx) && !m_cv.closed())
+            return -EINTR;
+    }
+
+    if (!is_readable()) {
+        current_process->signals.set(kernel::SIGPIPE);
+        return -EPIPE;
+    }
+
+    return this->buf.avail();
+}
+
 SECTION(".text.kinit")
 void init_vfs(void)
 {
     using namespace fs;
     // null
-    register_char_device(make_node(1, 0), { b_null_read, b_null_write });
+    register_char_device(make_node(1, 0), { b_null_read, b_null_write, nullptr }, "null", 0666);
     // console (supports serial console only for now)
     // TODO: add interface to bind console device to other devices
-    register_char_device(make_node(2, 0), { console_read, console_write });
+    register_char_device(make_node(2, 0), { console_read, console_write, console_ioctl }, "console", 0666);
 
     fs_es = types::pnew<types::kernel_ident_allocator>(fs_es);
+    init_page_cache();
 
     auto* rootfs = new tmpfs;
     fs_es->push_back(rootfs);
     fs_root = rootfs->root();
 
     vfs_mkdir(fs_root, "dev");
+    vfs_mkdir(fs_root, "sys");
+    vfs_mkdir(fs_root, "proc");
     vfs_mkdir(fs_root, "root");
     vfs_mkdir(fs_root, "mnt");
     vfs_mkfile(fs_root, "init", 0755);
@@ -971,8 +2266,8 @@ void init_vfs(void)
     const char* str = "#/bin/sh\nexec /bin/sh\n";
     vfs_write(init->ind, str, 0, strlen(str));
 
+    // the init process opens its console before devtmpfs is mounted
     auto* dev = vfs_open(*fs_root, "/dev");
     assert(dev);
-    vfs_mknode(dev, "null", 0666 | S_IFCHR, make_node(1, 0));
     vfs_mknode(dev, "console", 0666 | S_IFCHR, make_node(2, 0));
 }
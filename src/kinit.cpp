@@ -6,6 +6,7 @@
 #include <kernel/hw/pci.hpp>
 #include <kernel/hw/serial.h>
 #include <kernel/hw/timer.h>
+#include <kernel/boottime.hpp>
 #include <kernel/interrupt.h>
 #include <kernel/log.hpp>
 #include <kernel/mem.h>
@@ -65,16 +66,12 @@ static inline int init_console(const char* name)
 {
     if (name[0] == 't' && name[1] == 't' && name[2] == 'y') {
         if (name[3] == 'S' || name[3] == 's') {
-            if (name[4] == '0') {
-                console = types::_new<types::kernel_ident_allocator, serial_tty>(PORT_SERIAL0);
-                return GB_OK;
-            }
-            if (name[4] == '1') {
-                console = types::_new<types::kernel_ident_allocator, serial_tty>(PORT_SERIAL1);
-                return GB_OK;
+            if (name[4] >= '0' && name[4] <= '9' && !name[5]) {
+                console = kernel::hw::serial::get_tty(name[4] - '0');
+                return console ? GB_OK : GB_FAILED;
             }
         }
-        if (name[3] == 'V' && name[3] == 'G' && name[3] == 'A') {
+        if (name[3] == 'V' && name[4] == 'G' && name[5] == 'A') {
             console = types::_new<types::kernel_ident_allocator, vga_tty>();
             return GB_OK;
         }
@@ -90,6 +87,8 @@ extern "C" SECTION(".text.kinit") void NORETURN kernel_init(void)
 
     init_bss_section();
 
+    kernel::kinit::boot_phase("early init");
+
     save_loader_data();
 
     load_new_gdt();
@@ -103,20 +102,30 @@ extern "C" SECTION(".text.kinit") void NORETURN kernel_init(void)
     }
 
     init_idt();
+
+    kernel::kinit::boot_phase("memory");
     init_mem();
+
+    kernel::kinit::boot_phase("interrupts and timer");
     init_pic();
     init_pit();
 
-    int ret = init_serial_port(PORT_SERIAL0);
-    assert(ret == GB_OK);
+    kernel::kinit::boot_phase("console");
+    kernel::hw::serial::init_serial();
 
-    ret = init_console("ttyS0");
+    int ret = init_console("ttyS0");
     assert(ret == GB_OK);
 
+    kernel::kinit::boot_phase("pci");
     kernel::kinit::init_pci();
+
+    kernel::kinit::boot_phase("vfs and syscalls");
     init_vfs();
+    kernel::hw::serial::init_serial_devices();
     init_syscall();
 
+    kernel::kinit::boot_phase("scheduler");
+
     kmsg("switching execution to the scheduler...\n");
     init_scheduler();
 }
@@ -0,0 +1,82 @@
+#include <vector>
+
+#include <kernel/errno.h>
+#include <kernel/mem.h>
+#include <kernel/mm.hpp>
+#include <kernel/process.hpp>
+#include <kernel/vfs.hpp>
+#include <types/aout.hpp>
+#include <types/string.hpp>
+
+int types::aout::aout_load(types::elf::elf32_load_data* d)
+{
+    auto* ent_exec = d->exec_dent;
+    if (!ent_exec) {
+        d->errcode = ENOENT;
+        return GB_FAILED;
+    }
+
+    types::aout::aout_header hdr {};
+    auto n_read = fs::vfs_read(ent_exec->ind, (char*)&hdr, sizeof(hdr), 0, sizeof(hdr));
+
+    if (n_read != sizeof(hdr) || !aout_check_magic(&hdr)) {
+        d->errcode = ENOEXEC;
+        return GB_FAILED;
+    }
+
+    // text and data of QMAGIC files are page aligned, and the
+    // image has to fit below the stack
+    uint32_t image_len = hdr.text + hdr.data;
+    uint32_t mem_len = image_len + hdr.bss;
+    if ((hdr.text & 0xfff) || (hdr.data & 0xfff) || mem_len < image_len
+        || mem_len > types::elf::ELF_DYN_BASE - AOUT_TEXT_ADDR
+        || image_len > ent_exec->ind->size
+        || hdr.entry < AOUT_TEXT_ADDR || hdr.entry >= AOUT_TEXT_ADDR + hdr.text) {
+        d->errcode = ENOEXEC;
+        return GB_FAILED;
+    }
+
+    // copy argv and envp
+    types::string<> filename(d->filename);
+    std::vector<types::string<>> argv, envp;
+    for (const char* const* p = d->argv; *p; ++p)
+        argv.emplace_back(*p);
+    for (const char* const* p = d->envp; *p; ++p)
+        envp.emplace_back(*p);
+
+    // the point of no return
+    current_process->mms.clear_user();
+
+    // TODO: remove this
+    auto* null_dent = fs::vfs_open(*fs::fs_root, "/dev/null");
+    if (!null_dent)
+        kill_current(-1);
+
+    int ret = mmap((char*)AOUT_TEXT_ADDR, image_len, ent_exec->ind, 0, 1, d->system);
+    if (ret != GB_OK)
+        kill_current(-1);
+
+    uint32_t bss_start = AOUT_TEXT_ADDR + image_len;
+    uint32_t bss_end = align_up<12>(bss_start + hdr.bss);
+    if (bss_end > bss_start) {
+        ret = mmap((char*)bss_start, bss_end - bss_start, null_dent->ind, 0, 1, d->system);
+        if (ret != GB_OK)
+            kill_current(-1);
+    }
+
+    current_process->mms.register_brk((char*)bss_end + 0x10000);
+
+    current_process->build_id.clear();
+
+    d->eip = (void*)hdr.entry;
+
+    std::vector<types::elf::elf32_auxv_entry> auxv {
+        { types::elf::elf32_auxv_entry::AT_PAGESZ, PAGE_SIZE },
+        { types::elf::elf32_auxv_entry::AT_ENTRY, hdr.entry },
+    };
+    types::elf::elf32_init_stack(d, filename, argv, envp, std::move(auxv));
+
+    current_thread->name = ent_exec->name;
+
+    return GB_OK;
+}
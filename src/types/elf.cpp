@@ -2,8 +2,10 @@
 
 #include <assert.h>
 #include <kernel/errno.h>
+#include <kernel/hw/cpu.hpp>
 #include <kernel/mem.h>
 #include <kernel/process.hpp>
+#include <kernel/random.hpp>
 #include <kernel/vfs.hpp>
 #include <stdint.h>
 #include <stdio.h>
@@ -28,6 +30,53 @@ inline void _user_push(char** sp, const char* str)
     memcpy(*sp, str, len + 1);
 }
 
+// a page aligned value in [0, range)
+static uint32_t random_page_offset(uint32_t range)
+{
+    return (kernel::random::get_random_u32() % (range >> 12)) << 12;
+}
+
+// look for NT_GNU_BUILD_ID in the PT_NOTE segment phent
+// @return true if found, and build_id is filled
+static bool find_build_id(fs::inode* ind,
+    const types::elf::elf32_program_header_entry& phent, std::vector<uint8_t>& build_id)
+{
+    // build id notes are tiny, don't bother with the large ones
+    if (phent.filesz > PAGE_SIZE)
+        return false;
+
+    std::vector<char> notes(phent.filesz);
+    auto n_read = fs::vfs_read(ind, notes.data(), notes.size(), phent.offset, notes.size());
+    if (n_read != phent.filesz)
+        return false;
+
+    size_t off = 0;
+    while (off + sizeof(types::elf::elf32_note_header) <= notes.size()) {
+        auto* note = (types::elf::elf32_note_header*)(notes.data() + off);
+        off += sizeof(types::elf::elf32_note_header);
+
+        size_t name_off = off;
+        size_t desc_off = name_off + align_up<2>(note->namesz);
+        off = desc_off + align_up<2>(note->descsz);
+        if (off > notes.size() || off < desc_off)
+            return false;
+
+        if (note->type != types::elf::elf32_note_header::NT_GNU_BUILD_ID)
+            continue;
+        if (note->namesz != 4 || strcmp(notes.data() + name_off, "GNU") != 0)
+            continue;
+        if (!note->descsz || note->descsz > types::elf::ELF_BUILD_ID_MAX)
+            continue;
+
+        build_id.clear();
+        for (size_t i = 0; i < note->descsz; ++i)
+            build_id.push_back(notes[desc_off + i]);
+        return true;
+    }
+
+    return false;
+}
+
 int types::elf::elf32_load(types::elf::elf32_load_data* d)
 {
     auto* ent_exec = d->exec_dent;
@@ -36,7 +85,6 @@ int types::elf::elf32_load(types::elf::elf32_load_data* d)
         return GB_FAILED;
     }
 
-    // TODO: detect file format
     types::elf::elf32_header hdr {};
     auto n_read = fs::vfs_read(
         ent_exec->ind,
@@ -45,7 +93,12 @@ int types::elf::elf32_load(types::elf::elf32_load_data* d)
         0, sizeof(types::elf::elf32_header));
 
     if (n_read != sizeof(types::elf::elf32_header)) {
-        d->errcode = EINVAL;
+        d->errcode = ENOEXEC;
+        return GB_FAILED;
+    }
+
+    if (!types::elf::elf32_check_magic(hdr.magic)) {
+        d->errcode = ENOEXEC;
         return GB_FAILED;
     }
 
@@ -77,7 +130,38 @@ int types::elf::elf32_load(types::elf::elf32_load_data* d)
         return GB_FAILED;
     }
 
+    std::vector<uint8_t> build_id;
+    const types::elf::elf32_program_header_entry* relro = nullptr;
+
+    for (const auto& phent : phents) {
+        switch (phent.type) {
+        case types::elf::elf32_program_header_entry::PT_NOTE:
+            if (build_id.empty())
+                find_build_id(ent_exec->ind, phent, build_id);
+            break;
+        case types::elf::elf32_program_header_entry::PT_GNU_RELRO:
+            relro = &phent;
+            break;
+        // without PAE, every readable page is executable and a
+        // non-executable stack requested by PT_GNU_STACK can't be
+        // provided, so it's accepted as is
+        case types::elf::elf32_program_header_entry::PT_GNU_STACK:
+        default:
+            break;
+        }
+    }
+
+    // static PIE executables are relocated by themselves
+    uint32_t base = 0;
+    if (hdr.type == types::elf::elf32_header::ET_DYN)
+        base = types::elf::ELF_DYN_BASE + random_page_offset(types::elf::ELF_DYN_RANDOM);
+    else if (hdr.type != types::elf::elf32_header::ET_EXEC) {
+        d->errcode = ENOEXEC;
+        return GB_FAILED;
+    }
+
     // copy argv and envp
+    types::string<> filename(d->filename);
     std::vector<string<>> argv, envp;
     for (const char* const* p = d->argv; *p; ++p)
         argv.emplace_back(*p);
@@ -88,6 +172,17 @@ int types::elf::elf32_load(types::elf::elf32_load_data* d)
     // so we can't just simply return to it on error.
     current_process->mms.clear_user();
 
+    // setuid and setgid executables run with the ids of their owner
+    if (!d->system) {
+        auto* proc = current_process;
+        if (ent_exec->ind->mode & S_ISUID)
+            proc->euid = ent_exec->ind->uid;
+        if (ent_exec->ind->mode & S_ISGID)
+            proc->egid = ent_exec->ind->gid;
+        proc->suid = proc->euid;
+        proc->sgid = proc->egid;
+    }
+
     // TODO: remove this
     fs::inode* null_ind = nullptr;
     {
@@ -98,14 +193,23 @@ int types::elf::elf32_load(types::elf::elf32_load_data* d)
     }
 
     uint32_t data_segment_end = 0;
+    uint32_t phdr_addr = 0;
 
     for (const auto& phent : phents) {
+        if (phent.type == types::elf::elf32_program_header_entry::PT_PHDR)
+            phdr_addr = base + phent.vaddr;
+
         if (phent.type != types::elf::elf32_program_header_entry::PT_LOAD)
             continue;
 
-        auto vaddr = align_down<12>(phent.vaddr);
-        auto vlen = align_up<12>(phent.vaddr + phent.memsz) - vaddr;
-        auto flen = align_up<12>(phent.vaddr + phent.filesz) - vaddr;
+        // the segment containing the program headers
+        if (!phdr_addr && hdr.phoff >= phent.offset
+            && hdr.phoff + phents_size <= phent.offset + phent.filesz)
+            phdr_addr = base + phent.vaddr + (hdr.phoff - phent.offset);
+
+        auto vaddr = align_down<12>(base + phent.vaddr);
+        auto vlen = align_up<12>(base + phent.vaddr + phent.memsz) - vaddr;
+        auto flen = align_up<12>(base + phent.vaddr + phent.filesz) - vaddr;
         auto fileoff = align_down<12>(phent.offset);
 
         auto ret = mmap(
@@ -140,20 +244,69 @@ int types::elf::elf32_load(types::elf::elf32_load_data* d)
 
     for (const auto& shent : shents) {
         if (shent.sh_type == elf32_section_header_entry::SHT_NOBITS)
-            memset((char*)shent.sh_addr, 0x00, shent.sh_size);
+            memset((char*)(base + shent.sh_addr), 0x00, shent.sh_size);
+    }
+
+    // executables with fixed addresses need no relocation so the
+    // RELRO area is protected right now. static PIE executables are
+    // relocated by their startup code which calls mprotect after that
+    if (relro && !base) {
+        auto start = align_down<12>(relro->vaddr);
+        auto end = align_down<12>(relro->vaddr + relro->memsz);
+        if (end > start)
+            current_process->mms.protect((void*)start, end - start, false);
     }
 
+    current_process->build_id = std::move(build_id);
+
+    d->eip = (void*)(base + hdr.entry);
+
+    std::vector<types::elf::elf32_auxv_entry> auxv {
+        { types::elf::elf32_auxv_entry::AT_PHDR, phdr_addr },
+        { types::elf::elf32_auxv_entry::AT_PHENT, hdr.phentsize },
+        { types::elf::elf32_auxv_entry::AT_PHNUM, hdr.phnum },
+        { types::elf::elf32_auxv_entry::AT_PAGESZ, PAGE_SIZE },
+        // no interpreter
+        { types::elf::elf32_auxv_entry::AT_BASE, 0 },
+        { types::elf::elf32_auxv_entry::AT_ENTRY, base + hdr.entry },
+    };
+    types::elf::elf32_init_stack(d, filename, argv, envp, std::move(auxv));
+
+    // rename current thread
+    current_thread->name = ent_exec->name;
+
+    return GB_OK;
+}
+
+void types::elf::elf32_init_stack(types::elf::elf32_load_data* d,
+    const types::string<>& filename,
+    const std::vector<types::string<>>& argv,
+    const std::vector<types::string<>>& envp,
+    std::vector<types::elf::elf32_auxv_entry> auxv)
+{
+    // TODO: remove this
+    auto* null_dent = fs::vfs_open(*fs::fs_root, "/dev/null");
+    if (!null_dent)
+        kill_current(-1);
+
     // map stack area
     auto ret = mmap((void*)types::elf::ELF_STACK_TOP,
         types::elf::ELF_STACK_SIZE,
-        null_ind, 0, 1, 0);
+        null_dent->ind, 0, 1, 0);
     assert(ret == GB_OK);
 
-    d->eip = (void*)hdr.entry;
     d->sp = reinterpret_cast<uint32_t*>(types::elf::ELF_STACK_BOTTOM);
 
     auto* sp = (char**)&d->sp;
 
+    _user_push(sp, filename.c_str());
+    char* execfn = *sp;
+
+    // seed for the stack protector and pointer guard of libc
+    *sp -= 16;
+    kernel::random::get_random_bytes(*sp, 16);
+    char* random_bytes = *sp;
+
     // fill information block area
     std::vector<char*> args, envs;
     for (const auto& env : envp) {
@@ -165,10 +318,29 @@ int types::elf::elf32_load(types::elf::elf32_load_data* d)
         args.push_back(*sp);
     }
 
+    auxv.push_back({ types::elf::elf32_auxv_entry::AT_HWCAP, kernel::hw::cpu_hwcap() });
+    auxv.push_back({ types::elf::elf32_auxv_entry::AT_HWCAP2, kernel::hw::cpu_hwcap2() });
+    // times are reported in USER_HZ
+    auxv.push_back({ types::elf::elf32_auxv_entry::AT_CLKTCK, 100 });
+    auto* proc = current_process;
+    auxv.push_back({ types::elf::elf32_auxv_entry::AT_UID, proc->uid });
+    auxv.push_back({ types::elf::elf32_auxv_entry::AT_EUID, proc->euid });
+    auxv.push_back({ types::elf::elf32_auxv_entry::AT_GID, proc->gid });
+    auxv.push_back({ types::elf::elf32_auxv_entry::AT_EGID, proc->egid });
+    // tells libc not to trust the environment
+    auxv.push_back({ types::elf::elf32_auxv_entry::AT_SECURE,
+        proc->euid != proc->uid || proc->egid != proc->gid });
+    auxv.push_back({ types::elf::elf32_auxv_entry::AT_RANDOM, (uint32_t)random_bytes });
+    auxv.push_back({ types::elf::elf32_auxv_entry::AT_EXECFN, (uint32_t)execfn });
+
     // push null auxiliary vector entry
     _user_push(sp, 0);
     _user_push(sp, 0);
 
+    // push auxiliary vector
+    *sp -= sizeof(types::elf::elf32_auxv_entry) * auxv.size();
+    memcpy(*sp, auxv.data(), sizeof(types::elf::elf32_auxv_entry) * auxv.size());
+
     // push 0 for envp
     _user_push(sp, 0);
 
@@ -185,9 +357,4 @@ int types::elf::elf32_load(types::elf::elf32_load_data* d)
 
     // push argc
     _user_push(sp, args.size());
-
-    // rename current thread
-    current_thread->name = ent_exec->name;
-
-    return GB_OK;
 }
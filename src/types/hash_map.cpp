@@ -0,0 +1,22 @@
+#include <kernel/crypto/siphash.hpp>
+#include <kernel/random.hpp>
+#include <types/hash_map.hpp>
+
+namespace types {
+
+// generated on the first use, before any of the tables holds a string,
+// and never changed afterwards or the strings hashed would be lost
+static kernel::crypto::siphash_key s_key;
+static bool s_key_ready;
+
+uint32_t hash_string(const char* str, std::size_t len)
+{
+    if (!s_key_ready) {
+        kernel::random::get_random_bytes(&s_key, sizeof(s_key));
+        s_key_ready = true;
+    }
+
+    return kernel::crypto::siphash_1_3(str, len, s_key);
+}
+
+} // namespace types
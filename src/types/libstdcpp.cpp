@@ -1,6 +1,7 @@
 #include <asm/port_io.h>
 #include <assert.h>
 #include <kernel/log.hpp>
+#include <kernel/oops.hpp>
 #include <kernel/process.hpp>
 #include <stdio.h>
 #include <types/types.h>
@@ -21,8 +22,7 @@ void NORETURN
 __assert_fail(const char* statement, const char* file, int line, const char* func)
 {
     char buf[256];
-    snprintf(buf, sizeof(buf), "Kernel assertion failed: (%s), %s:%d, %s\n",
+    snprintf(buf, sizeof(buf), "Kernel assertion failed: (%s), %s:%d, %s",
         statement, file, line, func);
-    kmsg(buf);
-    freeze();
+    kernel::oops(buf, nullptr, (ptr_t)__builtin_return_address(0));
 }